@@ -127,6 +127,7 @@ pub(crate) fn send_message(
         info!("Send message: {:?}", message);
         server
             .send_message_to_target::<Channel1, Message1>(Message1(5), NetworkTarget::All)
+            .map(|_| ())
             .unwrap_or_else(|e| {
                 error!("Failed to send message: {:?}", e);
             });
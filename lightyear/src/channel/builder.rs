@@ -5,19 +5,25 @@ use bevy::utils::Duration;
 
 use lightyear_macros::ChannelInternal;
 
+use crate::channel::receivers::keyed_ordered_reliable::KeyedOrderedReliableReceiver;
 use crate::channel::receivers::ordered_reliable::OrderedReliableReceiver;
 use crate::channel::receivers::sequenced_reliable::SequencedReliableReceiver;
 use crate::channel::receivers::sequenced_unreliable::SequencedUnreliableReceiver;
+use crate::channel::receivers::stream::StreamReceiver;
 use crate::channel::receivers::tick_unreliable::TickUnreliableReceiver;
 use crate::channel::receivers::unordered_reliable::UnorderedReliableReceiver;
 use crate::channel::receivers::unordered_unreliable::UnorderedUnreliableReceiver;
 use crate::channel::receivers::ChannelReceiver;
+use crate::channel::senders::keyed_reliable::KeyedReliableSender;
 use crate::channel::senders::reliable::ReliableSender;
 use crate::channel::senders::sequenced_unreliable::SequencedUnreliableSender;
+use crate::channel::senders::stream::StreamSender;
 use crate::channel::senders::tick_unreliable::TickUnreliableSender;
 use crate::channel::senders::unordered_unreliable::UnorderedUnreliableSender;
 use crate::channel::senders::unordered_unreliable_with_acks::UnorderedUnreliableWithAcksSender;
 use crate::channel::senders::ChannelSender;
+use crate::channel::stream::STREAM_HEADER_BYTES;
+use crate::packet::packet::FRAGMENT_SIZE;
 use crate::prelude::ChannelKind;
 
 /// A ChannelContainer is a struct that implements the [`Channel`] trait
@@ -101,10 +107,18 @@ impl ChannelContainer {
                 receiver = OrderedReliableReceiver::new().into();
                 sender = ReliableSender::new(reliable_settings).into();
             }
+            ChannelMode::KeyedOrderedReliable(reliable_settings) => {
+                receiver = KeyedOrderedReliableReceiver::new().into();
+                sender = KeyedReliableSender::new(reliable_settings).into();
+            }
             ChannelMode::TickBuffered => {
                 receiver = TickUnreliableReceiver::new().into();
                 sender = TickUnreliableSender::new().into();
             }
+            ChannelMode::Streaming(stream_settings) => {
+                receiver = StreamReceiver::new(&stream_settings).into();
+                sender = StreamSender::new(stream_settings).into();
+            }
         }
         Self {
             setting: settings_clone,
@@ -152,9 +166,21 @@ pub enum ChannelMode {
     SequencedReliable(ReliableSettings),
     /// Messages will arrive in the correct order at the destination
     OrderedReliable(ReliableSettings),
+    /// Each message is sent with an ordering key (0 if no key is provided); messages will arrive in the correct order
+    /// relative to the other messages with the same key.
+    ///
+    /// A lost message only delays the messages with the same key, instead of every message of the channel.
+    KeyedOrderedReliable(ReliableSettings),
     /// Inputs from the client are associated with the current tick on the client.
     /// The server will buffer them and only receive them on the same tick.
     TickBuffered,
+    /// Large payloads (maps, replays, user-generated content) are split into chunks that are sent reliably.
+    /// Only a limited number of chunks are in flight at any time, and a lost chunk is sent again without
+    /// restarting the transfer. Payloads are delivered in the order in which they are fully received.
+    ///
+    /// The progress of each stream is reported with [`StreamProgressEvent`](crate::shared::events::components::StreamProgressEvent)s
+    /// on both peers, and a stream can be cancelled by the sender.
+    Streaming(StreamSettings),
 }
 
 impl ChannelMode {
@@ -166,7 +192,9 @@ impl ChannelMode {
             ChannelMode::UnorderedReliable(_) => true,
             ChannelMode::SequencedReliable(_) => true,
             ChannelMode::OrderedReliable(_) => true,
+            ChannelMode::KeyedOrderedReliable(_) => true,
             ChannelMode::TickBuffered => false,
+            ChannelMode::Streaming(_) => true,
         }
     }
 
@@ -179,12 +207,14 @@ impl ChannelMode {
             ChannelMode::UnorderedReliable(_) => true,
             ChannelMode::SequencedReliable(_) => true,
             ChannelMode::OrderedReliable(_) => true,
+            ChannelMode::KeyedOrderedReliable(_) => true,
             ChannelMode::TickBuffered => false,
+            ChannelMode::Streaming(_) => true,
         }
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 /// [`ChannelDirection`] specifies in which direction the packets can be sent
 pub enum ChannelDirection {
     ClientToServer,
@@ -216,6 +246,47 @@ impl ReliableSettings {
     }
 }
 
+/// Settings of a [`ChannelMode::Streaming`] channel
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamSettings {
+    /// Settings used to resend the chunks that were not acked
+    pub reliable: ReliableSettings,
+    /// Number of payload bytes in each chunk.
+    /// It is capped so that a chunk always fits in a single packet.
+    pub chunk_size: usize,
+    /// Maximum number of chunks that were sent but not acked yet.
+    /// New chunks are only sent once previous chunks have been acked.
+    pub max_chunks_in_flight: usize,
+    /// The receiver rejects the streams whose payload is larger than this number of bytes
+    pub max_payload_size: usize,
+    /// Maximum number of streams that the receiver buffers at the same time.
+    /// The receiver rejects the new streams beyond this limit.
+    pub max_concurrent_streams: usize,
+    /// Maximum number of bytes buffered by the receiver for all its partially received streams.
+    /// The receiver rejects the stream whose chunk would go over this limit.
+    pub max_buffered_bytes: usize,
+}
+
+impl Default for StreamSettings {
+    fn default() -> Self {
+        Self {
+            reliable: ReliableSettings::default(),
+            chunk_size: FRAGMENT_SIZE - STREAM_HEADER_BYTES,
+            max_chunks_in_flight: 64,
+            max_payload_size: 64 * 1024 * 1024,
+            max_concurrent_streams: 16,
+            max_buffered_bytes: 128 * 1024 * 1024,
+        }
+    }
+}
+
+impl StreamSettings {
+    pub(crate) fn chunk_size(&self) -> usize {
+        self.chunk_size
+            .clamp(1, FRAGMENT_SIZE - STREAM_HEADER_BYTES)
+    }
+}
+
 /// Default channel to replicate entity actions.
 /// This is an Unordered Reliable channel.
 /// (SpawnEntity, DespawnEntity, InsertComponent, RemoveComponent)
@@ -227,6 +298,16 @@ pub struct EntityActionsChannel;
 /// This is a Sequenced Unreliable channel
 pub struct EntityUpdatesChannel;
 
+/// Default channel to send changes of authority over entities from the server to the clients.
+/// This is an Ordered Reliable channel, so that a revoke is never applied before the grant it cancels.
+#[derive(ChannelInternal)]
+pub struct AuthorityChannel;
+
+/// Default channel to send to each client the list of the other connected clients, when the server rebroadcasts the inputs.
+/// This is an Ordered Reliable channel, so that the latest list is always the one that is applied.
+#[derive(ChannelInternal)]
+pub struct PlayersChannel;
+
 /// Default channel to send pings. This is a Sequenced Unreliable channel, because
 /// there is no point in getting older pings.
 #[derive(ChannelInternal)]
@@ -0,0 +1,118 @@
+use std::collections::{btree_map, BTreeMap, HashMap, VecDeque};
+
+use anyhow::anyhow;
+use bytes::Buf;
+
+use crate::channel::receivers::unordered_reliable::UnorderedReliableReceiver;
+use crate::channel::receivers::ChannelReceive;
+use crate::channel::senders::keyed_reliable::KEY_HEADER_BYTES;
+use crate::packet::message::{MessageContainer, MessageId, SingleData};
+use crate::shared::tick_manager::TickManager;
+use crate::shared::time_manager::TimeManager;
+
+/// Messages of a single ordering key
+#[derive(Default)]
+struct KeyedMessages {
+    /// Next sequence number that we are waiting to receive for this key
+    pending_sequence: MessageId,
+    /// Messages that were received before the messages that precede them
+    buffer: BTreeMap<MessageId, SingleData>,
+}
+
+/// Keyed Ordered Reliable receiver: make sure that all messages are received,
+/// and return the messages that have the same key in order.
+///
+/// A missing message only delays the following messages with the same key.
+pub struct KeyedOrderedReliableReceiver {
+    /// Makes sure that every message is received exactly once
+    receiver: UnorderedReliableReceiver,
+    keys: HashMap<u32, KeyedMessages>,
+    /// Messages that can be read
+    ready: VecDeque<SingleData>,
+}
+
+impl KeyedOrderedReliableReceiver {
+    pub fn new() -> Self {
+        Self {
+            receiver: UnorderedReliableReceiver::new(),
+            keys: HashMap::new(),
+            ready: VecDeque::new(),
+        }
+    }
+}
+
+impl ChannelReceive for KeyedOrderedReliableReceiver {
+    fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager) {
+        self.receiver.update(time_manager, tick_manager)
+    }
+
+    fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
+        self.receiver.buffer_recv(message)?;
+        while let Some(mut data) = self.receiver.read_message() {
+            if data.bytes.len() < KEY_HEADER_BYTES {
+                return Err(anyhow!("message is missing its ordering key"));
+            }
+            let key = data.bytes.get_u32();
+            let sequence = MessageId(data.bytes.get_u16());
+            let messages = self.keys.entry(key).or_default();
+            if sequence < messages.pending_sequence {
+                continue;
+            }
+            if let btree_map::Entry::Vacant(entry) = messages.buffer.entry(sequence) {
+                entry.insert(data);
+            }
+            while let Some(data) = messages.buffer.remove(&messages.pending_sequence) {
+                self.ready.push_back(data);
+                messages.pending_sequence += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_message(&mut self) -> Option<SingleData> {
+        self.ready.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use crate::channel::builder::ReliableSettings;
+    use crate::channel::senders::keyed_reliable::KeyedReliableSender;
+    use crate::channel::senders::ChannelSend;
+
+    use super::*;
+
+    #[test]
+    fn test_keyed_ordered_reliable_receiver() -> anyhow::Result<()> {
+        let mut sender = KeyedReliableSender::new(ReliableSettings::default());
+        let mut receiver = KeyedOrderedReliableReceiver::new();
+
+        sender.buffer_send_with_key(Bytes::from("a1"), 1.0, 1);
+        sender.buffer_send_with_key(Bytes::from("b1"), 1.0, 2);
+        sender.buffer_send_with_key(Bytes::from("a2"), 1.0, 1);
+        sender.buffer_send_with_key(Bytes::from("b2"), 1.0, 2);
+        sender.collect_messages_to_send();
+        let (messages, _) = sender.send_packet();
+        let bytes = |data: Option<SingleData>| data.map(|data| data.bytes);
+
+        // the first message of key 1 is lost: the messages of key 2 are not blocked
+        receiver.buffer_recv(messages[2].clone().into())?;
+        receiver.buffer_recv(messages[1].clone().into())?;
+        receiver.buffer_recv(messages[3].clone().into())?;
+        assert_eq!(bytes(receiver.read_message()), Some(Bytes::from("b1")));
+        assert_eq!(bytes(receiver.read_message()), Some(Bytes::from("b2")));
+        assert_eq!(receiver.read_message(), None);
+
+        // the lost message is resent: the messages of key 1 are received in order
+        receiver.buffer_recv(messages[0].clone().into())?;
+        assert_eq!(bytes(receiver.read_message()), Some(Bytes::from("a1")));
+        assert_eq!(bytes(receiver.read_message()), Some(Bytes::from("a2")));
+
+        // duplicates are ignored
+        receiver.buffer_recv(messages[0].clone().into())?;
+        assert_eq!(receiver.read_message(), None);
+        Ok(())
+    }
+}
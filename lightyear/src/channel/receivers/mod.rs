@@ -7,6 +7,9 @@ use enum_dispatch::enum_dispatch;
 /// Utilities to receive a Message from multiple fragment packets
 pub(crate) mod fragment_receiver;
 
+/// Receive messages in an Ordered Reliable manner, separately for each ordering key
+pub(crate) mod keyed_ordered_reliable;
+
 /// Receive messages in an Ordered Reliable manner
 pub(crate) mod ordered_reliable;
 
@@ -16,6 +19,9 @@ pub(crate) mod sequenced_reliable;
 /// Receive messages in an Sequenced Unreliable manner
 pub(crate) mod sequenced_unreliable;
 
+/// Receive the payloads of a streaming channel
+pub(crate) mod stream;
+
 pub(crate) mod tick_unreliable;
 
 /// Receive messages in an Unordered Reliable manner
@@ -43,7 +49,9 @@ pub enum ChannelReceiver {
     UnorderedUnreliable(unordered_unreliable::UnorderedUnreliableReceiver),
     SequencedUnreliable(sequenced_unreliable::SequencedUnreliableReceiver),
     OrderedReliable(ordered_reliable::OrderedReliableReceiver),
+    KeyedOrderedReliable(keyed_ordered_reliable::KeyedOrderedReliableReceiver),
     SequencedReliable(sequenced_reliable::SequencedReliableReceiver),
     UnorderedReliable(unordered_reliable::UnorderedReliableReceiver),
     TickUnreliable(tick_unreliable::TickUnreliableReceiver),
+    Stream(stream::StreamReceiver),
 }
@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use anyhow::anyhow;
+use bytes::{Bytes, BytesMut};
+use tracing::{error, trace};
+
+use crate::channel::builder::StreamSettings;
+use crate::channel::receivers::ChannelReceive;
+use crate::channel::stream::{push_stream_update, StreamChunk, StreamDirection, StreamUpdate};
+use crate::packet::message::{MessageContainer, MessageId, SingleData};
+use crate::shared::tick_manager::TickManager;
+use crate::shared::time_manager::TimeManager;
+
+/// Number of completed or cancelled streams that we remember, to ignore the chunks that arrive late
+const FINISHED_STREAMS_CAPACITY: usize = 1024;
+
+/// A payload that is being received
+struct IncomingStream {
+    total_len: u64,
+    /// The received parts of the payload, by offset. The parts never overlap.
+    parts: BTreeMap<u64, Bytes>,
+    received_bytes: u64,
+}
+
+impl IncomingStream {
+    /// Byte ranges between `offset` and `end` that were not received yet
+    fn missing_ranges(&self, offset: u64, end: u64) -> Vec<(u64, u64)> {
+        // the parts are sorted and don't overlap, so their ends are sorted too
+        let mut overlapping: Vec<(u64, u64)> = self
+            .parts
+            .range(..end)
+            .rev()
+            .map(|(start, part)| (*start, *start + part.len() as u64))
+            .take_while(|(_, part_end)| *part_end > offset)
+            .collect();
+        overlapping.reverse();
+
+        let mut missing = vec![];
+        let mut cursor = offset;
+        for (start, part_end) in overlapping {
+            if start > cursor {
+                missing.push((cursor, start));
+            }
+            cursor = cursor.max(part_end);
+        }
+        if cursor < end {
+            missing.push((cursor, end));
+        }
+        missing
+    }
+
+    /// Store the missing ranges of a chunk that starts at `offset`
+    fn insert(&mut self, offset: u64, bytes: &Bytes, missing: Vec<(u64, u64)>) {
+        for (start, end) in missing {
+            let part = bytes.slice((start - offset) as usize..(end - offset) as usize);
+            self.parts.insert(start, part);
+            self.received_bytes += end - start;
+        }
+    }
+
+    /// Concatenate the parts of a fully received payload
+    fn payload(self) -> Bytes {
+        let mut payload = BytesMut::with_capacity(self.total_len as usize);
+        for part in self.parts.into_values() {
+            payload.extend_from_slice(&part);
+        }
+        payload.freeze()
+    }
+}
+
+/// Receiver for [`ChannelMode::Streaming`](crate::channel::builder::ChannelMode::Streaming) channels.
+///
+/// The chunks of each stream are buffered as they arrive; the payload can be read once all the chunks have been received.
+/// Partially received streams are only discarded if the sender cancels them, or if the receiver rejects them because they
+/// go over the limits of the [`StreamSettings`].
+pub struct StreamReceiver {
+    max_payload_size: usize,
+    max_concurrent_streams: usize,
+    max_buffered_bytes: usize,
+    streams: HashMap<MessageId, IncomingStream>,
+    /// Number of bytes buffered for all the partially received streams
+    buffered_bytes: u64,
+    /// Streams that were recently completed or cancelled
+    finished_streams: VecDeque<MessageId>,
+    /// Payloads that were fully received but not read yet
+    recv_message_buffer: VecDeque<SingleData>,
+    /// Progress of the streams since the last time the updates were read
+    updates: Vec<StreamUpdate>,
+}
+
+impl StreamReceiver {
+    pub(crate) fn new(settings: &StreamSettings) -> Self {
+        Self {
+            max_payload_size: settings.max_payload_size,
+            max_concurrent_streams: settings.max_concurrent_streams,
+            max_buffered_bytes: settings.max_buffered_bytes,
+            streams: HashMap::new(),
+            buffered_bytes: 0,
+            finished_streams: VecDeque::new(),
+            recv_message_buffer: VecDeque::new(),
+            updates: Vec::new(),
+        }
+    }
+
+    /// Take the updates of the streams since the last call
+    pub(crate) fn take_updates(&mut self) -> Vec<StreamUpdate> {
+        std::mem::take(&mut self.updates)
+    }
+
+    fn finish(&mut self, stream_id: MessageId) -> Option<IncomingStream> {
+        let stream = self.streams.remove(&stream_id);
+        if let Some(stream) = &stream {
+            self.buffered_bytes -= stream.received_bytes;
+        }
+        if self.finished_streams.len() == FINISHED_STREAMS_CAPACITY {
+            self.finished_streams.pop_front();
+        }
+        self.finished_streams.push_back(stream_id);
+        stream
+    }
+
+    fn cancel(&mut self, stream_id: MessageId) {
+        self.finish(stream_id);
+        push_stream_update(
+            &mut self.updates,
+            StreamUpdate::Cancelled {
+                stream_id,
+                direction: StreamDirection::Receive,
+            },
+        );
+    }
+}
+
+impl ChannelReceive for StreamReceiver {
+    fn update(&mut self, _: &TimeManager, _: &TickManager) {}
+
+    fn buffer_recv(&mut self, message: MessageContainer) -> anyhow::Result<()> {
+        let MessageContainer::Single(data) = message else {
+            return Err(anyhow!(
+                "streaming channels do not send fragmented messages"
+            ));
+        };
+        let tick = data.tick;
+        match StreamChunk::from_bytes(data.bytes)? {
+            StreamChunk::Data {
+                stream_id,
+                total_len,
+                offset,
+                bytes,
+            } => {
+                if self.finished_streams.contains(&stream_id) {
+                    return Ok(());
+                }
+                if offset.saturating_add(bytes.len() as u64) > total_len {
+                    return Err(anyhow!(
+                        "a chunk of stream {stream_id:?} is outside of the payload"
+                    ));
+                }
+                let end = offset + bytes.len() as u64;
+                let is_new = !self.streams.contains_key(&stream_id);
+                if is_new {
+                    if total_len > self.max_payload_size as u64 {
+                        error!(
+                            ?stream_id,
+                            ?total_len,
+                            "Rejecting a stream that is larger than the max payload size"
+                        );
+                        self.cancel(stream_id);
+                        return Ok(());
+                    }
+                    if self.streams.len() >= self.max_concurrent_streams {
+                        error!(
+                            ?stream_id,
+                            "Rejecting a stream because too many streams are being received"
+                        );
+                        self.cancel(stream_id);
+                        return Ok(());
+                    }
+                }
+                let stream = self
+                    .streams
+                    .entry(stream_id)
+                    .or_insert_with(|| IncomingStream {
+                        total_len,
+                        parts: BTreeMap::new(),
+                        received_bytes: 0,
+                    });
+                if stream.total_len != total_len {
+                    return Err(anyhow!(
+                        "the chunks of stream {stream_id:?} have a different payload length"
+                    ));
+                }
+                // only the bytes that were not received yet count towards the limit
+                let missing = stream.missing_ranges(offset, end);
+                let new_bytes: u64 = missing.iter().map(|(start, end)| end - start).sum();
+                if self.buffered_bytes + new_bytes > self.max_buffered_bytes as u64 {
+                    error!(
+                        ?stream_id,
+                        "Rejecting a stream because too many bytes are buffered"
+                    );
+                    self.cancel(stream_id);
+                    return Ok(());
+                }
+                stream.insert(offset, &bytes, missing);
+                self.buffered_bytes += new_bytes;
+                if is_new || new_bytes > 0 {
+                    push_stream_update(
+                        &mut self.updates,
+                        StreamUpdate::Progress {
+                            stream_id,
+                            direction: StreamDirection::Receive,
+                            bytes: stream.received_bytes,
+                            total: total_len,
+                        },
+                    );
+                }
+                if stream.received_bytes == total_len {
+                    trace!(?stream_id, "Received all the chunks of the stream");
+                    let payload = self.finish(stream_id).unwrap().payload();
+                    let mut data = SingleData::new(Some(stream_id), payload, 1.0);
+                    data.tick = tick;
+                    self.recv_message_buffer.push_back(data);
+                }
+            }
+            StreamChunk::Cancel { stream_id } => {
+                if !self.finished_streams.contains(&stream_id) {
+                    self.cancel(stream_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_message(&mut self) -> Option<SingleData> {
+        self.recv_message_buffer.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::channel::senders::stream::StreamSender;
+    use crate::channel::senders::ChannelSend;
+
+    use super::*;
+
+    #[test]
+    fn test_stream_receiver() -> anyhow::Result<()> {
+        let mut sender = StreamSender::new(StreamSettings {
+            chunk_size: 10,
+            ..Default::default()
+        });
+        let mut receiver = StreamReceiver::new(&StreamSettings {
+            max_payload_size: 100,
+            ..Default::default()
+        });
+        let payload = Bytes::from((0..25).collect::<Vec<u8>>());
+        let stream_id = sender.buffer_send(payload.clone(), 1.0).unwrap();
+        sender.collect_messages_to_send();
+        let (chunks, _) = sender.send_packet();
+        assert_eq!(chunks.len(), 3);
+
+        // receive the chunks out of order, with a duplicate
+        receiver.buffer_recv(chunks[2].clone().into())?;
+        receiver.buffer_recv(chunks[0].clone().into())?;
+        receiver.buffer_recv(chunks[0].clone().into())?;
+        assert_eq!(receiver.read_message(), None);
+        assert_eq!(
+            receiver.take_updates(),
+            vec![StreamUpdate::Progress {
+                stream_id,
+                direction: StreamDirection::Receive,
+                bytes: 15,
+                total: 25,
+            }]
+        );
+        receiver.buffer_recv(chunks[1].clone().into())?;
+        assert_eq!(
+            receiver.read_message(),
+            Some(SingleData::new(Some(stream_id), payload, 1.0))
+        );
+
+        // a chunk that arrives after the end of the stream is ignored
+        receiver.buffer_recv(chunks[1].clone().into())?;
+        assert!(receiver.streams.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_receiver_cancel() -> anyhow::Result<()> {
+        let mut sender = StreamSender::new(StreamSettings {
+            chunk_size: 10,
+            ..Default::default()
+        });
+        let mut receiver = StreamReceiver::new(&StreamSettings {
+            max_payload_size: 20,
+            ..Default::default()
+        });
+
+        // the payload is too large
+        let stream_id = sender.buffer_send(Bytes::from(vec![0; 25]), 1.0).unwrap();
+        sender.collect_messages_to_send();
+        let (chunks, _) = sender.send_packet();
+        receiver.buffer_recv(chunks[0].clone().into())?;
+        assert_eq!(
+            receiver.take_updates(),
+            vec![StreamUpdate::Cancelled {
+                stream_id,
+                direction: StreamDirection::Receive,
+            }]
+        );
+
+        // the sender cancels the stream
+        let stream_id = sender.buffer_send(Bytes::from(vec![0; 15]), 1.0).unwrap();
+        sender.collect_messages_to_send();
+        let (chunks, _) = sender.send_packet();
+        receiver.buffer_recv(chunks[0].clone().into())?;
+        assert_eq!(receiver.streams.len(), 1);
+        sender.cancel(stream_id);
+        sender.collect_messages_to_send();
+        let (cancel, _) = sender.send_packet();
+        receiver.buffer_recv(cancel[0].clone().into())?;
+        receiver.buffer_recv(chunks[1].clone().into())?;
+        assert!(receiver.streams.is_empty());
+        assert_eq!(receiver.read_message(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_receiver_overlapping_chunks() -> anyhow::Result<()> {
+        let mut receiver = StreamReceiver::new(&StreamSettings::default());
+        let payload = Bytes::from((0..30).collect::<Vec<u8>>());
+        let chunk = |offset: usize, end: usize| -> MessageContainer {
+            SingleData::new(
+                None,
+                StreamChunk::Data {
+                    stream_id: MessageId(0),
+                    total_len: 30,
+                    offset: offset as u64,
+                    bytes: payload.slice(offset..end),
+                }
+                .to_bytes(),
+                1.0,
+            )
+            .into()
+        };
+
+        receiver.buffer_recv(chunk(0, 10))?;
+        receiver.buffer_recv(chunk(5, 15))?;
+        receiver.buffer_recv(chunk(20, 30))?;
+        receiver.buffer_recv(chunk(0, 25))?;
+        // the overlapping bytes are only counted once
+        assert_eq!(
+            receiver.take_updates(),
+            vec![StreamUpdate::Progress {
+                stream_id: MessageId(0),
+                direction: StreamDirection::Receive,
+                bytes: 30,
+                total: 30,
+            }]
+        );
+        assert_eq!(
+            receiver.read_message(),
+            Some(SingleData::new(Some(MessageId(0)), payload, 1.0))
+        );
+        assert_eq!(receiver.buffered_bytes, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_receiver_limits() -> anyhow::Result<()> {
+        let mut sender = StreamSender::new(StreamSettings {
+            chunk_size: 10,
+            ..Default::default()
+        });
+        let mut receiver = StreamReceiver::new(&StreamSettings {
+            max_concurrent_streams: 2,
+            max_buffered_bytes: 25,
+            ..Default::default()
+        });
+        let first = sender.buffer_send(Bytes::from(vec![0; 20]), 1.0).unwrap();
+        let second = sender.buffer_send(Bytes::from(vec![0; 20]), 1.0).unwrap();
+        let third = sender.buffer_send(Bytes::from(vec![0; 20]), 1.0).unwrap();
+        sender.collect_messages_to_send();
+        let (chunks, _) = sender.send_packet();
+        let first_chunk = |stream_id: MessageId| {
+            chunks
+                .iter()
+                .find(|chunk| {
+                    matches!(StreamChunk::from_bytes(chunk.bytes.clone()),
+                        Ok(StreamChunk::Data { stream_id: id, offset: 0, .. }) if id == stream_id)
+                })
+                .unwrap()
+                .clone()
+        };
+
+        // the buffer does not grow past the chunks that were received
+        receiver.buffer_recv(first_chunk(first).into())?;
+        receiver.buffer_recv(first_chunk(second).into())?;
+        assert_eq!(receiver.buffered_bytes, 20);
+        receiver.take_updates();
+
+        // too many concurrent streams
+        receiver.buffer_recv(first_chunk(third).into())?;
+        assert_eq!(
+            receiver.take_updates(),
+            vec![StreamUpdate::Cancelled {
+                stream_id: third,
+                direction: StreamDirection::Receive,
+            }]
+        );
+
+        // a duplicate chunk adds no bytes, so it does not go over the limit
+        receiver.buffer_recv(first_chunk(second).into())?;
+        assert!(receiver.take_updates().is_empty());
+        assert_eq!(receiver.streams.len(), 2);
+
+        // too many buffered bytes: the second chunk of the first stream would go over the limit
+        let second_chunk = chunks
+            .iter()
+            .find(|chunk| {
+                matches!(StreamChunk::from_bytes(chunk.bytes.clone()),
+                    Ok(StreamChunk::Data { stream_id, offset: 10, .. }) if stream_id == first)
+            })
+            .unwrap()
+            .clone();
+        receiver.buffer_recv(second_chunk.into())?;
+        assert_eq!(
+            receiver.take_updates(),
+            vec![StreamUpdate::Cancelled {
+                stream_id: first,
+                direction: StreamDirection::Receive,
+            }]
+        );
+        assert_eq!(receiver.streams.len(), 1);
+        assert_eq!(receiver.buffered_bytes, 10);
+        Ok(())
+    }
+}
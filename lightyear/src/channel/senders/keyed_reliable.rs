@@ -0,0 +1,86 @@
+use std::collections::{HashMap, VecDeque};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use crossbeam_channel::Receiver;
+
+use crate::channel::builder::ReliableSettings;
+use crate::channel::senders::reliable::ReliableSender;
+use crate::channel::senders::ChannelSend;
+use crate::packet::message::{FragmentData, MessageAck, MessageId, SingleData};
+use crate::shared::ping::manager::PingManager;
+use crate::shared::tick_manager::TickManager;
+use crate::shared::time_manager::TimeManager;
+
+/// Number of bytes added in front of each message: the ordering key and the sequence number of the message for that key
+pub(crate) const KEY_HEADER_BYTES: usize = 4 + 2;
+
+/// A [`ReliableSender`] where each message is tagged with an ordering key, and a sequence number that is
+/// incremented separately for each key.
+///
+/// The receiver only orders the messages that have the same key.
+pub struct KeyedReliableSender {
+    sender: ReliableSender,
+    /// Sequence number to use for the next message of each key
+    next_sequence: HashMap<u32, MessageId>,
+}
+
+impl KeyedReliableSender {
+    pub fn new(reliable_settings: ReliableSettings) -> Self {
+        Self {
+            sender: ReliableSender::new(reliable_settings),
+            next_sequence: HashMap::new(),
+        }
+    }
+
+    /// Buffer a message that will be ordered relative to the other messages with the same `key`
+    pub(crate) fn buffer_send_with_key(
+        &mut self,
+        message: Bytes,
+        priority: f32,
+        key: u32,
+    ) -> Option<MessageId> {
+        let sequence = self.next_sequence.entry(key).or_default();
+        let mut bytes = BytesMut::with_capacity(KEY_HEADER_BYTES + message.len());
+        bytes.put_u32(key);
+        bytes.put_u16(sequence.0);
+        bytes.put_slice(&message);
+        *sequence += 1;
+        self.sender.buffer_send(bytes.freeze(), priority)
+    }
+}
+
+impl ChannelSend for KeyedReliableSender {
+    fn update(
+        &mut self,
+        time_manager: &TimeManager,
+        ping_manager: &PingManager,
+        tick_manager: &TickManager,
+    ) {
+        self.sender.update(time_manager, ping_manager, tick_manager)
+    }
+
+    /// Messages that are sent without a key use the key 0
+    fn buffer_send(&mut self, message: Bytes, priority: f32) -> Option<MessageId> {
+        self.buffer_send_with_key(message, priority, 0)
+    }
+
+    fn send_packet(&mut self) -> (VecDeque<SingleData>, VecDeque<FragmentData>) {
+        self.sender.send_packet()
+    }
+
+    fn collect_messages_to_send(&mut self) {
+        self.sender.collect_messages_to_send()
+    }
+
+    fn notify_message_delivered(&mut self, message_ack: &MessageAck) {
+        self.sender.notify_message_delivered(message_ack)
+    }
+
+    fn has_messages_to_send(&self) -> bool {
+        self.sender.has_messages_to_send()
+    }
+
+    fn subscribe_acks(&mut self) -> Receiver<MessageId> {
+        self.sender.subscribe_acks()
+    }
+}
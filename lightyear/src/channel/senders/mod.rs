@@ -9,8 +9,10 @@ use std::collections::VecDeque;
 
 pub(crate) mod fragment_ack_receiver;
 pub(crate) mod fragment_sender;
+pub(crate) mod keyed_reliable;
 pub(crate) mod reliable;
 pub(crate) mod sequenced_unreliable;
+pub(crate) mod stream;
 pub(crate) mod tick_unreliable;
 pub(crate) mod unordered_unreliable;
 pub(crate) mod unordered_unreliable_with_acks;
@@ -62,5 +64,7 @@ pub enum ChannelSender {
     UnorderedUnreliable(unordered_unreliable::UnorderedUnreliableSender),
     SequencedUnreliable(sequenced_unreliable::SequencedUnreliableSender),
     Reliable(reliable::ReliableSender),
+    KeyedReliable(keyed_reliable::KeyedReliableSender),
     TickUnreliable(tick_unreliable::TickUnreliableSender),
+    Stream(stream::StreamSender),
 }
@@ -3,7 +3,7 @@ use std::collections::VecDeque;
 use std::collections::{BTreeMap, HashSet};
 
 use bytes::Bytes;
-use crossbeam_channel::Receiver;
+use crossbeam_channel::{Receiver, Sender};
 use tracing::{info, trace};
 
 use crate::channel::builder::ReliableSettings;
@@ -59,6 +59,8 @@ pub struct ReliableSender {
     /// Used to split a message into fragments if the message is too big
     fragment_sender: FragmentSender,
 
+    /// List of senders that want to be notified when a message is acked
+    ack_senders: Vec<Sender<MessageId>>,
     current_rtt: Duration,
     current_time: WrappedTime,
 }
@@ -73,10 +75,18 @@ impl ReliableSender {
             fragmented_messages_to_send: Default::default(),
             message_ids_to_send: Default::default(),
             fragment_sender: FragmentSender::new(),
+            ack_senders: Vec::new(),
             current_rtt: Duration::default(),
             current_time: WrappedTime::default(),
         }
     }
+
+    /// Notify the subscribers that every part of a message was acked
+    fn notify_acked(&self, message_id: MessageId) {
+        for sender in &self.ack_senders {
+            sender.send(message_id).unwrap();
+        }
+    }
 }
 
 // Stragegy:
@@ -243,6 +253,7 @@ impl ChannelSend for ReliableSender {
                         )
                     }
                     self.unacked_messages.remove(&message_ack.message_id);
+                    self.notify_acked(message_ack.message_id);
                 }
                 UnackedMessage::Fragmented(fragment_acks) => {
                     let Some(fragment_id) = message_ack.fragment_id else {
@@ -254,6 +265,7 @@ impl ChannelSend for ReliableSender {
                         // all fragments were acked
                         if fragment_acks.iter().all(|f| f.acked) {
                             self.unacked_messages.remove(&message_ack.message_id);
+                            self.notify_acked(message_ack.message_id);
                         }
                     }
                 }
@@ -265,8 +277,11 @@ impl ChannelSend for ReliableSender {
         !self.single_messages_to_send.is_empty() || !self.fragmented_messages_to_send.is_empty()
     }
 
+    /// Create a new receiver that will receive a message id when every part of a message is acked
     fn subscribe_acks(&mut self) -> Receiver<MessageId> {
-        todo!()
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.ack_senders.push(sender);
+        receiver
     }
 }
 
@@ -289,6 +304,7 @@ mod tests {
         });
         sender.current_rtt = Duration::from_millis(100);
         sender.current_time = WrappedTime::new(0);
+        let acks = sender.subscribe_acks();
 
         // Buffer a new message
         let message1 = Bytes::from("hello");
@@ -319,6 +335,7 @@ mod tests {
             fragment_id: None,
         });
         assert_eq!(sender.unacked_messages.len(), 0);
+        assert_eq!(acks.try_recv(), Ok(MessageId(0)));
 
         // Advance by a time that is above the resend threshold
         sender.current_time += Duration::from_millis(200);
@@ -0,0 +1,339 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use bevy::utils::Duration;
+use bytes::Bytes;
+use crossbeam_channel::{Receiver, Sender};
+use tracing::{debug, trace};
+
+use crate::channel::builder::StreamSettings;
+use crate::channel::senders::ChannelSend;
+use crate::channel::stream::{push_stream_update, StreamChunk, StreamDirection, StreamUpdate};
+use crate::packet::message::{FragmentData, MessageAck, MessageId, SingleData};
+use crate::shared::ping::manager::PingManager;
+use crate::shared::tick_manager::TickManager;
+use crate::shared::time_manager::{TimeManager, WrappedTime};
+
+/// A payload that is being streamed
+struct OutgoingStream {
+    bytes: Bytes,
+    /// Offset in the payload of the next chunk to send
+    next_offset: usize,
+    /// True if every chunk of the payload has been sent at least once
+    sent_all: bool,
+    /// Number of bytes of the payload that have been acked
+    acked_bytes: u64,
+    priority: f32,
+}
+
+/// A chunk that has not been acked yet
+struct UnackedChunk {
+    stream_id: MessageId,
+    /// The encoded chunk
+    bytes: Bytes,
+    /// Number of bytes of the payload contained in the chunk
+    payload_len: usize,
+    priority: f32,
+    /// If None: this chunk has never been sent before
+    last_sent: Option<WrappedTime>,
+}
+
+/// A sender that splits the payloads into chunks and streams them reliably, with a limit on the number of chunks in flight
+pub struct StreamSender {
+    settings: StreamSettings,
+    /// Streams that are not fully acked yet, with the id of the stream
+    streams: BTreeMap<MessageId, OutgoingStream>,
+    /// Chunks that were sent but not acked yet, with the message id of the chunk
+    unacked_chunks: BTreeMap<MessageId, UnackedChunk>,
+    /// Id to use for the next stream
+    next_stream_id: MessageId,
+    /// Message id to use for the next chunk
+    next_chunk_id: MessageId,
+    /// list of chunks that we want to fit into packets and send
+    chunks_to_send: VecDeque<SingleData>,
+    /// Progress of the streams since the last time the updates were read
+    updates: Vec<StreamUpdate>,
+    /// List of senders that want to be notified when a stream is fully acked
+    ack_senders: Vec<Sender<MessageId>>,
+    current_rtt: Duration,
+    current_time: WrappedTime,
+}
+
+impl StreamSender {
+    pub(crate) fn new(settings: StreamSettings) -> Self {
+        Self {
+            settings,
+            streams: BTreeMap::new(),
+            unacked_chunks: BTreeMap::new(),
+            next_stream_id: MessageId(0),
+            next_chunk_id: MessageId(0),
+            chunks_to_send: VecDeque::new(),
+            updates: Vec::new(),
+            ack_senders: Vec::new(),
+            current_rtt: Duration::default(),
+            current_time: WrappedTime::default(),
+        }
+    }
+
+    /// Stop sending the stream `stream_id`, and tell the receiver to discard the bytes it received.
+    ///
+    /// Returns false if the stream was already fully acked (or cancelled).
+    pub(crate) fn cancel(&mut self, stream_id: MessageId) -> bool {
+        if self.streams.remove(&stream_id).is_none() {
+            return false;
+        }
+        debug!(?stream_id, "Cancelling stream");
+        self.unacked_chunks
+            .retain(|_, chunk| chunk.stream_id != stream_id);
+        let chunk_id = self.next_chunk_id;
+        self.next_chunk_id += 1;
+        self.unacked_chunks.insert(
+            chunk_id,
+            UnackedChunk {
+                stream_id,
+                bytes: StreamChunk::Cancel { stream_id }.to_bytes(),
+                payload_len: 0,
+                priority: 1.0,
+                last_sent: None,
+            },
+        );
+        push_stream_update(
+            &mut self.updates,
+            StreamUpdate::Cancelled {
+                stream_id,
+                direction: StreamDirection::Send,
+            },
+        );
+        true
+    }
+
+    /// Take the updates of the streams since the last call
+    pub(crate) fn take_updates(&mut self) -> Vec<StreamUpdate> {
+        std::mem::take(&mut self.updates)
+    }
+}
+
+impl ChannelSend for StreamSender {
+    fn update(&mut self, time_manager: &TimeManager, ping_manager: &PingManager, _: &TickManager) {
+        self.current_time = time_manager.current_time();
+        self.current_rtt = ping_manager.rtt();
+    }
+
+    /// Start streaming a new payload. The returned MessageId identifies the stream.
+    fn buffer_send(&mut self, message: Bytes, priority: f32) -> Option<MessageId> {
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 1;
+        let total = message.len() as u64;
+        self.streams.insert(
+            stream_id,
+            OutgoingStream {
+                bytes: message,
+                next_offset: 0,
+                sent_all: false,
+                acked_bytes: 0,
+                priority,
+            },
+        );
+        push_stream_update(
+            &mut self.updates,
+            StreamUpdate::Progress {
+                stream_id,
+                direction: StreamDirection::Send,
+                bytes: 0,
+                total,
+            },
+        );
+        Some(stream_id)
+    }
+
+    fn send_packet(&mut self) -> (VecDeque<SingleData>, VecDeque<FragmentData>) {
+        (std::mem::take(&mut self.chunks_to_send), VecDeque::new())
+    }
+
+    /// Collect the chunks that need to be resent because they were not acked in time,
+    /// then the new chunks that fit in the window of chunks in flight
+    fn collect_messages_to_send(&mut self) {
+        let resend_delay =
+            chrono::Duration::from_std(self.settings.reliable.resend_delay(self.current_rtt))
+                .unwrap();
+        for (chunk_id, chunk) in self.unacked_chunks.iter_mut() {
+            let should_send = match chunk.last_sent {
+                None => true,
+                Some(last_sent) => self.current_time - last_sent > resend_delay,
+            };
+            if should_send {
+                trace!(?chunk_id, stream_id = ?chunk.stream_id, "Sending stream chunk again");
+                self.chunks_to_send.push_back(SingleData::new(
+                    Some(*chunk_id),
+                    chunk.bytes.clone(),
+                    chunk.priority,
+                ));
+                chunk.last_sent = Some(self.current_time);
+            }
+        }
+
+        let chunk_size = self.settings.chunk_size();
+        for (stream_id, stream) in self.streams.iter_mut() {
+            while !stream.sent_all && self.unacked_chunks.len() < self.settings.max_chunks_in_flight
+            {
+                let start = stream.next_offset;
+                let end = std::cmp::min(start + chunk_size, stream.bytes.len());
+                let chunk = StreamChunk::Data {
+                    stream_id: *stream_id,
+                    total_len: stream.bytes.len() as u64,
+                    offset: start as u64,
+                    bytes: stream.bytes.slice(start..end),
+                }
+                .to_bytes();
+                let chunk_id = self.next_chunk_id;
+                self.next_chunk_id += 1;
+                self.chunks_to_send.push_back(SingleData::new(
+                    Some(chunk_id),
+                    chunk.clone(),
+                    stream.priority,
+                ));
+                self.unacked_chunks.insert(
+                    chunk_id,
+                    UnackedChunk {
+                        stream_id: *stream_id,
+                        bytes: chunk,
+                        payload_len: end - start,
+                        priority: stream.priority,
+                        last_sent: Some(self.current_time),
+                    },
+                );
+                stream.next_offset = end;
+                stream.sent_all = end == stream.bytes.len();
+            }
+        }
+    }
+
+    fn notify_message_delivered(&mut self, message_ack: &MessageAck) {
+        let Some(chunk) = self.unacked_chunks.remove(&message_ack.message_id) else {
+            return;
+        };
+        // the stream could have been cancelled
+        let Some(stream) = self.streams.get_mut(&chunk.stream_id) else {
+            return;
+        };
+        stream.acked_bytes += chunk.payload_len as u64;
+        let total = stream.bytes.len() as u64;
+        push_stream_update(
+            &mut self.updates,
+            StreamUpdate::Progress {
+                stream_id: chunk.stream_id,
+                direction: StreamDirection::Send,
+                bytes: stream.acked_bytes,
+                total,
+            },
+        );
+        if stream.sent_all && stream.acked_bytes == total {
+            trace!(stream_id = ?chunk.stream_id, "Stream was fully acked");
+            self.streams.remove(&chunk.stream_id);
+            for sender in &self.ack_senders {
+                sender.send(chunk.stream_id).unwrap();
+            }
+        }
+    }
+
+    fn has_messages_to_send(&self) -> bool {
+        !self.chunks_to_send.is_empty()
+    }
+
+    /// Create a new receiver that will receive the id of a stream when all its chunks are acked
+    fn subscribe_acks(&mut self) -> Receiver<MessageId> {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        self.ack_senders.push(sender);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ack(sender: &mut StreamSender, message_id: MessageId) {
+        sender.notify_message_delivered(&MessageAck {
+            message_id,
+            fragment_id: None,
+        });
+    }
+
+    #[test]
+    fn test_stream_sender_flow_control() {
+        let mut sender = StreamSender::new(StreamSettings {
+            chunk_size: 10,
+            max_chunks_in_flight: 2,
+            ..Default::default()
+        });
+        let acks = sender.subscribe_acks();
+        let stream_id = sender.buffer_send(Bytes::from(vec![1u8; 35]), 1.0).unwrap();
+
+        // only 2 chunks can be in flight
+        sender.collect_messages_to_send();
+        let (chunks, _) = sender.send_packet();
+        assert_eq!(chunks.len(), 2);
+        sender.collect_messages_to_send();
+        assert!(!sender.has_messages_to_send());
+
+        // the first chunk is acked: we can send the third chunk
+        ack(&mut sender, chunks[0].id.unwrap());
+        sender.collect_messages_to_send();
+        let (new_chunks, _) = sender.send_packet();
+        assert_eq!(new_chunks.len(), 1);
+        assert_eq!(
+            StreamChunk::from_bytes(new_chunks[0].bytes.clone()).unwrap(),
+            StreamChunk::Data {
+                stream_id,
+                total_len: 35,
+                offset: 20,
+                bytes: Bytes::from(vec![1u8; 10]),
+            }
+        );
+        assert_eq!(
+            sender.take_updates(),
+            vec![StreamUpdate::Progress {
+                stream_id,
+                direction: StreamDirection::Send,
+                bytes: 10,
+                total: 35,
+            }]
+        );
+
+        // the second chunk is lost: only that chunk is sent again
+        sender.current_time += Duration::from_millis(100);
+        ack(&mut sender, new_chunks[0].id.unwrap());
+        sender.collect_messages_to_send();
+        let (resent_chunks, _) = sender.send_packet();
+        assert_eq!(resent_chunks.len(), 2);
+        assert_eq!(resent_chunks[0], chunks[1]);
+
+        // ack everything
+        for chunk in resent_chunks {
+            ack(&mut sender, chunk.id.unwrap());
+        }
+        assert!(sender.streams.is_empty());
+        assert_eq!(acks.try_recv(), Ok(stream_id));
+    }
+
+    #[test]
+    fn test_stream_sender_cancel() {
+        let mut sender = StreamSender::new(StreamSettings {
+            chunk_size: 10,
+            ..Default::default()
+        });
+        let stream_id = sender.buffer_send(Bytes::from(vec![1u8; 35]), 1.0).unwrap();
+        sender.collect_messages_to_send();
+        let (chunks, _) = sender.send_packet();
+        assert_eq!(chunks.len(), 4);
+
+        assert!(sender.cancel(stream_id));
+        assert!(!sender.cancel(stream_id));
+        sender.collect_messages_to_send();
+        let (chunks, _) = sender.send_packet();
+        assert_eq!(
+            StreamChunk::from_bytes(chunks[0].bytes.clone()).unwrap(),
+            StreamChunk::Cancel { stream_id }
+        );
+        assert_eq!(sender.unacked_chunks.len(), 1);
+    }
+}
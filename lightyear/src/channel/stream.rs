@@ -0,0 +1,186 @@
+/*! Wire format and events of the [`ChannelMode::Streaming`](crate::channel::builder::ChannelMode::Streaming) channels
+
+A payload sent on a streaming channel is split into chunks. Every chunk is sent as an individual reliable message,
+so only the chunks that were lost are sent again. Each chunk starts with a small header:
+- the kind of chunk (data or cancellation)
+- the id of the stream (the [`MessageId`] returned when the payload was buffered)
+- for data chunks: the total length of the payload and the offset of the chunk inside the payload
+*/
+use anyhow::{anyhow, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::packet::message::MessageId;
+
+const DATA_CHUNK: u8 = 0;
+const CANCEL_CHUNK: u8 = 1;
+
+/// Number of bytes of the header of a data chunk
+pub(crate) const STREAM_HEADER_BYTES: usize = 1 + 2 + 8 + 8;
+
+/// Whether a stream is sent or received by the local peer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamDirection {
+    Send,
+    Receive,
+}
+
+/// Change in the state of a stream, reported by the streaming senders and receivers
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum StreamUpdate {
+    /// `bytes` out of `total` bytes of the payload have been acked (when sending) or received (when receiving)
+    Progress {
+        stream_id: MessageId,
+        direction: StreamDirection,
+        bytes: u64,
+        total: u64,
+    },
+    /// The stream was cancelled by the sender, or rejected by the receiver
+    Cancelled {
+        stream_id: MessageId,
+        direction: StreamDirection,
+    },
+}
+
+impl StreamUpdate {
+    pub(crate) fn stream_id(&self) -> MessageId {
+        match self {
+            StreamUpdate::Progress { stream_id, .. } => *stream_id,
+            StreamUpdate::Cancelled { stream_id, .. } => *stream_id,
+        }
+    }
+}
+
+/// Add an update to the list of pending updates.
+///
+/// Only the latest progress of a stream is kept, so that a stream emits at most one progress event per frame.
+pub(crate) fn push_stream_update(updates: &mut Vec<StreamUpdate>, update: StreamUpdate) {
+    if let StreamUpdate::Progress { stream_id, .. } = update {
+        if let Some(last) = updates
+            .iter_mut()
+            .rev()
+            .find(|u| u.stream_id() == stream_id)
+        {
+            if matches!(last, StreamUpdate::Progress { .. }) {
+                *last = update;
+                return;
+            }
+        }
+    }
+    updates.push(update);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum StreamChunk {
+    Data {
+        stream_id: MessageId,
+        total_len: u64,
+        offset: u64,
+        bytes: Bytes,
+    },
+    Cancel {
+        stream_id: MessageId,
+    },
+}
+
+impl StreamChunk {
+    pub(crate) fn to_bytes(&self) -> Bytes {
+        match self {
+            StreamChunk::Data {
+                stream_id,
+                total_len,
+                offset,
+                bytes,
+            } => {
+                let mut buf = BytesMut::with_capacity(STREAM_HEADER_BYTES + bytes.len());
+                buf.put_u8(DATA_CHUNK);
+                buf.put_u16(stream_id.0);
+                buf.put_u64(*total_len);
+                buf.put_u64(*offset);
+                buf.put_slice(bytes);
+                buf.freeze()
+            }
+            StreamChunk::Cancel { stream_id } => {
+                let mut buf = BytesMut::with_capacity(3);
+                buf.put_u8(CANCEL_CHUNK);
+                buf.put_u16(stream_id.0);
+                buf.freeze()
+            }
+        }
+    }
+
+    pub(crate) fn from_bytes(mut bytes: Bytes) -> Result<Self> {
+        if bytes.remaining() < 3 {
+            return Err(anyhow!("stream chunk is too short"));
+        }
+        let kind = bytes.get_u8();
+        let stream_id = MessageId(bytes.get_u16());
+        match kind {
+            DATA_CHUNK => {
+                if bytes.remaining() < 16 {
+                    return Err(anyhow!("stream chunk is too short"));
+                }
+                let total_len = bytes.get_u64();
+                let offset = bytes.get_u64();
+                if offset.saturating_add(bytes.len() as u64) > total_len {
+                    return Err(anyhow!("stream chunk is out of the bounds of the payload"));
+                }
+                Ok(StreamChunk::Data {
+                    stream_id,
+                    total_len,
+                    offset,
+                    bytes,
+                })
+            }
+            CANCEL_CHUNK => Ok(StreamChunk::Cancel { stream_id }),
+            _ => Err(anyhow!("unknown stream chunk kind {kind}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_serde() -> Result<()> {
+        let chunk = StreamChunk::Data {
+            stream_id: MessageId(3),
+            total_len: 10,
+            offset: 5,
+            bytes: Bytes::from_static(b"hello"),
+        };
+        let bytes = chunk.to_bytes();
+        assert_eq!(bytes.len(), STREAM_HEADER_BYTES + 5);
+        assert_eq!(StreamChunk::from_bytes(bytes)?, chunk);
+
+        let chunk = StreamChunk::Cancel {
+            stream_id: MessageId(3),
+        };
+        assert_eq!(StreamChunk::from_bytes(chunk.to_bytes())?, chunk);
+
+        // the chunk does not fit in the payload
+        let chunk = StreamChunk::Data {
+            stream_id: MessageId(3),
+            total_len: 4,
+            offset: 0,
+            bytes: Bytes::from_static(b"hello"),
+        };
+        assert!(StreamChunk::from_bytes(chunk.to_bytes()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_push_stream_update() {
+        let mut updates = vec![];
+        let progress = |stream_id, bytes| StreamUpdate::Progress {
+            stream_id: MessageId(stream_id),
+            direction: StreamDirection::Send,
+            bytes,
+            total: 10,
+        };
+        push_stream_update(&mut updates, progress(0, 1));
+        push_stream_update(&mut updates, progress(1, 1));
+        push_stream_update(&mut updates, progress(0, 5));
+        assert_eq!(updates, vec![progress(0, 5), progress(1, 1)]);
+    }
+}
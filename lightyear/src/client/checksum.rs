@@ -0,0 +1,160 @@
+//! # Checksums
+//!
+//! Compare the checksums of the components sent by the server (see [`server::checksum`](crate::server::checksum))
+//! with the state of the client for the same tick, and emit a [`DesyncEvent::Entity`] if they differ.
+//!
+//! For each entity, the client's state at the tick of the checksum is:
+//! - the value of the confirmed entity, if the latest update that we received for that entity is for that tick
+//! - otherwise, the value stored in the [`PredictionHistory`] of the predicted entity for that tick
+//!
+//! If neither is available (for example if the entity is not predicted and didn't change during that tick),
+//! the checksum is ignored.
+use std::marker::PhantomData;
+
+use bevy::prelude::{
+    App, Entity, EventWriter, IntoSystemConfigs, IntoSystemSetConfigs, Plugin, PreUpdate, Query,
+    Res, ResMut, Resource, SystemSet,
+};
+use bevy::utils::HashMap;
+use tracing::{debug, warn};
+
+use crate::_reexport::{ClientMarker, FromType, WriteWordBuffer};
+use crate::client::components::{Confirmed, SyncComponent};
+use crate::client::connection::ConnectionManager;
+use crate::client::prediction::predicted_history::{ComponentState, PredictionHistory};
+use crate::protocol::Protocol;
+use crate::serialize::writer::WriteBuffer;
+use crate::shared::checksum::{component_checksum, DesyncEvent};
+use crate::shared::sets::InternalMainSet;
+use crate::shared::tick_manager::Tick;
+
+/// Components whose checksum didn't match during the current frame
+#[derive(Resource)]
+struct ChecksumMismatches<P: Protocol> {
+    mismatches: Vec<(Tick, Entity, P::ComponentKinds)>,
+}
+
+impl<P: Protocol> Default for ChecksumMismatches<P> {
+    fn default() -> Self {
+        Self {
+            mismatches: Vec::new(),
+        }
+    }
+}
+
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+enum ChecksumSet {
+    Check,
+    Emit,
+}
+
+/// Plugin that compares the checksums of the component `C` sent by the server with the state of the client
+pub struct ChecksumPlugin<P: Protocol, C> {
+    _marker: PhantomData<(P, C)>,
+}
+
+impl<P: Protocol, C> Default for ChecksumPlugin<P, C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol, C: SyncComponent> Plugin for ChecksumPlugin<P, C>
+where
+    P::ComponentKinds: FromType<C>,
+{
+    fn build(&self, app: &mut App) {
+        // the systems shared by every component only need to be added once
+        if !app.world.contains_resource::<ChecksumMismatches<P>>() {
+            // RESOURCES
+            app.init_resource::<ChecksumMismatches<P>>();
+            // EVENTS
+            app.add_event::<DesyncEvent<P>>();
+            // SETS
+            app.configure_sets(
+                PreUpdate,
+                (ChecksumSet::Check, ChecksumSet::Emit)
+                    .chain()
+                    .after(InternalMainSet::<ClientMarker>::Receive),
+            );
+            // SYSTEMS
+            app.add_systems(PreUpdate, emit_desync_events::<P>.in_set(ChecksumSet::Emit));
+        }
+        app.add_systems(
+            PreUpdate,
+            check_checksums::<P, C>.in_set(ChecksumSet::Check),
+        );
+    }
+}
+
+/// Compare the checksums of the component `C` received this frame with the state of the client
+fn check_checksums<P: Protocol, C: SyncComponent>(
+    connection: Res<ConnectionManager<P>>,
+    mut mismatches: ResMut<ChecksumMismatches<P>>,
+    confirmed_query: Query<(Option<&C>, Option<&Confirmed>)>,
+    predicted_query: Query<&PredictionHistory<C>>,
+) where
+    P::ComponentKinds: FromType<C>,
+{
+    let kind = <P::ComponentKinds as FromType<C>>::from_type();
+    let mut writer = WriteWordBuffer::with_capacity(64);
+    for message in connection.received_checksums.iter() {
+        let tick = message.tick;
+        for (remote_entity, _, checksum) in message.checksums.iter().filter(|(_, k, _)| *k == kind)
+        {
+            let Some(&entity) = connection
+                .replication_receiver
+                .remote_entity_map
+                .get_local(*remote_entity)
+            else {
+                continue;
+            };
+            let Ok((component, confirmed)) = confirmed_query.get(entity) else {
+                continue;
+            };
+            let local_checksum = if connection.replication_receiver.get_confirmed_tick(entity)
+                == Some(tick)
+            {
+                component.map(|c| component_checksum(&kind, c, &mut writer))
+            } else {
+                let Some(state) = confirmed
+                    .and_then(|confirmed| confirmed.predicted)
+                    .and_then(|predicted| predicted_query.get(predicted).ok())
+                    .and_then(|history| history.get(tick))
+                else {
+                    continue;
+                };
+                match state {
+                    ComponentState::Updated(c) => Some(component_checksum(&kind, c, &mut writer)),
+                    ComponentState::Removed => None,
+                }
+            };
+            if local_checksum != Some(*checksum) {
+                debug!(?tick, ?entity, ?kind, "Checksum mismatch");
+                mismatches.mismatches.push((tick, entity, kind));
+            }
+        }
+    }
+}
+
+/// Emit one [`DesyncEvent::Entity`] per tick and entity whose state differs from the server
+fn emit_desync_events<P: Protocol>(
+    mut mismatches: ResMut<ChecksumMismatches<P>>,
+    mut events: EventWriter<DesyncEvent<P>>,
+) {
+    let mut desyncs: HashMap<(Tick, Entity), Vec<P::ComponentKinds>> = HashMap::default();
+    for (tick, entity, kind) in mismatches.mismatches.drain(..) {
+        desyncs.entry((tick, entity)).or_default().push(kind);
+    }
+    for ((tick, entity), mut kinds) in desyncs {
+        kinds.sort();
+        warn!(?tick, ?entity, ?kinds, "Desync detected");
+        events.send(DesyncEvent::Entity {
+            tick,
+            entity,
+            kinds,
+        });
+    }
+}
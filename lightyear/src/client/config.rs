@@ -11,7 +11,8 @@ use crate::client::prediction::plugin::PredictionConfig;
 use crate::client::replication::ReplicationConfig;
 use crate::client::sync::SyncConfig;
 use crate::connection::client::NetConfig;
-use crate::shared::config::{Mode, SharedConfig};
+use crate::packet::congestion::CongestionConfig;
+use crate::shared::config::{Mode, ResumptionConfig, SharedConfig};
 use crate::shared::ping::manager::PingConfig;
 
 #[derive(Clone, Reflect)]
@@ -56,6 +57,9 @@ pub struct PacketConfig {
     pub send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// Congestion control: estimate the available bandwidth from the measured packet loss and RTT,
+    /// and limit the amount of bytes sent accordingly
+    pub congestion: CongestionConfig,
 }
 
 impl Default for PacketConfig {
@@ -64,6 +68,7 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            congestion: CongestionConfig::default(),
         }
     }
 }
@@ -84,6 +89,11 @@ impl PacketConfig {
         self.bandwidth_cap_enabled = true;
         self
     }
+
+    pub fn with_congestion_control(mut self, congestion: CongestionConfig) -> Self {
+        self.congestion = congestion;
+        self
+    }
 }
 
 /// The configuration object that lets you create a `ClientPlugin` with the desired settings.
@@ -113,4 +123,5 @@ pub struct ClientConfig {
     pub prediction: PredictionConfig,
     pub interpolation: InterpolationConfig,
     pub replication: ReplicationConfig,
+    pub resumption: ResumptionConfig,
 }
@@ -6,30 +6,40 @@ use bevy::prelude::{Entity, Local, Resource, World};
 use bevy::reflect::Reflect;
 use bevy::utils::Duration;
 use serde::Serialize;
-use tracing::{debug, info, trace, trace_span, warn};
+use tracing::{debug, error, info, trace, trace_span, warn};
 
 use crate::_reexport::{ClientMarker, EntityUpdatesChannel, PingChannel, ReplicationSend};
+use crate::channel::builder::DefaultUnorderedUnreliableChannel;
 use crate::channel::senders::ChannelSend;
+use crate::client::components::Confirmed;
 use crate::client::config::PacketConfig;
-use crate::client::message::ClientMessage;
+use crate::client::message::{ClientMessage, HandshakeMessage};
 use crate::client::sync::SyncConfig;
+use crate::connection::server::DeniedReason;
 use crate::inputs::native::input_buffer::InputBuffer;
+use crate::packet::message::MessageId;
 use crate::packet::message_manager::MessageManager;
 use crate::packet::packet::Packet;
 use crate::packet::packet_manager::Payload;
 use crate::prelude::{Channel, ChannelKind, ClientId, Message, NetworkTarget};
 use crate::protocol::channel::ChannelRegistry;
+use crate::protocol::dynamic::{DynamicProtocol, RawMessage};
+use crate::protocol::schema::ProtocolSchema;
 use crate::protocol::Protocol;
 use crate::serialize::reader::ReadBuffer;
 use crate::server::message::ServerMessage;
+use crate::shared::checksum::ChecksumMessage;
+use crate::shared::config::ResumptionSecret;
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::ping::manager::{PingConfig, PingManager};
 use crate::shared::ping::message::SyncMessage;
-use crate::shared::replication::components::{Replicate, ReplicationGroupId};
+use crate::shared::replication::authority::{AuthorityMessage, ClientAuthority, HasAuthority};
+use crate::shared::replication::components::{Replicate, ReplicationGroupId, ShouldBePredicted};
 use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
 use crate::shared::replication::ReplicationMessage;
 use crate::shared::replication::ReplicationMessageData;
+use crate::shared::rpc::{Request, RequestId, RequestMessage, ResponseMessage, RpcManager};
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
 use crate::shared::time_manager::TimeManager;
@@ -63,6 +73,26 @@ pub struct ConnectionManager<P: Protocol> {
 
     pub(crate) ping_manager: PingManager,
     pub(crate) sync_manager: SyncManager,
+    /// Checksums of the server's components received this frame
+    pub(crate) received_checksums: Vec<ChecksumMessage<P::ComponentKinds>>,
+    /// Entities over which the server granted us authority
+    pub(crate) authority: ClientAuthority<P::ComponentKinds>,
+    /// Requests sent to the server that are waiting for a response
+    pub(crate) rpc: RpcManager,
+    /// Secret issued by the server, that we can use to resume the session if we get disconnected
+    pub(crate) resumption_secret: Option<ResumptionSecret>,
+    /// True if the server answered the handshake of the current connection
+    pub(crate) handshake_done: bool,
+    /// Schema of the protocol, without the types of the [`DynamicProtocol`]
+    protocol_schema: ProtocolSchema,
+    /// Fingerprint of our protocol, sent in the handshake so that the server can check that it uses the same protocol
+    protocol_fingerprint: u64,
+    /// Messages and components registered on the [`App`](bevy::prelude::App)
+    pub(crate) dynamic_protocol: DynamicProtocol,
+    /// Reason sent by the server if it refused our handshake
+    pub(crate) denied_reason: Option<DeniedReason>,
+    /// The other clients connected to the server, if the server sent a new list since the last frame
+    pub(crate) received_players: Option<Vec<ClientId>>,
     // TODO: maybe don't do any replication until connection is synced?
 }
 
@@ -73,6 +103,7 @@ impl<P: Protocol> ConnectionManager<P> {
         sync_config: SyncConfig,
         ping_config: PingConfig,
         input_delay_ticks: u16,
+        protocol_schema: ProtocolSchema,
     ) -> Self {
         // create the message manager and the channels
         let mut message_manager = MessageManager::new(channel_registry, packet_config.into());
@@ -96,9 +127,29 @@ impl<P: Protocol> ConnectionManager<P> {
             ping_manager: PingManager::new(ping_config),
             sync_manager: SyncManager::new(sync_config, input_delay_ticks),
             events: ConnectionEvents::default(),
+            received_checksums: Vec::new(),
+            authority: ClientAuthority::default(),
+            rpc: RpcManager::default(),
+            resumption_secret: None,
+            handshake_done: false,
+            protocol_fingerprint: protocol_schema.fingerprint(),
+            protocol_schema,
+            dynamic_protocol: DynamicProtocol::default(),
+            denied_reason: None,
+            received_players: None,
         }
     }
 
+    /// Use the types registered in the [`DynamicProtocol`] (they are part of the protocol fingerprint)
+    pub(crate) fn set_dynamic_protocol(&mut self, dynamic_protocol: &DynamicProtocol) {
+        self.protocol_fingerprint = self
+            .protocol_schema
+            .clone()
+            .with_dynamic_protocol(dynamic_protocol)
+            .fingerprint();
+        self.dynamic_protocol = dynamic_protocol.clone();
+    }
+
     #[doc(hidden)]
     /// Whether or not the connection is synced with the server
     pub fn is_synced(&self) -> bool {
@@ -122,16 +173,41 @@ impl<P: Protocol> ConnectionManager<P> {
         self.events.clear();
     }
 
+    /// Returns the entity to use when replicating the component `kind` of `entity` to the server.
+    ///
+    /// For entities over which the server granted us authority, this is the server entity;
+    /// returns None if we don't have authority over that component.
+    fn authority_entity(&self, entity: Entity, kind: &P::ComponentKinds) -> Option<Entity> {
+        match self.authority.get(entity) {
+            Some((server_entity, kinds)) => kinds.contains(kind).then_some(*server_entity),
+            None => Some(entity),
+        }
+    }
+
     pub(crate) fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager) {
         self.message_manager
             .update(time_manager, &self.ping_manager, tick_manager);
         self.ping_manager.update(time_manager);
+        self.rpc.update(time_manager.current_time());
 
         // (we update the sync manager in POST_UPDATE)
     }
 
+    /// Bandwidth (in bytes per second) of the link to the server, estimated by the congestion control.
+    ///
+    /// Returns None if congestion control is not enabled in the [`PacketConfig`](crate::client::config::PacketConfig)
+    pub fn estimated_bandwidth(&self) -> Option<u32> {
+        self.message_manager.estimated_bandwidth()
+    }
+
     /// Send a message to the server
-    pub fn send_message<C: Channel, M: Message>(&mut self, message: M) -> Result<()>
+    ///
+    /// Returns the [`MessageId`] of the message, if the channel assigns ids to its messages.
+    /// If the channel watches acks (every [`ChannelMode`](crate::channel::builder::ChannelMode) except `UnorderedUnreliable`,
+    /// `SequencedUnreliable` and `TickBuffered`),
+    /// a [`MessageAckEvent`](crate::client::events::MessageAckEvent) or a [`MessageLostEvent`](crate::client::events::MessageLostEvent)
+    /// with the same id is emitted once we know if the message was delivered.
+    pub fn send_message<C: Channel, M: Message>(&mut self, message: M) -> Result<Option<MessageId>>
     where
         P::Message: From<M>,
     {
@@ -141,12 +217,24 @@ impl<P: Protocol> ConnectionManager<P> {
         self.buffer_message(message.into(), channel, NetworkTarget::None)
     }
 
+    /// Send a message that was registered in the [`DynamicProtocol`] to the server
+    pub fn send_dynamic_message<C: Channel, M: Message + Serialize>(
+        &mut self,
+        message: M,
+    ) -> Result<Option<MessageId>> {
+        let message = self.dynamic_protocol.raw_message(&message)?;
+        self.send_message::<C, RawMessage>(message)
+    }
+
     /// Send a message to the server, the message should be re-broadcasted according to the `target`
+    ///
+    /// The server relays the message in the same frame that it receives it (unless the message is rejected by the
+    /// server's [`RelayFilter`](crate::server::connection::RelayFilter)). The server also receives the message.
     pub fn send_message_to_target<C: Channel, M: Message>(
         &mut self,
         message: M,
         target: NetworkTarget,
-    ) -> Result<()>
+    ) -> Result<Option<MessageId>>
     where
         P::Message: From<M>,
     {
@@ -154,12 +242,82 @@ impl<P: Protocol> ConnectionManager<P> {
         self.buffer_message(message.into(), channel, target)
     }
 
+    /// Send a message to the server on the [`ChannelMode::KeyedOrderedReliable`](crate::channel::builder::ChannelMode::KeyedOrderedReliable)
+    /// channel `C`. The message is only ordered relative to the other messages sent with the same `key`.
+    pub fn send_message_with_key<C: Channel, M: Message>(
+        &mut self,
+        message: M,
+        key: u32,
+    ) -> Result<Option<MessageId>>
+    where
+        P::Message: From<M>,
+    {
+        let channel = ChannelKind::of::<C>();
+        let channel_name = self
+            .message_manager
+            .channel_registry
+            .name(&channel)
+            .unwrap_or("unknown")
+            .to_string();
+        let message = ClientMessage::<P>::Message(message.into(), NetworkTarget::None);
+        message.emit_send_logs(&channel_name);
+        let message_id = self
+            .message_manager
+            .buffer_send_with_key(message, channel, key)?;
+        if let Some(message_id) = message_id {
+            self.message_manager.track_delivery(channel, message_id);
+        }
+        Ok(message_id)
+    }
+
+    /// Cancel a stream that is being sent on the [`ChannelMode::Streaming`](crate::channel::builder::ChannelMode::Streaming) channel `C`.
+    /// The server discards the bytes it received and emits a [`StreamCancelledEvent`](crate::server::events::StreamCancelledEvent).
+    ///
+    /// `stream_id` is the id of the stream in the [`StreamProgressEvent`](crate::client::events::StreamProgressEvent)s.
+    /// Returns false if the stream was already fully received by the server.
+    pub fn cancel_stream<C: Channel>(&mut self, stream_id: MessageId) -> Result<bool> {
+        self.message_manager
+            .cancel_stream(ChannelKind::of::<C>(), stream_id)
+    }
+
+    /// Send a [`Request`] to the server on the channel `C`
+    ///
+    /// The response of the server is emitted as a [`ResponseEvent`](crate::client::events::ResponseEvent) with the
+    /// returned [`RequestId`]; the [`RpcPlugin`](crate::client::rpc::RpcPlugin) must be added for the request type `R`.
+    pub fn send_request<C: Channel, R: Request>(&mut self, request: R) -> Result<RequestId>
+    where
+        P::Message: From<RequestMessage<R>>,
+    {
+        let request_id = self.rpc.new_request::<R>();
+        let message = RequestMessage::new(request_id, request);
+        if let Err(e) = self.send_message::<C, _>(message) {
+            self.rpc.cancel_request(request_id);
+            return Err(e);
+        }
+        Ok(request_id)
+    }
+
+    /// Respond to a [`Request`] received from the server, on the channel `C`
+    ///
+    /// `request_id` is the id of the [`RequestMessage`] that was received.
+    pub fn send_response<C: Channel, R: Request>(
+        &mut self,
+        request_id: RequestId,
+        response: R::Response,
+    ) -> Result<()>
+    where
+        P::Message: From<ResponseMessage<R::Response>>,
+    {
+        self.send_message::<C, _>(ResponseMessage::new(request_id, response))
+            .map(|_| ())
+    }
+
     pub(crate) fn buffer_message(
         &mut self,
         message: P::Message,
         channel: ChannelKind,
         target: NetworkTarget,
-    ) -> Result<()> {
+    ) -> Result<Option<MessageId>> {
         // TODO: i know channel names never change so i should be able to get them as static
         // TODO: just have a channel registry enum as well?
         let channel_name = self
@@ -170,8 +328,11 @@ impl<P: Protocol> ConnectionManager<P> {
             .to_string();
         let message = ClientMessage::<P>::Message(message, target);
         message.emit_send_logs(&channel_name);
-        self.message_manager.buffer_send(message, channel)?;
-        Ok(())
+        let message_id = self.message_manager.buffer_send(message, channel)?;
+        if let Some(message_id) = message_id {
+            self.message_manager.track_delivery(channel, message_id);
+        }
+        Ok(message_id)
     }
 
     pub(crate) fn buffer_replication_messages(
@@ -215,6 +376,8 @@ impl<P: Protocol> ConnectionManager<P> {
                     self.replication_sender
                         .updates_message_id_to_group_id
                         .insert(message_id, (group_id, bevy_tick));
+                    self.replication_sender
+                        .track_delta_values(message_id, group_id);
                 }
                 Ok(())
             })
@@ -231,10 +394,29 @@ impl<P: Protocol> ConnectionManager<P> {
         //   - can give infinity priority to this channel?
         //   - can write directly to io otherwise?
         if time_manager.is_client_ready_to_send() {
+            // send the handshake until the server answers, with the secret of the previous session if we are resuming it.
+            // The channel is unreliable, so the handshake is simply sent again if it (or the answer) is lost
+            if !self.handshake_done {
+                let message = ClientMessage::<P>::Handshake(HandshakeMessage {
+                    resumption_secret: self.resumption_secret,
+                    protocol_fingerprint: self.protocol_fingerprint,
+                });
+                let channel = ChannelKind::of::<DefaultUnorderedUnreliableChannel>();
+                self.message_manager.buffer_send(message, channel)?;
+            }
             // maybe send pings
             // same thing, we want the correct send time for the ping
             // (and not have the delay between when we prepare the ping and when we send the packet)
-            if let Some(ping) = self.ping_manager.maybe_prepare_ping(time_manager) {
+            if let Some(mut ping) = self.ping_manager.maybe_prepare_ping(time_manager) {
+                // report our interpolation delay to the server (used for lag compensation)
+                if self.sync_manager.is_synced() {
+                    ping.interpolation_delay_ms = Some(
+                        (self.sync_manager.server_time_estimate()
+                            - self.sync_manager.interpolation_time)
+                            .num_milliseconds()
+                            .max(0) as u32,
+                    );
+                }
                 trace!("Sending ping {:?}", ping);
                 let message = ClientMessage::<P>::Sync(SyncMessage::Ping(ping));
                 let channel = ChannelKind::of::<PingChannel>();
@@ -271,6 +453,8 @@ impl<P: Protocol> ConnectionManager<P> {
         tick_manager: &TickManager,
     ) -> ConnectionEvents<P> {
         let _span = trace_span!("receive").entered();
+        // the checksums are only kept for the frame where they are received
+        self.received_checksums.clear();
         for (channel_kind, messages) in self.message_manager.read_messages::<ServerMessage<P>>() {
             let channel_name = self
                 .message_manager
@@ -294,6 +478,25 @@ impl<P: Protocol> ConnectionManager<P> {
                             // buffer the replication message
                             self.replication_receiver.recv_message(replication, tick);
                         }
+                        ServerMessage::Checksum(checksums) => {
+                            self.received_checksums.push(checksums);
+                        }
+                        ServerMessage::Session(secret) => {
+                            self.resumption_secret = Some(secret);
+                            self.handshake_done = true;
+                        }
+                        ServerMessage::Denied(reason) => {
+                            error!(?reason, "the server refused our handshake");
+                            self.denied_reason = Some(reason);
+                        }
+                        ServerMessage::Players(players) => {
+                            self.received_players = Some(players);
+                        }
+                        ServerMessage::Authority(authority) => {
+                            // the authority changes are applied after the replication messages,
+                            // because they need the server entity to be replicated
+                            self.authority.pending.push(authority);
+                        }
                         ServerMessage::Sync(ref sync) => {
                             match sync {
                                 SyncMessage::Ping(ping) => {
@@ -348,6 +551,16 @@ impl<P: Protocol> ConnectionManager<P> {
                         );
                     });
             }
+            self.apply_authority_messages(world);
+        }
+
+        // progress of the streams that are sent or received
+        for (channel_kind, update) in self.message_manager.take_stream_updates() {
+            self.events.push_stream_update(channel_kind, update);
+        }
+        // messages that we sent and that were acked or lost
+        for (channel_kind, delivery) in self.message_manager.take_delivery_updates() {
+            self.events.push_message_delivery(channel_kind, delivery);
         }
 
         // TODO: do i really need this? I could just create events in this function directly?
@@ -356,6 +569,73 @@ impl<P: Protocol> ConnectionManager<P> {
         std::mem::replace(&mut self.events, ConnectionEvents::new())
     }
 
+    /// Apply the authority changes sent by the server.
+    ///
+    /// A grant is kept pending until the server entity (and its predicted entity, if it is predicted)
+    /// has been spawned on the client.
+    fn apply_authority_messages(&mut self, world: &mut World) {
+        for message in std::mem::take(&mut self.authority.pending) {
+            match message {
+                AuthorityMessage::Grant { entity, kinds } => {
+                    let Some(&confirmed) = self
+                        .replication_receiver
+                        .remote_entity_map
+                        .get_local(entity)
+                    else {
+                        self.authority
+                            .pending
+                            .push(AuthorityMessage::Grant { entity, kinds });
+                        continue;
+                    };
+                    // if the entity is predicted, we take authority over the predicted entity
+                    let local_entity = match world
+                        .get::<Confirmed>(confirmed)
+                        .and_then(|confirmed| confirmed.predicted)
+                    {
+                        Some(predicted) => predicted,
+                        None if world.get::<ShouldBePredicted>(confirmed).is_some() => {
+                            self.authority
+                                .pending
+                                .push(AuthorityMessage::Grant { entity, kinds });
+                            continue;
+                        }
+                        None => confirmed,
+                    };
+                    let Some(mut entity_mut) = world.get_entity_mut(local_entity) else {
+                        warn!(
+                            ?entity,
+                            "Received authority for an entity that does not exist"
+                        );
+                        continue;
+                    };
+                    debug!(?entity, ?local_entity, ?kinds, "Received authority");
+                    entity_mut.insert((HasAuthority, Replicate::<P>::default()));
+                    self.authority
+                        .entities
+                        .insert(local_entity, (entity, kinds));
+                }
+                AuthorityMessage::Revoke { entity } => {
+                    // the grant might not have been applied yet
+                    self.authority.pending.retain(
+                        |m| !matches!(m, AuthorityMessage::Grant { entity: e, .. } if *e == entity),
+                    );
+                    let Some(local_entity) = self.authority.local_entity(entity) else {
+                        continue;
+                    };
+                    debug!(?entity, ?local_entity, "Authority revoked");
+                    self.authority.entities.remove(&local_entity);
+                    // stop replicating the entity without notifying the server
+                    self.replication_sender
+                        .replicate_component_cache
+                        .remove(&local_entity);
+                    if let Some(mut entity_mut) = world.get_entity_mut(local_entity) {
+                        entity_mut.remove::<(HasAuthority, Replicate<P>)>();
+                    }
+                }
+            }
+        }
+    }
+
     pub(crate) fn recv_packet(&mut self, packet: Packet, tick_manager: &TickManager) -> Result<()> {
         // receive the packets, buffer them, update any sender that were waiting for their sent messages to be acked
         let tick = self.message_manager.recv_packet(packet)?;
@@ -397,6 +677,17 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         Ok(())
     }
 
+    fn update_send_interval(
+        &mut self,
+        replication_group_id: ReplicationGroupId,
+        client_id: ClientId,
+        send_interval: u16,
+    ) -> Result<()> {
+        self.replication_sender
+            .update_send_interval(replication_group_id, send_interval);
+        Ok(())
+    }
+
     fn new_connected_clients(&self) -> Vec<ClientId> {
         vec![]
     }
@@ -409,6 +700,10 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         system_current_tick: BevyTick,
     ) -> Result<()> {
         trace!(?entity, "Prepare entity spawn to server");
+        // the entity is owned by the server, we only replicate some of its components
+        if self.authority.get(entity).is_some() {
+            return Ok(());
+        }
         let group_id = replicate.replication_group.group_id(Some(entity));
         let replication_sender = &mut self.replication_sender;
         // update the collect changes tick
@@ -421,13 +716,18 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         //     .update_collect_changes_since_this_tick(system_current_tick);
         replication_sender.prepare_entity_spawn(entity, group_id);
 
-        // also set the priority for the group when we spawn it
+        // also set the priority and send interval for the group when we spawn it
+        // (the client id argument is ignored on the client)
         self.update_priority(
             group_id,
-            // the client id argument is ignored on the client
             ClientId::Local(0),
             replicate.replication_group.priority(),
         )?;
+        self.update_send_interval(
+            group_id,
+            ClientId::Local(0),
+            replicate.replication_group.send_interval(),
+        )?;
         // Prediction/interpolation
         Ok(())
     }
@@ -440,6 +740,10 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         system_current_tick: BevyTick,
     ) -> Result<()> {
         // trace!(?entity, "Send entity despawn for tick {:?}", self.tick());
+        // only the server can despawn an entity that it owns
+        if self.authority.entities.remove(&entity).is_some() {
+            return Ok(());
+        }
         let group_id = replicate.replication_group.group_id(Some(entity));
         let replication_sender = &mut self.replication_sender;
         // update the collect changes tick
@@ -453,6 +757,23 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         Ok(())
     }
 
+    fn prepare_entity_unreplicate(
+        &mut self,
+        entity: Entity,
+        replicate: &Replicate<P>,
+        _: NetworkTarget,
+        _: BevyTick,
+    ) -> Result<()> {
+        // the server keeps replicating the entities over which we have authority
+        if self.authority.entities.remove(&entity).is_some() {
+            return Ok(());
+        }
+        let group_id = replicate.replication_group.group_id(Some(entity));
+        self.replication_sender
+            .prepare_entity_unreplicate(entity, group_id);
+        Ok(())
+    }
+
     fn prepare_component_insert(
         &mut self,
         entity: Entity,
@@ -461,8 +782,11 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         target: NetworkTarget,
         system_current_tick: BevyTick,
     ) -> Result<()> {
-        let group_id = replicate.replication_group.group_id(Some(entity));
         let kind: P::ComponentKinds = (&component).into();
+        let Some(entity) = self.authority_entity(entity, &kind) else {
+            return Ok(());
+        };
+        let group_id = replicate.replication_group.group_id(Some(entity));
         // debug!(
         //     ?entity,
         //     component = ?kind,
@@ -488,6 +812,9 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         target: NetworkTarget,
         system_current_tick: BevyTick,
     ) -> Result<()> {
+        let Some(entity) = self.authority_entity(entity, &component_kind) else {
+            return Ok(());
+        };
         let group_id = replicate.replication_group.group_id(Some(entity));
         debug!(?entity, ?component_kind, "Sending RemoveComponent");
         // self.replication_sender
@@ -510,6 +837,9 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         system_current_tick: BevyTick,
     ) -> Result<()> {
         let kind: P::ComponentKinds = (&component).into();
+        let Some(entity) = self.authority_entity(entity, &kind) else {
+            return Ok(());
+        };
         let group_id = replicate.group_id(Some(entity));
         // TODO: should we have additional state tracking so that we know we are in the process of sending this entity to clients?
         let collect_changes_since_this_tick = self
@@ -541,6 +871,20 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         Ok(())
     }
 
+    fn has_pending_update(
+        &mut self,
+        group_id: ReplicationGroupId,
+        _: NetworkTarget,
+        component_change_tick: BevyTick,
+        system_current_tick: BevyTick,
+    ) -> bool {
+        self.replication_sender.is_update_pending(
+            group_id,
+            component_change_tick,
+            system_current_tick,
+        )
+    }
+
     fn buffer_replication_messages(&mut self, tick: Tick, bevy_tick: BevyTick) -> Result<()> {
         let _span = trace_span!("buffer_replication_messages").entered();
         self.buffer_replication_messages(tick, bevy_tick)
@@ -564,7 +908,13 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
                     group_channel.last_action_tick = None;
                 }
             }
+            if let Some(last_update_tick) = group_channel.last_update_tick {
+                if tick - last_update_tick > (i16::MAX / 2) {
+                    group_channel.last_update_tick = None;
+                }
+            }
         }
+        self.replication_sender.cleanup_delta_values(tick);
         // if it's been enough time since we last had any update for the group, we update the latest_tick for the group
         for group_channel in self.replication_receiver.group_channels.values_mut() {
             debug!("Checking group channel: {:?}", group_channel);
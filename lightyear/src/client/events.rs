@@ -13,6 +13,8 @@
 //! }
 //! ```
 
+use crate::client::input::RemoteInputTarget;
+use crate::connection::server::DeniedReason;
 use crate::prelude::{ClientId, Protocol};
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::events::plugin::EventsPlugin;
@@ -37,6 +39,7 @@ impl<P: Protocol> Plugin for ClientEventsPlugin<P> {
         app
             // EVENTS
             .add_event::<ConnectEvent>()
+            .add_event::<DisconnectEvent>()
             // PLUGIN
             // TODO: it's annoying to have to keep that () around...
             //  revisit this.. maybe the into_iter_messages returns directly an object that
@@ -64,9 +67,29 @@ impl ConnectEvent {
 }
 
 /// Bevy [`Event`] emitted on the client on the frame where the connection is disconnected
-pub type DisconnectEvent = crate::shared::events::components::DisconnectEvent<()>;
+///
+/// If the server denied our connection request, the event contains the reason that was sent by the server
+#[derive(Event)]
+pub struct DisconnectEvent {
+    reason: Option<DeniedReason>,
+}
+
+impl DisconnectEvent {
+    pub fn new(reason: Option<DeniedReason>) -> Self {
+        Self { reason }
+    }
+    pub fn reason(&self) -> Option<DeniedReason> {
+        self.reason
+    }
+}
 /// Bevy [`Event`] emitted on the client to indicate the user input for the tick
 pub type InputEvent<I> = crate::shared::events::components::InputEvent<I, ()>;
+/// Bevy [`Event`] emitted on the client to indicate the input of a remote client for the tick.
+/// The context is the [`RemoteInputTarget`] of the input: the local entity controlled by the remote client,
+/// or the remote client itself if it doesn't control any entity.
+///
+/// Remote inputs are only available if the server rebroadcasts the inputs (see [`InputConfig`](crate::prelude::server::InputConfig))
+pub type RemoteInputEvent<I> = crate::shared::events::components::InputEvent<I, RemoteInputTarget>;
 /// Bevy [`Event`] emitted on the client when a EntitySpawn replication message is received
 pub type EntitySpawnEvent = crate::shared::events::components::EntitySpawnEvent<()>;
 /// Bevy [`Event`] emitted on the client when a EntityDespawn replication message is received
@@ -79,3 +102,13 @@ pub type ComponentInsertEvent<C> = crate::shared::events::components::ComponentI
 pub type ComponentRemoveEvent<C> = crate::shared::events::components::ComponentRemoveEvent<C, ()>;
 /// Bevy [`Event`] emitted on the client when a (non-replication) message is received
 pub type MessageEvent<M> = crate::shared::events::components::MessageEvent<M, ()>;
+/// Bevy [`Event`] emitted on the client when a stream sent to or received from the server makes progress
+pub type StreamProgressEvent = crate::shared::events::components::StreamProgressEvent<()>;
+/// Bevy [`Event`] emitted on the client when a stream sent to or received from the server is cancelled
+pub type StreamCancelledEvent = crate::shared::events::components::StreamCancelledEvent<()>;
+/// Bevy [`Event`] emitted on the client when a message sent to the server is acked by the server
+pub type MessageAckEvent = crate::shared::events::components::MessageAckEvent<()>;
+/// Bevy [`Event`] emitted on the client when a message sent to the server is lost
+pub type MessageLostEvent = crate::shared::events::components::MessageLostEvent<()>;
+/// Bevy [`Event`] emitted on the client when the server responds to a request, or when the request times out
+pub type ResponseEvent<M> = crate::shared::events::components::ResponseEvent<M, ()>;
@@ -20,26 +20,40 @@
 //!
 //! You will also need to implement a system in the [`InputSystemSet::BufferInputs`] system set to add inputs to the input buffer every tick.
 //!
+//! If the server rebroadcasts the inputs (see [`InputConfig`](crate::prelude::server::InputConfig)), the inputs of the
+//! other clients are also stored in the [`InputManager`], and are emitted every tick as [`RemoteInputEvent`]s.
+//! This lets you predict the remote players using their actual inputs, including during rollbacks.
+//! The remote inputs are stored per entity: each client declares the entities that are driven by its inputs with
+//! [`InputManager::add_controlled_entity`], and the other clients receive these inputs on their own copy of these entities.
+//! The inputs of a client that doesn't control any replicated entity (for example in deterministic mode) are stored
+//! per client instead (see [`RemoteInputTarget`]).
+//!
 //! NOTE: I would advise to activate the `leafwing` feature to handle inputs via the `input_leafwing` module, instead.
 //! That module is more up-to-date and has more features.
 //! This module is kept for simplicity but might get removed in the future.
 use crate::_reexport::ClientMarker;
 use bevy::prelude::{
-    not, App, Condition, EventReader, EventWriter, Events, FixedPostUpdate, FixedPreUpdate, In,
-    IntoSystemConfigs, IntoSystemSetConfigs, Plugin, PostUpdate, Res, ResMut, Resource, SystemSet,
+    not, App, Condition, Entity, Event, EventReader, EventWriter, Events, FixedPostUpdate,
+    FixedPreUpdate, In, IntoSystemConfigs, IntoSystemSetConfigs, Plugin, PostUpdate, PreUpdate,
+    Query, Res, ResMut, Resource, SystemSet,
 };
 use bevy::reflect::Reflect;
+use bevy::utils::HashMap;
 use tracing::{debug, error, info, trace};
 
 use crate::channel::builder::InputChannel;
+use crate::client::components::Confirmed;
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
-use crate::client::events::InputEvent;
-use crate::client::prediction::plugin::is_in_rollback;
+use crate::client::events::{InputEvent, MessageEvent, RemoteInputEvent};
+use crate::client::prediction::deterministic::DeterministicChecksums;
+use crate::client::prediction::plugin::{is_in_rollback, PredictionSet};
 use crate::client::prediction::rollback::{Rollback, RollbackState};
+use crate::client::prediction::Predicted;
 use crate::client::sync::{client_is_synced, SyncSet};
 use crate::connection::client::NetClient;
-use crate::inputs::native::input_buffer::InputBuffer;
+use crate::connection::id::ClientId;
+use crate::inputs::native::input_buffer::{InputBuffer, InputMessage};
 use crate::inputs::native::UserAction;
 use crate::prelude::client::ClientConnection;
 use crate::prelude::{server, SharedConfig, Tick, TickManager};
@@ -57,6 +71,15 @@ pub struct InputConfig {
     pub packet_redundancy: u16,
 }
 
+/// What the inputs of a remote client are applied to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub enum RemoteInputTarget {
+    /// A local entity controlled by the remote client (the predicted entity if there is one)
+    Entity(Entity),
+    /// The remote client itself, if it didn't declare any controlled entity
+    Client(ClientId),
+}
+
 /// Resource that handles buffering and sending inputs to the server
 ///
 /// Note: it is advised to enable the feature `leafwing` and  switch to the `LeafwingInputPlugin`,
@@ -64,12 +87,22 @@ pub struct InputConfig {
 #[derive(Debug, Resource)]
 pub struct InputManager<A: UserAction> {
     pub(crate) input_buffer: InputBuffer<A>,
+    /// Local entities that are controlled by the inputs of this client
+    pub(crate) controlled_entities: Vec<Entity>,
+    /// Inputs of the other clients, that were rebroadcast by the server
+    pub(crate) remote_input_buffers: HashMap<RemoteInputTarget, InputBuffer<A>>,
+    /// The other clients connected to the server, with the latest tick for which we received their inputs.
+    /// This is `None` until the server sends us the list of connected clients.
+    pub(crate) remote_players: Option<HashMap<ClientId, Option<Tick>>>,
 }
 
 impl<A: UserAction> Default for InputManager<A> {
     fn default() -> Self {
         Self {
             input_buffer: InputBuffer::default(),
+            controlled_entities: vec![],
+            remote_input_buffers: HashMap::default(),
+            remote_players: None,
         }
     }
 }
@@ -85,6 +118,68 @@ impl<A: UserAction> InputManager<A> {
     pub fn add_input(&mut self, input: A, tick: Tick) {
         self.input_buffer.set(tick, Some(input));
     }
+
+    /// Mark a local entity (predicted or confirmed) as being controlled by the inputs of this client.
+    ///
+    /// If the server rebroadcasts the inputs, the other clients will receive our inputs on their copy of this entity.
+    pub fn add_controlled_entity(&mut self, entity: Entity) {
+        if !self.controlled_entities.contains(&entity) {
+            self.controlled_entities.push(entity);
+        }
+    }
+
+    /// Stop sending our inputs to the other clients for this entity
+    pub fn remove_controlled_entity(&mut self, entity: Entity) {
+        self.controlled_entities.retain(|e| *e != entity);
+    }
+
+    /// Latest tick for which we know the inputs of all the other clients connected to the server.
+    ///
+    /// Returns `None` if we don't know yet which clients are connected, or if we haven't received
+    /// any input from one of them.
+    pub(crate) fn confirmed_tick(&self, current_tick: Tick) -> Option<Tick> {
+        self.remote_players
+            .as_ref()?
+            .values()
+            .try_fold(current_tick, |confirmed_tick, end_tick| {
+                let end_tick = (*end_tick)?;
+                Some(if end_tick < confirmed_tick {
+                    end_tick
+                } else {
+                    confirmed_tick
+                })
+            })
+    }
+
+    /// Update the list of the other clients connected to the server.
+    ///
+    /// The inputs of the clients that left are dropped, so that we don't wait for them anymore.
+    fn set_remote_players(&mut self, players: Vec<ClientId>) {
+        let previous = self.remote_players.take().unwrap_or_default();
+        self.remote_input_buffers.retain(|target, _| match target {
+            RemoteInputTarget::Client(client_id) => players.contains(client_id),
+            RemoteInputTarget::Entity(_) => true,
+        });
+        self.remote_players = Some(
+            players
+                .into_iter()
+                .map(|client_id| (client_id, previous.get(&client_id).copied().flatten()))
+                .collect(),
+        );
+    }
+
+    /// Get the input of a remote client for the given target and tick.
+    ///
+    /// If we haven't received the remote input for that tick yet, we assume that the remote client
+    /// keeps the latest input that we received.
+    pub fn get_remote_input(&self, target: RemoteInputTarget, tick: Tick) -> Option<A> {
+        let buffer = self.remote_input_buffers.get(&target)?;
+        let tick = match buffer.end_tick() {
+            Some(end_tick) if tick > end_tick => end_tick,
+            _ => tick,
+        };
+        buffer.get(tick).cloned()
+    }
 }
 
 impl Default for InputConfig {
@@ -147,7 +242,7 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
         // SYSTEMS
         app.add_systems(
             FixedPostUpdate,
-            clear_input_events::<P::Input>.in_set(InputSystemSet::ClearInputEvent),
+            clear_input_events::<InputEvent<P::Input>>.in_set(InputSystemSet::ClearInputEvent),
         );
 
         if app.world.resource::<ClientConfig>().shared.mode == Mode::HostServer {
@@ -159,7 +254,16 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
             return;
         }
 
+        // EVENT
+        app.add_event::<RemoteInputEvent<P::Input>>();
         // SETS
+        app.configure_sets(
+            PreUpdate,
+            InputSystemSet::ReceiveRemoteInputs
+                .after(InternalMainSet::<ClientMarker>::Receive)
+                // in deterministic mode, receiving remote inputs can trigger a rollback
+                .before(PredictionSet::PrepareRollback),
+        );
         app.configure_sets(
             PostUpdate,
             (
@@ -180,10 +284,19 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
                 .chain(),
         );
         // SYSTEMS
+        app.add_systems(
+            PreUpdate,
+            receive_remote_inputs::<P>.in_set(InputSystemSet::ReceiveRemoteInputs),
+        );
         app.add_systems(
             FixedPreUpdate,
             write_input_event::<P::Input>.in_set(InputSystemSet::WriteInputEvent),
         );
+        app.add_systems(
+            FixedPostUpdate,
+            clear_input_events::<RemoteInputEvent<P::Input>>
+                .in_set(InputSystemSet::ClearInputEvent),
+        );
 
         app.add_systems(
             PostUpdate,
@@ -201,6 +314,10 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
 pub enum InputSystemSet {
+    // PRE UPDATE
+    /// Receive the inputs of the other clients, that were rebroadcast by the server
+    ReceiveRemoteInputs,
+
     // FIXED UPDATE
     /// System Set to write the input events to the input buffer.
     /// The User should add their system here!!
@@ -230,10 +347,113 @@ pub enum InputSystemSet {
 
 /// System that clears the input events.
 /// It is necessary because events are cleared every frame, but we want to clear every tick instead
-fn clear_input_events<A: UserAction>(mut input_events: EventReader<InputEvent<A>>) {
+fn clear_input_events<E: Event>(mut input_events: EventReader<E>) {
     input_events.clear();
 }
 
+/// Store the inputs of the remote clients in the [`InputManager`], on the local entities that they control.
+///
+/// The inputs are stored on the predicted entity if there is one, and on the confirmed entity otherwise.
+///
+/// In deterministic mode, we also check if the inputs that we just received are different from the ones that we
+/// assumed when we simulated the previous ticks (we assume that the remote client keeps its latest input).
+/// If they are, we rollback to the first mispredicted tick.
+fn receive_remote_inputs<P: Protocol>(
+    config: Res<ClientConfig>,
+    tick_manager: Res<TickManager>,
+    mut connection: ResMut<ConnectionManager<P>>,
+    mut input_manager: ResMut<InputManager<P::Input>>,
+    mut messages: EventReader<MessageEvent<InputMessage<P::Input>>>,
+    confirmed_query: Query<&Confirmed>,
+    rollback: Option<ResMut<Rollback>>,
+) {
+    let current_tick = tick_manager.tick();
+    let mut rollback_tick: Option<Tick> = None;
+    if let Some(players) = connection.received_players.take() {
+        input_manager.set_remote_players(players);
+    }
+    for event in messages.read() {
+        let message = event.message();
+        let Some(client_id) = message.client_id else {
+            continue;
+        };
+        trace!(?client_id, end_tick = ?message.end_tick, "received remote input message");
+        if let Some(end_tick) = input_manager
+            .remote_players
+            .as_mut()
+            .and_then(|players| players.get_mut(&client_id))
+        {
+            if end_tick.map_or(true, |end_tick| message.end_tick > end_tick) {
+                *end_tick = Some(message.end_tick);
+            }
+        }
+        let targets: Vec<RemoteInputTarget> = if message.entities.is_empty() {
+            vec![RemoteInputTarget::Client(client_id)]
+        } else {
+            message
+                .entities
+                .iter()
+                .filter_map(|server_entity| {
+                    let Some(local) = connection
+                        .replication_receiver
+                        .remote_entity_map
+                        .get_local(*server_entity)
+                    else {
+                        trace!(
+                            ?server_entity,
+                            "received remote inputs for an entity that is not replicated"
+                        );
+                        return None;
+                    };
+                    let entity = confirmed_query
+                        .get(*local)
+                        .ok()
+                        .and_then(|confirmed| confirmed.predicted)
+                        .unwrap_or(*local);
+                    Some(RemoteInputTarget::Entity(entity))
+                })
+                .collect()
+        };
+        for target in targets {
+            let buffer = input_manager
+                .remote_input_buffers
+                .entry(target)
+                .or_default();
+            // the input that we used for the ticks after the end of the buffer
+            let previous_end_tick = buffer.end_tick();
+            let predicted_input = previous_end_tick.and_then(|tick| buffer.get(tick).cloned());
+            buffer.update_from_message(message.clone());
+
+            if !config.prediction.deterministic {
+                continue;
+            }
+            let message_start_tick =
+                message.end_tick - (message.inputs.len() as u16).saturating_sub(1);
+            let mut tick = previous_end_tick.map_or(message_start_tick, |end_tick| end_tick + 1);
+            // only the ticks that we already simulated could have been mispredicted
+            while tick <= message.end_tick && tick <= current_tick {
+                if buffer.get(tick) != predicted_input.as_ref() {
+                    debug!(?client_id, ?target, ?tick, "Remote input was mispredicted");
+                    if rollback_tick.map_or(true, |rollback_tick| tick < rollback_tick) {
+                        rollback_tick = Some(tick);
+                    }
+                    break;
+                }
+                tick = tick + 1;
+            }
+        }
+    }
+    if let (Some(tick), Some(mut rollback)) = (rollback_tick, rollback) {
+        // we restore the state at the end of the tick before the mispredicted tick, and re-simulate from there
+        match rollback.state {
+            RollbackState::ShouldRollback { current_tick } if current_tick <= tick => {}
+            _ => {
+                rollback.state = RollbackState::ShouldRollback { current_tick: tick };
+            }
+        }
+    }
+}
+
 // Create a system that reads from the input buffer and returns the inputs of all clients for the current tick.
 // The only tricky part is that events are cleared every frame, but we want to clear every tick instead
 // Do it in this system because we want an input for every tick
@@ -241,16 +461,20 @@ fn write_input_event<A: UserAction>(
     tick_manager: Res<TickManager>,
     input_manager: Res<InputManager<A>>,
     mut client_input_events: EventWriter<InputEvent<A>>,
+    mut remote_input_events: EventWriter<RemoteInputEvent<A>>,
     rollback: Option<Res<Rollback>>,
 ) {
-    let tick = rollback.map_or(tick_manager.tick(), |rollback| match rollback.state {
-        RollbackState::Default => tick_manager.tick(),
-        RollbackState::ShouldRollback {
-            current_tick: rollback_tick,
-        } => rollback_tick,
-    });
-    let input = input_manager.get_input(tick);
+    let tick = rollback
+        .and_then(|rollback| rollback.get_rollback_tick())
+        .unwrap_or(tick_manager.tick());
     client_input_events.send(InputEvent::new(input_manager.get_input(tick), ()));
+    // also emit the inputs of the remote clients, so that they can be predicted (and replayed during rollback)
+    for target in input_manager.remote_input_buffers.keys() {
+        remote_input_events.send(RemoteInputEvent::new(
+            input_manager.get_remote_input(*target, tick),
+            *target,
+        ));
+    }
 }
 
 /// Receive an [`TickEvent`] signifying that the local tick has been updated,
@@ -271,6 +495,11 @@ fn receive_tick_events<A: UserAction>(
                     input_manager.input_buffer.start_tick =
                         Some(start_tick + (*new_tick - *old_tick));
                 };
+                for buffer in input_manager.remote_input_buffers.values_mut() {
+                    if let Some(start_tick) = buffer.start_tick {
+                        buffer.start_tick = Some(start_tick + (*new_tick - *old_tick));
+                    }
+                }
             }
         }
     }
@@ -280,8 +509,10 @@ fn receive_tick_events<A: UserAction>(
 fn prepare_input_message<P: Protocol>(
     connection: Option<ResMut<ConnectionManager<P>>>,
     mut input_manager: ResMut<InputManager<P::Input>>,
+    mut checksums: Option<ResMut<DeterministicChecksums>>,
     config: Res<ClientConfig>,
     tick_manager: Res<TickManager>,
+    predicted_query: Query<&Predicted>,
 ) {
     let Some(mut connection) = connection else {
         return;
@@ -308,9 +539,30 @@ fn prepare_input_message<P: Protocol>(
     //  - buffer an input every frame; and require some redundancy (number of tick per frame)
     //  - or buffer an input only when we are sending, and require more redundancy
     // let message_len = 20 as u16;
-    let message = input_manager
+    let mut message = input_manager
         .input_buffer
         .create_message(tick_manager.tick(), message_len);
+    // the server entities that are controlled by our inputs, so that the other clients can apply them
+    message.entities = input_manager
+        .controlled_entities
+        .iter()
+        .filter_map(|entity| {
+            let confirmed = predicted_query
+                .get(*entity)
+                .map_or(Some(*entity), |predicted| predicted.confirmed_entity)?;
+            connection
+                .replication_receiver
+                .remote_entity_map
+                .get_remote(confirmed)
+                .copied()
+        })
+        .collect();
+    // in deterministic mode, report the checksum of the latest tick for which we know the inputs of all players
+    if let Some(checksums) = checksums.as_deref_mut() {
+        message.checksum = input_manager
+            .confirmed_tick(current_tick)
+            .and_then(|confirmed_tick| checksums.confirm(confirmed_tick));
+    }
     // all inputs are absent
     if !message.is_empty() {
         // TODO: should we provide variants of each user-facing function, so that it pushes the error
@@ -320,11 +572,9 @@ fn prepare_input_message<P: Protocol>(
             "sending input message: {:?}",
             message.end_tick
         );
-        connection
-            .send_message::<InputChannel, _>(message)
-            .unwrap_or_else(|err| {
-                error!("Error while sending input message: {:?}", err);
-            })
+        if let Err(err) = connection.send_message::<InputChannel, _>(message) {
+            error!("Error while sending input message: {:?}", err);
+        }
     }
     // NOTE: actually we keep the input values! because they might be needed when we rollback for client prediction
     // TODO: figure out when we can delete old inputs. Basically when the oldest prediction group tick has passed?
@@ -333,6 +583,16 @@ fn prepare_input_message<P: Protocol>(
     // delete old input values
     let interpolation_tick = connection.sync_manager.interpolation_tick(&tick_manager);
     input_manager.input_buffer.pop(interpolation_tick);
+    // the clients only send inputs when they are not absent, so we stop predicting the remote clients
+    // for which we haven't received any recent inputs
+    input_manager.remote_input_buffers.retain(|_, buffer| {
+        buffer
+            .end_tick()
+            .map_or(false, |end| end > interpolation_tick)
+    });
+    for buffer in input_manager.remote_input_buffers.values_mut() {
+        buffer.pop(interpolation_tick);
+    }
     // .pop(current_tick - (message_len + 1));
 }
 
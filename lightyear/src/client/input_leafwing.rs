@@ -38,14 +38,16 @@ use std::marker::PhantomData;
 
 use crate::_reexport::ClientMarker;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, Instant};
 use leafwing_input_manager::plugin::InputManagerSystem;
 use leafwing_input_manager::prelude::*;
 use tracing::{error, trace};
 
 use crate::channel::builder::InputChannel;
+use crate::client::components::Confirmed;
 use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
+use crate::client::events::MessageEvent;
 use crate::client::prediction::plugin::{is_in_rollback, PredictionSet};
 use crate::client::prediction::rollback::{Rollback, RollbackState};
 use crate::client::prediction::Predicted;
@@ -54,7 +56,7 @@ use crate::inputs::leafwing::input_buffer::{
     ActionDiff, ActionDiffBuffer, ActionDiffEvent, InputBuffer, InputMessage, InputTarget,
 };
 use crate::inputs::leafwing::LeafwingUserAction;
-use crate::prelude::{Mode, TickManager};
+use crate::prelude::{Mode, Tick, TickManager};
 use crate::protocol::Protocol;
 use crate::shared::replication::components::PrePredicted;
 use crate::shared::sets::{FixedUpdateSet, InternalMainSet};
@@ -224,6 +226,9 @@ where
                     .after(InputManagerSystem::ManualControl)
                     .after(InputManagerSystem::Tick),
                 add_action_state_buffer::<A>.after(PredictionSet::SpawnPrediction),
+                receive_remote_input_messages::<P, A>
+                    .after(InternalMainSet::<ClientMarker>::Receive)
+                    .after(PredictionSet::SpawnPrediction),
             ),
         );
         // NOTE: we do not tick the ActionState during FixedUpdate
@@ -243,6 +248,7 @@ where
                     .chain()
                     .run_if(run_if_enabled::<A>.and_then(not(is_in_rollback))),
                 get_rollback_action_state::<A>.run_if(run_if_enabled::<A>.and_then(is_in_rollback)),
+                get_remote_action_state::<A>,
             )
                 .in_set(InputSystemSet::BufferClientInputs),
         );
@@ -319,6 +325,62 @@ pub enum InputSystemSet {
     CleanUp,
 }
 
+/// Stores the inputs of a remote player, that were rebroadcast by the server.
+///
+/// This is added to the predicted entity controlled by the remote player.
+/// We keep the [`ActionState`] of the entity for each tick, so that we can replay the remote inputs during rollback.
+#[derive(Component, Debug)]
+pub(crate) struct RemoteInputBuffer<A: LeafwingUserAction> {
+    buffer: InputBuffer<A>,
+    /// Latest tick for which we received the inputs of the remote player
+    last_tick: Option<Tick>,
+    /// ActionState of the remote player at `last_tick`
+    last_action_state: ActionState<A>,
+}
+
+impl<A: LeafwingUserAction> Default for RemoteInputBuffer<A> {
+    fn default() -> Self {
+        Self {
+            buffer: InputBuffer::default(),
+            last_tick: None,
+            last_action_state: ActionState::default(),
+        }
+    }
+}
+
+impl<A: LeafwingUserAction> RemoteInputBuffer<A> {
+    /// Apply the diffs received from the server to compute the ActionState of the remote player for each tick
+    fn update_from_message(&mut self, end_tick: Tick, diffs: &[Vec<ActionDiff<A>>]) {
+        let start_tick = end_tick - (diffs.len() as u16).saturating_sub(1);
+        for (delta, tick_diffs) in diffs.iter().enumerate() {
+            let tick = start_tick + delta as i16;
+            // the message contains redundant inputs, skip the ticks that we already applied
+            if self.last_tick.map_or(false, |last_tick| tick <= last_tick) {
+                continue;
+            }
+            // actions that were just pressed on the previous tick are now simply pressed
+            let now = Instant::now();
+            self.last_action_state.tick(now, now);
+            for diff in tick_diffs {
+                diff.clone().apply(&mut self.last_action_state);
+            }
+            self.buffer.set(tick, &self.last_action_state);
+            self.last_tick = Some(tick);
+        }
+    }
+
+    /// Get the ActionState of the remote player for the given tick.
+    ///
+    /// If we haven't received the inputs for that tick yet, we assume that the remote player
+    /// keeps the same inputs.
+    fn get(&self, tick: Tick) -> ActionState<A> {
+        if self.last_tick.map_or(true, |last_tick| tick >= last_tick) {
+            return self.last_action_state.clone();
+        }
+        self.buffer.get(tick).cloned().unwrap_or_default()
+    }
+}
+
 /// Add an [`InputBuffer`] and a [`ActionDiffBuffer`] to newly controlled entities
 fn add_action_state_buffer_added_input_map<A: LeafwingUserAction>(
     mut commands: Commands,
@@ -489,8 +551,8 @@ fn get_non_rollback_action_state<A: LeafwingUserAction>(
 
 // During rollback, fetch the action-state from the history for the corresponding tick and use that
 // to set the ActionState resource/component
-// For actions from other players (with no InputBuffer), no need to do anything, because we just received their latest action
-//  and we consider that they will keep playing that action in the future
+// For actions from other players (with no InputBuffer), the ActionState is set by `get_remote_action_state`
+// if the server rebroadcasts the inputs
 // TODO: implement some decay for the rollback ActionState of other players?
 fn get_rollback_action_state<A: LeafwingUserAction>(
     global_input_buffer: Res<InputBuffer<A>>,
@@ -521,6 +583,72 @@ fn get_rollback_action_state<A: LeafwingUserAction>(
     }
 }
 
+/// Set the ActionState of the remote players' entities for the current tick (or the rollback tick)
+/// using the inputs that were rebroadcast by the server
+fn get_remote_action_state<A: LeafwingUserAction>(
+    tick_manager: Res<TickManager>,
+    rollback: Option<Res<Rollback>>,
+    mut action_state_query: Query<(&mut ActionState<A>, &RemoteInputBuffer<A>)>,
+) {
+    let tick = rollback
+        .and_then(|rollback| rollback.get_rollback_tick())
+        .unwrap_or(tick_manager.tick());
+    for (mut action_state, remote_input_buffer) in action_state_query.iter_mut() {
+        *action_state = remote_input_buffer.get(tick);
+    }
+}
+
+/// Read the input messages of the remote players that were rebroadcast by the server,
+/// and store them on the corresponding predicted entities
+fn receive_remote_input_messages<P: Protocol, A: LeafwingUserAction>(
+    mut commands: Commands,
+    connection: Res<ConnectionManager<P>>,
+    mut messages: EventReader<MessageEvent<InputMessage<A>>>,
+    confirmed_query: Query<&Confirmed>,
+    mut remote_query: Query<(Option<&mut RemoteInputBuffer<A>>, Has<ActionState<A>>)>,
+) {
+    for event in messages.read() {
+        let message = event.message();
+        for (target, diffs) in message.diffs.iter() {
+            // the server only rebroadcasts inputs for entities
+            let InputTarget::Entity(server_entity) = target else {
+                continue;
+            };
+            // the inputs are applied to the predicted entity of the remote player
+            let Some(predicted) = connection
+                .replication_receiver
+                .remote_entity_map
+                .get_local(*server_entity)
+                .and_then(|confirmed| confirmed_query.get(*confirmed).ok())
+                .and_then(|confirmed| confirmed.predicted)
+            else {
+                trace!(
+                    ?server_entity,
+                    "received remote inputs for an entity that is not predicted"
+                );
+                continue;
+            };
+            let Ok((remote_input_buffer, has_action_state)) = remote_query.get_mut(predicted)
+            else {
+                continue;
+            };
+            if let Some(mut remote_input_buffer) = remote_input_buffer {
+                remote_input_buffer.update_from_message(message.end_tick, diffs);
+            } else {
+                debug!(?predicted, "adding remote input buffer");
+                let mut remote_input_buffer = RemoteInputBuffer::<A>::default();
+                remote_input_buffer.update_from_message(message.end_tick, diffs);
+                commands.entity(predicted).insert(remote_input_buffer);
+                if !has_action_state {
+                    commands
+                        .entity(predicted)
+                        .insert(ActionState::<A>::default());
+                }
+            }
+        }
+    }
+}
+
 /// Read the action-diffs and store them in a buffer.
 ///
 /// NOTE: we have an ActionState buffer used for rollbacks,
@@ -567,6 +695,7 @@ fn clean_buffers<P: Protocol, A: LeafwingUserAction>(
     mut action_diff_buffer_query: Query<(Entity, &mut ActionDiffBuffer<A>), With<InputMap<A>>>,
     global_input_buffer: Option<ResMut<InputBuffer<A>>>,
     mut input_buffer_query: Query<(Entity, &mut InputBuffer<A>)>,
+    mut remote_input_buffer_query: Query<&mut RemoteInputBuffer<A>>,
 ) {
     // delete old input values
     // anything beyond interpolation tick should be safe to be deleted
@@ -581,6 +710,9 @@ fn clean_buffers<P: Protocol, A: LeafwingUserAction>(
     for (entity, mut action_diff_buffer) in action_diff_buffer_query.iter_mut() {
         action_diff_buffer.pop(interpolation_tick);
     }
+    for mut remote_input_buffer in remote_input_buffer_query.iter_mut() {
+        remote_input_buffer.buffer.pop(interpolation_tick);
+    }
     if let Some(mut input_buffer) = global_input_buffer {
         input_buffer.pop(interpolation_tick);
     }
@@ -694,11 +826,9 @@ fn prepare_input_message<P: Protocol, A: LeafwingUserAction>(
             "sending input message: {:?}",
             message.diffs
         );
-        connection
-            .send_message::<InputChannel, InputMessage<A>>(message)
-            .unwrap_or_else(|err| {
-                error!("Error while sending input message: {:?}", err);
-            })
+        if let Err(err) = connection.send_message::<InputChannel, InputMessage<A>>(message) {
+            error!("Error while sending input message: {:?}", err);
+        }
     }
 
     // NOTE: actually we keep the input values! because they might be needed when we rollback for client prediction
@@ -712,6 +842,7 @@ fn receive_tick_events<A: LeafwingUserAction>(
     mut global_input_buffer: Option<ResMut<InputBuffer<A>>>,
     mut action_diff_buffer_query: Query<&mut ActionDiffBuffer<A>>,
     mut input_buffer_query: Query<&mut InputBuffer<A>>,
+    mut remote_input_buffer_query: Query<&mut RemoteInputBuffer<A>>,
 ) {
     for tick_event in tick_events.read() {
         match tick_event {
@@ -752,6 +883,15 @@ fn receive_tick_events<A: LeafwingUserAction>(
                         );
                     }
                 }
+                for mut remote_input_buffer in remote_input_buffer_query.iter_mut() {
+                    let delta = *new_tick - *old_tick;
+                    if let Some(start_tick) = remote_input_buffer.buffer.start_tick {
+                        remote_input_buffer.buffer.start_tick = Some(start_tick + delta);
+                    }
+                    if let Some(last_tick) = remote_input_buffer.last_tick {
+                        remote_input_buffer.last_tick = Some(last_tick + delta);
+                    }
+                }
             }
         }
     }
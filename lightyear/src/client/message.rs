@@ -4,10 +4,12 @@ use tracing::{info_span, trace};
 
 use bitcode::encoding::Fixed;
 use bitcode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
 
 use crate::_reexport::{BitSerializable, MessageProtocol, ReadBuffer, WriteBuffer};
 use crate::prelude::{ChannelKind, NetworkTarget};
 use crate::protocol::Protocol;
+use crate::shared::config::ResumptionSecret;
 use crate::shared::ping::message::SyncMessage;
 use crate::shared::replication::{ReplicationMessage, ReplicationMessageData};
 
@@ -29,6 +31,25 @@ pub enum ClientMessage<P: Protocol> {
     // the reason why we include sync here instead of doing another MessageManager is so that
     // the sync messages can be added to packets that have other messages
     Sync(SyncMessage),
+    /// Sent by the client at the start of every connection, until the server answers with a
+    /// [`ServerMessage::Session`](crate::server::message::ServerMessage::Session).
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Handshake(HandshakeMessage),
+}
+
+/// Handshake sent by the client at the start of every connection
+///
+/// It is sent on an unreliable channel, but the client adds it to every packet until the server answers with
+/// a [`ServerMessage::Session`](crate::server::message::ServerMessage::Session), so a lost handshake (or a lost answer)
+/// only delays the session. The server buffers the other messages of the client until the handshake is verified.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct HandshakeMessage {
+    /// Secret of the previous session, if the client wants to resume it
+    pub(crate) resumption_secret: Option<ResumptionSecret>,
+    /// [`fingerprint`](crate::protocol::schema::ProtocolSchema::fingerprint) of the client's protocol.
+    /// The server disconnects the client if it doesn't match its own protocol.
+    pub(crate) protocol_fingerprint: u64,
 }
 
 impl<P: Protocol> BitSerializable for ClientMessage<P> {
@@ -70,6 +91,9 @@ impl<P: Protocol> ClientMessage<P> {
                                 #[cfg(metrics)]
                                 metrics::counter!("send_entity_despawn").increment(1);
                             }
+                            if actions.unreplicate {
+                                trace!("Send entity unreplicate");
+                            }
                             if !actions.insert.is_empty() {
                                 let components = actions
                                     .insert
@@ -140,6 +164,9 @@ impl<P: Protocol> ClientMessage<P> {
                     metrics::counter!("send_pong", "channel" => channel_name).increment(1);
                 }
             },
+            ClientMessage::Handshake(_) => {
+                trace!(channel = ?channel_name, "Sending handshake");
+            }
         }
     }
 }
@@ -1,6 +1,8 @@
 /*! Modules related to the client
 */
 
+pub mod checksum;
+
 pub mod components;
 
 pub mod config;
@@ -27,3 +29,4 @@ pub mod input_leafwing;
 pub(crate) mod message;
 pub(crate) mod networking;
 pub mod replication;
+pub mod rpc;
@@ -5,7 +5,8 @@ use std::ops::DerefMut;
 use bevy::ecs::system::{RunSystemOnce, SystemChangeTick, SystemParam, SystemState};
 use bevy::prelude::ResMut;
 use bevy::prelude::*;
-use tracing::{error, trace};
+use bevy::utils::Duration;
+use tracing::{error, info, trace};
 
 use crate::_reexport::{ClientMarker, ReplicationSend};
 use crate::client::config::ClientConfig;
@@ -15,10 +16,12 @@ use crate::client::sync::SyncSet;
 use crate::connection::client::{ClientConnection, NetClient, NetConfig};
 use crate::prelude::{SharedConfig, TickManager, TimeManager};
 use crate::protocol::component::ComponentProtocol;
+use crate::protocol::dynamic::{push_dynamic_events, DynamicProtocol};
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
 use crate::shared::config::Mode;
 use crate::shared::events::connection::{IterEntityDespawnEvent, IterEntitySpawnEvent};
+use crate::shared::events::systems::{push_message_delivery_events, push_stream_events};
 use crate::shared::sets::InternalMainSet;
 use crate::shared::tick_manager::TickEvent;
 use crate::shared::time_manager::is_client_ready_to_send;
@@ -62,7 +65,12 @@ impl<P: Protocol> Plugin for ClientNetworkingPlugin<P> {
             // SYSTEMS
             .add_systems(
                 PreUpdate,
-                receive::<P>.in_set(InternalMainSet::<ClientMarker>::Receive),
+                (
+                    sync_dynamic_protocol::<P>
+                        .run_if(resource_exists_and_changed::<DynamicProtocol>)
+                        .before(InternalMainSet::<ClientMarker>::Receive),
+                    receive::<P>.in_set(InternalMainSet::<ClientMarker>::Receive),
+                ),
             )
             .add_systems(
                 PostUpdate,
@@ -78,6 +86,7 @@ impl<P: Protocol> Plugin for ClientNetworkingPlugin<P> {
         //  a ConnectionManager or a NetConfig at startup
         // Create a new `ClientConnection` and `ConnectionManager` at startup, so that systems
         // that depend on these resources do not panic
+        app.init_resource::<ResumableSession>();
         app.world.run_system_once(rebuild_net_config::<P>);
 
         // CONNECTING
@@ -92,14 +101,19 @@ impl<P: Protocol> Plugin for ClientNetworkingPlugin<P> {
         );
         app.add_systems(
             PreUpdate,
-            handle_connection_failure.run_if(in_state(NetworkingState::Connecting)),
+            handle_connection_failure.run_if(
+                in_state(NetworkingState::Connecting).or_else(
+                    in_state(NetworkingState::Connected)
+                        .and_then(not(SharedConfig::is_host_server_condition)),
+                ),
+            ),
         );
 
         // CONNECTED
         app.add_systems(OnEnter(NetworkingState::Connected), on_connect);
 
         // DISCONNECTED
-        app.add_systems(OnEnter(NetworkingState::Disconnected), on_disconnect);
+        app.add_systems(OnEnter(NetworkingState::Disconnected), on_disconnect::<P>);
     }
 }
 
@@ -160,9 +174,26 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                             time_manager.as_ref(),
                                                             tick_manager.as_ref(),
                                                         );
+                                                        // the server refused our handshake: the session cannot be resumed
+                                                        if connection.denied_reason.is_some() {
+                                                            if let Err(e) = netclient.disconnect() {
+                                                                error!("Error disconnecting: {e:?}");
+                                                            }
+                                                            world.resource_mut::<ResumableSession>().closed = true;
+                                                            next_state.set(NetworkingState::Disconnected);
+                                                        }
                                                         // TODO: run these in EventsPlugin!
                                                         // HANDLE EVENTS
                                                         if !events.is_empty() {
+                                                            // Messages and component events of the DynamicProtocol
+                                                            push_dynamic_events(world, &mut events);
+
+                                                            // Stream events
+                                                            push_stream_events(world, &mut events);
+
+                                                            // Delivery of the messages we sent
+                                                            push_message_delivery_events(world, &mut events);
+
                                                             // Message Events
                                                             P::Message::push_message_events(world, &mut events);
 
@@ -292,14 +323,26 @@ fn handle_connection_failure(
     }
 }
 
+/// Keeps track of the previous session of the client, so that it can be resumed if the client
+/// reconnects before the end of the [`ResumptionConfig::grace_period`](crate::shared::config::ResumptionConfig::grace_period)
+#[derive(Resource, Default, Debug)]
+pub(crate) struct ResumableSession {
+    /// Time at which the client got disconnected (according to `Time<Real>`)
+    disconnected_at: Option<Duration>,
+    /// True if the client disconnected on purpose, in which case the session cannot be resumed
+    pub(crate) closed: bool,
+}
+
 /// System that runs when we enter the Connected state
 /// Updates the ConnectEvent events
 fn on_connect(
     mut connect_event_writer: EventWriter<ConnectEvent>,
     netcode: Res<ClientConnection>,
     config: Res<ClientConfig>,
+    mut session: ResMut<ResumableSession>,
     mut server_connect_event_writer: Option<ResMut<Events<crate::server::events::ConnectEvent>>>,
 ) {
+    *session = ResumableSession::default();
     connect_event_writer.send(ConnectEvent::new(netcode.id()));
 
     // in host-server mode, we also want to send a connect event to the server
@@ -313,15 +356,22 @@ fn on_connect(
 }
 /// System that runs when we enter the Disconnected state
 /// Updates the DisconnectEvent events
-fn on_disconnect(
+fn on_disconnect<P: Protocol>(
     mut disconnect_event_writer: EventWriter<DisconnectEvent>,
     netcode: Res<ClientConnection>,
+    connection: Res<ConnectionManager<P>>,
     config: Res<ClientConfig>,
+    time: Res<Time<Real>>,
+    mut session: ResMut<ResumableSession>,
     mut server_disconnect_event_writer: Option<
         ResMut<Events<crate::server::events::DisconnectEvent>>,
     >,
 ) {
-    disconnect_event_writer.send(DisconnectEvent::new(()));
+    // the grace period starts when we first lose the connection, not after the failed reconnection attempts
+    session.disconnected_at.get_or_insert(time.elapsed());
+    // the server can also refuse us after the connection, during the handshake
+    let denied_reason = netcode.denied_reason().or(connection.denied_reason);
+    disconnect_event_writer.send(DisconnectEvent::new(denied_reason));
 
     // in host-server mode, we also want to send a connect event to the server
     if config.shared.mode == Mode::HostServer {
@@ -354,6 +404,10 @@ pub(crate) fn is_disconnected(netclient: Option<Res<ClientConnection>>) -> bool
 /// This has several benefits:
 /// - the client connection's internal time is up-to-date (otherwise it might not be, since we don't call `update` while disconnected)
 /// - we can take into account any changes to the client config
+///
+/// If the client is resuming a session that was interrupted less than
+/// [`ResumptionConfig::grace_period`](crate::shared::config::ResumptionConfig::grace_period) ago,
+/// we keep the existing [`ConnectionManager`] so that the client keeps its replicated entities and message numbers.
 fn rebuild_net_config<P: Protocol>(world: &mut World) {
     let client_config = world.resource::<ClientConfig>().clone();
     if client_config.shared.mode == Mode::HostServer {
@@ -363,15 +417,33 @@ fn rebuild_net_config<P: Protocol>(world: &mut World) {
         );
     }
 
-    // insert a new connection manager (to reset sync, priority, message numbers, etc.)
-    let connection_manager = ConnectionManager::<P>::new(
-        world.resource::<P>().channel_registry(),
-        client_config.packet.clone(),
-        client_config.sync.clone(),
-        client_config.ping.clone(),
-        client_config.prediction.input_delay_ticks,
-    );
-    world.insert_resource(connection_manager);
+    let now = world.resource::<Time<Real>>().elapsed();
+    let session = world.resource::<ResumableSession>();
+    let resume = client_config.resumption.is_enabled()
+        && !session.closed
+        && session.disconnected_at.map_or(false, |disconnected_at| {
+            now.saturating_sub(disconnected_at) < client_config.resumption.grace_period
+        })
+        && world.contains_resource::<ConnectionManager<P>>();
+    if resume {
+        info!("resuming the previous session");
+        // present the secret of the previous session to the server
+        world.resource_mut::<ConnectionManager<P>>().handshake_done = false;
+    } else {
+        // insert a new connection manager (to reset sync, priority, message numbers, etc.)
+        let mut connection_manager = ConnectionManager::<P>::new(
+            world.resource::<P>().channel_registry(),
+            client_config.packet.clone(),
+            client_config.sync.clone(),
+            client_config.ping.clone(),
+            client_config.prediction.input_delay_ticks,
+            world.resource::<P>().schema(),
+        );
+        if let Some(dynamic_protocol) = world.get_resource::<DynamicProtocol>() {
+            connection_manager.set_dynamic_protocol(dynamic_protocol);
+        }
+        world.insert_resource(connection_manager);
+    }
 
     // drop the previous client connection to make sure we release any resources before creating the new one
     world.remove_resource::<ClientConnection>();
@@ -380,6 +452,14 @@ fn rebuild_net_config<P: Protocol>(world: &mut World) {
     world.insert_resource(netclient);
 }
 
+/// Update the [`ConnectionManager`] when types are registered in the [`DynamicProtocol`]
+fn sync_dynamic_protocol<P: Protocol>(
+    dynamic_protocol: Res<DynamicProtocol>,
+    mut connection: ResMut<ConnectionManager<P>>,
+) {
+    connection.set_dynamic_protocol(&dynamic_protocol);
+}
+
 /// Connect the client
 fn connect(mut netclient: ResMut<ClientConnection>) {
     info!("calling connect on netclient");
@@ -394,6 +474,7 @@ pub struct ClientConnectionParam<'w, 's> {
     next_state: ResMut<'w, NextState<NetworkingState>>,
     connection: ResMut<'w, ClientConnection>,
     config: Res<'w, ClientConfig>,
+    session: ResMut<'w, ResumableSession>,
     _marker: std::marker::PhantomData<&'s ()>,
 }
 
@@ -412,10 +493,13 @@ impl<'w, 's> ClientConnectionParam<'w, 's> {
     }
 
     /// Public system that should be used by the user to disconnect
+    ///
+    /// The session cannot be resumed after a disconnection initiated by the user.
     pub fn disconnect(&mut self) -> Result<()> {
         self.connection
             .disconnect()
             .context("Error disconnecting")?;
+        self.session.closed = true;
         self.next_state.set(NetworkingState::Disconnected);
         Ok(())
     }
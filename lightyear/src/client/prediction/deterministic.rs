@@ -0,0 +1,172 @@
+//! Deterministic mode: every client runs the full simulation, and only the inputs are exchanged.
+//!
+//! In this mode the server doesn't replicate any component, it only relays the inputs of each client to the
+//! other clients (see [`InputConfig::rebroadcast_inputs`](crate::prelude::server::InputConfig)).
+//! Every client simulates the entities marked with [`Deterministic`] using the inputs of all the players:
+//! - when the inputs of a remote player haven't arrived yet, we assume that the player kept its latest input
+//! - when the inputs arrive and they differ from what we assumed, we rollback to the first tick that was mispredicted
+//!   and re-simulate using the [`PredictionHistory`] of the [`Deterministic`] entities
+//!
+//! Every tick, the client computes a checksum of the [`Deterministic`] entities. Once the inputs of all players are
+//! known for a tick, the checksum for that tick is sent to the server along with the inputs; the server emits a
+//! [`DesyncEvent::Clients`](crate::prelude::DesyncEvent::Clients) if the clients reported different checksums for the same tick.
+//! The server sends to each client the list of the other connected clients, so that a client only waits for the inputs
+//! of the players that are currently connected. No checksum is sent until that list is received.
+//!
+//! Only the native inputs are supported: the leafwing inputs don't carry the checksums of the simulation.
+use std::collections::VecDeque;
+
+use bevy::prelude::{Commands, Component, Entity, Query, Reflect, Res, ResMut, Resource, With};
+use tracing::{debug, trace};
+
+use crate::_reexport::{FromType, WriteWordBuffer};
+use crate::client::components::SyncComponent;
+use crate::client::prediction::predicted_history::{ComponentState, PredictionHistory};
+use crate::client::prediction::rollback::{Rollback, RollbackState};
+use crate::prelude::TickManager;
+use crate::protocol::Protocol;
+use crate::serialize::writer::WriteBuffer;
+use crate::shared::checksum::component_checksum;
+use crate::shared::tick_manager::Tick;
+
+/// Marks an entity that is simulated deterministically by every client.
+///
+/// The entity must be spawned identically on every client (it is not replicated by the server).
+/// Its components with [`ComponentSyncMode::Full`](crate::prelude::client::ComponentSyncMode) get a [`PredictionHistory`]
+/// so that they can be rolled back, and are included in the checksum of the simulation.
+///
+/// NOTE: spawns and despawns are not rolled back, so deterministic entities should not be spawned or despawned
+/// in the `FixedUpdate` schedule.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Reflect)]
+pub struct Deterministic;
+
+/// Resource that stores the checksums of the deterministic simulation for the recent ticks
+#[derive(Resource, Debug, Default)]
+pub struct DeterministicChecksums {
+    /// Checksum of the tick that is currently being simulated
+    current: u64,
+    /// Checksums of the previous ticks, ordered by tick
+    buffer: VecDeque<(Tick, u64)>,
+    /// Latest tick for which the inputs of all players are known.
+    /// We will never rollback before this tick.
+    confirmed_tick: Option<Tick>,
+}
+
+impl DeterministicChecksums {
+    /// Get the checksum of the deterministic simulation at the given tick
+    pub fn get(&self, tick: Tick) -> Option<u64> {
+        self.buffer
+            .iter()
+            .find(|(t, _)| *t == tick)
+            .map(|(_, checksum)| *checksum)
+    }
+
+    /// Store the checksum for a tick. During rollback, this replaces the checksums of the ticks that get re-simulated.
+    fn record(&mut self, tick: Tick, checksum: u64) {
+        while self.buffer.back().map_or(false, |(t, _)| *t >= tick) {
+            self.buffer.pop_back();
+        }
+        self.buffer.push_back((tick, checksum));
+    }
+
+    /// The inputs of all players are known up to `tick`: returns the checksum for that tick if it was not reported yet
+    pub(crate) fn confirm(&mut self, tick: Tick) -> Option<(Tick, u64)> {
+        if self
+            .confirmed_tick
+            .map_or(false, |confirmed| confirmed >= tick)
+        {
+            return None;
+        }
+        self.confirmed_tick = Some(tick);
+        // we don't need the checksums of the older ticks anymore
+        while self.buffer.front().map_or(false, |(t, _)| *t < tick) {
+            self.buffer.pop_front();
+        }
+        self.get(tick).map(|checksum| (tick, checksum))
+    }
+}
+
+/// Add the value of the component `C` of every [`Deterministic`] entity to the checksum of the current tick.
+///
+/// The values are summed so that the checksum doesn't depend on the order of the entities (which can be
+/// different on each client).
+/// We also use this system to clear the history that is older than the confirmed tick, since we won't rollback
+/// before that tick.
+pub(crate) fn update_checksum<C: SyncComponent, P: Protocol>(
+    mut checksums: ResMut<DeterministicChecksums>,
+    mut query: Query<(Option<&C>, Option<&mut PredictionHistory<C>>), With<Deterministic>>,
+) where
+    P::ComponentKinds: FromType<C>,
+{
+    let kind = <P::ComponentKinds as FromType<C>>::from_type();
+    let mut writer = WriteWordBuffer::with_capacity(64);
+    let confirmed_tick = checksums.confirmed_tick;
+    for (component, history) in query.iter_mut() {
+        if let Some(component) = component {
+            checksums.current =
+                checksums
+                    .current
+                    .wrapping_add(component_checksum(&kind, component, &mut writer));
+        }
+        if let (Some(mut history), Some(confirmed_tick)) = (history, confirmed_tick) {
+            history.pop_until_tick(confirmed_tick);
+        }
+    }
+}
+
+/// Record the checksum of the tick that was just simulated
+pub(crate) fn record_checksum(
+    mut checksums: ResMut<DeterministicChecksums>,
+    tick_manager: Res<TickManager>,
+    rollback: Res<Rollback>,
+) {
+    let tick = rollback.get_rollback_tick().unwrap_or(tick_manager.tick());
+    let checksum = std::mem::take(&mut checksums.current);
+    trace!(?tick, ?checksum, "record deterministic checksum");
+    checksums.record(tick, checksum);
+}
+
+/// Restore the components of the [`Deterministic`] entities to their value at the end of the tick before the
+/// rollback tick.
+///
+/// Contrary to the other predicted entities, we don't clear the history: the inputs of different players can arrive
+/// at different times, so a later rollback might need to go further in the past.
+pub(crate) fn prepare_rollback_deterministic<C: SyncComponent, P: Protocol>(
+    mut commands: Commands,
+    mut query: Query<(Entity, Option<&mut C>, &mut PredictionHistory<C>), With<Deterministic>>,
+    rollback: Res<Rollback>,
+) where
+    P::ComponentKinds: FromType<C>,
+{
+    let kind = <P::ComponentKinds as FromType<C>>::from_type();
+    let RollbackState::ShouldRollback {
+        current_tick: rollback_tick_plus_one,
+    } = rollback.state
+    else {
+        return;
+    };
+    for (entity, component, mut history) in query.iter_mut() {
+        // remove the values that will be re-simulated
+        history.buffer.drain_after(&rollback_tick_plus_one);
+        let value = history
+            .buffer
+            .heap
+            .iter()
+            .max_by_key(|item| item.key)
+            .map(|item| item.item.clone());
+        match (value, component) {
+            (Some(ComponentState::Updated(value)), Some(mut component)) => {
+                *component = value;
+            }
+            (Some(ComponentState::Updated(value)), None) => {
+                debug!(?entity, ?kind, "Re-inserting component for rollback");
+                commands.entity(entity).insert(value);
+            }
+            (None | Some(ComponentState::Removed), Some(_)) => {
+                debug!(?entity, ?kind, "Removing component for rollback");
+                commands.entity(entity).remove::<C>();
+            }
+            (None | Some(ComponentState::Removed), None) => {}
+        }
+    }
+}
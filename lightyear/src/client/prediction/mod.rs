@@ -18,6 +18,7 @@ use crate::shared::tick_manager::Tick;
 
 pub(crate) mod correction;
 mod despawn;
+pub mod deterministic;
 pub mod plugin;
 mod pre_prediction;
 pub mod predicted_history;
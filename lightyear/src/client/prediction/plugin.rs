@@ -1,8 +1,8 @@
 use std::marker::PhantomData;
 
 use bevy::prelude::{
-    apply_deferred, App, FixedPostUpdate, IntoSystemConfigs, IntoSystemSetConfigs, Plugin,
-    PostUpdate, PreUpdate, Res, SystemSet,
+    apply_deferred, resource_exists, App, FixedPostUpdate, IntoSystemConfigs, IntoSystemSetConfigs,
+    Plugin, PostUpdate, PreUpdate, Res, SystemSet,
 };
 use bevy::reflect::Reflect;
 use bevy::transform::TransformSystem;
@@ -17,11 +17,15 @@ use crate::client::prediction::despawn::{
     despawn_confirmed, remove_component_for_despawn_predicted, remove_despawn_marker,
     restore_components_if_despawn_rolled_back, PredictionDespawnMarker,
 };
+use crate::client::prediction::deterministic::{
+    prepare_rollback_deterministic, record_checksum, update_checksum, Deterministic,
+    DeterministicChecksums,
+};
 use crate::client::prediction::predicted_history::{
     add_prespawned_component_history, update_prediction_history,
 };
 use crate::client::prediction::prespawn::{
-    PreSpawnedPlayerObjectPlugin, PreSpawnedPlayerObjectSet,
+    PreSpawnMatchConfig, PreSpawnedPlayerObjectPlugin, PreSpawnedPlayerObjectSet,
 };
 use crate::client::prediction::resource::PredictionManager;
 use crate::client::prediction::Predicted;
@@ -58,6 +62,14 @@ pub struct PredictionConfig {
     /// (i.e. if the client is 10 ticks head and correction_ticks is 1.0, then the correction will be done over 10 ticks)
     // Number of ticks it will take to visually update the Predicted state to the new Corrected state
     pub correction_ticks_factor: f32,
+    /// If true, the client runs in deterministic mode: the entities marked with [`Deterministic`] are simulated
+    /// by every client using the inputs of all the players, instead of being replicated by the server.
+    /// Only the native inputs are supported: the leafwing inputs don't carry the checksums of the simulation.
+    ///
+    /// See the [`deterministic`](crate::client::prediction::deterministic) module for more information.
+    pub deterministic: bool,
+    /// How to handle the pre-spawned entities that could not be matched one-to-one with a server entity
+    pub prespawn_match: PreSpawnMatchConfig,
 }
 
 impl PredictionConfig {
@@ -82,6 +94,18 @@ impl PredictionConfig {
         self.correction_ticks_factor = factor;
         self
     }
+
+    /// Enable the deterministic mode
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Update how pre-spawned entities that could not be matched are handled
+    pub fn with_prespawn_match(mut self, prespawn_match: PreSpawnMatchConfig) -> Self {
+        self.prespawn_match = prespawn_match;
+        self
+    }
 }
 
 pub struct PredictionPlugin<P: Protocol> {
@@ -137,6 +161,8 @@ pub enum PredictionSet {
     EntityDespawn,
     /// Update the client's predicted history; runs after each physics step in the FixedUpdate Schedule
     UpdateHistory,
+    /// Record the checksum of the deterministic simulation for the tick (only in deterministic mode)
+    UpdateChecksum,
 
     // PostUpdate Sets
     /// Visually interpolate the predicted components to the corrected state
@@ -177,7 +203,11 @@ where
                 (
                     // for SyncMode::Full, we need to check if we need to rollback.
                     check_rollback::<C, P>.in_set(PredictionSet::CheckRollback),
-                    (prepare_rollback::<C, P>, prepare_rollback_prespawn::<C, P>)
+                    (
+                        prepare_rollback::<C, P>,
+                        prepare_rollback_prespawn::<C, P>,
+                        prepare_rollback_deterministic::<C, P>,
+                    )
                         .in_set(PredictionSet::PrepareRollback),
                 ),
             );
@@ -187,6 +217,7 @@ where
                     add_prespawned_component_history::<C, P>.in_set(PredictionSet::SpawnHistory),
                     // we need to run this during fixed update to know accurately the history for each tick
                     update_prediction_history::<C>.in_set(PredictionSet::UpdateHistory),
+                    update_checksum::<C, P>.in_set(PredictionSet::UpdateChecksum),
                 ),
             );
             app.add_systems(
@@ -237,6 +268,7 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
             .register_type::<Rollback>()
             .register_type::<RollbackState>()
             .register_type::<PredictionDespawnMarker>()
+            .register_type::<Deterministic>()
             .register_type::<PredictionConfig>();
 
         P::Components::add_prediction_systems(app);
@@ -246,6 +278,9 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
         app.insert_resource(Rollback {
             state: RollbackState::Default,
         });
+        if self.config.deterministic {
+            app.init_resource::<DeterministicChecksums>();
+        }
 
         // PreUpdate systems:
         // 1. Receive confirmed entities, add Confirmed and Predicted components
@@ -302,6 +337,7 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
                 // right away to avoid rollbacks
                 PredictionSet::SpawnHistory,
                 PredictionSet::UpdateHistory,
+                PredictionSet::UpdateChecksum.run_if(resource_exists::<DeterministicChecksums>),
                 PredictionSet::IncrementRollbackTick.run_if(is_in_rollback),
             )
                 .in_set(PredictionSet::All)
@@ -315,6 +351,12 @@ impl<P: Protocol> Plugin for PredictionPlugin<P> {
             FixedPostUpdate,
             (
                 remove_despawn_marker.in_set(PredictionSet::EntityDespawn),
+                // record the checksum once every component was added to it
+                record_checksum
+                    .in_set(PredictionSet::All)
+                    .after(PredictionSet::UpdateChecksum)
+                    .before(PredictionSet::IncrementRollbackTick)
+                    .run_if(resource_exists::<DeterministicChecksums>),
                 increment_rollback_tick.in_set(PredictionSet::IncrementRollbackTick),
             ),
         );
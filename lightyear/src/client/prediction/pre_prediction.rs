@@ -11,6 +11,7 @@ use crate::client::sync::client_is_synced;
 use crate::connection::client::NetClient;
 use crate::prelude::client::{ClientConnection, PredictionSet};
 use crate::prelude::{NetworkTarget, Protocol, ShouldBePredicted};
+use crate::shared::replication::commands::RemoveReplicateCommandsExt;
 use crate::shared::replication::components::{PrePredicted, Replicate};
 use crate::shared::sets::InternalReplicationSet;
 use bevy::prelude::*;
@@ -173,12 +174,14 @@ impl<P: Protocol> PrePredictionPlugin<P> {
     ) {
         for entity in pre_predicted_entities.iter() {
             debug!(?entity, "removing replicate from pre-predicted entity");
-            commands
-                .entity(entity)
-                .remove::<Replicate<P>>()
-                .insert((Predicted {
-                    confirmed_entity: None,
-                },));
+            let mut entity_commands = commands.entity(entity);
+            // the server now has authority over the entity, so the removal must not be replicated
+            RemoveReplicateCommandsExt::<P, ConnectionManager<P>>::remove_replicate(
+                &mut entity_commands,
+            );
+            entity_commands.insert((Predicted {
+                confirmed_entity: None,
+            },));
         }
     }
 }
@@ -8,6 +8,7 @@ use bevy::prelude::{
 use tracing::{debug, error, info, trace};
 
 use crate::client::components::{ComponentSyncMode, SyncComponent, SyncMetadata};
+use crate::client::prediction::deterministic::Deterministic;
 use crate::client::prediction::resource::PredictionManager;
 use crate::client::prediction::rollback::{Rollback, RollbackState};
 use crate::prelude::{ExternalMapper, PreSpawnedPlayerObject, ShouldBePredicted, TickManager};
@@ -78,6 +79,18 @@ impl<T: SyncComponent> PredictionHistory<T> {
         })
     }
 
+    /// Get the value of the component at the specified tick, without modifying the history.
+    ///
+    /// Returns None if the history doesn't go back as far as the specified tick.
+    pub(crate) fn get(&self, tick: Tick) -> Option<&ComponentState<T>> {
+        self.buffer
+            .heap
+            .iter()
+            .filter(|item| item.key <= tick)
+            .max_by_key(|item| item.key)
+            .map(|item| &item.item)
+    }
+
     // /// Get the value of the component at the specified tick.
     // /// Clears the history buffer of all ticks older than the specified tick.
     // /// Returns None
@@ -195,7 +208,7 @@ pub(crate) fn add_component_history<C: SyncComponent, P: Protocol>(
     }
 }
 
-/// Add the history for prespawned entities.
+/// Add the history for prespawned entities (and for [`Deterministic`] entities).
 /// This must run on FixedUpdate (for entities spawned on FixedUpdate and PreUpdate (for entities spawned on Update)
 #[allow(clippy::type_complexity)]
 pub fn add_prespawned_component_history<C: SyncComponent, P: Protocol>(
@@ -207,7 +220,11 @@ pub fn add_prespawned_component_history<C: SyncComponent, P: Protocol>(
             Without<PredictionHistory<C>>,
             Without<Confirmed>,
             // for pre-spawned entities
-            Or<(With<ShouldBePredicted>, With<PreSpawnedPlayerObject>)>,
+            Or<(
+                With<ShouldBePredicted>,
+                With<PreSpawnedPlayerObject>,
+                With<Deterministic>,
+            )>,
         ),
     >,
 ) where
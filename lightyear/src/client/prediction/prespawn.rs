@@ -1,13 +1,15 @@
 //! Handles spawning entities that are predicted
 
+use bevy::ecs::archetype::Archetype;
+use bevy::ecs::component::Components;
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::any::{Any, TypeId};
-use std::hash::{BuildHasher, Hash, Hasher};
+use std::hash::{Hash, Hasher};
 use tracing::{debug, info, trace, warn};
 
-use crate::_reexport::{ClientMarker, ComponentProtocol};
+use crate::_reexport::{ClientMarker, ComponentProtocol, FromType};
 use crate::client::components::Confirmed;
+use crate::client::config::ClientConfig;
 use crate::client::connection::ConnectionManager;
 use crate::client::events::ComponentInsertEvent;
 use crate::client::networking::{is_connected, NetworkingState};
@@ -17,9 +19,9 @@ use crate::client::prediction::rollback::{Rollback, RollbackState};
 use crate::client::prediction::Predicted;
 use crate::client::sync::client_is_synced;
 use crate::prelude::client::PredictionSet;
-use crate::prelude::{ShouldBePredicted, TickManager};
+use crate::prelude::{PrePredicted, ShouldBePredicted, Tick, TickManager};
 use crate::protocol::Protocol;
-use crate::shared::replication::components::{DespawnTracker, Replicate};
+use crate::shared::replication::components::ShouldBeInterpolated;
 use crate::shared::sets::InternalReplicationSet;
 
 pub(crate) struct PreSpawnedPlayerObjectPlugin<P> {
@@ -49,6 +51,8 @@ pub enum PreSpawnedPlayerObjectSet {
 
 impl<P: Protocol> Plugin for PreSpawnedPlayerObjectPlugin<P> {
     fn build(&self, app: &mut App) {
+        // RESOURCES
+        app.init_resource::<PreSpawnHashConfig<P>>();
         app.configure_sets(
             PreUpdate,
             PreSpawnedPlayerObjectSet::Spawn.in_set(PredictionSet::SpawnPrediction),
@@ -109,11 +113,11 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
         };
 
         world.resource_scope(|world: &mut World, mut manager: Mut<PredictionManager>| {
-            let components = world.components();
-
             // ignore confirmed entities just in case we somehow didn't remove their hash during PreUpdate
             let mut pre_spawned_query = world
                 .query_filtered::<(EntityRef, Ref<PreSpawnedPlayerObject>), Without<Confirmed>>();
+            let components = world.components();
+            let hash_config = world.resource::<PreSpawnHashConfig<P>>();
             // let mut predicted_entities = vec![];
             for (entity_ref, prespawn) in pre_spawned_query.iter(world) {
                 // we only care about newly-added PreSpawnedPlayerObject components
@@ -123,62 +127,12 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
                 let entity = entity_ref.id();
                 let hash = prespawn.hash.map_or_else(
                     || {
-                        // TODO: try EntityHasher instead since we only hash the 64 lower bits of TypeId
-                        // TODO: should I create the hasher once outside?
-                        // let mut hasher =
-                        //     bevy::utils::RandomState::with_seeds(1, 2, 3, 4).build_hasher();
-
-                        let mut hasher = seahash::SeaHasher::new();
-                        // let mut hasher = xxhash_rust::xxh3::Xxh3Builder::new()
-                        //     .with_seed(1)
-                        //     .build_hasher();
-                        // TODO: the default hasher doesn't seem to be deterministic across processes
-                        // let mut hasher = bevy::utils::AHasher::default();
-
-                        // TODO: this only works currently for entities that are spawned during Update!
-                        //  if we want the tick to be valid, compute_hash should also be run at the end of FixedUpdate::Main
-                        //  so that we have the exact spawn tick! Solutions:
-                        //  run compute_hash in post-update as well
-                        // we include the spawn tick in the hash
-                        tick.hash(&mut hasher);
-                        //
-                        // // TODO: we only want to use components from the protocol, because server/client might use a lot of different stuff...
-                        // entity_ref.contains_type_id()
-
-                        let protocol_component_types = P::Components::type_ids();
-
-                        // NOTE: we cannot call hash() multiple times because the components in the archetype
-                        //  might get iterated in any order!
-                        //  Instead we will get the sorted list of types to hash first, sorted by type_id
-                        let mut kinds_to_hash = entity_ref
-                            .archetype()
-                            .components()
-                            .filter_map(|component_id| {
-                                if let Some(type_id) =
-                                    world.components().get_info(component_id).unwrap().type_id()
-                                {
-                                    // ignore some book-keeping components
-                                    if type_id != TypeId::of::<Replicate<P>>()
-                                        && type_id != TypeId::of::<PreSpawnedPlayerObject>()
-                                        && type_id != TypeId::of::<ShouldBePredicted>()
-                                        && type_id != TypeId::of::<DespawnTracker>()
-                                    {
-                                        return protocol_component_types.get(&type_id).copied();
-                                    }
-                                }
-                                None
-                            })
-                            .collect::<Vec<_>>();
-                        kinds_to_hash.sort();
-                        kinds_to_hash.into_iter().for_each(|kind| {
-                            trace!(?kind, "using kind for hash");
-                            kind.hash(&mut hasher)
-                        });
-
-                        // No need to set the value on the component here, we only need the value in the resource!
-                        // prespawn.hash = Some(hasher.finish());
-
-                        let new_hash = hasher.finish();
+                        let new_hash = hash_config.compute_hash(
+                            entity_ref.archetype(),
+                            components,
+                            tick,
+                            prespawn.salt,
+                        );
                         trace!(?entity, ?tick, hash = ?new_hash, "computed spawn hash for entity");
                         new_hash
                     },
@@ -193,13 +147,18 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
                     },
                 );
 
-                // TODO: what to do in multiple entities share the same hash?
-                //  just match a random one of them? or should the user have a more precise hash?
-                manager
-                    .prespawn_hash_to_entities
-                    .entry(hash)
-                    .or_default()
-                    .push(entity);
+                // multiple entities can share the same hash, they are handled according to the
+                // PreSpawnCollisionPolicy when the server entity is received
+                let entities = manager.prespawn_hash_to_entities.entry(hash).or_default();
+                if !entities.is_empty() {
+                    warn!(
+                        ?entity,
+                        ?tick,
+                        ?hash,
+                        "Multiple pre-spawned entities share the same hash. Consider adding a salt."
+                    );
+                }
+                entities.push(entity);
                 // add a timer on the entity so that it gets despawned if the interpolation tick
                 // reaches it without matching with any server entity
                 manager.prespawn_tick_to_hash.add_item(tick, hash);
@@ -229,7 +188,9 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
         mut manager: ResMut<PredictionManager>,
         mut events: EventReader<ComponentInsertEvent<PreSpawnedPlayerObject>>,
         query: Query<&PreSpawnedPlayerObject>,
+        config: Res<ClientConfig>,
     ) {
+        let match_config = config.prediction.prespawn_match;
         for event in events.read() {
             let confirmed_entity = event.entity();
             // we handle the PreSpawnedPlayerObject hash in this system and don't need it afterwards
@@ -246,15 +207,28 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
                 manager.prespawn_hash_to_entities.remove(&server_hash)
             else {
                 debug!(?server_hash, "Received a PreSpawnedPlayerObject entity from the server with a hash that does not match any client entity");
-                // remove the PreSpawnedPlayerObject so that the entity can be normal-predicted
-                commands
-                    .entity(confirmed_entity)
-                    .remove::<PreSpawnedPlayerObject>();
+                Self::handle_server_no_match(&mut commands, confirmed_entity, match_config);
                 continue;
             };
 
-            // if there are multiple entities, we will use the first one
-            let client_entity = client_entity_list.pop().unwrap();
+            if client_entity_list.len() > 1
+                && match_config.collision == PreSpawnCollisionPolicy::Reject
+            {
+                warn!(
+                    ?server_hash,
+                    "Multiple client pre-spawned entities share the hash of the server entity. Rejecting all of them"
+                );
+                for client_entity in client_entity_list {
+                    if let Some(entity_commands) = commands.get_entity(client_entity) {
+                        entity_commands.despawn_recursive();
+                    }
+                }
+                Self::handle_server_no_match(&mut commands, confirmed_entity, match_config);
+                continue;
+            }
+
+            // if there are multiple entities, we match them in the order in which they were spawned
+            let client_entity = client_entity_list.remove(0);
             debug!("found a client pre-spawned entity corresponding to server pre-spawned entity! Spawning a Predicted entity for it");
 
             // we found the corresponding client entity!
@@ -309,12 +283,30 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
         }
     }
 
+    /// Handle a server entity that could not be matched with any client pre-spawned entity
+    fn handle_server_no_match(
+        commands: &mut Commands,
+        confirmed_entity: Entity,
+        match_config: PreSpawnMatchConfig,
+    ) {
+        match match_config.server_no_match {
+            // the PreSpawnedPlayerObject is removed so that the entity can be normal-predicted
+            ServerNoMatchPolicy::Predict => {}
+            ServerNoMatchPolicy::ConfirmedOnly => {
+                commands
+                    .entity(confirmed_entity)
+                    .remove::<ShouldBePredicted>();
+            }
+        }
+    }
+
     /// Cleanup the client prespawned entities for which we couldn't find a mapped server entity
     pub(crate) fn pre_spawned_player_object_cleanup(
         mut commands: Commands,
         tick_manager: Res<TickManager>,
         connection: Res<ConnectionManager<P>>,
         mut manager: ResMut<PredictionManager>,
+        config: Res<ClientConfig>,
     ) {
         let tick = tick_manager.tick();
         // TODO: why is interpolation tick not good enough and we need to use an earlier tick?
@@ -336,14 +328,26 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
                 .iter()
                 .flatten()
                 .for_each(|entity| {
-                    if let Some(entity_commands) = commands.get_entity(*entity) {
-                        trace!(
-                            ?tick,
-                            ?entity,
-                            "Cleaning up prespawned player object up to past tick: {:?}",
-                            past_tick
-                        );
-                        entity_commands.despawn_recursive();
+                    if let Some(mut entity_commands) = commands.get_entity(*entity) {
+                        match config.prediction.prespawn_match.client_no_match {
+                            ClientNoMatchPolicy::Despawn => {
+                                trace!(
+                                    ?tick,
+                                    ?entity,
+                                    "Cleaning up prespawned player object up to past tick: {:?}",
+                                    past_tick
+                                );
+                                entity_commands.despawn_recursive();
+                            }
+                            ClientNoMatchPolicy::Keep => {
+                                trace!(
+                                    ?tick,
+                                    ?entity,
+                                    "Keeping unmatched prespawned player object"
+                                );
+                                entity_commands.remove::<PreSpawnedPlayerObject>();
+                            }
+                        }
                     }
                 });
         }
@@ -355,39 +359,158 @@ impl<P: Protocol> PreSpawnedPlayerObjectPlugin<P> {
 )]
 pub struct PreSpawnedPlayerObject {
     /// The hash that will identify the spawned entity
-    /// By default, if the hash is not set, it will be generated from the entity's archetype (list of components) and spawn tick
+    /// By default, if the hash is not set, it will be generated from the entity's archetype (list of components),
+    /// spawn tick and salt (see [`PreSpawnHashConfig`]).
     /// Otherwise you can manually set it to a value that will be the same on both the client and server
     pub hash: Option<u64>,
-    //
-    // pub conflict_resolution: ConflictResolution,
+    /// Optional value that is included in the hash, to differentiate entities that have the same components
+    /// and are spawned during the same tick. (for example the index of the bullet in a burst)
+    pub salt: Option<u64>,
 }
 
-// pub enum ClientNoMatchHandling {
-//     /// If we don't get any server-entity that matches this prespawned player object, then we despawn it on the client
-//     /// Once we are sure that we won't get any more server updates for that entity
-//     /// (i.e. once interpolation_tick is reached)
-//     Despawn,
-//
-//     /// Even if we don't get any server-entity that matches this prespawned player object, we don't bother despawning it
-//     /// and we just leave it as is
-//     Allow,
-// }
-//
-// pub enum ServerNoMatchHandling {
-//     /// If the server sends an entity that doesn't match any existing client prespawned player object, we consider that the server
-//     /// entity is still valid and we spawn a Predicted entity for it.
-//     ForcePrediction,
-// }
+impl PreSpawnedPlayerObject {
+    /// Use a salt to differentiate entities with the same components that are spawned during the same tick
+    pub fn with_salt(mut self, salt: u64) -> Self {
+        self.salt = Some(salt);
+        self
+    }
+}
 
-// pub enum ConflictResolution {
-//     /// If we don't get any server-entity that matches this prespawned player object, then we despawn it on the client
-//     /// Once we are sure that we won't get any more server updates for that entity
-//     /// (i.e. once interpolation_tick is reached)
-//     DespawnClient,
-//     /// If the server sends us an entity that doesn't match any client prespawned player object, we consider that the entity
-//     /// should still be predicted normally.
-//     AllowDuplicate,
-// }
+/// Seeds of the hasher used for the pre-spawn hash. They are fixed so that the hash is identical in every process
+const PRESPAWN_HASH_SEEDS: (u64, u64, u64, u64) = (
+    0x16f1_1fe8_9b0d_677c,
+    0xb480_a793_d8e6_c86c,
+    0x6fe2_e5aa_f078_ebc9,
+    0x14f9_94a4_c525_9381,
+);
+
+/// Configuration of the hash used to match client pre-spawned entities with server entities.
+///
+/// It must be identical on the client and on the server.
+#[derive(Resource, Debug, Clone)]
+pub struct PreSpawnHashConfig<P: Protocol> {
+    /// If set, only these components are included in the hash. Otherwise, all the components of the entity
+    /// that are part of the protocol are included.
+    pub components: Option<Vec<P::ComponentKinds>>,
+}
+
+impl<P: Protocol> Default for PreSpawnHashConfig<P> {
+    fn default() -> Self {
+        Self { components: None }
+    }
+}
+
+impl<P: Protocol> PreSpawnHashConfig<P> {
+    /// Include the component `C` in the hash. If this is never called, all the components are included
+    pub fn with_component<C>(mut self) -> Self
+    where
+        P::ComponentKinds: FromType<C>,
+    {
+        self.components
+            .get_or_insert_with(Vec::new)
+            .push(<P::ComponentKinds as FromType<C>>::from_type());
+        self
+    }
+
+    /// Compute the hash of a pre-spawned entity from its spawn tick, its salt, and the kinds of its components
+    pub(crate) fn compute_hash(
+        &self,
+        archetype: &Archetype,
+        components: &Components,
+        tick: Tick,
+        salt: Option<u64>,
+    ) -> u64 {
+        let (k1, k2, k3, k4) = PRESPAWN_HASH_SEEDS;
+        let mut hasher = seahash::SeaHasher::with_seeds(k1, k2, k3, k4);
+        // NOTE: we only hash explicit fixed-width values: the derived `Hash` implementations hash enum
+        //  discriminants and lengths as `isize`/`usize`, whose width differs between wasm32 and 64-bit targets
+        hasher.write_u64(tick.0 as u64);
+        match salt {
+            Some(salt) => {
+                hasher.write_u64(1);
+                hasher.write_u64(salt);
+            }
+            None => hasher.write_u64(0),
+        }
+
+        // book-keeping components that are not present on both the client and server entities
+        let ignored_kinds = [
+            <P::ComponentKinds as FromType<PreSpawnedPlayerObject>>::from_type(),
+            <P::ComponentKinds as FromType<ShouldBePredicted>>::from_type(),
+            <P::ComponentKinds as FromType<ShouldBeInterpolated>>::from_type(),
+            <P::ComponentKinds as FromType<PrePredicted>>::from_type(),
+        ];
+        let protocol_component_types = P::Components::type_ids();
+        // NOTE: the components in the archetype can be iterated in any order, so we sort them first
+        let mut kinds_to_hash = archetype
+            .components()
+            .filter_map(|component_id| {
+                components
+                    .get_info(component_id)
+                    .and_then(|info| info.type_id())
+                    .and_then(|type_id| protocol_component_types.get(&type_id).copied())
+            })
+            .filter(|kind| {
+                !ignored_kinds.contains(kind)
+                    && self
+                        .components
+                        .as_ref()
+                        .map_or(true, |selected| selected.contains(kind))
+            })
+            .collect::<Vec<_>>();
+        kinds_to_hash.sort();
+        kinds_to_hash.into_iter().for_each(|kind| {
+            trace!(?kind, "using kind for hash");
+            // the kind is hashed through its network encoding, which is identical on every platform
+            let bytes = bitcode::serialize(&kind).expect("could not serialize component kind");
+            hasher.write_u64(bytes.len() as u64);
+            hasher.write(&bytes);
+        });
+        hasher.finish()
+    }
+}
+
+/// Configuration of how the client handles pre-spawned entities that could not be matched one-to-one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Reflect)]
+pub struct PreSpawnMatchConfig {
+    /// What to do when multiple client entities share the hash of a server entity
+    pub collision: PreSpawnCollisionPolicy,
+    /// What to do with client entities that never get matched with a server entity
+    pub client_no_match: ClientNoMatchPolicy,
+    /// What to do with server entities that don't match any client entity
+    pub server_no_match: ServerNoMatchPolicy,
+}
+
+/// What to do when multiple client pre-spawned entities share the same hash
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum PreSpawnCollisionPolicy {
+    /// Match the client entities with the server entities in the order in which they were spawned
+    #[default]
+    MatchInOrder,
+    /// Despawn all the client entities that share the hash, and handle the server entity as if it didn't match
+    /// any client entity
+    Reject,
+}
+
+/// What to do with client pre-spawned entities that don't match any server entity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum ClientNoMatchPolicy {
+    /// Despawn the client entity once we are sure that the server won't send a matching entity
+    #[default]
+    Despawn,
+    /// Keep the client entity as a local entity
+    Keep,
+}
+
+/// What to do with server entities that don't match any client pre-spawned entity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub enum ServerNoMatchPolicy {
+    /// Spawn a Predicted entity for the server entity, as for normal predicted entities
+    #[default]
+    Predict,
+    /// Only keep the Confirmed entity
+    ConfirmedOnly,
+}
 
 // TODO: maybe provide a prediction_spawn command instead of running the `compute_hash` in both FixedUpdate and PostUpdate?
 
@@ -477,7 +600,8 @@ mod tests {
 
         let current_tick = stepper.client_app.world.resource::<TickManager>().tick();
         let prediction_manager = stepper.client_app.world.resource::<PredictionManager>();
-        let expected_hash: u64 = 16099262544408252231;
+        // the hash only depends on fixed-width values, so it is the same on every platform
+        let expected_hash: u64 = 1931273315187335255;
         dbg!(&prediction_manager.prespawn_hash_to_entities);
         assert_eq!(
             prediction_manager.prespawn_hash_to_entities,
@@ -511,4 +635,166 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_compute_hash_with_salt() {
+        let mut stepper = BevyStepper::default();
+
+        // entities spawned during the same tick with different salts get different hashes
+        stepper.client_app.world.spawn((
+            Component1(1.0),
+            PreSpawnedPlayerObject::default().with_salt(0),
+        ));
+        stepper.client_app.world.spawn((
+            Component1(1.0),
+            PreSpawnedPlayerObject::default().with_salt(1),
+        ));
+        stepper.frame_step();
+
+        let prediction_manager = stepper.client_app.world.resource::<PredictionManager>();
+        assert_eq!(prediction_manager.prespawn_hash_to_entities.len(), 2);
+        assert!(prediction_manager
+            .prespawn_hash_to_entities
+            .values()
+            .all(|entities| entities.len() == 1));
+    }
+
+    fn setup_match(prespawn_match: PreSpawnMatchConfig) -> BevyStepper {
+        let frame_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(frame_duration),
+            ..Default::default()
+        };
+        let mut stepper = BevyStepper::new(
+            shared_config,
+            SyncConfig::default(),
+            PredictionConfig::default().with_prespawn_match(prespawn_match),
+            InterpolationConfig::default(),
+            LinkConditionerConfig {
+                incoming_latency: Duration::from_millis(0),
+                incoming_jitter: Duration::from_millis(0),
+                incoming_loss: 0.0,
+            },
+            frame_duration,
+        );
+        stepper.init();
+        stepper
+    }
+
+    /// Spawn two client entities and one server entity that share the same hash
+    fn spawn_colliding_entities(stepper: &mut BevyStepper) -> (Entity, Entity, Entity) {
+        let prespawn = PreSpawnedPlayerObject {
+            hash: Some(1),
+            salt: None,
+        };
+        let client_entity_1 = stepper
+            .client_app
+            .world
+            .spawn((Component1(1.0), prespawn))
+            .id();
+        let client_entity_2 = stepper
+            .client_app
+            .world
+            .spawn((Component1(1.0), prespawn))
+            .id();
+        stepper.frame_step();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component1(1.0),
+                prespawn,
+                Replicate {
+                    prediction_target: NetworkTarget::All,
+                    ..Default::default()
+                },
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        (client_entity_1, client_entity_2, server_entity)
+    }
+
+    #[test]
+    fn test_collision_match_in_order() {
+        let mut stepper = setup_match(PreSpawnMatchConfig::default());
+        let (client_entity_1, client_entity_2, _) = spawn_colliding_entities(&mut stepper);
+
+        // the first client entity is matched with the server entity
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Predicted>(client_entity_1)
+            .is_some());
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Predicted>(client_entity_2)
+            .is_none());
+    }
+
+    #[test]
+    fn test_collision_reject() {
+        let mut stepper = setup_match(PreSpawnMatchConfig {
+            collision: PreSpawnCollisionPolicy::Reject,
+            ..Default::default()
+        });
+        let (client_entity_1, client_entity_2, server_entity) =
+            spawn_colliding_entities(&mut stepper);
+
+        // both client entities are despawned, and the server entity is predicted normally
+        assert!(stepper
+            .client_app
+            .world
+            .get_entity(client_entity_1)
+            .is_none());
+        assert!(stepper
+            .client_app
+            .world
+            .get_entity(client_entity_2)
+            .is_none());
+        let confirmed_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        let predicted_entity = stepper
+            .client_app
+            .world
+            .get::<Confirmed>(confirmed_entity)
+            .unwrap()
+            .predicted
+            .unwrap();
+        assert_ne!(predicted_entity, client_entity_1);
+        assert_ne!(predicted_entity, client_entity_2);
+    }
+
+    #[test]
+    fn test_client_no_match_keep() {
+        let mut stepper = setup_match(PreSpawnMatchConfig {
+            client_no_match: ClientNoMatchPolicy::Keep,
+            ..Default::default()
+        });
+        let client_entity = stepper
+            .client_app
+            .world
+            .spawn((Component1(1.0), PreSpawnedPlayerObject::default()))
+            .id();
+        for _ in 0..50 {
+            stepper.frame_step();
+        }
+        // the entity never matched with a server entity, but is kept as a local entity
+        assert!(stepper
+            .client_app
+            .world
+            .get::<PreSpawnedPlayerObject>(client_entity)
+            .is_none());
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(1.0))
+        );
+    }
 }
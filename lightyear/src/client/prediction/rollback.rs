@@ -46,6 +46,19 @@ pub enum RollbackState {
     },
 }
 
+impl Rollback {
+    /// Returns the tick that is currently being re-simulated, if we are in rollback.
+    ///
+    /// The input systems use it to fetch the inputs of that tick from the input buffers (for the local
+    /// player, and for the remote players whose inputs were rebroadcast by the server)
+    pub fn get_rollback_tick(&self) -> Option<Tick> {
+        match self.state {
+            RollbackState::Default => None,
+            RollbackState::ShouldRollback { current_tick } => Some(current_tick),
+        }
+    }
+}
+
 #[allow(clippy::type_complexity)]
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn check_rollback<C: SyncComponent, P: Protocol>(
@@ -95,6 +108,12 @@ pub(crate) fn check_rollback<C: SyncComponent, P: Protocol>(
             debug!("Predicted entity {:?} was not found", confirmed.predicted);
             continue;
         };
+        // the server doesn't send us updates for components that we have authority over,
+        // the predicted history is the source of truth
+        if connection.authority.has_authority(p, &kind) {
+            predicted_history.pop_until_tick(confirmed.tick);
+            continue;
+        }
 
         // 2. We will compare the predicted history and the confirmed entity at the current confirmed entity tick
         // - Confirmed contains the server state at the tick
@@ -182,6 +201,7 @@ pub(crate) fn prepare_rollback<C: SyncComponent, P: Protocol>(
         ),
     >,
     confirmed_query: Query<(Entity, Option<&C>, Ref<Confirmed>)>,
+    connection: Res<ConnectionManager<P>>,
     rollback: Res<Rollback>,
 ) where
     <P as Protocol>::ComponentKinds: FromType<C>,
@@ -221,11 +241,39 @@ pub(crate) fn prepare_rollback<C: SyncComponent, P: Protocol>(
             continue;
         };
 
-        // 2. we need to clear the history so we can write a new one
-        predicted_history.clear();
         // SAFETY: we know the predicted entity exists
         let mut entity_mut = commands.entity(predicted_entity);
 
+        // if we have authority over the component, we rollback to our own state at the rollback tick
+        if connection.authority.has_authority(predicted_entity, &kind) {
+            let state = predicted_history.pop_until_tick(rollback_tick);
+            predicted_history.clear();
+            match state {
+                Some(ComponentState::Updated(c)) => {
+                    predicted_history
+                        .buffer
+                        .add_item(rollback_tick, ComponentState::Updated(c.clone()));
+                    match predicted_component {
+                        Some(mut predicted_component) => *predicted_component = c,
+                        None => {
+                            entity_mut.insert(c);
+                        }
+                    }
+                }
+                Some(ComponentState::Removed) => {
+                    predicted_history
+                        .buffer
+                        .add_item(rollback_tick, ComponentState::Removed);
+                    entity_mut.remove::<C>();
+                }
+                None => {}
+            }
+            continue;
+        }
+
+        // 2. we need to clear the history so we can write a new one
+        predicted_history.clear();
+
         // 3. we update the state to the Corrected state
         // NOTE: visually, we will use the CorrectionFn to interpolate between the current Predicted state and the Corrected state
         //  even though for other purposes (physics, etc.) we switch directly to the Corrected state
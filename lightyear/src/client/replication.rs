@@ -1,4 +1,5 @@
 use crate::_reexport::ClientMarker;
+use crate::channel::builder::ChannelDirection;
 use crate::client::config::ClientConfig;
 use bevy::prelude::*;
 use bevy::utils::Duration;
@@ -48,6 +49,7 @@ impl<P: Protocol> Plugin for ClientReplicationPlugin<P> {
                 config.shared.tick.tick_duration,
                 config.replication.enable_send,
                 config.replication.enable_receive,
+                ChannelDirection::ClientToServer,
             ))
             // TODO: currently we only support pre-spawned entities spawned during the FixedUpdate schedule
             // // SYSTEM SETS
@@ -0,0 +1,159 @@
+/*! Plugin to handle the [`Request`]s sent to the server, and the requests received from the server
+
+See the [`rpc`](crate::shared::rpc) module for more information.
+*/
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::system::{BoxedSystem, SystemId};
+use bevy::prelude::{
+    App, EventReader, EventWriter, Events, IntoSystem, IntoSystemConfigs, Local, Plugin, PreUpdate,
+    Res, ResMut, Resource, World,
+};
+use bevy::utils::Duration;
+use tracing::error;
+
+use crate::client::connection::ConnectionManager;
+use crate::client::events::{MessageEvent, ResponseEvent};
+use crate::prelude::{Channel, ChannelKind, NetworkTarget};
+use crate::protocol::Protocol;
+use crate::shared::rpc::{Request, RequestMessage, ResponseMessage, RpcError, DEFAULT_RPC_TIMEOUT};
+use crate::shared::sets::{ClientMarker, InternalMainSet};
+
+/// Plugin that emits a [`ResponseEvent`] for each [`Request`] of type `R` sent to the server,
+/// when the server responds or when the request times out.
+///
+/// The requests of type `R` received from the server can be handled either by reading the
+/// [`MessageEvent<RequestMessage<R>>`](MessageEvent) events and calling [`ConnectionManager::send_response`],
+/// or by registering a handler system with [`RpcPlugin::with_handler`].
+pub struct RpcPlugin<P: Protocol, R: Request> {
+    timeout: Duration,
+    handler: Mutex<Option<(ChannelKind, BoxedSystem<R, R::Response>)>>,
+    _marker: PhantomData<P>,
+}
+
+impl<P: Protocol, R: Request> Default for RpcPlugin<P, R> {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_RPC_TIMEOUT,
+            handler: Mutex::new(None),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol, R: Request> RpcPlugin<P, R> {
+    /// Delay after which a request that did not receive a response fails with [`RpcError::Timeout`]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Respond to the requests of type `R` received from the server with the output of the `handler` system.
+    /// The responses are sent on the channel `C`.
+    pub fn with_handler<C: Channel, M>(
+        self,
+        handler: impl IntoSystem<R, R::Response, M> + 'static,
+    ) -> Self {
+        *self.handler.lock().unwrap() = Some((
+            ChannelKind::of::<C>(),
+            Box::new(IntoSystem::into_system(handler)),
+        ));
+        self
+    }
+}
+
+#[derive(Resource)]
+struct RpcSettings<R> {
+    timeout: Duration,
+    _marker: PhantomData<R>,
+}
+
+#[derive(Resource)]
+struct RequestHandler<R: Request> {
+    channel: ChannelKind,
+    system: SystemId<R, R::Response>,
+}
+
+impl<P: Protocol, R: Request> Plugin for RpcPlugin<P, R>
+where
+    P::Message: From<RequestMessage<R>> + From<ResponseMessage<R::Response>>,
+{
+    fn build(&self, app: &mut App) {
+        app.add_event::<ResponseEvent<R::Response>>()
+            .insert_resource(RpcSettings::<R> {
+                timeout: self.timeout,
+                _marker: PhantomData,
+            })
+            .add_systems(
+                PreUpdate,
+                receive_responses::<P, R>.after(InternalMainSet::<ClientMarker>::Receive),
+            );
+        if let Some((channel, handler)) = self.handler.lock().unwrap().take() {
+            let system = app.world.register_boxed_system(handler);
+            app.insert_resource(RequestHandler::<R> { channel, system })
+                .add_systems(
+                    PreUpdate,
+                    handle_requests::<P, R>.after(InternalMainSet::<ClientMarker>::Receive),
+                );
+        }
+    }
+}
+
+/// Emit the responses received from the server, and the requests that timed out
+fn receive_responses<P: Protocol, R: Request>(
+    settings: Res<RpcSettings<R>>,
+    mut connection: ResMut<ConnectionManager<P>>,
+    mut messages: EventReader<MessageEvent<ResponseMessage<R::Response>>>,
+    mut events: EventWriter<ResponseEvent<R::Response>>,
+) {
+    for message in messages.read() {
+        let message = message.message();
+        if connection.rpc.receive_response(message.id()) {
+            events.send(ResponseEvent::new(
+                message.id(),
+                Ok(message.response().clone()),
+                (),
+            ));
+        }
+    }
+    for request_id in connection.rpc.take_expired::<R>(settings.timeout) {
+        events.send(ResponseEvent::new(request_id, Err(RpcError::Timeout), ()));
+    }
+}
+
+/// Run the handler system on the requests received from the server, and send back the responses
+fn handle_requests<P: Protocol, R: Request>(
+    world: &mut World,
+    mut reader: Local<ManualEventReader<MessageEvent<RequestMessage<R>>>>,
+) where
+    P::Message: From<ResponseMessage<R::Response>>,
+{
+    let requests: Vec<RequestMessage<R>> = reader
+        .read(world.resource::<Events<MessageEvent<RequestMessage<R>>>>())
+        .map(|event| event.message().clone())
+        .collect();
+    if requests.is_empty() {
+        return;
+    }
+    let handler = world.resource::<RequestHandler<R>>();
+    let (channel, system) = (handler.channel, handler.system);
+    for request in requests {
+        let response = match world.run_system_with_input(system, request.request().clone()) {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Error while running the request handler: {:?}", e);
+                continue;
+            }
+        };
+        let message = ResponseMessage::new(request.id(), response);
+        if let Err(e) = world.resource_mut::<ConnectionManager<P>>().buffer_message(
+            message.into(),
+            channel,
+            NetworkTarget::None,
+        ) {
+            error!("Error while sending the response to a request: {:?}", e);
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use anyhow::Result;
@@ -10,6 +11,8 @@ use crate::client::config::NetcodeConfig;
 use crate::client::networking::NetworkingState;
 use crate::connection::id::ClientId;
 use crate::connection::netcode::ConnectToken;
+use crate::connection::replay::ReplayPlayer;
+use crate::connection::server::DeniedReason;
 
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 use crate::connection::steam::client::SteamConfig;
@@ -42,6 +45,9 @@ pub trait NetClient: Send + Sync {
     /// Get the id of the client
     fn id(&self) -> ClientId;
 
+    /// Get the reason sent by the server if it denied our connection request
+    fn denied_reason(&self) -> Option<DeniedReason>;
+
     /// Get the local address of the client
     fn local_addr(&self) -> SocketAddr;
 
@@ -79,6 +85,12 @@ pub enum NetConfig {
     Local {
         id: u64,
     },
+    /// Replay the packets that the server sent to the client `client_id` in a recording
+    /// made with the [`ReplayRecorderPlugin`](crate::server::replay::ReplayRecorderPlugin)
+    Replay {
+        path: PathBuf,
+        client_id: ClientId,
+    },
 }
 
 impl Default for NetConfig {
@@ -99,6 +111,13 @@ impl NetConfig {
                 config,
                 io: io_config,
             } => {
+                #[cfg(not(target_family = "wasm"))]
+                let token_request = match &auth {
+                    Authentication::Request { addr, credentials } => Some(
+                        super::netcode::TokenRequest::new(*addr, credentials.clone()),
+                    ),
+                    _ => None,
+                };
                 let token = auth
                     .get_token(config.client_timeout_secs, config.token_expire_secs)
                     .expect("could not generate token");
@@ -110,6 +129,8 @@ impl NetConfig {
                     client: netcode,
                     io_config,
                     io: None,
+                    #[cfg(not(target_family = "wasm"))]
+                    token_request,
                 };
                 ClientConnection {
                     client: Box::new(client),
@@ -133,6 +154,13 @@ impl NetConfig {
                     client: Box::new(client),
                 }
             }
+            NetConfig::Replay { path, client_id } => {
+                // the file is read when connecting, so that an invalid file returns an error instead of panicking
+                let client = ReplayPlayer::from_path(path, client_id);
+                ClientConnection {
+                    client: Box::new(client),
+                }
+            }
         }
     }
 }
@@ -166,6 +194,10 @@ impl NetClient for ClientConnection {
         self.client.id()
     }
 
+    fn denied_reason(&self) -> Option<DeniedReason> {
+        self.client.denied_reason()
+    }
+
     fn local_addr(&self) -> SocketAddr {
         self.client.local_addr()
     }
@@ -192,6 +224,17 @@ pub enum Authentication {
         private_key: Key,
         protocol_id: u64,
     },
+    /// Request a connect token from a [`TokenServer`](crate::connection::netcode::TokenServer) at the given address,
+    /// using the given credentials.
+    ///
+    /// The request runs in a background thread every time the client starts connecting;
+    /// the client stays in the `Connecting` state until the token server answers.
+    /// This is not available on wasm.
+    #[cfg(not(target_family = "wasm"))]
+    Request {
+        addr: SocketAddr,
+        credentials: Vec<u8>,
+    },
     #[default]
     /// Request a connect token from the backend
     RequestConnectToken,
@@ -215,18 +258,23 @@ impl Authentication {
                 .expire_seconds(token_expire_secs)
                 .generate()
                 .ok(),
-            Authentication::RequestConnectToken => {
-                // create a fake connect token so that we have a NetcodeClient
-                ConnectToken::build(
-                    SocketAddr::from_str("0.0.0.0:0").unwrap(),
-                    0,
-                    0,
-                    generate_key(),
-                )
-                .timeout_seconds(client_timeout_secs)
-                .generate()
-                .ok()
-            }
+            // the token is requested when the client connects; until then we use a fake token
+            #[cfg(not(target_family = "wasm"))]
+            Authentication::Request { .. } => Self::placeholder_token(client_timeout_secs),
+            Authentication::RequestConnectToken => Self::placeholder_token(client_timeout_secs),
         }
     }
+
+    /// Create a fake connect token, so that we have a NetcodeClient
+    fn placeholder_token(client_timeout_secs: i32) -> Option<ConnectToken> {
+        ConnectToken::build(
+            SocketAddr::from_str("0.0.0.0:0").unwrap(),
+            0,
+            0,
+            generate_key(),
+        )
+        .timeout_seconds(client_timeout_secs)
+        .generate()
+        .ok()
+    }
 }
@@ -1,5 +1,6 @@
 use crate::client::networking::NetworkingState;
 use crate::connection::client::NetClient;
+use crate::connection::server::DeniedReason;
 use crate::packet::packet::Packet;
 use crate::prelude::{ClientId, Io};
 use crate::transport::LOCAL_SOCKET;
@@ -56,6 +57,10 @@ impl NetClient for Client {
         ClientId::Local(self.id)
     }
 
+    fn denied_reason(&self) -> Option<DeniedReason> {
+        None
+    }
+
     fn local_addr(&self) -> SocketAddr {
         LOCAL_SOCKET
     }
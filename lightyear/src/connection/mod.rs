@@ -7,6 +7,7 @@ pub(crate) mod server;
 
 pub mod id;
 mod local;
+pub mod replay;
 #[cfg_attr(docsrs, doc(cfg(all(feature = "steam", not(target_family = "wasm")))))]
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
 pub(crate) mod steam;
@@ -0,0 +1,469 @@
+//! A small TCP service that issues [`ConnectToken`]s to clients.
+//!
+//! In the netcode.io deployment model, the private key is only known by the dedicated servers and by a backend
+//! that authenticates the clients and gives them a `ConnectToken`. The [`TokenServer`] is a minimal version of that backend
+//! that can run next to the dedicated server:
+//! - the client connects to the [`TokenServer`] over TCP and sends its credentials (see [`request_token`])
+//! - the [`CredentialHandler`] checks the credentials and chooses the client id
+//! - the [`TokenServer`] answers with a `ConnectToken` that the client can use to connect to the dedicated server
+//!
+//! On the client, use [`Authentication::Request`](crate::connection::client::Authentication::Request) to fetch the token automatically when connecting.
+//! The request runs in a background thread, so it is not available on wasm.
+//!
+//! Note that the exchange is not encrypted: the `TokenServer` should be put behind a TLS proxy if the credentials are sensitive.
+use std::fmt::Debug;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crossbeam_channel::{Receiver, TryRecvError};
+use tracing::{debug, error, info, warn};
+
+use super::token::TOKEN_EXPIRE_SEC;
+use super::{ConnectToken, Error, Key, Result, CONNECT_TOKEN_BYTES};
+
+/// Maximum size (in bytes) of the credentials sent by a client
+pub const MAX_CREDENTIALS_BYTES: usize = 1024;
+/// Timeout used for every read or write on the TCP connection
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// Maximum duration for the client to send its whole request to the [`TokenServer`]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default maximum number of token requests that the [`TokenServer`] handles at the same time
+const MAX_CONCURRENT_REQUESTS: usize = 64;
+
+/// The token request was accepted, the response contains a `ConnectToken`
+const TOKEN_ACCEPTED: u8 = 0;
+/// The token request was denied
+const TOKEN_DENIED: u8 = 1;
+
+/// Hook that checks the credentials sent by a client to the [`TokenServer`].
+///
+/// ```rust,ignore
+/// #[derive(Debug)]
+/// struct Passwords(HashMap<Vec<u8>, u64>);
+///
+/// impl CredentialHandler for Passwords {
+///     fn authenticate(&self, credentials: &[u8], _: SocketAddr) -> Option<u64> {
+///         self.0.get(credentials).copied()
+///     }
+/// }
+/// ```
+pub trait CredentialHandler: Debug + Send + Sync {
+    /// Returns the client id that should be put in the `ConnectToken`, or `None` to deny the request.
+    ///
+    /// `addr` is the address that the request was sent from.
+    fn authenticate(&self, credentials: &[u8], addr: SocketAddr) -> Option<u64>;
+}
+
+/// TCP server that issues [`ConnectToken`]s for a dedicated server
+#[derive(Debug, Clone)]
+pub struct TokenServer {
+    listen_addr: SocketAddr,
+    server_addr: SocketAddr,
+    protocol_id: u64,
+    private_key: Key,
+    expire_secs: i32,
+    timeout_secs: i32,
+    max_concurrent_requests: usize,
+    credential_handler: Option<Arc<dyn CredentialHandler>>,
+}
+
+impl TokenServer {
+    /// Create a token server that listens on `listen_addr`, and issues tokens to connect to the
+    /// dedicated server at `server_addr`.
+    ///
+    /// `protocol_id` and `private_key` must be the same as the ones used by the dedicated server.
+    pub fn new(
+        listen_addr: SocketAddr,
+        server_addr: SocketAddr,
+        protocol_id: u64,
+        private_key: Key,
+    ) -> Self {
+        Self {
+            listen_addr,
+            server_addr,
+            protocol_id,
+            private_key,
+            expire_secs: TOKEN_EXPIRE_SEC,
+            timeout_secs: 3,
+            max_concurrent_requests: MAX_CONCURRENT_REQUESTS,
+            credential_handler: None,
+        }
+    }
+
+    /// Set the duration in seconds after which the issued tokens expire. A negative value means that the tokens never expire.
+    pub fn with_expire_secs(mut self, expire_secs: i32) -> Self {
+        self.expire_secs = expire_secs;
+        self
+    }
+
+    /// Set the duration in seconds after which the server disconnects a client if they don't hear from them.
+    /// This should match the `client_timeout_secs` of the server's [`NetcodeConfig`](crate::server::config::NetcodeConfig).
+    pub fn with_timeout_secs(mut self, timeout_secs: i32) -> Self {
+        self.timeout_secs = timeout_secs;
+        self
+    }
+
+    /// Set the maximum number of token requests that are handled at the same time.
+    /// Connections that arrive while the limit is reached are closed immediately.
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests;
+        self
+    }
+
+    /// Set the hook that checks the client credentials.
+    /// If no handler is provided, every request is denied.
+    pub fn with_credential_handler(mut self, handler: Arc<dyn CredentialHandler>) -> Self {
+        self.credential_handler = Some(handler);
+        self
+    }
+
+    /// Start listening for token requests in a background thread.
+    /// Each request is handled in its own thread, so that a slow client doesn't delay the others.
+    ///
+    /// Returns the address that the server is listening on.
+    pub fn start(self) -> Result<SocketAddr> {
+        let listener = TcpListener::bind(self.listen_addr)?;
+        let local_addr = listener.local_addr()?;
+        info!("Token server listening on {}", local_addr);
+        if self.credential_handler.is_none() {
+            warn!(
+                "Token server started without a credential handler, every request will be denied"
+            );
+        }
+        let server = Arc::new(self);
+        let active_requests = Arc::new(AtomicUsize::new(0));
+        std::thread::Builder::new()
+            .name("token_server".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            error!("Error accepting token request: {}", e);
+                            continue;
+                        }
+                    };
+                    if active_requests.fetch_add(1, Ordering::AcqRel)
+                        >= server.max_concurrent_requests
+                    {
+                        active_requests.fetch_sub(1, Ordering::AcqRel);
+                        debug!("Too many concurrent token requests, closing the connection");
+                        continue;
+                    }
+                    let server = server.clone();
+                    let request_count = active_requests.clone();
+                    let spawned = std::thread::Builder::new()
+                        .name("token_request".to_string())
+                        .spawn(move || {
+                            if let Err(e) = server.handle_request(stream) {
+                                error!("Error handling token request: {}", e);
+                            }
+                            request_count.fetch_sub(1, Ordering::AcqRel);
+                        });
+                    if let Err(e) = spawned {
+                        error!("Could not spawn a thread for the token request: {}", e);
+                        active_requests.fetch_sub(1, Ordering::AcqRel);
+                    }
+                }
+            })?;
+        Ok(local_addr)
+    }
+
+    fn handle_request(&self, mut stream: TcpStream) -> Result<()> {
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        let addr = stream.peer_addr()?;
+        let deadline = Instant::now() + REQUEST_TIMEOUT;
+        let mut len = [0; 2];
+        read_before(&mut stream, &mut len, deadline)?;
+        let len = u16::from_le_bytes(len) as usize;
+        if len > MAX_CREDENTIALS_BYTES {
+            debug!(?addr, "Denying token request with too large credentials");
+            stream.write_u8(TOKEN_DENIED)?;
+            return Ok(());
+        }
+        let mut credentials = vec![0; len];
+        read_before(&mut stream, &mut credentials, deadline)?;
+
+        match self.issue_token(&credentials, addr)? {
+            Some(token) => {
+                stream.write_u8(TOKEN_ACCEPTED)?;
+                stream.write_all(&token.try_into_bytes()?)?;
+            }
+            None => {
+                debug!(?addr, "Denying token request");
+                stream.write_u8(TOKEN_DENIED)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Generate a token for the client if its credentials are valid
+    fn issue_token(&self, credentials: &[u8], addr: SocketAddr) -> Result<Option<ConnectToken>> {
+        let Some(client_id) = self
+            .credential_handler
+            .as_ref()
+            .and_then(|handler| handler.authenticate(credentials, addr))
+        else {
+            return Ok(None);
+        };
+        debug!(?addr, ?client_id, "Issuing connect token");
+        let token = ConnectToken::build(
+            self.server_addr,
+            self.protocol_id,
+            client_id,
+            self.private_key,
+        )
+        .expire_seconds(self.expire_secs)
+        .timeout_seconds(self.timeout_secs)
+        .generate()?;
+        Ok(Some(token))
+    }
+}
+
+/// Fill `buf` from the stream, failing if the bytes don't all arrive before `deadline`.
+///
+/// The read timeout is shortened before each read, so that a client that sends its request
+/// one byte at a time cannot keep the connection open.
+fn read_before(stream: &mut TcpStream, buf: &mut [u8], deadline: Instant) -> Result<()> {
+    let mut read = 0;
+    while read < buf.len() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::Error::from(ErrorKind::TimedOut).into());
+        }
+        stream.set_read_timeout(Some(remaining))?;
+        match stream.read(&mut buf[read..]) {
+            Ok(0) => return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into()),
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Request a [`ConnectToken`] from the [`TokenServer`] at `addr`, using the given credentials.
+///
+/// This blocks until the token server answers (or until the request times out).
+pub fn request_token(addr: SocketAddr, credentials: &[u8]) -> Result<ConnectToken> {
+    if credentials.len() > MAX_CREDENTIALS_BYTES {
+        return Err(Error::SizeMismatch(
+            MAX_CREDENTIALS_BYTES,
+            credentials.len(),
+        ));
+    }
+    let mut stream = TcpStream::connect_timeout(&addr, IO_TIMEOUT)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    stream.write_u16::<LittleEndian>(credentials.len() as u16)?;
+    stream.write_all(credentials)?;
+
+    if stream.read_u8()? != TOKEN_ACCEPTED {
+        return Err(Error::TokenRequestDenied);
+    }
+    let mut token_bytes = [0; CONNECT_TOKEN_BYTES];
+    stream.read_exact(&mut token_bytes)?;
+    ConnectToken::try_from_bytes(&token_bytes).map_err(Error::InvalidToken)
+}
+
+/// A [`request_token`] call that runs in a background thread, so that connecting doesn't block the app
+#[derive(Debug)]
+pub(crate) struct TokenRequest {
+    addr: SocketAddr,
+    credentials: Vec<u8>,
+    pending: Option<Receiver<Result<ConnectToken>>>,
+}
+
+impl TokenRequest {
+    pub(crate) fn new(addr: SocketAddr, credentials: Vec<u8>) -> Self {
+        Self {
+            addr,
+            credentials,
+            pending: None,
+        }
+    }
+
+    /// Start a new request; the result of a previous request is discarded
+    pub(crate) fn start(&mut self) {
+        let (sender, receiver) = crossbeam_channel::bounded(1);
+        let (addr, credentials) = (self.addr, self.credentials.clone());
+        let spawned = std::thread::Builder::new()
+            .name("token_request".to_string())
+            .spawn(move || {
+                let _ = sender.send(request_token(addr, &credentials));
+            });
+        if let Err(e) = spawned {
+            error!("Could not spawn a thread for the token request: {}", e);
+        }
+        self.pending = Some(receiver);
+    }
+
+    /// Stop waiting for the current request
+    pub(crate) fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Returns the result of the current request, once the token server has answered
+    pub(crate) fn poll(&mut self) -> Option<Result<ConnectToken>> {
+        let result = match self.pending.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "the token request thread stopped",
+            )
+            .into()),
+        };
+        self.pending = None;
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connection::netcode::generate_key;
+    use crate::connection::netcode::token::ConnectTokenPrivate;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Password;
+
+    impl CredentialHandler for Password {
+        fn authenticate(&self, credentials: &[u8], _: SocketAddr) -> Option<u64> {
+            (credentials == b"password").then_some(42)
+        }
+    }
+
+    fn token_server() -> TokenServer {
+        TokenServer::new(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            SocketAddr::from(([127, 0, 0, 1], 5000)),
+            7,
+            generate_key(),
+        )
+    }
+
+    #[test]
+    fn test_request_token() {
+        let protocol_id = 7;
+        let private_key = generate_key();
+        let server_addr = SocketAddr::from(([127, 0, 0, 1], 5000));
+        let addr = TokenServer::new(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            server_addr,
+            protocol_id,
+            private_key,
+        )
+        .with_credential_handler(Arc::new(Password))
+        .start()
+        .unwrap();
+
+        // invalid credentials
+        assert!(matches!(
+            request_token(addr, b"wrong"),
+            Err(Error::TokenRequestDenied)
+        ));
+
+        // valid credentials
+        let mut token = request_token(addr, b"password").unwrap();
+        assert_eq!(token.protocol_id, protocol_id);
+        // the token can only be used by a server with the same protocol id
+        let mut private_data = token.private_data;
+        assert!(ConnectTokenPrivate::decrypt(
+            &mut private_data,
+            protocol_id + 1,
+            token.expire_timestamp,
+            token.nonce,
+            &private_key,
+        )
+        .is_err());
+        let private = ConnectTokenPrivate::decrypt(
+            &mut token.private_data,
+            protocol_id,
+            token.expire_timestamp,
+            token.nonce,
+            &private_key,
+        )
+        .unwrap();
+        assert_eq!(private.client_id, 42);
+    }
+
+    #[test]
+    fn test_deny_without_credential_handler() {
+        let addr = token_server().start().unwrap();
+        assert!(matches!(
+            request_token(addr, b"password"),
+            Err(Error::TokenRequestDenied)
+        ));
+    }
+
+    /// A client that doesn't send its request must not block the other clients
+    #[test]
+    fn test_idle_client_does_not_block() {
+        let addr = token_server()
+            .with_credential_handler(Arc::new(Password))
+            .start()
+            .unwrap();
+
+        let _idle = TcpStream::connect(addr).unwrap();
+        let start = Instant::now();
+        request_token(addr, b"password").unwrap();
+        assert!(start.elapsed() < REQUEST_TIMEOUT);
+    }
+
+    #[test]
+    fn test_max_concurrent_requests() {
+        let addr = token_server()
+            .with_credential_handler(Arc::new(Password))
+            .with_max_concurrent_requests(1)
+            .start()
+            .unwrap();
+
+        let idle = TcpStream::connect(addr).unwrap();
+        // the connection is closed because the idle client is using the only slot
+        assert!(matches!(
+            request_token(addr, b"password"),
+            Err(Error::Io(_))
+        ));
+
+        // the slot is released once the idle client leaves
+        drop(idle);
+        let start = Instant::now();
+        while request_token(addr, b"password").is_err() {
+            assert!(start.elapsed() < REQUEST_TIMEOUT);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_token_request_in_background() {
+        let addr = token_server()
+            .with_credential_handler(Arc::new(Password))
+            .start()
+            .unwrap();
+
+        let mut request = TokenRequest::new(addr, b"password".to_vec());
+        assert!(request.poll().is_none());
+        request.start();
+        assert!(request.is_pending());
+        let start = Instant::now();
+        let token = loop {
+            if let Some(result) = request.poll() {
+                break result.unwrap();
+            }
+            assert!(start.elapsed() < REQUEST_TIMEOUT);
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(!request.is_pending());
+        assert_eq!(token.protocol_id, 7);
+    }
+}
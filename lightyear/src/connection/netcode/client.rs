@@ -8,6 +8,7 @@ use tracing::{debug, error, info, trace, warn};
 
 use crate::connection::client::NetClient;
 use crate::connection::id;
+use crate::connection::server::DeniedReason;
 use crate::prelude::client::NetworkingState;
 use crate::prelude::IoConfig;
 use crate::serialize::reader::ReadBuffer;
@@ -15,6 +16,8 @@ use crate::serialize::wordbuffer::reader::{BufferPool, ReadWordBuffer};
 use crate::transport::io::Io;
 use crate::transport::{PacketReceiver, PacketSender, Transport, LOCAL_SOCKET};
 
+#[cfg(feature = "zstd")]
+use super::compression::{CompressionConfig, PayloadCompression};
 use super::{
     bytes::Bytes,
     error::{Error, Result},
@@ -194,8 +197,11 @@ pub struct NetcodeClient<Ctx = ()> {
     replay_protection: ReplayProtection,
     should_disconnect: bool,
     should_disconnect_state: ClientState,
+    denied_reason: Option<DeniedReason>,
     packet_queue: VecDeque<crate::packet::packet::Packet>,
     buffer_pool: BufferPool,
+    #[cfg(feature = "zstd")]
+    pub(super) compression: Option<PayloadCompression>,
     cfg: ClientConfig<Ctx>,
 }
 
@@ -229,8 +235,11 @@ impl<Ctx> NetcodeClient<Ctx> {
             replay_protection: ReplayProtection::new(),
             should_disconnect: false,
             should_disconnect_state: ClientState::Disconnected,
+            denied_reason: None,
             packet_queue: VecDeque::new(),
             buffer_pool: BufferPool::default(),
+            #[cfg(feature = "zstd")]
+            compression: None,
             cfg,
         })
     }
@@ -290,6 +299,7 @@ impl<Ctx> NetcodeClient<Ctx> {
 
 impl<Ctx> NetcodeClient<Ctx> {
     const ALLOWED_PACKETS: u8 = 1 << Packet::DENIED
+        | 1 << Packet::DENIED_REASON
         | 1 << Packet::CHALLENGE
         | 1 << Packet::KEEP_ALIVE
         | 1 << Packet::PAYLOAD
@@ -377,10 +387,17 @@ impl<Ctx> NetcodeClient<Ctx> {
             return Ok(());
         }
         match (packet, self.state) {
+            (
+                Packet::DeniedReason(pkt),
+                ClientState::SendingConnectionRequest | ClientState::SendingChallengeResponse,
+            ) => {
+                self.denied_reason = Some(pkt.reason);
+            }
             (
                 Packet::Denied(_),
                 ClientState::SendingConnectionRequest | ClientState::SendingChallengeResponse,
             ) => {
+                info!(reason = ?self.denied_reason, "client connection denied by the server");
                 self.should_disconnect = true;
                 self.should_disconnect_state = ClientState::ConnectionDenied;
             }
@@ -405,6 +422,23 @@ impl<Ctx> NetcodeClient<Ctx> {
                 //  we could just
                 // instead of allocating a new buffer, fetch one from the pool
                 trace!("read from netcode client pre");
+                #[cfg(feature = "zstd")]
+                let mut decompressed = [0u8; MAX_PACKET_SIZE];
+                #[cfg(feature = "zstd")]
+                let pkt = match self.compression.as_mut() {
+                    Some(compression) => {
+                        match compression.decompress(pkt.buf, &mut decompressed) {
+                            Ok(size) => PayloadPacket {
+                                buf: &decompressed[..size],
+                            },
+                            Err(e) => {
+                                debug!("client ignored payload packet that could not be decompressed: {e}");
+                                return Ok(());
+                            }
+                        }
+                    }
+                    None => pkt,
+                };
                 let mut reader = self.buffer_pool.start_read(pkt.buf);
                 let packet = crate::packet::packet::Packet::decode(&mut reader)
                     .map_err(|_| super::packet::Error::InvalidPayload)?;
@@ -512,11 +546,18 @@ impl<Ctx> NetcodeClient<Ctx> {
         self.id
     }
 
+    /// Replace the connect token used for the next connection attempt
+    pub(crate) fn set_token(&mut self, token: ConnectToken) {
+        self.token = token;
+        self.server_addr_idx = 0;
+    }
+
     /// Prepares the client to connect to the server.
     ///
     /// This function does not perform any IO, it only readies the client to send/receive packets on the next call to [`update`](NetcodeClient::update). <br>
     pub fn connect(&mut self) {
         self.reset_connection();
+        self.denied_reason = None;
         self.set_state(ClientState::SendingConnectionRequest);
         info!(
             "client connecting to server {} [{}/{}]",
@@ -601,6 +642,13 @@ impl<Ctx> NetcodeClient<Ctx> {
         if buf.len() > MAX_PACKET_SIZE {
             return Err(Error::SizeMismatch(MAX_PACKET_SIZE, buf.len()));
         }
+        #[cfg(feature = "zstd")]
+        if let Some(compression) = self.compression.as_mut() {
+            // the compression header is counted in the MAX_PACKET_SIZE budget
+            let mut payload = [0u8; MAX_PACKET_SIZE];
+            let size = compression.compress(buf, &mut payload)?;
+            return self.send_packet(PayloadPacket::create(&payload[..size]), io);
+        }
         self.send_packet(PayloadPacket::create(buf), io)?;
         Ok(())
     }
@@ -623,6 +671,10 @@ impl<Ctx> NetcodeClient<Ctx> {
     pub fn state(&self) -> ClientState {
         self.state
     }
+    /// Returns the reason sent by the server if it denied our last connection request
+    pub fn denied_reason(&self) -> Option<DeniedReason> {
+        self.denied_reason
+    }
     /// Returns true if the client is in an error state.
     pub fn is_error(&self) -> bool {
         self.state < ClientState::Disconnected
@@ -648,18 +700,40 @@ pub struct Client<Ctx> {
     pub client: NetcodeClient<Ctx>,
     pub io_config: IoConfig,
     pub io: Option<Io>,
+    /// If set, the connect token is requested from a [`TokenServer`](super::TokenServer) every time we connect
+    #[cfg(not(target_family = "wasm"))]
+    pub(crate) token_request: Option<super::TokenRequest>,
 }
 
 impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
     fn connect(&mut self) -> anyhow::Result<()> {
         let io_config = self.io_config.clone();
+        #[cfg(feature = "zstd")]
+        {
+            self.client.compression = io_config
+                .compression
+                .as_ref()
+                .map(CompressionConfig::build)
+                .transpose()
+                .context("could not create the payload compression")?;
+        }
         let io = io_config.connect().context("could not connect io")?;
         self.io = Some(io);
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(request) = &mut self.token_request {
+            // the netcode client starts connecting once the token server has answered
+            request.start();
+            return Ok(());
+        }
         self.client.connect();
         Ok(())
     }
 
     fn disconnect(&mut self) -> anyhow::Result<()> {
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(request) = &mut self.token_request {
+            request.cancel();
+        }
         let io = self.io.as_mut().context("io is not initialized")?;
         self.client
             .disconnect(io)
@@ -671,6 +745,14 @@ impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
     }
 
     fn state(&self) -> NetworkingState {
+        #[cfg(not(target_family = "wasm"))]
+        if self
+            .token_request
+            .as_ref()
+            .map_or(false, super::TokenRequest::is_pending)
+        {
+            return NetworkingState::Connecting;
+        }
         match self.client.state() {
             ClientState::SendingConnectionRequest | ClientState::SendingChallengeResponse => {
                 NetworkingState::Connecting
@@ -685,6 +767,21 @@ impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
             .io
             .as_mut()
             .context("io is not initialized, did you call connect?")?;
+        #[cfg(not(target_family = "wasm"))]
+        if let Some(result) = self
+            .token_request
+            .as_mut()
+            .and_then(super::TokenRequest::poll)
+        {
+            match result {
+                Ok(token) => {
+                    self.client.set_token(token);
+                    self.client.connect();
+                }
+                // the netcode client stays disconnected, so the connection attempt fails
+                Err(e) => error!("Could not get a connect token from the token server: {e}"),
+            }
+        }
         self.client
             .try_update(delta_ms, io)
             .inspect_err(|e| error!("error updating client: {:?}", e))
@@ -704,6 +801,10 @@ impl<Ctx: Send + Sync> NetClient for Client<Ctx> {
         id::ClientId::Netcode(self.client.id())
     }
 
+    fn denied_reason(&self) -> Option<DeniedReason> {
+        self.client.denied_reason()
+    }
+
     fn local_addr(&self) -> SocketAddr {
         self.io.as_ref().map_or(LOCAL_SOCKET, |io| io.local_addr())
     }
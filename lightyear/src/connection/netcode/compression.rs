@@ -0,0 +1,209 @@
+//! Contains the payload compression, which compresses the payload of outgoing netcode packets and decompresses
+//! the payload of incoming netcode packets using [zstd](https://facebook.github.io/zstd/)
+//!
+//! The compression is applied on the plaintext payload, before it gets encrypted by netcode (encrypted data does
+//! not compress).
+//!
+//! Every payload is prefixed with a 1-byte header that indicates if the rest of the payload is compressed.
+//! This header is counted in the [`MAX_PACKET_SIZE`](super::MAX_PACKET_SIZE) budget of the packet
+//! (see [`COMPRESSION_HEADER_BYTES`](super::COMPRESSION_HEADER_BYTES)).
+//! Payloads that are smaller than [`CompressionConfig::threshold`], or that don't get smaller after compression,
+//! are sent uncompressed.
+//!
+//! Both the client and the server must use the same [`CompressionConfig`] (in particular the same dictionary).
+use bevy::reflect::Reflect;
+use tracing::trace;
+use zstd::bulk::{Compressor, Decompressor};
+
+use super::error::{Error, Result};
+use super::COMPRESSION_HEADER_BYTES;
+
+/// Header byte for payloads that are sent as-is
+const UNCOMPRESSED: u8 = 0;
+/// Header byte for payloads that are compressed
+const COMPRESSED: u8 = 1;
+
+/// Contains configuration required to initialize the payload compression
+#[derive(Clone, Debug, Reflect)]
+pub struct CompressionConfig {
+    /// The zstd compression level (between 1 and 22). Higher levels compress better but are slower.
+    pub level: i32,
+    /// Payloads whose size (in bytes) is below this threshold are sent uncompressed
+    pub threshold: usize,
+    /// Optional dictionary shared by the client and the server.
+    ///
+    /// Small packets compress poorly on their own, a dictionary trained on captured traffic
+    /// (see [`CompressionConfig::train_dictionary`]) can improve the compression ratio significantly.
+    pub dictionary: Option<Vec<u8>>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            level: 3,
+            threshold: 64,
+            dictionary: None,
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn with_level(mut self, level: i32) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_dictionary(mut self, dictionary: Vec<u8>) -> Self {
+        self.dictionary = Some(dictionary);
+        self
+    }
+
+    /// Train a compression dictionary from a set of captured packet payloads.
+    ///
+    /// `max_size` is the maximum size of the dictionary in bytes (a few KBs is usually enough).
+    pub fn train_dictionary(samples: &[Vec<u8>], max_size: usize) -> Result<Vec<u8>> {
+        Ok(zstd::dict::from_samples(samples, max_size)?)
+    }
+
+    pub(crate) fn build(&self) -> Result<PayloadCompression> {
+        let (compressor, decompressor) = match &self.dictionary {
+            Some(dictionary) => (
+                Compressor::with_dictionary(self.level, dictionary)?,
+                Decompressor::with_dictionary(dictionary)?,
+            ),
+            None => (Compressor::new(self.level)?, Decompressor::new()?),
+        };
+        Ok(PayloadCompression {
+            compressor,
+            decompressor,
+            threshold: self.threshold,
+        })
+    }
+}
+
+/// Compresses the payloads sent to the remote, and decompresses the payloads received from the remote
+pub(crate) struct PayloadCompression {
+    compressor: Compressor<'static>,
+    decompressor: Decompressor<'static>,
+    threshold: usize,
+}
+
+impl PayloadCompression {
+    /// Write the `payload` (compressed if that makes it smaller) prefixed by the compression header into `out`.
+    ///
+    /// Returns the number of bytes written.
+    pub(crate) fn compress(&mut self, payload: &[u8], out: &mut [u8]) -> Result<usize> {
+        let size = payload.len() + COMPRESSION_HEADER_BYTES;
+        if size > out.len() {
+            return Err(Error::SizeMismatch(out.len(), size));
+        }
+        if !payload.is_empty() && payload.len() >= self.threshold {
+            // the destination is smaller than the payload, so the compression fails
+            // if the compressed payload is not smaller than the uncompressed one
+            let destination = &mut out[COMPRESSION_HEADER_BYTES..size - 1];
+            if let Ok(compressed) = self.compressor.compress_to_buffer(payload, destination) {
+                trace!(
+                    uncompressed = payload.len(),
+                    compressed,
+                    "compressed payload"
+                );
+                out[0] = COMPRESSED;
+                return Ok(compressed + COMPRESSION_HEADER_BYTES);
+            }
+        }
+        out[0] = UNCOMPRESSED;
+        out[COMPRESSION_HEADER_BYTES..size].copy_from_slice(payload);
+        Ok(size)
+    }
+
+    /// Write the decompressed `data` into `out`.
+    ///
+    /// Returns the number of bytes written, or an error if the data is invalid or does not fit in `out`.
+    pub(crate) fn decompress(&mut self, data: &[u8], out: &mut [u8]) -> Result<usize> {
+        match data.split_first() {
+            Some((&UNCOMPRESSED, payload)) => {
+                if payload.len() > out.len() {
+                    return Err(Error::SizeMismatch(out.len(), payload.len()));
+                }
+                out[..payload.len()].copy_from_slice(payload);
+                Ok(payload.len())
+            }
+            // the output buffer has a fixed size, so a malicious payload cannot make us allocate memory
+            Some((&COMPRESSED, payload)) => {
+                Ok(self.decompressor.decompress_to_buffer(payload, out)?)
+            }
+            _ => Err(super::packet::Error::InvalidPayload.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::MAX_PACKET_SIZE;
+    use super::*;
+
+    #[test]
+    fn test_compression() {
+        let samples: Vec<Vec<u8>> = (0..100u32)
+            .map(|i| format!("snapshot {i}: position=(1.0, 2.0) velocity=(0.0, 0.0)").into_bytes())
+            .collect();
+        let dictionary = CompressionConfig::train_dictionary(&samples, 1024).unwrap();
+        let config = CompressionConfig::default()
+            .with_threshold(10)
+            .with_dictionary(dictionary);
+        let mut compression = config.build().unwrap();
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        let mut out = [0u8; MAX_PACKET_SIZE];
+
+        // small payloads are not compressed
+        let size = compression.compress(&[1, 2, 3], &mut buf).unwrap();
+        assert_eq!(&buf[..size], &[UNCOMPRESSED, 1, 2, 3]);
+        let size = compression.decompress(&buf[..size], &mut out).unwrap();
+        assert_eq!(&out[..size], &[1, 2, 3]);
+
+        // large payloads are compressed
+        let payload = b"snapshot 200: position=(1.0, 2.0) velocity=(0.0, 0.0)".to_vec();
+        let compressed_size = compression.compress(&payload, &mut buf).unwrap();
+        assert_eq!(buf[0], COMPRESSED);
+        assert!(compressed_size < payload.len());
+        let size = compression
+            .decompress(&buf[..compressed_size], &mut out)
+            .unwrap();
+        assert_eq!(&out[..size], payload.as_slice());
+
+        // invalid or truncated data returns an error
+        assert!(compression
+            .decompress(&buf[..compressed_size - 3], &mut out)
+            .is_err());
+        assert!(compression.decompress(&[7, 1, 2], &mut out).is_err());
+        assert!(compression.decompress(&[], &mut out).is_err());
+        // the decompressed payload must fit in the output buffer
+        assert!(compression
+            .decompress(&buf[..compressed_size], &mut out[..10])
+            .is_err());
+    }
+
+    #[test]
+    fn test_payload_fits_in_packet() {
+        let mut compression = CompressionConfig::default().build().unwrap();
+        let mut buf = [0u8; MAX_PACKET_SIZE];
+        // whether the payload gets compressed or not, it fits in the packet with the header
+        let payload: Vec<u8> = (0..MAX_PACKET_SIZE - COMPRESSION_HEADER_BYTES)
+            .map(|i| (i * 7919 % 251) as u8 ^ (i >> 3) as u8)
+            .collect();
+        let size = compression.compress(&payload, &mut buf).unwrap();
+        assert!(size <= MAX_PACKET_SIZE);
+        let mut out = [0u8; MAX_PACKET_SIZE];
+        let size = compression.decompress(&buf[..size], &mut out).unwrap();
+        assert_eq!(&out[..size], payload.as_slice());
+
+        // payloads that don't leave room for the header are rejected
+        let payload = vec![0u8; MAX_PACKET_SIZE];
+        assert!(compression.compress(&payload, &mut buf).is_err());
+    }
+}
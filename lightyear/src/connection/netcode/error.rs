@@ -17,6 +17,8 @@ pub enum Error {
     SystemTime(#[from] std::time::SystemTimeError),
     #[error("invalid connect token: {0}")]
     InvalidToken(super::token::InvalidTokenError),
+    #[error("the token server denied the connect token request")]
+    TokenRequestDenied,
     #[error(transparent)]
     Crypto(#[from] super::crypto::Error),
     #[error("invalid packet: {0}")]
@@ -25,6 +25,7 @@
  1. The `Client` authenticates with the web backend service. (e.g., by OAuth or some other means)
  2. The authenticated `Client` requests a connection token from the web backend.
  3. The web backend generates a [`ConnectToken`] and sends it to the `Client`. (e.g., as a JSON response)
+    A minimal backend is provided with the [`TokenServer`].
  4. The `Client` uses the token to connect to a dedicated `Server`.
  5. The `Server` makes sure the token is valid and allows the `Client` to connect.
  6. The `Client` and `Server` can now exchange encrypted and signed UDP packets.
@@ -123,14 +124,25 @@ loop {
 ```
 */
 
+#[cfg(not(target_family = "wasm"))]
+pub(crate) use auth::TokenRequest;
+#[cfg(not(target_family = "wasm"))]
+pub use auth::{request_token, CredentialHandler, TokenServer, MAX_CREDENTIALS_BYTES};
 pub use client::{Client, ClientConfig, ClientState, NetcodeClient};
+#[cfg(feature = "zstd")]
+pub use compression::CompressionConfig;
 pub use crypto::{generate_key, try_generate_key, Key};
 pub use error::{Error, Result};
 pub use server::{Callback, ClientId, NetcodeServer, Server, ServerConfig};
 pub use token::{ConnectToken, ConnectTokenBuilder, InvalidTokenError};
 
+#[cfg(not(target_family = "wasm"))]
+mod auth;
 mod bytes;
 mod client;
+#[cfg_attr(docsrs, doc(cfg(feature = "zstd")))]
+#[cfg(feature = "zstd")]
+mod compression;
 mod crypto;
 mod error;
 mod packet;
@@ -152,5 +164,7 @@ pub const USER_DATA_BYTES: usize = 256;
 pub const CONNECT_TOKEN_BYTES: usize = 2048;
 /// The maximum size of a packet in bytes.
 pub const MAX_PACKET_SIZE: usize = 1200;
+/// Number of bytes of [`MAX_PACKET_SIZE`] that are reserved for the header added by the payload compression
+pub(crate) const COMPRESSION_HEADER_BYTES: usize = 1;
 /// The version of the netcode protocol implemented by this crate.
 pub const NETCODE_VERSION: &[u8; 13] = b"NETCODE 1.02\0";
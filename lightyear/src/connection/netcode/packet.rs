@@ -8,6 +8,7 @@ use chacha20poly1305::XNonce;
 use tracing::debug;
 
 use crate::connection::netcode::ClientId;
+use crate::connection::server::DeniedReason;
 
 use super::{
     bytes::Bytes,
@@ -177,6 +178,56 @@ impl Bytes for DeniedPacket {
     }
 }
 
+/// Lightyear extension to the netcode protocol: the server sends this packet right before a
+/// [`DeniedPacket`] to tell the client why its connection was denied.
+///
+/// This packet type is not part of the netcode standard, so the [`DeniedPacket`] itself stays
+/// spec-compliant; other netcode implementations discard this packet as an unknown packet type.
+pub struct DeniedReasonPacket {
+    pub reason: DeniedReason,
+}
+
+impl DeniedReasonPacket {
+    pub fn create(reason: DeniedReason) -> Packet<'static> {
+        Packet::DeniedReason(DeniedReasonPacket { reason })
+    }
+}
+
+impl Bytes for DeniedReasonPacket {
+    type Error = io::Error;
+    fn write_to(&self, writer: &mut impl WriteBytesExt) -> Result<(), Self::Error> {
+        let (kind, code) = match self.reason {
+            DeniedReason::ServerFull => (0, 0),
+            DeniedReason::Banned => (1, 0),
+            DeniedReason::InvalidVersion => (2, 0),
+            DeniedReason::Custom(code) => (3, code),
+            DeniedReason::ProtocolMismatch => (4, 0),
+        };
+        writer.write_u8(kind)?;
+        writer.write_u16::<LittleEndian>(code)?;
+        Ok(())
+    }
+
+    fn read_from(reader: &mut impl byteorder::ReadBytesExt) -> Result<Self, io::Error> {
+        let kind = reader.read_u8()?;
+        let code = reader.read_u16::<LittleEndian>()?;
+        let reason = match kind {
+            0 => DeniedReason::ServerFull,
+            1 => DeniedReason::Banned,
+            2 => DeniedReason::InvalidVersion,
+            3 => DeniedReason::Custom(code),
+            4 => DeniedReason::ProtocolMismatch,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid denied reason",
+                ))
+            }
+        };
+        Ok(Self { reason })
+    }
+}
+
 pub struct ChallengePacket {
     pub sequence: u64,
     pub token: [u8; ChallengeToken::SIZE],
@@ -297,6 +348,7 @@ pub enum Packet<'p> {
     KeepAlive(KeepAlivePacket),
     Payload(PayloadPacket<'p>),
     Disconnect(DisconnectPacket),
+    DeniedReason(DeniedReasonPacket),
 }
 
 impl std::fmt::Display for Packet<'_> {
@@ -309,6 +361,7 @@ impl std::fmt::Display for Packet<'_> {
             Packet::Disconnect(_) => write!(f, "disconnect packet"),
             Packet::Denied(_) => write!(f, "denied packet"),
             Packet::Challenge(_) => write!(f, "challenge packet"),
+            Packet::DeniedReason(_) => write!(f, "denied reason packet"),
         }
     }
 }
@@ -323,6 +376,8 @@ impl<'p> Packet<'p> {
     pub const KEEP_ALIVE: PacketKind = 4;
     pub const PAYLOAD: PacketKind = 5;
     pub const DISCONNECT: PacketKind = 6;
+    /// Not part of the netcode standard, see [`DeniedReasonPacket`]
+    pub const DENIED_REASON: PacketKind = 7;
     fn kind(&self) -> PacketKind {
         match self {
             Packet::Request(_) => Packet::REQUEST,
@@ -332,6 +387,7 @@ impl<'p> Packet<'p> {
             Packet::KeepAlive(_) => Packet::KEEP_ALIVE,
             Packet::Payload(_) => Packet::PAYLOAD,
             Packet::Disconnect(_) => Packet::DISCONNECT,
+            Packet::DeniedReason(_) => Packet::DENIED_REASON,
         }
     }
     fn set_prefix(&self, sequence: u64) -> u8 {
@@ -377,6 +433,7 @@ impl<'p> Packet<'p> {
             Packet::Response(pkt) => pkt.write_to(&mut cursor)?,
             Packet::KeepAlive(pkt) => pkt.write_to(&mut cursor)?,
             Packet::Disconnect(pkt) => pkt.write_to(&mut cursor)?,
+            Packet::DeniedReason(pkt) => pkt.write_to(&mut cursor)?,
             Packet::Payload(PayloadPacket { buf }) => cursor.write_all(buf)?,
             _ => unreachable!(), // Packet::Request variant is handled above
         }
@@ -412,8 +469,9 @@ impl<'p> Packet<'p> {
         let mut cursor = std::io::Cursor::new(&mut buf[..]);
         let prefix_byte = cursor.read_u8()?;
         let (sequence_len, pkt_kind) = Packet::get_prefix(prefix_byte);
-        if allowed_packets & (1 << pkt_kind) == 0 {
+        if pkt_kind >= 8 || allowed_packets & (1 << pkt_kind) == 0 {
             debug!("ignoring packet of type {}, not allowed", pkt_kind);
+            return Err(Error::InvalidType(pkt_kind).into());
         }
         if prefix_byte == Packet::REQUEST {
             // connection request packet: first byte should be 0x00
@@ -459,6 +517,9 @@ impl<'p> Packet<'p> {
             Packet::RESPONSE => Packet::Response(ResponsePacket::read_from(&mut cursor)?),
             Packet::KEEP_ALIVE => Packet::KeepAlive(KeepAlivePacket::read_from(&mut cursor)?),
             Packet::DISCONNECT => Packet::Disconnect(DisconnectPacket::read_from(&mut cursor)?),
+            Packet::DENIED_REASON => {
+                Packet::DeniedReason(DeniedReasonPacket::read_from(&mut cursor)?)
+            }
             Packet::PAYLOAD => {
                 buf.copy_within(decryption_start..(decryption_end - MAC_BYTES), 0);
                 Packet::Payload(PayloadPacket {
@@ -591,6 +652,8 @@ mod tests {
         let size = packet
             .write(&mut buf, sequence, &packet_key, protocol_id)
             .unwrap();
+        // the denied packet has no payload, as in the netcode standard
+        assert_eq!(size, 1 + 1 + MAC_BYTES);
 
         let packet = Packet::read(
             &mut buf[..size],
@@ -607,6 +670,50 @@ mod tests {
         };
     }
 
+    #[test]
+    fn denied_reason_packet() {
+        let packet_key = generate_key();
+        let protocol_id = 0x1234_5678_9abc_def0;
+        let sequence = 0u64;
+        let mut replay_protection = ReplayProtection::new();
+
+        let packet = Packet::DeniedReason(DeniedReasonPacket {
+            reason: DeniedReason::Custom(7),
+        });
+
+        let mut buf = [0u8; MAX_PKT_BUF_SIZE];
+        let size = packet
+            .write(&mut buf, sequence, &packet_key, protocol_id)
+            .unwrap();
+
+        // packets that are not allowed are rejected
+        let mut rejected = buf;
+        assert!(Packet::read(
+            &mut rejected[..size],
+            protocol_id,
+            0,
+            packet_key,
+            None,
+            1 << Packet::DENIED,
+        )
+        .is_err());
+
+        let packet = Packet::read(
+            &mut buf[..size],
+            protocol_id,
+            0,
+            packet_key,
+            Some(&mut replay_protection),
+            0xff,
+        )
+        .unwrap();
+
+        let Packet::DeniedReason(denied_pkt) = packet else {
+            panic!("wrong packet type");
+        };
+        assert_eq!(denied_pkt.reason, DeniedReason::Custom(7));
+    }
+
     #[test]
     pub fn challenge_packet() {
         let token = [0u8; ChallengeToken::SIZE];
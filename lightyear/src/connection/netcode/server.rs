@@ -1,5 +1,6 @@
 use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::{anyhow, Context};
@@ -8,7 +9,9 @@ use tracing::{debug, error, trace};
 
 use crate::connection::id;
 use crate::connection::netcode::token::TOKEN_EXPIRE_SEC;
-use crate::connection::server::NetServer;
+use crate::connection::server::{
+    ConnectionDecision, ConnectionRequestHandler, DeniedReason, NetServer,
+};
 use crate::prelude::IoConfig;
 use crate::serialize::reader::ReadBuffer;
 use crate::serialize::wordbuffer::reader::{BufferPool, ReadWordBuffer};
@@ -16,14 +19,16 @@ use crate::server::config::NetcodeConfig;
 use crate::transport::io::Io;
 use crate::transport::{PacketReceiver, PacketSender, Transport};
 
+#[cfg(feature = "zstd")]
+use super::compression::{CompressionConfig, PayloadCompression};
 use super::{
     bytes::Bytes,
     crypto::{self, Key},
     error::{Error, Result},
     generate_key,
     packet::{
-        ChallengePacket, DeniedPacket, DisconnectPacket, KeepAlivePacket, Packet, PayloadPacket,
-        RequestPacket, ResponsePacket,
+        ChallengePacket, DeniedPacket, DeniedReasonPacket, DisconnectPacket, KeepAlivePacket,
+        Packet, PayloadPacket, RequestPacket, ResponsePacket,
     },
     replay::ReplayProtection,
     token::{ChallengeToken, ConnectToken, ConnectTokenBuilder, ConnectTokenPrivate},
@@ -224,6 +229,7 @@ pub type Callback<Ctx> = Box<dyn FnMut(ClientId, &mut Ctx) + Send + Sync + 'stat
 /// * `keep_alive_send_rate` - The rate at which keep-alive packets will be sent to clients.
 /// * `on_connect` - A callback that will be called when a client is connected to the server.
 /// * `on_disconnect` - A callback that will be called when a client is disconnected from the server.
+/// * `connection_request_handler` - A hook that decides whether a client's connection request should be accepted.
 ///
 /// # Example
 /// ```
@@ -250,6 +256,7 @@ pub struct ServerConfig<Ctx> {
     context: Ctx,
     on_connect: Option<Callback<Ctx>>,
     on_disconnect: Option<Callback<Ctx>>,
+    connection_request_handler: Option<Arc<dyn ConnectionRequestHandler>>,
 }
 
 impl Default for ServerConfig<()> {
@@ -263,6 +270,7 @@ impl Default for ServerConfig<()> {
             context: (),
             on_connect: None,
             on_disconnect: None,
+            connection_request_handler: None,
         }
     }
 }
@@ -283,6 +291,7 @@ impl<Ctx> ServerConfig<Ctx> {
             context: ctx,
             on_connect: None,
             on_disconnect: None,
+            connection_request_handler: None,
         }
     }
     /// Set the number of redundant disconnect packets that will be sent to a client when the server is disconnecting it. <br>
@@ -337,6 +346,16 @@ impl<Ctx> ServerConfig<Ctx> {
         self.on_disconnect = Some(Box::new(cb));
         self
     }
+    /// Provide a hook that will be called for every valid connection request, to decide whether
+    /// the client should be accepted, rejected or if the decision should be deferred. <br>
+    /// By default, all clients with a valid connect token are accepted.
+    pub fn connection_request_handler(
+        mut self,
+        handler: Arc<dyn ConnectionRequestHandler>,
+    ) -> Self {
+        self.connection_request_handler = Some(handler);
+        self
+    }
 }
 
 /// The `netcode` server.
@@ -382,6 +401,8 @@ pub struct NetcodeServer<Ctx = ()> {
     protocol_id: u64,
     conn_cache: ConnectionCache,
     token_entries: TokenEntries,
+    #[cfg(feature = "zstd")]
+    compression: Option<PayloadCompression>,
     cfg: ServerConfig<Ctx>,
 }
 
@@ -400,6 +421,8 @@ impl NetcodeServer {
             challenge_key: crypto::generate_key(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            #[cfg(feature = "zstd")]
+            compression: None,
             cfg: ServerConfig::default(),
         };
         // info!("server started on {}", server.io.local_addr());
@@ -434,6 +457,8 @@ impl<Ctx> NetcodeServer<Ctx> {
             challenge_key: crypto::generate_key(),
             conn_cache: ConnectionCache::new(0.0),
             token_entries: TokenEntries::new(),
+            #[cfg(feature = "zstd")]
+            compression: None,
             cfg,
         };
         // info!("server started on {}", server.addr());
@@ -492,6 +517,23 @@ impl<Ctx> NetcodeServer<Ctx> {
             Packet::Payload(packet) => {
                 self.touch_client(client_id)?;
                 if let Some(idx) = client_id {
+                    #[cfg(feature = "zstd")]
+                    let mut decompressed = [0u8; MAX_PACKET_SIZE];
+                    #[cfg(feature = "zstd")]
+                    let packet = match self.compression.as_mut() {
+                        Some(compression) => {
+                            match compression.decompress(packet.buf, &mut decompressed) {
+                                Ok(size) => PayloadPacket {
+                                    buf: &decompressed[..size],
+                                },
+                                Err(e) => {
+                                    debug!("server ignored payload packet that could not be decompressed: {e}");
+                                    return Ok(());
+                                }
+                            }
+                        }
+                        None => packet,
+                    };
                     // use a buffer from the pool to avoid re-allocating
                     let mut reader = self.conn_cache.buffer_pool.start_read(packet.buf);
                     let packet = crate::packet::packet::Packet::decode(&mut reader)
@@ -526,6 +568,18 @@ impl<Ctx> NetcodeServer<Ctx> {
         self.sequence += 1;
         Ok(())
     }
+    /// Deny a connection request: the reason is sent in a [`DeniedReasonPacket`] (a lightyear extension),
+    /// followed by the standard [`DeniedPacket`]
+    fn send_denied(
+        &mut self,
+        reason: DeniedReason,
+        addr: SocketAddr,
+        key: Key,
+        sender: &mut impl PacketSender,
+    ) -> Result<()> {
+        self.send_to_addr(DeniedReasonPacket::create(reason), addr, key, sender)?;
+        self.send_to_addr(DeniedPacket::create(), addr, key, sender)
+    }
     fn send_to_client(
         &mut self,
         packet: Packet,
@@ -601,14 +655,32 @@ impl<Ctx> NetcodeServer<Ctx> {
         };
         if self.num_connected_clients() >= MAX_CLIENTS {
             debug!("server denied connection request. server is full");
-            self.send_to_addr(
-                DeniedPacket::create(),
+            self.send_denied(
+                DeniedReason::ServerFull,
                 from_addr,
                 token.server_to_client_key,
                 sender,
             )?;
             return Ok(());
         };
+        if let Some(handler) = self.cfg.connection_request_handler.as_ref() {
+            match handler.handle_request(
+                id::ClientId::Netcode(token.client_id),
+                &token.user_data,
+                from_addr,
+            ) {
+                ConnectionDecision::Accept => {}
+                ConnectionDecision::Reject(reason) => {
+                    debug!(?reason, "server denied connection request. rejected by the connection request handler");
+                    self.send_denied(reason, from_addr, token.server_to_client_key, sender)?;
+                    return Ok(());
+                }
+                ConnectionDecision::Defer => {
+                    trace!("server deferred connection request");
+                    return Ok(());
+                }
+            }
+        };
         self.conn_cache.add(
             token.client_id,
             from_addr,
@@ -658,8 +730,8 @@ impl<Ctx> NetcodeServer<Ctx> {
 
         if self.num_connected_clients() >= MAX_CLIENTS {
             debug!("server denied connection response. server is full");
-            self.send_to_addr(
-                DeniedPacket::create(),
+            self.send_denied(
+                DeniedReason::ServerFull,
                 from_addr,
                 self.conn_cache
                     .clients
@@ -767,6 +839,14 @@ impl<Ctx> NetcodeServer<Ctx> {
                 debug!(error = ?e, "server ignored packet because it failed to decrypt.");
                 return Ok(());
             }
+            Err(Error::Packet(super::packet::Error::BadProtocolId { expected, actual })) => {
+                error!(
+                    "server rejected connection request from {addr}: the client uses the protocol id {actual:#x} \
+                    but the server expects {expected:#x}. Make sure that the client and the server use the same \
+                    `protocol_id`"
+                );
+                return Ok(());
+            }
             Err(e) => {
                 error!("server ignored packet: {e}");
                 return Ok(());
@@ -865,6 +945,14 @@ impl<Ctx> NetcodeServer<Ctx> {
             // send a keep-alive packet to the client to confirm the connection
             self.send_to_client(KeepAlivePacket::create(client_id), client_id, io)?;
         }
+        #[cfg(feature = "zstd")]
+        if let Some(compression) = self.compression.as_mut() {
+            // the compression header is counted in the MAX_PACKET_SIZE budget
+            let mut payload = [0u8; MAX_PACKET_SIZE];
+            let size = compression.compress(buf, &mut payload)?;
+            let packet = PayloadPacket::create(&payload[..size]);
+            return self.send_to_client(packet, client_id, io);
+        }
         let packet = PayloadPacket::create(buf);
         self.send_to_client(packet, client_id, io)
     }
@@ -983,6 +1071,9 @@ impl<Ctx> NetcodeServer<Ctx> {
 pub(crate) struct NetcodeServerContext {
     pub(crate) connections: Vec<id::ClientId>,
     pub(crate) disconnections: Vec<id::ClientId>,
+    /// Clients that we disconnected since the last update, that are reported as new disconnections
+    /// after the next update
+    pub(crate) forced_disconnections: Vec<id::ClientId>,
 }
 
 #[derive(Resource)]
@@ -995,6 +1086,15 @@ pub struct Server {
 impl NetServer for Server {
     fn start(&mut self) -> anyhow::Result<()> {
         let io_config = self.io_config.clone();
+        #[cfg(feature = "zstd")]
+        {
+            self.server.compression = io_config
+                .compression
+                .as_ref()
+                .map(CompressionConfig::build)
+                .transpose()
+                .context("could not create the payload compression")?;
+        }
         let io = io_config.connect().context("could not start io")?;
         self.io = Some(io);
         Ok(())
@@ -1026,7 +1126,11 @@ impl NetServer for Server {
                 self.server
                     .disconnect(id, io)
                     .context("Could not disconnect client")?;
-                self.server.cfg.context.disconnections.push(client_id);
+                self.server
+                    .cfg
+                    .context
+                    .forced_disconnections
+                    .push(client_id);
                 Ok(())
             }
             _ => Err(anyhow!("the client id must be of type Netcode")),
@@ -1043,8 +1147,9 @@ impl NetServer for Server {
     fn try_update(&mut self, delta_ms: f64) -> anyhow::Result<()> {
         let io = self.io.as_mut().context("io is not initialized")?;
         // reset the new connections/disconnections
-        self.server.cfg.context.connections.clear();
-        self.server.cfg.context.disconnections.clear();
+        let context = &mut self.server.cfg.context;
+        context.connections.clear();
+        context.disconnections = std::mem::take(&mut context.forced_disconnections);
 
         self.server
             .try_update(delta_ms, io)
@@ -1095,6 +1200,9 @@ impl Server {
         cfg = cfg.keep_alive_send_rate(config.keep_alive_send_rate);
         cfg = cfg.num_disconnect_packets(config.num_disconnect_packets);
         cfg = cfg.client_timeout_secs(config.client_timeout_secs);
+        if let Some(handler) = config.connection_request_handler {
+            cfg = cfg.connection_request_handler(handler);
+        }
         let server = NetcodeServer::with_config(config.protocol_id, private_key, cfg)
             .expect("Could not create server netcode");
 
@@ -1105,3 +1213,134 @@ impl Server {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use crate::connection::netcode::{ClientState, NetcodeClient, USER_DATA_BYTES};
+    use crate::prelude::TransportConfig;
+    use crate::transport::LOCAL_SOCKET;
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestHandler(Mutex<ConnectionDecision>);
+
+    impl ConnectionRequestHandler for TestHandler {
+        fn handle_request(
+            &self,
+            client_id: id::ClientId,
+            user_data: &[u8],
+            _: SocketAddr,
+        ) -> ConnectionDecision {
+            assert_eq!(client_id, id::ClientId::Netcode(1));
+            assert_eq!(user_data[0], 7);
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn setup(handler: Arc<TestHandler>) -> (NetcodeServer, Io, NetcodeClient, Io) {
+        let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+        let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+        let client_io = IoConfig::from_transport(TransportConfig::LocalChannel {
+            send: to_server_send,
+            recv: from_server_recv,
+        })
+        .connect()
+        .unwrap();
+        let server_io = IoConfig::from_transport(TransportConfig::Channels {
+            channels: vec![(LOCAL_SOCKET, to_server_recv, from_server_send)],
+        })
+        .connect()
+        .unwrap();
+
+        let cfg = ServerConfig::default().connection_request_handler(handler);
+        let mut server = NetcodeServer::with_config(0, generate_key(), cfg).unwrap();
+        let mut user_data = [0u8; USER_DATA_BYTES];
+        user_data[0] = 7;
+        let token = server
+            .token(1, LOCAL_SOCKET)
+            .user_data(user_data)
+            .generate()
+            .unwrap()
+            .try_into_bytes()
+            .unwrap();
+        let client = NetcodeClient::new(&token).unwrap();
+        (server, server_io, client, client_io)
+    }
+
+    fn step(
+        server: &mut NetcodeServer,
+        server_io: &mut Io,
+        client: &mut NetcodeClient,
+        client_io: &mut Io,
+    ) {
+        for _ in 0..10 {
+            client.try_update(0.2, client_io).unwrap();
+            server.try_update(0.2, server_io).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_connection_request_rejected() {
+        let handler = Arc::new(TestHandler(Mutex::new(ConnectionDecision::Reject(
+            DeniedReason::Custom(3),
+        ))));
+        let (mut server, mut server_io, mut client, mut client_io) = setup(handler);
+        client.connect();
+        step(&mut server, &mut server_io, &mut client, &mut client_io);
+
+        assert_eq!(client.state(), ClientState::ConnectionDenied);
+        assert_eq!(client.denied_reason(), Some(DeniedReason::Custom(3)));
+        assert_eq!(server.num_connected_clients(), 0);
+    }
+
+    #[test]
+    fn test_connection_request_deferred() {
+        let handler = Arc::new(TestHandler(Mutex::new(ConnectionDecision::Defer)));
+        let (mut server, mut server_io, mut client, mut client_io) = setup(handler.clone());
+        client.connect();
+        step(&mut server, &mut server_io, &mut client, &mut client_io);
+
+        // the client keeps sending connection requests while the decision is deferred
+        assert_eq!(client.state(), ClientState::SendingConnectionRequest);
+        assert_eq!(server.num_connected_clients(), 0);
+
+        *handler.0.lock().unwrap() = ConnectionDecision::Accept;
+        step(&mut server, &mut server_io, &mut client, &mut client_io);
+        assert_eq!(client.state(), ClientState::Connected);
+        assert_eq!(server.num_connected_clients(), 1);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_payload_compression() {
+        use crate::packet::message::SingleData;
+        use crate::packet::packet_manager::PacketBuilder;
+
+        let handler = Arc::new(TestHandler(Mutex::new(ConnectionDecision::Accept)));
+        let (mut server, mut server_io, mut client, mut client_io) = setup(handler);
+        let config = CompressionConfig::default();
+        server.compression = Some(config.build().unwrap());
+        client.compression = Some(config.build().unwrap());
+        client.connect();
+        step(&mut server, &mut server_io, &mut client, &mut client_io);
+        assert_eq!(client.state(), ClientState::Connected);
+
+        let mut builder = PacketBuilder::new();
+        let mut packet = builder.build_new_single_packet();
+        packet.add_message(0, SingleData::new(None, vec![1u8; 500].into(), 1.0));
+        let payload = builder.encode_packet(&packet).unwrap();
+
+        // the payload is compressed before being encrypted
+        let bytes_sent = client_io.stats().bytes_sent;
+        client.send(&payload, &mut client_io).unwrap();
+        assert!(client_io.stats().bytes_sent - bytes_sent < payload.len() / 2);
+
+        server.try_update(0.0, &mut server_io).unwrap();
+        let (received, id) = server.recv().unwrap();
+        assert_eq!(id, 1);
+        assert_eq!(received.num_messages(), 1);
+    }
+}
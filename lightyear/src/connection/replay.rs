@@ -0,0 +1,364 @@
+//! Recording and replaying of the packets exchanged between the server and its clients
+//!
+//! The server can record every packet that it sends or receives (see [`ReplayRecorderPlugin`](crate::server::replay::ReplayRecorderPlugin)).
+//! The recording can later be fed back into a client using a [`ReplayPlayer`], which acts as a "fake" connection to the server:
+//! the client rebuilds the whole match offline, as if it was receiving the packets from the server.
+//!
+//! This can be used to reproduce bugs, or to implement spectator replays and killcams.
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::_reexport::{ReadBuffer, ReadWordBuffer};
+use crate::client::networking::NetworkingState;
+use crate::connection::client::NetClient;
+use crate::connection::id::ClientId;
+use crate::connection::server::DeniedReason;
+use crate::packet::packet::Packet;
+use crate::prelude::Io;
+use crate::shared::tick_manager::Tick;
+use crate::transport::LOCAL_SOCKET;
+
+/// Maximum size of an encoded record. A record contains a single packet, so a larger length prefix
+/// means that the recording is corrupted
+const MAX_RECORD_BYTES: usize = 64 * 1024;
+
+/// Direction of a recorded packet
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayDirection {
+    /// Packet sent by the server to a client
+    ServerToClient,
+    /// Packet received by the server from a client
+    ClientToServer,
+}
+
+/// A single packet recorded by the server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReplayRecord {
+    pub direction: ReplayDirection,
+    /// Server tick at which the packet was sent or received
+    pub tick: Tick,
+    /// Server time (in milliseconds) at which the packet was sent or received
+    pub time_ms: u32,
+    /// Client that the packet was sent to or received from
+    pub client_id: ClientId,
+    /// The encoded packet
+    pub payload: Vec<u8>,
+}
+
+impl ReplayRecord {
+    /// Write the record to the writer, prefixed by its length
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        let bytes = bitcode::serialize(self).context("could not serialize replay record")?;
+        writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    /// Read the next record from the reader. Returns `None` if the reader is exhausted
+    pub fn read(reader: &mut impl Read) -> Result<Option<Self>> {
+        let mut len = [0; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD_BYTES {
+            return Err(anyhow!(
+                "replay record is too large ({len} bytes), the recording is corrupted"
+            ));
+        }
+        let mut bytes = vec![0; len];
+        reader
+            .read_exact(&mut bytes)
+            .context("replay record is truncated")?;
+        let record = bitcode::deserialize(&bytes).context("could not deserialize replay record")?;
+        Ok(Some(record))
+    }
+
+    /// Read all the records from the reader
+    pub fn read_all(reader: &mut impl Read) -> Result<Vec<Self>> {
+        let mut records = vec![];
+        while let Some(record) = Self::read(reader)? {
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+/// A "fake" [`NetClient`] that replays the packets that a server sent to a given client.
+///
+/// The packets are delivered to the client with the same timing as in the recording.
+/// The timeline is aligned on the first packet sent by the client: the replayed packets only start flowing
+/// once the client sends its first packet, so that the recorded pongs arrive after the matching pings
+/// and the client can sync with the recording.
+/// Any packet sent by the client is discarded.
+pub struct ReplayPlayer {
+    client_id: ClientId,
+    /// Packets that haven't been delivered yet, with the time (relative to the start of the recording)
+    /// at which they should be delivered
+    records: VecDeque<(Duration, Vec<u8>)>,
+    /// File from which the records are loaded when the player connects
+    path: Option<PathBuf>,
+    packet_queue: VecDeque<Packet>,
+    elapsed: Duration,
+    is_connected: bool,
+    /// True once the client has sent its first packet, or if the recording contains no packet from the client
+    is_started: bool,
+}
+
+impl ReplayPlayer {
+    /// Create a player that replays the packets sent by the server to `client_id`
+    pub fn new(records: Vec<ReplayRecord>, client_id: ClientId) -> Self {
+        let (records, is_started) = Self::client_records(records, client_id);
+        Self {
+            client_id,
+            records,
+            path: None,
+            packet_queue: VecDeque::new(),
+            elapsed: Duration::ZERO,
+            is_connected: false,
+            is_started,
+        }
+    }
+
+    /// Load a recording from a file written by the [`ReplayRecorderPlugin`](crate::server::replay::ReplayRecorderPlugin)
+    pub fn from_file(path: impl AsRef<Path>, client_id: ClientId) -> Result<Self> {
+        Ok(Self::new(Self::read_file(path)?, client_id))
+    }
+
+    /// Create a player that loads the recording from the file at `path` when it connects.
+    ///
+    /// If the file cannot be read, [`NetClient::connect`] returns an error.
+    pub fn from_path(path: impl Into<PathBuf>, client_id: ClientId) -> Self {
+        Self {
+            path: Some(path.into()),
+            ..Self::new(vec![], client_id)
+        }
+    }
+
+    fn read_file(path: impl AsRef<Path>) -> Result<Vec<ReplayRecord>> {
+        let file = File::open(path).context("could not open replay file")?;
+        ReplayRecord::read_all(&mut BufReader::new(file))
+    }
+
+    /// Keep only the packets sent to `client_id`, with their time relative to the first packet sent by the client.
+    ///
+    /// Also returns true if the recording contains no packet from the client, in which case the timeline
+    /// starts with the first packet sent to the client.
+    fn client_records(
+        records: Vec<ReplayRecord>,
+        client_id: ClientId,
+    ) -> (VecDeque<(Duration, Vec<u8>)>, bool) {
+        let first_time = |direction: ReplayDirection| {
+            records
+                .iter()
+                .filter(|r| r.client_id == client_id && r.direction == direction)
+                .map(|r| r.time_ms)
+                .min()
+        };
+        let (start, is_started) = match first_time(ReplayDirection::ClientToServer) {
+            Some(start) => (start, false),
+            None => (
+                first_time(ReplayDirection::ServerToClient).unwrap_or_default(),
+                true,
+            ),
+        };
+        let records = records
+            .into_iter()
+            .filter(|r| r.direction == ReplayDirection::ServerToClient && r.client_id == client_id)
+            .map(|r| {
+                (
+                    Duration::from_millis(r.time_ms.saturating_sub(start) as u64),
+                    r.payload,
+                )
+            })
+            .collect();
+        (records, is_started)
+    }
+
+    /// Returns true if all the recorded packets have been delivered
+    pub fn is_finished(&self) -> bool {
+        self.records.is_empty() && self.packet_queue.is_empty()
+    }
+}
+
+impl NetClient for ReplayPlayer {
+    fn connect(&mut self) -> Result<()> {
+        if let Some(path) = self.path.as_ref() {
+            (self.records, self.is_started) =
+                Self::client_records(Self::read_file(path)?, self.client_id);
+            self.path = None;
+        }
+        self.is_connected = true;
+        Ok(())
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        self.is_connected = false;
+        Ok(())
+    }
+
+    fn state(&self) -> NetworkingState {
+        if self.is_connected {
+            NetworkingState::Connected
+        } else {
+            NetworkingState::Disconnected
+        }
+    }
+
+    fn try_update(&mut self, delta_secs: f64) -> Result<()> {
+        if !self.is_connected || !self.is_started {
+            return Ok(());
+        }
+        self.elapsed += Duration::from_secs_f64(delta_secs);
+        while self
+            .records
+            .front()
+            // the server sends its response to a client packet in the same frame as it receives it,
+            // so the packets must be delivered strictly after the matching client packet was sent
+            .map_or(false, |(time, _)| *time < self.elapsed)
+        {
+            let (_, payload) = self.records.pop_front().unwrap();
+            let mut reader = ReadWordBuffer::start_read(payload.as_slice());
+            match Packet::decode(&mut reader) {
+                Ok(packet) => self.packet_queue.push_back(packet),
+                Err(e) => error!("could not decode replayed packet: {:?}", e),
+            }
+        }
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Option<Packet> {
+        self.packet_queue.pop_front()
+    }
+
+    fn send(&mut self, _: &[u8]) -> Result<()> {
+        self.is_started = true;
+        Ok(())
+    }
+
+    fn id(&self) -> ClientId {
+        self.client_id
+    }
+
+    fn denied_reason(&self) -> Option<DeniedReason> {
+        None
+    }
+
+    fn local_addr(&self) -> SocketAddr {
+        LOCAL_SOCKET
+    }
+
+    fn io(&self) -> Option<&Io> {
+        None
+    }
+
+    fn io_mut(&mut self) -> Option<&mut Io> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packet::packet_manager::PacketBuilder;
+
+    use super::*;
+
+    fn record(
+        direction: ReplayDirection,
+        time_ms: u32,
+        client: u64,
+        payload: Vec<u8>,
+    ) -> ReplayRecord {
+        ReplayRecord {
+            direction,
+            tick: Tick(time_ms as u16),
+            time_ms,
+            client_id: ClientId::Netcode(client),
+            payload,
+        }
+    }
+
+    #[test]
+    fn test_write_read_records() {
+        let records = vec![
+            record(ReplayDirection::ServerToClient, 10, 1, vec![1, 2, 3]),
+            record(ReplayDirection::ClientToServer, 20, 2, vec![]),
+        ];
+        let mut bytes = vec![];
+        for r in &records {
+            r.write(&mut bytes).unwrap();
+        }
+        assert_eq!(
+            ReplayRecord::read_all(&mut bytes.as_slice()).unwrap(),
+            records
+        );
+
+        // a truncated record is an error
+        bytes.pop();
+        assert!(ReplayRecord::read_all(&mut bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_read_corrupted_length() {
+        let mut bytes = vec![];
+        record(ReplayDirection::ServerToClient, 10, 1, vec![1, 2, 3])
+            .write(&mut bytes)
+            .unwrap();
+        // the length prefix is corrupted: the record must not be allocated
+        bytes[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = ReplayRecord::read_all(&mut bytes.as_slice()).unwrap_err();
+        assert!(err.to_string().contains("too large"));
+    }
+
+    #[test]
+    fn test_replay_player() {
+        let mut manager = PacketBuilder::new();
+        let packet = manager.build_new_single_packet();
+        let payload = manager.encode_packet(&packet).unwrap();
+        let records = vec![
+            record(ReplayDirection::ServerToClient, 100, 1, payload.clone()),
+            // packets from other clients or from the client are ignored
+            record(ReplayDirection::ServerToClient, 100, 2, payload.clone()),
+            record(ReplayDirection::ClientToServer, 110, 1, payload.clone()),
+            record(ReplayDirection::ServerToClient, 150, 1, payload),
+        ];
+        let mut player = ReplayPlayer::new(records, ClientId::Netcode(1));
+        assert_eq!(player.state(), NetworkingState::Disconnected);
+        player.connect().unwrap();
+        assert_eq!(player.state(), NetworkingState::Connected);
+
+        // nothing is delivered until the client sends its first packet
+        player.try_update(0.2).unwrap();
+        assert!(player.recv().is_none());
+
+        // the packets sent before the first packet of the client are delivered immediately
+        player.send(&[]).unwrap();
+        player.try_update(0.01).unwrap();
+        assert!(player.recv().is_some());
+        assert!(player.recv().is_none());
+
+        // the second packet is delivered after the 40ms following the first packet of the client
+        player.try_update(0.03).unwrap();
+        assert!(player.recv().is_none());
+        player.try_update(0.02).unwrap();
+        assert!(player.recv().is_some());
+        assert!(player.is_finished());
+    }
+
+    #[test]
+    fn test_missing_replay_file() {
+        let mut player = ReplayPlayer::from_path("missing/file.replay", ClientId::Netcode(1));
+        assert!(player.connect().is_err());
+        assert_eq!(player.state(), NetworkingState::Disconnected);
+    }
+}
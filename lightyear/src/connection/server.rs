@@ -1,6 +1,10 @@
+use std::fmt::Debug;
+use std::net::SocketAddr;
+
 use anyhow::{anyhow, Result};
-use bevy::prelude::Resource;
-use bevy::utils::HashMap;
+use bevy::prelude::{Reflect, Resource};
+use bevy::utils::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
 
 use crate::connection::id::ClientId;
 #[cfg(all(feature = "steam", not(target_family = "wasm")))]
@@ -9,6 +13,66 @@ use crate::packet::packet::Packet;
 use crate::prelude::{Io, IoConfig, LinkConditionerConfig};
 use crate::server::config::NetcodeConfig;
 
+/// Reason sent to a client when the server refuses its connection request
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Reflect)]
+pub enum DeniedReason {
+    /// The server has reached its maximum number of clients
+    ServerFull,
+    /// The client is not allowed to connect to this server (e.g. it is on a ban list)
+    Banned,
+    /// The client is running a version of the game that is incompatible with the server
+    InvalidVersion,
+    /// The client was built with a different [`Protocol`](crate::protocol::Protocol) than the server
+    ProtocolMismatch,
+    /// Application-specific reason code
+    Custom(u16),
+}
+
+/// Decision returned by a [`ConnectionRequestHandler`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionDecision {
+    /// Accept the connection request
+    Accept,
+    /// Deny the connection request; the reason is sent to the client
+    Reject(DeniedReason),
+    /// Don't make a decision yet: the request is ignored, and the handler will be called again
+    /// the next time the client re-sends its connection request.
+    ///
+    /// This can be used to run asynchronous checks (database lookups, etc.) before accepting a client.
+    Defer,
+}
+
+/// Hook that lets the server decide whether a client's connection request should be accepted.
+///
+/// The handler is called for every valid connection request, after the connect token has been verified.
+///
+/// ```rust,ignore
+/// #[derive(Debug)]
+/// struct BanList(HashSet<ClientId>);
+///
+/// impl ConnectionRequestHandler for BanList {
+///     fn handle_request(&self, client_id: ClientId, _: &[u8], _: SocketAddr) -> ConnectionDecision {
+///         if self.0.contains(&client_id) {
+///             ConnectionDecision::Reject(DeniedReason::Banned)
+///         } else {
+///             ConnectionDecision::Accept
+///         }
+///     }
+/// }
+/// ```
+pub trait ConnectionRequestHandler: Debug + Send + Sync {
+    /// Decide what to do with the connection request of the client `client_id`.
+    ///
+    /// `user_data` is the user data contained in the client's connect token, and `addr` is the address
+    /// that the request was sent from.
+    fn handle_request(
+        &self,
+        client_id: ClientId,
+        user_data: &[u8],
+        addr: SocketAddr,
+    ) -> ConnectionDecision;
+}
+
 pub trait NetServer: Send + Sync {
     /// Start the server
     /// (i.e. start listening for client connections)
@@ -18,8 +82,6 @@ pub trait NetServer: Send + Sync {
     /// (i.e. stop listening for client connections and stop all networking)
     fn stop(&mut self) -> Result<()>;
 
-    // TODO: should we also have an API for accepting a client? i.e. we receive a connection request
-    //  and we decide whether to accept it or not
     /// Disconnect a specific client
     /// Is also responsible for adding the client to the list of new disconnections.
     fn disconnect(&mut self, client_id: ClientId) -> Result<()>;
@@ -152,6 +214,8 @@ pub struct ServerConnections {
     pub(crate) servers: Vec<ServerConnection>,
     /// Mapping from the connection's [`ClientId`] into the index of the [`ServerConnection`] in the `servers` list
     pub(crate) client_server_map: HashMap<ClientId, ServerConnectionIdx>,
+    /// Clients that were disconnected by the server. Their session cannot be resumed
+    pub(crate) forced_disconnections: HashSet<ClientId>,
     /// Track whether the server is ready to listen to incoming connections
     is_listening: bool,
 }
@@ -166,6 +230,7 @@ impl ServerConnections {
         ServerConnections {
             servers,
             client_server_map: HashMap::default(),
+            forced_disconnections: HashSet::default(),
             is_listening: false,
         }
     }
@@ -196,6 +261,7 @@ impl ServerConnections {
             )),
             |&server_idx| {
                 self.servers[server_idx].disconnect(client_id)?;
+                self.forced_disconnections.insert(client_id);
                 // NOTE: we don't remove the client from the map here because it is done
                 //  in the server's `receive` method
                 // self.client_server_map.remove(&client_id);
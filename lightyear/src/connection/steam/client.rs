@@ -2,6 +2,7 @@ use crate::_reexport::{ReadBuffer, ReadWordBuffer};
 use crate::client::networking::NetworkingState;
 use crate::connection::client::NetClient;
 use crate::connection::id::ClientId;
+use crate::connection::server::DeniedReason;
 use crate::packet::packet::Packet;
 use crate::prelude::{Io, LinkConditionerConfig};
 use crate::serialize::wordbuffer::reader::BufferPool;
@@ -182,6 +183,10 @@ impl NetClient for Client {
         ClientId::Steam(Self::client().user().steam_id().raw())
     }
 
+    fn denied_reason(&self) -> Option<DeniedReason> {
+        None
+    }
+
     fn local_addr(&self) -> SocketAddr {
         LOCAL_SOCKET
     }
@@ -1,10 +1,11 @@
 use std::collections::VecDeque;
 use std::fmt::Debug;
 
-use bevy::prelude::{Reflect, Resource};
+use bevy::prelude::{Entity, Reflect, Resource};
 use serde::{Deserialize, Serialize};
 use tracing::{info, trace};
 
+use crate::connection::id::ClientId;
 use crate::protocol::BitSerializable;
 use crate::shared::tick_manager::Tick;
 
@@ -33,6 +34,15 @@ pub struct InputMessage<T> {
     pub(crate) end_tick: Tick,
     // first element is tick end_tick-N+1, last element is end_tick
     pub(crate) inputs: Vec<InputData<T>>,
+    /// The server entities controlled by these inputs.
+    /// The other clients store the rebroadcast inputs on their local copy of these entities.
+    pub(crate) entities: Vec<Entity>,
+    /// The client that generated the inputs.
+    /// This is only set when the server rebroadcasts the message to the other clients
+    pub(crate) client_id: Option<ClientId>,
+    /// Checksum of the deterministic simulation of the client for a tick where the inputs of all players are known.
+    /// This is only set in deterministic mode (see [`PredictionConfig::deterministic`](crate::client::prediction::plugin::PredictionConfig))
+    pub(crate) checksum: Option<(Tick, u64)>,
 }
 
 impl<T: UserAction> InputMessage<T> {
@@ -46,6 +56,45 @@ impl<T: UserAction> InputMessage<T> {
         }
         false
     }
+
+    /// Build a message from the inputs of the consecutive ticks that end at `end_tick`
+    pub(crate) fn from_inputs(end_tick: Tick, inputs: impl IntoIterator<Item = Option<T>>) -> Self {
+        let mut data = vec![];
+        let mut prev_value = None;
+        for input in inputs {
+            if prev_value.as_ref() == Some(&input) {
+                data.push(InputData::SameAsPrecedent);
+                continue;
+            }
+            data.push(input.clone().map_or(InputData::Absent, InputData::Input));
+            prev_value = Some(input);
+        }
+        Self {
+            end_tick,
+            inputs: data,
+            entities: vec![],
+            client_id: None,
+            checksum: None,
+        }
+    }
+
+    /// Returns the input for each tick of the message
+    pub(crate) fn inputs(&self) -> Vec<(Tick, Option<T>)> {
+        let start_tick = self.end_tick - self.inputs.len() as u16 + 1;
+        let mut prev_value = None;
+        self.inputs
+            .iter()
+            .enumerate()
+            .map(|(delta, input)| {
+                match input {
+                    InputData::Absent => prev_value = None,
+                    InputData::SameAsPrecedent => {}
+                    InputData::Input(input) => prev_value = Some(input.clone()),
+                }
+                (start_tick + delta as i16, prev_value.clone())
+            })
+            .collect()
+    }
 }
 
 impl<T: UserAction> Default for InputBuffer<T> {
@@ -91,6 +140,14 @@ impl<T: UserAction> InputBuffer<T> {
         self.buffer.pop_front().unwrap()
     }
 
+    /// Returns the last tick that is stored in the buffer
+    pub(crate) fn end_tick(&self) -> Option<Tick> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+        Some(self.start_tick? + (self.buffer.len() as i16 - 1))
+    }
+
     pub(crate) fn get(&self, tick: Tick) -> Option<&T> {
         let start_tick = self.start_tick?;
         if self.buffer.is_empty() {
@@ -183,7 +240,13 @@ impl<T: UserAction> InputBuffer<T> {
                 inputs.push(value);
             }
         }
-        InputMessage { inputs, end_tick }
+        InputMessage {
+            inputs,
+            end_tick,
+            entities: vec![],
+            client_id: None,
+            checksum: None,
+        }
     }
 }
 
@@ -236,6 +299,9 @@ mod tests {
                     InputData::SameAsPrecedent,
                     InputData::SameAsPrecedent,
                 ],
+                entities: vec![],
+                client_id: None,
+                checksum: None,
             }
         );
     }
@@ -256,6 +322,9 @@ mod tests {
                 InputData::SameAsPrecedent,
                 InputData::SameAsPrecedent,
             ],
+            entities: vec![],
+            client_id: None,
+            checksum: None,
         };
         input_buffer.update_from_message(message);
 
@@ -17,6 +17,7 @@ You can find more information in the [book](https://cbournhonesque.github.io/lig
 // re-exports (mostly used in the derive macro crate or for internal purposes)
 #[doc(hidden)]
 pub mod _reexport {
+    pub use anyhow;
     pub use enum_delegate;
     pub use enum_dispatch::enum_dispatch;
     pub use paste::paste;
@@ -27,7 +28,8 @@ pub mod _reexport {
 
     pub use crate::channel::builder::TickBufferChannel;
     pub use crate::channel::builder::{
-        EntityActionsChannel, EntityUpdatesChannel, InputChannel, PingChannel,
+        AuthorityChannel, EntityActionsChannel, EntityUpdatesChannel, InputChannel, PingChannel,
+        PlayersChannel,
     };
     pub use crate::client::interpolation::{
         add_interpolation_systems, add_prepare_interpolation_systems,
@@ -39,6 +41,7 @@ pub mod _reexport {
         ComponentBehaviour, ComponentKindBehaviour, ComponentProtocol, ComponentProtocolKind,
         FromType,
     };
+    pub use crate::protocol::dynamic::{RawComponent, RawMessage};
     pub use crate::protocol::message::InputMessageKind;
     pub use crate::protocol::message::{MessageKind, MessageProtocol};
     pub use crate::protocol::{BitSerializable, EventContext};
@@ -57,6 +60,7 @@ pub mod _reexport {
         push_component_insert_events, push_component_remove_events, push_component_update_events,
     };
     pub use crate::shared::replication::components::ShouldBeInterpolated;
+    pub use crate::shared::replication::delta::{apply_diff_component, diff_component};
     pub use crate::shared::replication::resources::{
         receive::add_resource_receive_systems, send::add_resource_send_systems,
     };
@@ -75,29 +79,39 @@ pub mod prelude {
     pub use crate::channel::builder::TickBufferChannel;
     pub use crate::channel::builder::{
         Channel, ChannelBuilder, ChannelContainer, ChannelDirection, ChannelMode, ChannelSettings,
-        DefaultUnorderedUnreliableChannel, ReliableSettings,
+        DefaultUnorderedUnreliableChannel, ReliableSettings, StreamSettings,
     };
-    pub use crate::client::prediction::prespawn::PreSpawnedPlayerObject;
+    pub use crate::channel::stream::StreamDirection;
+    pub use crate::client::prediction::prespawn::{PreSpawnHashConfig, PreSpawnedPlayerObject};
     pub use crate::connection::id::ClientId;
+    #[cfg(feature = "zstd")]
+    pub use crate::connection::netcode::CompressionConfig;
     pub use crate::connection::netcode::{generate_key, Key};
     #[cfg(feature = "leafwing")]
     pub use crate::inputs::leafwing::LeafwingUserAction;
     pub use crate::inputs::native::UserAction;
-    pub use crate::packet::message::Message;
+    pub use crate::packet::congestion::CongestionConfig;
+    pub use crate::packet::message::{Message, MessageId};
     pub use crate::protocol::channel::{ChannelKind, ChannelRegistry};
+    pub use crate::protocol::dynamic::{AppDynamicProtocolExt, DynamicProtocol};
+    pub use crate::protocol::schema::ProtocolSchema;
     pub use crate::protocol::Protocol;
     pub use crate::protocolize;
-    pub use crate::shared::config::{Mode, SharedConfig};
+    pub use crate::shared::checksum::DesyncEvent;
+    pub use crate::shared::config::{Mode, ResumptionConfig, SharedConfig};
     pub use crate::shared::ping::manager::PingConfig;
     pub use crate::shared::plugin::{NetworkIdentity, SharedPlugin};
     pub use crate::shared::replication::components::{
-        NetworkTarget, PrePredicted, ReplicationGroup, ReplicationMode, ShouldBePredicted,
+        NetworkTarget, PrePredicted, ReplicateRemovalPolicy, ReplicationGroup, ReplicationMode,
+        ShouldBePredicted,
     };
+    pub use crate::shared::replication::delta::Diffable;
     pub use crate::shared::replication::entity_map::{ExternalMapper, RemoteEntityMap};
     pub use crate::shared::replication::hierarchy::ParentSync;
     pub use crate::shared::replication::resources::{
         ReplicateResource, ReplicateResourceExt, StopReplicateResourceExt,
     };
+    pub use crate::shared::rpc::{Request, RequestId, RequestMessage, ResponseMessage, RpcError};
     pub use crate::shared::sets::{FixedUpdateSet, MainSet};
     pub use crate::shared::tick_manager::TickManager;
     pub use crate::shared::tick_manager::{Tick, TickConfig};
@@ -107,15 +121,20 @@ pub mod prelude {
     pub use crate::transport::middleware::conditioner::LinkConditionerConfig;
 
     pub mod client {
+        pub use crate::client::checksum::ChecksumPlugin;
         pub use crate::client::components::{
             ComponentSyncMode, Confirmed, LerpFn, SyncComponent, SyncMetadata,
         };
         pub use crate::client::config::{ClientConfig, NetcodeConfig, PacketConfig};
         pub use crate::client::events::{
             ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
-            DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
+            DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageAckEvent,
+            MessageEvent, MessageLostEvent, RemoteInputEvent, ResponseEvent, StreamCancelledEvent,
+            StreamProgressEvent,
+        };
+        pub use crate::client::input::{
+            InputConfig, InputManager, InputSystemSet, RemoteInputTarget,
         };
-        pub use crate::client::input::{InputConfig, InputManager, InputSystemSet};
         #[cfg(feature = "leafwing")]
         pub use crate::client::input_leafwing::{
             LeafwingInputConfig, LeafwingInputPlugin, ToggleActions,
@@ -130,33 +149,57 @@ pub mod prelude {
         pub use crate::client::networking::{ClientConnectionParam, NetworkingState};
         pub use crate::client::plugin::{ClientPlugin, PluginConfig};
         pub use crate::client::prediction::correction::Correction;
+        pub use crate::client::prediction::deterministic::{Deterministic, DeterministicChecksums};
         pub use crate::client::prediction::plugin::is_in_rollback;
         pub use crate::client::prediction::plugin::{PredictionConfig, PredictionSet};
         pub use crate::client::prediction::predicted_history::{ComponentState, PredictionHistory};
+        pub use crate::client::prediction::prespawn::{
+            ClientNoMatchPolicy, PreSpawnCollisionPolicy, PreSpawnMatchConfig, ServerNoMatchPolicy,
+        };
         pub use crate::client::prediction::rollback::{Rollback, RollbackState};
         pub use crate::client::prediction::{Predicted, PredictionDespawnCommandsExt};
         pub use crate::client::replication::ReplicationConfig;
+        pub use crate::client::rpc::RpcPlugin;
         pub use crate::client::sync::SyncConfig;
         pub use crate::connection::client::{
             Authentication, ClientConnection, NetClient, NetConfig,
         };
+        pub use crate::connection::replay::ReplayPlayer;
+        pub use crate::connection::server::DeniedReason;
         #[cfg(all(feature = "steam", not(target_family = "wasm")))]
         pub use crate::connection::steam::client::SteamConfig;
+        pub use crate::shared::replication::authority::HasAuthority;
     }
     pub mod server {
+        pub use crate::server::checksum::{ChecksumConfig, ChecksumPlugin};
         pub use crate::server::config::{NetcodeConfig, PacketConfig, ServerConfig};
         pub use crate::server::events::{
             ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, ConnectEvent,
-            DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageEvent,
+            DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, InputEvent, MessageAckEvent,
+            MessageEvent, MessageLostEvent, ResponseEvent, SessionResumedEvent,
+            SessionSuspendedEvent, StreamCancelledEvent, StreamProgressEvent,
+        };
+        pub use crate::server::input::{InputConfig, InputValidator, InputViolationEvent};
+        pub use crate::server::lag_compensation::{
+            rewind, LagCompensation, LagCompensationConfig, LagCompensationHistory,
+            LagCompensationPlugin,
         };
         pub use crate::server::plugin::{PluginConfig, ServerPlugin};
+        pub use crate::server::replay::{ReplayRecorder, ReplayRecorderPlugin};
         pub use crate::server::replication::{
             ReplicationConfig, ServerFilter, ServerReplicationSet,
         };
         pub use crate::server::room::{RoomId, RoomManager, RoomMut, RoomRef};
+        pub use crate::server::rpc::RpcPlugin;
+        pub use crate::server::spatial::{
+            SpatialInterestMode, SpatialInterestPlugin, SpatialObserver, SpatialPosition,
+        };
+        pub use crate::shared::replication::authority::Authority;
 
+        pub use crate::connection::netcode::{CredentialHandler, TokenServer};
         pub use crate::connection::server::{
-            NetConfig, NetServer, ServerConnection, ServerConnections,
+            ConnectionDecision, ConnectionRequestHandler, DeniedReason, NetConfig, NetServer,
+            ServerConnection, ServerConnections,
         };
         #[cfg(all(feature = "steam", not(target_family = "wasm")))]
         pub use crate::connection::steam::server::SteamConfig;
@@ -0,0 +1,287 @@
+/*! Congestion control for the packets sent on a connection
+
+The [`CongestionController`] estimates the bandwidth available on the link with an AIMD
+(additive increase, multiplicative decrease) algorithm:
+- every `update_interval`, if we used the whole bandwidth budget without seeing any sign of congestion,
+  the estimated bandwidth is increased by `additive_increase`
+- if too many packets were lost during the interval, or if the RTT increased a lot compared to the lowest RTT
+  that was measured (the queues of the routers are filling up), the estimated bandwidth is multiplied by `multiplicative_decrease`
+
+The estimated bandwidth is then used as a budget of bytes that can be sent each second, on top of the fixed
+bandwidth cap of the [`PriorityManager`](crate::packet::priority_manager::PriorityManager).
+
+A packet is considered lost if it is not acked after `2 * RTT` (and at least [`MIN_LOSS_TIMEOUT`]),
+which is much faster than the packet loss reported by the [`PacketStatsManager`](crate::packet::stats_manager::PacketStatsManager).
+*/
+use std::collections::HashMap;
+
+use bevy::reflect::Reflect;
+use bevy::utils::Duration;
+use tracing::{debug, trace};
+
+use crate::packet::packet::PacketId;
+use crate::shared::time_manager::WrappedTime;
+
+/// Minimum delay after which a packet that was not acked is considered lost
+const MIN_LOSS_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Maximum duration of unused budget that can be accumulated
+const MAX_BURST: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, Reflect)]
+pub struct CongestionConfig {
+    /// If false, the bandwidth is not estimated and all messages are sent as soon as possible
+    /// (unless the fixed bandwidth cap is enabled)
+    pub enabled: bool,
+    /// Estimated bandwidth (in bytes per second) when the connection starts
+    pub initial_bandwidth: u32,
+    /// The estimated bandwidth never goes below this value (in bytes per second)
+    pub min_bandwidth: u32,
+    /// The estimated bandwidth never goes above this value (in bytes per second)
+    pub max_bandwidth: u32,
+    /// Bandwidth (in bytes per second) added to the estimate after each interval without congestion
+    pub additive_increase: u32,
+    /// Factor applied to the estimated bandwidth after an interval with congestion
+    pub multiplicative_decrease: f32,
+    /// Ratio of lost packets over an interval above which we consider that the link is congested
+    pub loss_threshold: f32,
+    /// We consider that the link is congested if the RTT is higher than `(1.0 + rtt_increase_threshold)` times
+    /// the lowest RTT measured on the connection
+    pub rtt_increase_threshold: f32,
+    /// How often the estimated bandwidth is updated
+    pub update_interval: Duration,
+}
+
+impl Default for CongestionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            initial_bandwidth: 64_000,
+            min_bandwidth: 8_000,
+            max_bandwidth: 1_000_000,
+            additive_increase: 4_000,
+            multiplicative_decrease: 0.7,
+            loss_threshold: 0.05,
+            rtt_increase_threshold: 1.0,
+            update_interval: Duration::from_millis(200),
+        }
+    }
+}
+
+impl CongestionConfig {
+    pub fn enable(mut self) -> Self {
+        self.enabled = true;
+        self
+    }
+}
+
+pub(crate) struct CongestionController {
+    pub(crate) config: CongestionConfig,
+    /// Estimated bandwidth of the link, in bytes per second
+    bandwidth: f32,
+    /// Number of bytes that can still be sent. Can become negative if we send a message that is bigger than the budget.
+    budget: f32,
+    /// Packets that were sent but not acked yet, with the time when they were sent
+    in_flight: HashMap<PacketId, WrappedTime>,
+    /// Lowest RTT measured on the connection
+    min_rtt: Option<Duration>,
+    current_rtt: Duration,
+    current_time: WrappedTime,
+    /// Time elapsed since the start of the current interval
+    elapsed: Duration,
+    // stats over the current interval
+    num_acked: u32,
+    num_lost: u32,
+    /// True if some messages could not be sent during the interval because the budget was exhausted
+    limited: bool,
+}
+
+impl CongestionController {
+    pub(crate) fn new(config: CongestionConfig) -> Self {
+        let bandwidth = config.initial_bandwidth as f32;
+        Self {
+            config,
+            bandwidth,
+            budget: 0.0,
+            in_flight: HashMap::new(),
+            min_rtt: None,
+            current_rtt: Duration::default(),
+            current_time: WrappedTime::default(),
+            elapsed: Duration::default(),
+            num_acked: 0,
+            num_lost: 0,
+            limited: false,
+        }
+    }
+
+    /// Current estimate of the bandwidth of the link, in bytes per second
+    pub(crate) fn bandwidth(&self) -> u32 {
+        self.bandwidth as u32
+    }
+
+    /// Refill the budget, detect the lost packets and update the estimated bandwidth at the end of each interval
+    pub(crate) fn update(&mut self, current_time: WrappedTime, delta: Duration, rtt: Duration) {
+        self.current_time = current_time;
+        self.current_rtt = rtt;
+        // the RTT is 0 until the first pong is received
+        if rtt > Duration::default() {
+            self.min_rtt = Some(self.min_rtt.map_or(rtt, |min_rtt| min_rtt.min(rtt)));
+        }
+        self.budget = (self.budget + self.bandwidth * delta.as_secs_f32())
+            .min(self.bandwidth * MAX_BURST.as_secs_f32());
+
+        let loss_timeout =
+            chrono::Duration::from_std(std::cmp::max(rtt * 2, MIN_LOSS_TIMEOUT)).unwrap();
+        let num_in_flight = self.in_flight.len();
+        self.in_flight
+            .retain(|_, sent_time| current_time - *sent_time <= loss_timeout);
+        self.num_lost += (num_in_flight - self.in_flight.len()) as u32;
+
+        self.elapsed += delta;
+        if self.elapsed >= self.config.update_interval {
+            self.elapsed = Duration::default();
+            self.update_bandwidth();
+        }
+    }
+
+    fn update_bandwidth(&mut self) {
+        let num_packets = self.num_acked + self.num_lost;
+        let loss = if num_packets > 0 {
+            self.num_lost as f32 / num_packets as f32
+        } else {
+            0.0
+        };
+        let rtt_increased = self.min_rtt.map_or(false, |min_rtt| {
+            self.current_rtt.as_secs_f32()
+                > min_rtt.as_secs_f32() * (1.0 + self.config.rtt_increase_threshold)
+        });
+        if loss > self.config.loss_threshold || rtt_increased {
+            self.bandwidth *= self.config.multiplicative_decrease;
+            debug!(
+                ?loss,
+                rtt = ?self.current_rtt,
+                bandwidth = ?self.bandwidth,
+                "Congestion detected, decreasing the estimated bandwidth"
+            );
+        } else if self.limited {
+            // only increase the bandwidth if we actually needed more; otherwise we would be increasing the estimate
+            // without testing it
+            self.bandwidth += self.config.additive_increase as f32;
+            trace!(bandwidth = ?self.bandwidth, "Increasing the estimated bandwidth");
+        }
+        self.bandwidth = self.bandwidth.clamp(
+            self.config.min_bandwidth as f32,
+            self.config.max_bandwidth as f32,
+        );
+        #[cfg(feature = "metrics")]
+        metrics::gauge!("estimated_bandwidth").set(self.bandwidth as f64);
+        self.num_acked = 0;
+        self.num_lost = 0;
+        self.limited = false;
+    }
+
+    /// Returns true if the budget allows sending more bytes.
+    ///
+    /// A message can be sent as long as the budget is positive, even if it is bigger than the remaining budget
+    pub(crate) fn can_send(&mut self) -> bool {
+        if self.budget > 0.0 {
+            return true;
+        }
+        self.limited = true;
+        false
+    }
+
+    /// Use `bytes` of the budget
+    pub(crate) fn consume(&mut self, bytes: u32) {
+        self.budget -= bytes as f32;
+    }
+
+    /// Notify that a packet was sent
+    pub(crate) fn sent_packet(&mut self, packet_id: PacketId) {
+        self.in_flight.insert(packet_id, self.current_time);
+    }
+
+    /// Notify that a packet was acked by the remote
+    pub(crate) fn acked_packet(&mut self, packet_id: PacketId) {
+        // packets that are acked after we considered them lost are still counted as lost
+        if self.in_flight.remove(&packet_id).is_some() {
+            self.num_acked += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller() -> CongestionController {
+        CongestionController::new(CongestionConfig {
+            initial_bandwidth: 10_000,
+            min_bandwidth: 1_000,
+            additive_increase: 1_000,
+            multiplicative_decrease: 0.5,
+            update_interval: Duration::from_millis(100),
+            ..CongestionConfig::default().enable()
+        })
+    }
+
+    /// Send packets until the budget is exhausted, and ack or lose them
+    fn send_packets(controller: &mut CongestionController, first_id: u16, lost: bool) {
+        for i in 0..10 {
+            if controller.can_send() {
+                controller.consume(500);
+            }
+            controller.sent_packet(PacketId(first_id + i));
+            if !lost {
+                controller.acked_packet(PacketId(first_id + i));
+            }
+        }
+    }
+
+    #[test]
+    fn test_additive_increase() {
+        let mut controller = controller();
+        let rtt = Duration::from_millis(20);
+        let mut time = WrappedTime::default();
+        let delta = Duration::from_millis(50);
+        for i in 0..2 {
+            send_packets(&mut controller, i * 10, false);
+            time += delta;
+            controller.update(time, delta, rtt);
+        }
+        assert_eq!(controller.bandwidth(), 11_000);
+
+        // the budget was not used: the estimate stays the same
+        for _ in 0..2 {
+            time += delta;
+            controller.update(time, delta, rtt);
+        }
+        assert_eq!(controller.bandwidth(), 11_000);
+    }
+
+    #[test]
+    fn test_multiplicative_decrease() {
+        let mut controller = controller();
+        let rtt = Duration::from_millis(20);
+        let mut time = WrappedTime::default();
+        let delta = Duration::from_millis(150);
+
+        // the packets are lost
+        send_packets(&mut controller, 0, true);
+        time += delta;
+        controller.update(time, delta, rtt);
+        assert_eq!(controller.bandwidth(), 5_000);
+
+        // the RTT increases
+        time += delta;
+        controller.update(time, delta, rtt * 3);
+        assert_eq!(controller.bandwidth(), 2_500);
+
+        // the estimate never goes below the minimum bandwidth
+        for _ in 0..10 {
+            time += delta;
+            controller.update(time, delta, rtt * 3);
+        }
+        assert_eq!(controller.bandwidth(), 1_000);
+    }
+}
@@ -0,0 +1,130 @@
+/*! Track whether the messages sent by the user were delivered to the remote
+
+Only the messages sent on channels that watch acks (see [`ChannelMode::is_watching_acks`](crate::channel::builder::ChannelMode::is_watching_acks))
+can be tracked:
+- a message is acked when the remote has received every fragment of the message
+- a message sent on an unreliable channel is lost if one of the packets that contained it was lost, or if it was not acked
+  after [`LOST_TIMEOUT`] (for example because it was never sent because of the bandwidth limits).
+  Messages sent on reliable channels are never lost, they are sent again until they are acked.
+*/
+use std::collections::HashMap;
+
+use crossbeam_channel::Receiver;
+use tracing::trace;
+
+use crate::channel::builder::ChannelContainer;
+use crate::channel::senders::ChannelSend;
+use crate::packet::message::MessageId;
+use crate::protocol::channel::ChannelKind;
+use crate::shared::time_manager::WrappedTime;
+
+/// Delay after which a message sent on an unreliable channel that was not acked is considered lost
+const LOST_TIMEOUT: chrono::Duration = chrono::Duration::milliseconds(5000);
+
+/// Result of the delivery of a message sent by the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MessageDelivery {
+    Acked(MessageId),
+    Lost(MessageId),
+}
+
+struct TrackedChannel {
+    /// Receives the ids of the messages that were acked by the remote
+    acks: Receiver<MessageId>,
+    is_reliable: bool,
+    /// Messages that are waiting for an ack, with the time when they were buffered
+    pending: HashMap<MessageId, WrappedTime>,
+}
+
+pub(crate) struct DeliveryTracker {
+    channels: HashMap<ChannelKind, TrackedChannel>,
+    updates: Vec<(ChannelKind, MessageDelivery)>,
+    current_time: WrappedTime,
+}
+
+impl DeliveryTracker {
+    /// Subscribe to the acks of every channel that watches acks
+    pub(crate) fn new(channels: &mut HashMap<ChannelKind, ChannelContainer>) -> Self {
+        let channels = channels
+            .iter_mut()
+            .filter(|(_, channel)| channel.setting.mode.is_watching_acks())
+            .map(|(channel_kind, channel)| {
+                (
+                    *channel_kind,
+                    TrackedChannel {
+                        acks: channel.sender.subscribe_acks(),
+                        is_reliable: channel.setting.mode.is_reliable(),
+                        pending: HashMap::new(),
+                    },
+                )
+            })
+            .collect();
+        Self {
+            channels,
+            updates: Vec::new(),
+            current_time: WrappedTime::default(),
+        }
+    }
+
+    /// Start tracking the delivery of a message. Does nothing if the channel does not watch acks.
+    pub(crate) fn track(&mut self, channel_kind: ChannelKind, message_id: MessageId) {
+        if let Some(channel) = self.channels.get_mut(&channel_kind) {
+            channel.pending.insert(message_id, self.current_time);
+        }
+    }
+
+    /// Stop tracking the delivery of a message
+    pub(crate) fn untrack(&mut self, channel_kind: ChannelKind, message_id: MessageId) {
+        if let Some(channel) = self.channels.get_mut(&channel_kind) {
+            channel.pending.remove(&message_id);
+        }
+    }
+
+    /// Read the acks notified by the senders
+    pub(crate) fn receive_acks(&mut self) {
+        for (channel_kind, channel) in self.channels.iter_mut() {
+            for message_id in channel.acks.try_iter() {
+                if channel.pending.remove(&message_id).is_some() {
+                    trace!(?channel_kind, ?message_id, "Message was acked");
+                    self.updates
+                        .push((*channel_kind, MessageDelivery::Acked(message_id)));
+                }
+            }
+        }
+    }
+
+    /// Notify that a packet containing (a fragment of) the message was lost
+    pub(crate) fn message_lost(&mut self, channel_kind: ChannelKind, message_id: MessageId) {
+        let Some(channel) = self.channels.get_mut(&channel_kind) else {
+            return;
+        };
+        if !channel.is_reliable && channel.pending.remove(&message_id).is_some() {
+            trace!(?channel_kind, ?message_id, "Message was lost");
+            self.updates
+                .push((channel_kind, MessageDelivery::Lost(message_id)));
+        }
+    }
+
+    /// Consider the unreliable messages that have not been acked for too long as lost
+    pub(crate) fn update(&mut self, current_time: WrappedTime) {
+        self.current_time = current_time;
+        for (channel_kind, channel) in self.channels.iter_mut() {
+            if channel.is_reliable {
+                continue;
+            }
+            channel.pending.retain(|message_id, buffered_time| {
+                if current_time - *buffered_time > LOST_TIMEOUT {
+                    self.updates
+                        .push((*channel_kind, MessageDelivery::Lost(*message_id)));
+                    return false;
+                }
+                true
+            });
+        }
+    }
+
+    /// Take the deliveries of the tracked messages since the last call
+    pub(crate) fn take_updates(&mut self) -> Vec<(ChannelKind, MessageDelivery)> {
+        std::mem::take(&mut self.updates)
+    }
+}
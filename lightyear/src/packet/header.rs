@@ -63,6 +63,8 @@ pub struct PacketHeaderManager {
     // sent_packets_not_acked: HashSet<PacketId>,
     sent_packets_not_acked: HashMap<PacketId, WrappedTime>,
     stats_manager: PacketStatsManager,
+    /// Packets that were considered lost since the last time they were read
+    lost_packets: Vec<PacketId>,
 
     // channel to notify the sender of the packet_id of the packets that were delivered
     // ack_notification_sender: Sender<PacketId>,
@@ -82,6 +84,7 @@ impl PacketHeaderManager {
         Self {
             next_packet_id: PacketId(0),
             stats_manager: PacketStatsManager::default(),
+            lost_packets: Vec::new(),
             // sent_packets_not_acked: HashSet::with_capacity(MAX_SEND_PACKET_QUEUE_SIZE as usize),
             sent_packets_not_acked: HashMap::new(),
             recv_buffer: ReceiveBuffer::new(),
@@ -99,6 +102,7 @@ impl PacketHeaderManager {
             if self.current_time - (*time_sent) > CLEAR_UNACKED_PACKETS_DELAY {
                 trace!("sent packet got lost");
                 self.stats_manager.sent_packet_lost();
+                self.lost_packets.push(*packet_id);
                 return false;
             }
             true
@@ -111,6 +115,11 @@ impl PacketHeaderManager {
     //     &self.ack_notification_receiver
     // }
 
+    /// Take the list of packets that were considered lost since the last call
+    pub(crate) fn take_lost_packets(&mut self) -> Vec<PacketId> {
+        std::mem::take(&mut self.lost_packets)
+    }
+
     /// Return the packet id of the next packet to be sent
     pub fn next_packet_id(&self) -> PacketId {
         self.next_packet_id
@@ -151,6 +160,18 @@ impl PacketHeaderManager {
                 }
             }
         }
+        // the remote can only ack the `ACK_BITFIELD_SIZE` packets before its last received packet, so
+        // the packets that are older than that and were not acked will never be acked: they were lost
+        // (we don't need to wait for `CLEAR_UNACKED_PACKETS_DELAY`)
+        self.sent_packets_not_acked.retain(|packet_id, _| {
+            if header.last_ack_packet_id - *packet_id > ACK_BITFIELD_SIZE as i16 {
+                trace!(?packet_id, "sent packet got lost");
+                self.stats_manager.sent_packet_lost();
+                self.lost_packets.push(*packet_id);
+                return false;
+            }
+            true
+        });
         newly_acked_packets
     }
 
@@ -341,6 +362,56 @@ mod tests {
         assert_eq!(recv_buffer.get_bitfield(), 1 << (32 - 1));
     }
 
+    fn ack_header(last_ack_packet_id: u16, ack_bitfield: u32) -> PacketHeader {
+        PacketHeader {
+            packet_type: PacketType::Data,
+            packet_id: PacketId(0),
+            last_ack_packet_id: PacketId(last_ack_packet_id),
+            ack_bitfield,
+            tick: Tick(0),
+        }
+    }
+
+    #[test]
+    fn test_packets_lost_outside_of_ack_bitfield() {
+        let mut manager = PacketHeaderManager::new();
+        for _ in 0..=40 {
+            manager.prepare_send_packet_header(PacketType::Data);
+        }
+
+        // the remote received packets 8 to 40, except packet 39 (the first bit of the bitfield)
+        let acked = manager.process_recv_packet_header(&ack_header(40, !1));
+        assert_eq!(acked.len(), 32);
+        // packets 0 to 7 can't be acked anymore, so they are lost
+        let mut lost = manager.take_lost_packets();
+        lost.sort();
+        assert_eq!(lost, (0..8).map(PacketId).collect::<Vec<_>>());
+        assert!(manager.take_lost_packets().is_empty());
+        // packet 39 can still be acked by a later header
+        assert_eq!(
+            manager.sent_packets_not_acked().keys().collect::<Vec<_>>(),
+            vec![&PacketId(39)]
+        );
+        assert_eq!(
+            manager.process_recv_packet_header(&ack_header(39, 0)),
+            vec![PacketId(39)]
+        );
+        assert!(manager.take_lost_packets().is_empty());
+    }
+
+    #[test]
+    fn test_no_packets_lost_before_first_ack() {
+        let mut manager = PacketHeaderManager::new();
+        for _ in 0..50 {
+            manager.prepare_send_packet_header(PacketType::Data);
+        }
+        // the remote hasn't received any packet yet
+        let acked = manager.process_recv_packet_header(&ack_header(u16::MAX, 0));
+        assert!(acked.is_empty());
+        assert!(manager.take_lost_packets().is_empty());
+        assert_eq!(manager.sent_packets_not_acked().len(), 50);
+    }
+
     #[test]
     fn test_serde_header() -> anyhow::Result<()> {
         let header = PacketHeader {
@@ -9,8 +9,10 @@ use crossbeam_channel::Receiver;
 use tracing::{info, trace};
 
 use crate::channel::builder::ChannelContainer;
-use crate::channel::receivers::ChannelReceive;
-use crate::channel::senders::ChannelSend;
+use crate::channel::receivers::{ChannelReceive, ChannelReceiver};
+use crate::channel::senders::{ChannelSend, ChannelSender};
+use crate::channel::stream::StreamUpdate;
+use crate::packet::delivery::{DeliveryTracker, MessageDelivery};
 use crate::packet::message::{FragmentData, MessageAck, MessageId, SingleData};
 use crate::packet::packet::{Packet, PacketId, MTU_PAYLOAD_BYTES};
 use crate::packet::packet_manager::{PacketBuilder, Payload, PACKET_BUFFER_CAPACITY};
@@ -44,6 +46,8 @@ pub struct MessageManager {
     /// Map to keep track of which messages have been sent in which packets, so that
     /// reliable senders can stop trying to send a message that has already been received
     packet_to_message_ack_map: HashMap<PacketId, HashMap<ChannelKind, Vec<MessageAck>>>,
+    /// Keeps track of the delivery of the messages sent by the user
+    delivery_tracker: DeliveryTracker,
     writer: WriteWordBuffer,
     // read_buffer: WordBuffer,
     reader_pool: BufferPool,
@@ -51,12 +55,15 @@ pub struct MessageManager {
 
 impl MessageManager {
     pub fn new(channel_registry: &ChannelRegistry, priority_config: PriorityConfig) -> Self {
+        let mut channels = channel_registry.channels();
+        let delivery_tracker = DeliveryTracker::new(&mut channels);
         Self {
             packet_manager: PacketBuilder::new(),
             priority_manager: PriorityManager::new(priority_config),
-            channels: channel_registry.channels(),
+            channels,
             channel_registry: channel_registry.clone(),
             packet_to_message_ack_map: HashMap::new(),
+            delivery_tracker,
             writer: WriteWordBuffer::with_capacity(PACKET_BUFFER_CAPACITY),
             // TODO: it looks like we don't really need the pool this case, we can just keep re-using the same buffer
             reader_pool: BufferPool::new(1),
@@ -64,6 +71,13 @@ impl MessageManager {
         }
     }
 
+    /// Bandwidth of the link (in bytes per second) estimated by the congestion control,
+    /// or None if congestion control is disabled
+    pub fn estimated_bandwidth(&self) -> Option<u32> {
+        let congestion = &self.priority_manager.congestion;
+        congestion.config.enabled.then(|| congestion.bandwidth())
+    }
+
     pub(crate) fn get_replication_update_send_receiver(&mut self) -> Receiver<MessageId> {
         self.priority_manager
             .subscribe_replication_update_sent_messages()
@@ -77,6 +91,23 @@ impl MessageManager {
         tick_manager: &TickManager,
     ) {
         self.packet_manager.header_manager.update(time_manager);
+        // the messages that were in lost packets will never be acked
+        for packet_id in self.packet_manager.header_manager.take_lost_packets() {
+            if let Some(message_map) = self.packet_to_message_ack_map.remove(&packet_id) {
+                for (channel_kind, message_acks) in message_map {
+                    for message_ack in message_acks {
+                        self.delivery_tracker
+                            .message_lost(channel_kind, message_ack.message_id);
+                    }
+                }
+            }
+        }
+        self.delivery_tracker.update(time_manager.current_time());
+        self.priority_manager.congestion.update(
+            time_manager.current_time(),
+            time_manager.delta(),
+            ping_manager.rtt(),
+        );
         for channel in self.channels.values_mut() {
             channel
                 .sender
@@ -123,6 +154,84 @@ impl MessageManager {
         Ok(channel.sender.buffer_send(message_bytes.into(), priority))
     }
 
+    /// Buffer a message to be sent on a [`ChannelMode::KeyedOrderedReliable`](crate::channel::builder::ChannelMode::KeyedOrderedReliable) channel.
+    /// The message is only ordered relative to the other messages with the same `key`.
+    ///
+    /// Returns the message id associated with the message
+    pub fn buffer_send_with_key<M: BitSerializable>(
+        &mut self,
+        message: M,
+        channel_kind: ChannelKind,
+        key: u32,
+    ) -> anyhow::Result<Option<MessageId>> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .context("Channel not found")?;
+        let ChannelSender::KeyedReliable(sender) = &mut channel.sender else {
+            return Err(anyhow!("Channel is not a keyed ordered reliable channel"));
+        };
+        self.writer.start_write();
+        message.encode(&mut self.writer)?;
+        let message_bytes: Vec<u8> = self.writer.finish_write().into();
+        Ok(sender.buffer_send_with_key(message_bytes.into(), DEFAULT_MESSAGE_PRIORITY, key))
+    }
+
+    /// Cancel a stream that was sent on a [`ChannelMode::Streaming`](crate::channel::builder::ChannelMode::Streaming) channel.
+    ///
+    /// Returns false if the stream was already fully acked or cancelled.
+    pub(crate) fn cancel_stream(
+        &mut self,
+        channel_kind: ChannelKind,
+        stream_id: MessageId,
+    ) -> anyhow::Result<bool> {
+        let channel = self
+            .channels
+            .get_mut(&channel_kind)
+            .context("Channel not found")?;
+        let ChannelSender::Stream(sender) = &mut channel.sender else {
+            return Err(anyhow!("Channel is not a streaming channel"));
+        };
+        self.delivery_tracker.untrack(channel_kind, stream_id);
+        Ok(sender.cancel(stream_id))
+    }
+
+    /// Notify the user when the message `message_id` sent on the channel `channel_kind` is acked or lost.
+    ///
+    /// Does nothing if the channel does not watch acks.
+    pub(crate) fn track_delivery(&mut self, channel_kind: ChannelKind, message_id: MessageId) {
+        self.delivery_tracker.track(channel_kind, message_id);
+    }
+
+    /// Take the messages that were acked or lost since the last call
+    pub(crate) fn take_delivery_updates(&mut self) -> Vec<(ChannelKind, MessageDelivery)> {
+        self.delivery_tracker.take_updates()
+    }
+
+    /// Take the progress of the streams sent or received on the streaming channels
+    pub(crate) fn take_stream_updates(&mut self) -> Vec<(ChannelKind, StreamUpdate)> {
+        let mut updates = vec![];
+        for (channel_kind, channel) in self.channels.iter_mut() {
+            if let ChannelSender::Stream(sender) = &mut channel.sender {
+                updates.extend(
+                    sender
+                        .take_updates()
+                        .into_iter()
+                        .map(|u| (*channel_kind, u)),
+                );
+            }
+            if let ChannelReceiver::Stream(receiver) = &mut channel.receiver {
+                updates.extend(
+                    receiver
+                        .take_updates()
+                        .into_iter()
+                        .map(|u| (*channel_kind, u)),
+                );
+            }
+        }
+        updates
+    }
+
     /// Prepare buckets from the internal send buffers, and return the bytes to send
     // TODO: maybe pass TickManager instead of Tick? Find a more elegant way to pass extra data that might not be used?
     //  (ticks are not purely necessary without client prediction)
@@ -173,6 +282,7 @@ impl MessageManager {
             // Step 2. Get the packets to send over the network
             let payload = self.packet_manager.encode_packet(&packet)?;
             bytes.push(payload);
+            self.priority_manager.congestion.sent_packet(packet_id);
             // io.send(payload, &self.remote_addr)?;
 
             // TODO: update this to be cleaner
@@ -203,8 +313,13 @@ impl MessageManager {
         }
 
         // adjust the real amount of bytes that we sent through the limiter (to account for the actual packet size)
+        let total_bytes_sent = bytes.iter().map(|b| b.len() as u32).sum::<u32>();
+        if self.priority_manager.congestion.config.enabled {
+            self.priority_manager
+                .congestion
+                .consume(total_bytes_sent.saturating_sub(num_bytes_added_to_limiter));
+        }
         if self.priority_manager.config.enabled {
-            let total_bytes_sent = bytes.iter().map(|b| b.len() as u32).sum::<u32>();
             if let Ok(remaining_bytes_to_add) =
                 (total_bytes_sent - num_bytes_added_to_limiter).try_into()
             {
@@ -241,6 +356,7 @@ impl MessageManager {
 
         // Step 3. Update the list of messages that have been acked
         for acked_packet in acked_packets {
+            self.priority_manager.congestion.acked_packet(acked_packet);
             if let Some(message_map) = self.packet_to_message_ack_map.remove(&acked_packet) {
                 for (channel_kind, message_acks) in message_map {
                     let channel = self
@@ -253,6 +369,7 @@ impl MessageManager {
                 }
             }
         }
+        self.delivery_tracker.receive_acks();
 
         // Step 4. Put the messages from the packet in the internal buffers for each channel
         for (channel_net_id, messages) in packet.data.contents() {
@@ -19,6 +19,12 @@ Packets that are over the maximum packet size will be fragmented into multiple [
 [`FragmentedPacket`]: packet::FragmentedPacket
 */
 
+/// Estimates the bandwidth of the link to avoid congesting the network
+pub(crate) mod congestion;
+
+/// Tracks whether the messages sent by the user were delivered
+pub(crate) mod delivery;
+
 /// Manages the [`PacketHeader`](header::PacketHeader) which includes important packet information
 pub mod header;
 
@@ -2,7 +2,7 @@ use std::collections::{BTreeMap, HashMap};
 
 use bitcode::encoding::{Fixed, Gamma};
 
-use crate::connection::netcode::MAX_PACKET_SIZE;
+use crate::connection::netcode::{COMPRESSION_HEADER_BYTES, MAX_PACKET_SIZE};
 use crate::packet::header::PacketHeader;
 use crate::packet::message::{FragmentData, MessageAck, MessageContainer, SingleData};
 use crate::packet::packet_type::PacketType;
@@ -21,8 +21,9 @@ wrapping_id!(PacketId);
 /// Rest: 10 bytes
 const HEADER_BYTES: usize = 11;
 /// The maximum of bytes that the payload of the packet can contain (excluding the header)
-/// remove 1 byte for byte alignment at the end
-pub(crate) const MTU_PAYLOAD_BYTES: usize = MAX_PACKET_SIZE - HEADER_BYTES - 1;
+/// remove 1 byte for byte alignment at the end, and the bytes reserved for the compression header
+pub(crate) const MTU_PAYLOAD_BYTES: usize =
+    MAX_PACKET_SIZE - COMPRESSION_HEADER_BYTES - HEADER_BYTES - 1;
 
 /// The maximum number of bytes for a message before it is fragmented
 /// The final size of the fragmented packet (channel_net_id: 2, fragment_id: 1, tick: 2, message_id: 2, num_fragments: 1, number of bytes in fragment: 4)
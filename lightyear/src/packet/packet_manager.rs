@@ -3,7 +3,7 @@ use std::collections::{BTreeMap, VecDeque};
 use bitcode::encoding::Gamma;
 use bitcode::word_buffer::WordBuffer;
 
-use crate::connection::netcode::MAX_PACKET_SIZE;
+use crate::connection::netcode::{COMPRESSION_HEADER_BYTES, MAX_PACKET_SIZE};
 use crate::packet::header::PacketHeaderManager;
 use crate::packet::message::{FragmentData, MessageContainer, SingleData};
 use crate::packet::packet::{
@@ -70,7 +70,11 @@ impl PacketBuilder {
         // TODO: we should actually call finish write to byte align!
         // TODO: CAREFUL, THIS COULD ALLOCATE A BIT MORE TO BYTE ALIGN?
         let payload = Payload::from(write_buffer.finish_write());
-        assert!(payload.len() <= MAX_PACKET_SIZE, "packet = {:?}", packet);
+        assert!(
+            payload.len() <= MAX_PACKET_SIZE - COMPRESSION_HEADER_BYTES,
+            "packet = {:?}",
+            packet
+        );
         Ok(payload)
 
         // packet.encode(&mut self.write_buffer)?;
@@ -7,6 +7,7 @@ use nonzero_ext::*;
 use tracing::{debug, error, trace};
 
 use crate::_reexport::EntityUpdatesChannel;
+use crate::packet::congestion::{CongestionConfig, CongestionController};
 use crate::packet::message::{FragmentData, MessageContainer, MessageId, SingleData};
 use crate::prelude::{ChannelKind, ChannelRegistry, Tick};
 use crate::protocol::registry::NetId;
@@ -24,6 +25,8 @@ pub struct PriorityConfig {
     pub bandwidth_quota: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub enabled: bool,
+    /// Congestion control: the bandwidth is limited dynamically according to the loss and RTT measured on the link
+    pub congestion: CongestionConfig,
 }
 
 // this is mostly for testing
@@ -33,6 +36,7 @@ impl Default for PriorityConfig {
             // 56 KB/s bandwidth cap
             bandwidth_quota: Quota::per_second(nonzero!(56000u32)),
             enabled: false,
+            congestion: CongestionConfig::default(),
         }
     }
 }
@@ -42,6 +46,7 @@ impl From<crate::client::config::PacketConfig> for PriorityConfig {
         Self {
             bandwidth_quota: value.send_bandwidth_cap,
             enabled: value.bandwidth_cap_enabled,
+            congestion: value.congestion,
         }
     }
 }
@@ -51,6 +56,7 @@ impl From<crate::server::config::PacketConfig> for PriorityConfig {
         Self {
             bandwidth_quota: value.per_client_send_bandwidth_cap,
             enabled: value.bandwidth_cap_enabled,
+            congestion: value.congestion,
         }
     }
 }
@@ -59,6 +65,7 @@ pub(crate) struct PriorityManager {
     pub(crate) config: PriorityConfig,
     // TODO: can I do without this limiter?
     pub(crate) limiter: DefaultDirectRateLimiter,
+    pub(crate) congestion: CongestionController,
     // Messages that could not be sent because of the bandwidth quota
     // buffered_data: Vec<BufferedMessage>,
     /// List of senders to notify when a replication update message is actually sent (included in packet)
@@ -70,6 +77,7 @@ impl PriorityManager {
         Self {
             config: config.clone(),
             limiter: DefaultDirectRateLimiter::direct(config.bandwidth_quota),
+            congestion: CongestionController::new(config.congestion),
             // buffered_data: Vec::new(),
             replication_update_senders: Vec::new(),
         }
@@ -98,7 +106,7 @@ impl PriorityManager {
     ) {
         // if the bandwidth quota is disabled, just pass all messages through
         // As an optimization: no need to send the tick of the message, it is the same as the header tick
-        if !self.config.enabled {
+        if !self.config.enabled && !self.congestion.config.enabled {
             let mut data_to_send: BTreeMap<NetId, (VecDeque<SingleData>, VecDeque<FragmentData>)> =
                 BTreeMap::new();
             for (net_id, (single, fragment)) in data {
@@ -169,15 +177,26 @@ impl PriorityManager {
             // we don't use the exact size of the message, but the size of the bytes
             // we will adjust for this later
             let message_bytes = buffered_message.message_container.bytes().len() as u32;
-            let nonzero_message_bytes = NonZeroU32::try_from(message_bytes).unwrap();
-            let Ok(result) = self.limiter.check_n(nonzero_message_bytes) else {
-                error!("the bandwidth does not have enough capacity for a message of this size!");
+            if self.congestion.config.enabled && !self.congestion.can_send() {
+                debug!("Estimated bandwidth reached, no more messages can be sent this tick");
                 break;
-            };
-            let Ok(()) = result else {
-                debug!("Bandwidth quota reached, no more messages can be sent this tick");
-                break;
-            };
+            }
+            if self.config.enabled {
+                let nonzero_message_bytes = NonZeroU32::try_from(message_bytes).unwrap();
+                let Ok(result) = self.limiter.check_n(nonzero_message_bytes) else {
+                    error!(
+                        "the bandwidth does not have enough capacity for a message of this size!"
+                    );
+                    break;
+                };
+                let Ok(()) = result else {
+                    debug!("Bandwidth quota reached, no more messages can be sent this tick");
+                    break;
+                };
+            }
+            if self.congestion.config.enabled {
+                self.congestion.consume(message_bytes);
+            }
 
             // keep track of the bytes we added to the rate limiter
             bytes_used += message_bytes;
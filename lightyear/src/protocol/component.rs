@@ -14,6 +14,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::client::components::{ComponentSyncMode, LerpFn, SyncMetadata};
 use crate::prelude::{Message, PreSpawnedPlayerObject};
+use crate::protocol::dynamic::RawComponent;
+use crate::protocol::registry::NetId;
 use crate::protocol::{BitSerializable, EventContext, Protocol};
 use crate::shared::events::connection::{
     IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
@@ -39,6 +41,7 @@ pub trait ComponentProtocol:
     + From<ShouldBePredicted>
     + From<PrePredicted>
     + From<ShouldBeInterpolated>
+    + From<RawComponent>
     + TryInto<ShouldBePredicted>
     + TryInto<PrePredicted>
 {
@@ -47,12 +50,25 @@ pub trait ComponentProtocol:
     /// Map from the type-id to the component kind for each component in the protocol
     fn type_ids() -> HashMap<TypeId, <Self::Protocol as Protocol>::ComponentKinds>;
 
+    /// Name and type name of each variant of the protocol enum, in the order in which the components are registered
+    fn variants() -> Vec<(&'static str, &'static str)>;
+
     /// Apply a ComponentInsert to an entity
     fn insert(self, entity: &mut EntityWorldMut);
 
     /// Apply a ComponentUpdate to an entity
     fn update(self, entity: &mut EntityWorldMut);
 
+    /// Returns true if the component is replicated using delta-compression (i.e. it was marked with `#[protocol(delta)]`)
+    fn is_delta_compressed(&self) -> bool;
+
+    /// Compute the serialized diff between `self` (the base value) and `new`.
+    /// Returns an error if the component is not delta-compressed, or if `new` is a different component.
+    fn diff(&self, new: &Self) -> anyhow::Result<Vec<u8>>;
+
+    /// Apply a serialized diff (computed with [`ComponentProtocol::diff`]) to `self` and return the new value
+    fn apply_diff(&self, delta: &[u8]) -> anyhow::Result<Self>;
+
     /// Add systems to send component inserts/removes/updates
     fn add_per_component_replication_send_systems<R: ReplicationSend<Self::Protocol>>(
         app: &mut App,
@@ -211,6 +227,9 @@ cfg_if!(
 pub trait ComponentKindBehaviour {
     /// Remove the component for an entity
     fn remove(self, entity: &mut EntityWorldMut);
+
+    /// Kind of the component with the given [`NetId`] in the [`DynamicProtocol`](crate::protocol::dynamic::DynamicProtocol)
+    fn dynamic(net_id: NetId) -> Self;
 }
 
 // /// Trait to convert a component type into the corresponding ComponentProtocolKind
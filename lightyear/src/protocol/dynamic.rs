@@ -0,0 +1,467 @@
+//! Registration of messages and components on the [`App`], without listing them in the protocol enums.
+//!
+//! The [`MessageProtocol`](crate::protocol::message::MessageProtocol) and [`ComponentProtocol`](crate::protocol::component::ComponentProtocol)
+//! enums must contain every message and component ahead of time, which is inconvenient when the game is split
+//! into plugins that each own their networked types. These plugins can instead register their types directly on the [`App`]:
+//! ```rust,ignore
+//! app.register_component::<Health>(ChannelDirection::ServerToClient)
+//!     .add_message::<Chat>();
+//! ```
+//!
+//! The registered types are stored in the [`DynamicProtocol`] resource, which assigns each of them a [`NetId`]
+//! by sorting them by [`TypePath`], so the order in which the plugins register them does not matter. The client and the
+//! server must register the same types. The registered types are part of the [`ProtocolSchema`](crate::protocol::schema::ProtocolSchema),
+//! so a client that registered different types is disconnected by the server.
+//! The values are serialized to bytes and sent through a built-in variant of the protocol enums ([`RawMessage`] and [`RawComponent`]),
+//! so they go through the same channels and replication systems as the rest of the protocol.
+//!
+//! Dynamic messages are sent with `send_dynamic_message` on the client and server `ConnectionManager`s,
+//! and are received as regular [`MessageEvent`]s. Dynamic components are replicated like any other component
+//! of an entity with [`Replicate`](crate::prelude::Replicate), and emit the regular component events.
+//!
+//! Dynamic components do not support prediction, interpolation, delta-compression or entity mapping.
+use std::any::TypeId;
+
+use anyhow::Context;
+use bevy::ecs::component::ComponentId;
+use bevy::prelude::{App, Component, Entity, EntityRef, EntityWorldMut, Resource, World};
+use bevy::reflect::TypePath;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::channel::builder::ChannelDirection;
+use crate::packet::message::Message;
+use crate::prelude::ClientId;
+use crate::protocol::component::ComponentKindBehaviour;
+use crate::protocol::registry::NetId;
+use crate::protocol::{EventContext, Protocol};
+use crate::shared::events::components::{
+    ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, MessageEvent,
+};
+use crate::shared::events::connection::{IterComponentKindEvent, IterMessageEvent};
+
+/// Serialized value of a message that was registered in the [`DynamicProtocol`]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct RawMessage {
+    net_id: NetId,
+    bytes: Vec<u8>,
+}
+
+/// Serialized value of a component that was registered in the [`DynamicProtocol`]
+#[derive(Component, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct RawComponent {
+    net_id: NetId,
+    bytes: Vec<u8>,
+}
+
+impl RawComponent {
+    pub(crate) fn new(net_id: NetId, bytes: Vec<u8>) -> Self {
+        Self { net_id, bytes }
+    }
+
+    #[doc(hidden)]
+    pub fn net_id(&self) -> NetId {
+        self.net_id
+    }
+
+    /// Insert the deserialized component on the entity
+    #[doc(hidden)]
+    pub fn insert(self, entity: &mut EntityWorldMut) {
+        let Some(registration) = component_registration(entity, self.net_id) else {
+            return;
+        };
+        if let Err(e) = (registration.insert)(&self.bytes, entity) {
+            error!(
+                component = registration.name,
+                "could not insert component: {:?}", e
+            );
+        }
+    }
+
+    /// Update the component of the entity with the deserialized value
+    #[doc(hidden)]
+    pub fn update(self, entity: &mut EntityWorldMut) {
+        let Some(registration) = component_registration(entity, self.net_id) else {
+            return;
+        };
+        if let Err(e) = (registration.update)(&self.bytes, entity) {
+            error!(
+                component = registration.name,
+                "could not update component: {:?}", e
+            );
+        }
+    }
+
+    /// Remove the component with the given [`NetId`] from the entity
+    #[doc(hidden)]
+    pub fn remove(net_id: NetId, entity: &mut EntityWorldMut) {
+        if let Some(registration) = component_registration(entity, net_id) {
+            (registration.remove)(entity);
+        }
+    }
+}
+
+fn component_registration(entity: &EntityWorldMut, net_id: NetId) -> Option<ComponentRegistration> {
+    let registration = entity
+        .world()
+        .get_resource::<DynamicProtocol>()
+        .and_then(|protocol| protocol.components.get(net_id as usize))
+        .cloned();
+    if registration.is_none() {
+        error!(
+            ?net_id,
+            "received a component that is not registered in the DynamicProtocol"
+        );
+    }
+    registration
+}
+
+type ReceiveMessageFn<Ctx> = fn(&mut World, &[u8], Ctx) -> anyhow::Result<()>;
+type SerializeComponentFn = fn(&EntityRef) -> Option<Vec<u8>>;
+type ApplyComponentFn = fn(&[u8], &mut EntityWorldMut) -> anyhow::Result<()>;
+type RemoveComponentFn = fn(&mut EntityWorldMut);
+type ComponentEventsFn<Ctx> = fn(&mut World, DynamicComponentEvents<Ctx>);
+
+#[derive(Clone, Debug)]
+pub(crate) struct MessageRegistration {
+    type_id: TypeId,
+    name: &'static str,
+    receive_client: ReceiveMessageFn<()>,
+    receive_server: ReceiveMessageFn<ClientId>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct ComponentRegistration {
+    type_id: TypeId,
+    name: &'static str,
+    component_id: ComponentId,
+    direction: ChannelDirection,
+    serialize: SerializeComponentFn,
+    insert: ApplyComponentFn,
+    update: ApplyComponentFn,
+    remove: RemoveComponentFn,
+    events_client: ComponentEventsFn<()>,
+    events_server: ComponentEventsFn<ClientId>,
+}
+
+/// Component events received for a component of the [`DynamicProtocol`]
+pub(crate) struct DynamicComponentEvents<Ctx> {
+    inserts: Vec<(Entity, Ctx)>,
+    updates: Vec<(Entity, Ctx)>,
+    removes: Vec<(Entity, Ctx)>,
+}
+
+/// Registry of the messages and components that were registered on the [`App`]
+/// with [`AppDynamicProtocolExt`]
+#[derive(Resource, Default, Clone, Debug)]
+pub struct DynamicProtocol {
+    /// Registered messages, sorted by type path. The index of a message is its [`NetId`]
+    messages: Vec<MessageRegistration>,
+    /// Registered components, sorted by type path. The index of a component is its [`NetId`]
+    components: Vec<ComponentRegistration>,
+}
+
+impl DynamicProtocol {
+    /// Returns the [`NetId`] of the message `M`, if it is registered
+    pub fn message_net_id<M: 'static>(&self) -> Option<NetId> {
+        self.messages
+            .iter()
+            .position(|registration| registration.type_id == TypeId::of::<M>())
+            .map(|net_id| net_id as NetId)
+    }
+
+    /// Returns the [`NetId`] of the component `C`, if it is registered
+    pub fn component_net_id<C: 'static>(&self) -> Option<NetId> {
+        self.components
+            .iter()
+            .position(|registration| registration.type_id == TypeId::of::<C>())
+            .map(|net_id| net_id as NetId)
+    }
+
+    /// Serialize a registered message, so that it can be sent like any other message
+    pub(crate) fn raw_message<M: Message + Serialize>(
+        &self,
+        message: &M,
+    ) -> anyhow::Result<RawMessage> {
+        let net_id = self.message_net_id::<M>().with_context(|| {
+            format!(
+                "message {} is not registered in the DynamicProtocol",
+                std::any::type_name::<M>()
+            )
+        })?;
+        Ok(RawMessage {
+            net_id,
+            bytes: bitcode::serialize(message).context("could not serialize message")?,
+        })
+    }
+
+    /// Type paths of the registered messages, in [`NetId`] order
+    pub fn message_names(&self) -> Vec<&'static str> {
+        self.messages.iter().map(|r| r.name).collect()
+    }
+
+    /// Type paths and replication directions of the registered components, in [`NetId`] order
+    pub fn component_names(&self) -> Vec<(&'static str, ChannelDirection)> {
+        self.components
+            .iter()
+            .map(|r| (r.name, r.direction))
+            .collect()
+    }
+
+    /// Components that a peer sending in the given `direction` should replicate
+    pub(crate) fn sent_components(
+        &self,
+        direction: ChannelDirection,
+    ) -> Vec<(NetId, ComponentId, SerializeComponentFn)> {
+        self.sent(direction)
+            .map(|(net_id, registration)| {
+                (net_id, registration.component_id, registration.serialize)
+            })
+            .collect()
+    }
+
+    pub(crate) fn sent_component_ids(
+        &self,
+        direction: ChannelDirection,
+    ) -> Vec<(NetId, ComponentId)> {
+        self.sent(direction)
+            .map(|(net_id, registration)| (net_id, registration.component_id))
+            .collect()
+    }
+
+    fn sent(
+        &self,
+        direction: ChannelDirection,
+    ) -> impl Iterator<Item = (NetId, &ComponentRegistration)> {
+        self.components
+            .iter()
+            .enumerate()
+            .filter(move |(_, registration)| {
+                registration.direction == ChannelDirection::Bidirectional
+                    || registration.direction == direction
+            })
+            .map(|(net_id, registration)| (net_id as NetId, registration))
+    }
+
+    fn add_message<M: Message + TypePath + Serialize + DeserializeOwned>(&mut self) {
+        if self.message_net_id::<M>().is_some() {
+            return;
+        }
+        let index = self.messages.partition_point(|r| r.name < M::type_path());
+        self.messages.insert(
+            index,
+            MessageRegistration {
+                type_id: TypeId::of::<M>(),
+                name: M::type_path(),
+                receive_client: receive_message::<M, ()>,
+                receive_server: receive_message::<M, ClientId>,
+            },
+        );
+    }
+
+    fn add_component<C: Component + Message + TypePath + Serialize + DeserializeOwned>(
+        &mut self,
+        component_id: ComponentId,
+        direction: ChannelDirection,
+    ) {
+        if self.component_net_id::<C>().is_some() {
+            return;
+        }
+        let index = self.components.partition_point(|r| r.name < C::type_path());
+        self.components.insert(
+            index,
+            ComponentRegistration {
+                type_id: TypeId::of::<C>(),
+                name: C::type_path(),
+                component_id,
+                direction,
+                serialize: serialize_component::<C>,
+                insert: insert_component::<C>,
+                update: update_component::<C>,
+                remove: remove_component::<C>,
+                events_client: push_component_events::<C, ()>,
+                events_server: push_component_events::<C, ClientId>,
+            },
+        );
+    }
+}
+
+fn receive_message<M: Message + DeserializeOwned, Ctx: EventContext>(
+    world: &mut World,
+    bytes: &[u8],
+    context: Ctx,
+) -> anyhow::Result<()> {
+    let message: M = bitcode::deserialize(bytes).context("could not deserialize message")?;
+    world.send_event(MessageEvent::new(message, context));
+    Ok(())
+}
+
+fn serialize_component<C: Component + TypePath + Serialize>(entity: &EntityRef) -> Option<Vec<u8>> {
+    bitcode::serialize(entity.get::<C>()?)
+        .map_err(|e| {
+            error!(
+                component = C::type_path(),
+                "could not serialize component: {:?}", e
+            );
+        })
+        .ok()
+}
+
+fn insert_component<C: Component + DeserializeOwned>(
+    bytes: &[u8],
+    entity: &mut EntityWorldMut,
+) -> anyhow::Result<()> {
+    let component: C = bitcode::deserialize(bytes).context("could not deserialize component")?;
+    entity.insert(component);
+    Ok(())
+}
+
+fn update_component<C: Component + DeserializeOwned>(
+    bytes: &[u8],
+    entity: &mut EntityWorldMut,
+) -> anyhow::Result<()> {
+    let component: C = bitcode::deserialize(bytes).context("could not deserialize component")?;
+    if let Some(mut c) = entity.get_mut::<C>() {
+        *c = component;
+    } else {
+        entity.insert(component);
+    }
+    Ok(())
+}
+
+fn remove_component<C: Component>(entity: &mut EntityWorldMut) {
+    entity.remove::<C>();
+}
+
+fn push_component_events<C: Component, Ctx: EventContext>(
+    world: &mut World,
+    events: DynamicComponentEvents<Ctx>,
+) {
+    world.send_event_batch(
+        events
+            .inserts
+            .into_iter()
+            .map(|(entity, ctx)| ComponentInsertEvent::<C, Ctx>::new(entity, ctx)),
+    );
+    world.send_event_batch(
+        events
+            .updates
+            .into_iter()
+            .map(|(entity, ctx)| ComponentUpdateEvent::<C, Ctx>::new(entity, ctx)),
+    );
+    world.send_event_batch(
+        events
+            .removes
+            .into_iter()
+            .map(|(entity, ctx)| ComponentRemoveEvent::<C, Ctx>::new(entity, ctx)),
+    );
+}
+
+/// Context of the events emitted for the types of the [`DynamicProtocol`]
+/// (`()` on the client, the [`ClientId`] of the sender on the server)
+pub(crate) trait DynamicEventContext: EventContext + Sized {
+    fn receive_message_fn(registration: &MessageRegistration) -> ReceiveMessageFn<Self>;
+
+    fn component_events_fn(registration: &ComponentRegistration) -> ComponentEventsFn<Self>;
+}
+
+impl DynamicEventContext for () {
+    fn receive_message_fn(registration: &MessageRegistration) -> ReceiveMessageFn<Self> {
+        registration.receive_client
+    }
+
+    fn component_events_fn(registration: &ComponentRegistration) -> ComponentEventsFn<Self> {
+        registration.events_client
+    }
+}
+
+impl DynamicEventContext for ClientId {
+    fn receive_message_fn(registration: &MessageRegistration) -> ReceiveMessageFn<Self> {
+        registration.receive_server
+    }
+
+    fn component_events_fn(registration: &ComponentRegistration) -> ComponentEventsFn<Self> {
+        registration.events_server
+    }
+}
+
+/// Write the received messages and component events of the [`DynamicProtocol`] as bevy events
+pub(crate) fn push_dynamic_events<
+    P: Protocol,
+    E: IterMessageEvent<P, Ctx> + IterComponentKindEvent<P, Ctx>,
+    Ctx: DynamicEventContext,
+>(
+    world: &mut World,
+    events: &mut E,
+) {
+    if !world.contains_resource::<DynamicProtocol>() {
+        return;
+    }
+    world.resource_scope(|world, protocol: bevy::prelude::Mut<DynamicProtocol>| {
+        if events.has_messages::<RawMessage>() {
+            for (message, ctx) in events.into_iter_messages::<RawMessage>() {
+                let Some(registration) = protocol.messages.get(message.net_id as usize) else {
+                    error!(net_id = ?message.net_id, "received a message that is not registered in the DynamicProtocol");
+                    continue;
+                };
+                if let Err(e) = Ctx::receive_message_fn(registration)(world, &message.bytes, ctx) {
+                    error!(message = registration.name, "could not receive message: {:?}", e);
+                }
+            }
+        }
+        for (net_id, registration) in protocol.components.iter().enumerate() {
+            let kind = P::ComponentKinds::dynamic(net_id as NetId);
+            let component_events = DynamicComponentEvents {
+                inserts: events.iter_component_kind_insert(kind).collect(),
+                updates: events.iter_component_kind_update(kind).collect(),
+                removes: events.iter_component_kind_remove(kind).collect(),
+            };
+            Ctx::component_events_fn(registration)(world, component_events);
+        }
+    });
+}
+
+/// Extension trait to register messages and components in the [`DynamicProtocol`]
+pub trait AppDynamicProtocolExt {
+    /// Register a component that can be replicated without being part of the [`ComponentProtocol`](crate::protocol::component::ComponentProtocol).
+    ///
+    /// `direction` is the direction in which the component is replicated. The component must be
+    /// registered on both the client and the server.
+    fn register_component<C: Component + Message + TypePath + Serialize + DeserializeOwned>(
+        &mut self,
+        direction: ChannelDirection,
+    ) -> &mut Self;
+
+    /// Register a message that can be sent without being part of the [`MessageProtocol`](crate::protocol::message::MessageProtocol).
+    ///
+    /// The message must be registered on both the client and the server.
+    fn add_message<M: Message + TypePath + Serialize + DeserializeOwned>(&mut self) -> &mut Self;
+}
+
+impl AppDynamicProtocolExt for App {
+    fn register_component<C: Component + Message + TypePath + Serialize + DeserializeOwned>(
+        &mut self,
+        direction: ChannelDirection,
+    ) -> &mut Self {
+        let component_id = self.world.init_component::<C>();
+        self.world
+            .get_resource_or_insert_with(DynamicProtocol::default)
+            .add_component::<C>(component_id, direction);
+        // the same app can be both a client and a server (in HostServer mode)
+        self.add_event::<ComponentInsertEvent<C, ()>>()
+            .add_event::<ComponentUpdateEvent<C, ()>>()
+            .add_event::<ComponentRemoveEvent<C, ()>>()
+            .add_event::<ComponentInsertEvent<C, ClientId>>()
+            .add_event::<ComponentUpdateEvent<C, ClientId>>()
+            .add_event::<ComponentRemoveEvent<C, ClientId>>()
+    }
+
+    fn add_message<M: Message + TypePath + Serialize + DeserializeOwned>(&mut self) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(DynamicProtocol::default)
+            .add_message::<M>();
+        self.add_event::<MessageEvent<M, ()>>()
+            .add_event::<MessageEvent<M, ClientId>>()
+    }
+}
@@ -8,6 +8,7 @@ use serde::Serialize;
 
 use crate::inputs::native::input_buffer::InputMessage;
 use crate::packet::message::Message;
+use crate::protocol::dynamic::RawMessage;
 use crate::protocol::registry::TypeKind;
 use crate::protocol::{BitSerializable, EventContext, Protocol};
 #[cfg(feature = "leafwing")]
@@ -40,12 +41,17 @@ pub trait MessageProtocol:
     + Sync
     + From<InputMessage<<<Self as MessageProtocol>::Protocol as Protocol>::Input>>
     + TryInto<InputMessage<<<Self as MessageProtocol>::Protocol as Protocol>::Input>, Error = ()>
+    + From<RawMessage>
+    + TryInto<RawMessage, Error = ()>
 {
     type Protocol: Protocol;
 
     /// Get the name of the Message
     fn name(&self) -> &'static str;
 
+    /// Name and type name of each variant of the protocol enum, in the order in which the messages are registered
+    fn variants() -> Vec<(&'static str, &'static str)>;
+
     /// Returns the MessageKind of the Message
     fn kind(&self) -> MessageKind;
 
@@ -19,6 +19,7 @@ use crate::channel::builder::{Channel, ChannelSettings};
 use crate::protocol::channel::ChannelRegistry;
 use crate::protocol::component::{ComponentProtocol, ComponentProtocolKind};
 use crate::protocol::message::MessageProtocol;
+use crate::protocol::schema::ProtocolSchema;
 use crate::serialize::reader::ReadBuffer;
 use crate::serialize::writer::WriteBuffer;
 use crate::shared::replication::ReplicationSend;
@@ -32,9 +33,15 @@ pub(crate) mod component;
 /// Defines the various messages that can be sent over the network
 pub(crate) mod message;
 
+/// Messages and components that are registered on the App instead of in the protocol enums
+pub mod dynamic;
+
 /// Provides a mapping from a type to a unique identifier that can be serialized
 pub(crate) mod registry;
 
+/// Schema of the protocol, used to check that the client and server use the same protocol
+pub mod schema;
+
 // TODO: how to make components or messages or inputs optional? Just by having an implementation for () ?
 /// The [`Protocol`] trait defines the various channels, inputs, messages and components that will be used to transmit information between
 /// the client and server.
@@ -98,6 +105,11 @@ pub trait Protocol: Send + Sync + Clone + Debug + Resource + TypePath + 'static
 
     fn add_channel<C: Channel>(&mut self, settings: ChannelSettings) -> &mut Self;
     fn channel_registry(&self) -> &ChannelRegistry;
+
+    /// Returns the [`ProtocolSchema`] of the protocol
+    fn schema(&self) -> ProtocolSchema {
+        ProtocolSchema::new(self)
+    }
 }
 
 /// This macro is used to build the [`Protocol`] struct.
@@ -162,6 +174,16 @@ macro_rules! protocolize {
                         // we want to send the entity actions as soon as possible
                         priority: 10.0,
                     });
+                    protocol.add_channel::<AuthorityChannel>(ChannelSettings {
+                        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::ServerToClient,
+                        priority: 10.0,
+                    });
+                    protocol.add_channel::<PlayersChannel>(ChannelSettings {
+                        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::ServerToClient,
+                        priority: 10.0,
+                    });
                     protocol.add_channel::<EntityUpdatesChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliableWithAcks,
                         direction: ChannelDirection::Bidirectional,
@@ -175,7 +197,8 @@ macro_rules! protocolize {
                     });
                     protocol.add_channel::<InputChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
-                        direction: ChannelDirection::ClientToServer,
+                        // the server can rebroadcast the inputs of a client to the other clients
+                        direction: ChannelDirection::Bidirectional,
                         priority: 3.0,
                     });
                     protocol.add_channel::<DefaultUnorderedUnreliableChannel>(ChannelSettings {
@@ -297,6 +320,16 @@ macro_rules! protocolize {
                         // we want to send the entity actions as soon as possible
                         priority: 10.0,
                     });
+                    protocol.add_channel::<AuthorityChannel>(ChannelSettings {
+                        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::ServerToClient,
+                        priority: 10.0,
+                    });
+                    protocol.add_channel::<PlayersChannel>(ChannelSettings {
+                        mode: ChannelMode::OrderedReliable(ReliableSettings::default()),
+                        direction: ChannelDirection::ServerToClient,
+                        priority: 10.0,
+                    });
                     protocol.add_channel::<EntityUpdatesChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliableWithAcks,
                         direction: ChannelDirection::Bidirectional,
@@ -310,7 +343,8 @@ macro_rules! protocolize {
                     });
                     protocol.add_channel::<InputChannel>(ChannelSettings {
                         mode: ChannelMode::UnorderedUnreliable,
-                        direction: ChannelDirection::ClientToServer,
+                        // the server can rebroadcast the inputs of a client to the other clients
+                        direction: ChannelDirection::Bidirectional,
                         priority: 3.0,
                     });
                     protocol.add_channel::<DefaultUnorderedUnreliableChannel>(ChannelSettings {
@@ -0,0 +1,175 @@
+//! Schema of a [`Protocol`], used to check that the client and the server were built with the same protocol.
+//!
+//! Channels, messages and components are identified on the network by their position in the protocol, so a client
+//! and a server with a different protocol (for example two `ComponentProtocol` enums with a different variant order)
+//! would silently decode garbage.
+//!
+//! The [`ProtocolSchema`] lists the channels, messages and components of the protocol in the order in which they are
+//! registered, followed by the types registered in the [`DynamicProtocol`]. Each variant of the protocol enums is
+//! described by its name and by the [`type_name`](std::any::type_name) of its inner type, so that two builds where a
+//! variant holds a different type (for example `Position(Vec2)` and `Position(Vec3)`) have a different schema.
+//! Type names are not guaranteed to be the same with different compiler versions, so the client and the server should
+//! be built with the same compiler.
+//!
+//! The client sends the [`fingerprint`](ProtocolSchema::fingerprint) of its schema in its handshake, and the server
+//! disconnects the clients that use a different protocol with [`DeniedReason::ProtocolMismatch`](crate::connection::server::DeniedReason::ProtocolMismatch).
+//!
+//! The schema can also be written to a file, so that the protocols of two builds can be diffed:
+//! ```rust,ignore
+//! MyProtocol::default().schema().write_to_file("protocol.schema")?;
+//! ```
+use std::fmt::{Display, Formatter};
+use std::hash::Hasher;
+use std::path::Path;
+
+use crate::channel::builder::{ChannelMode, ChannelSettings};
+use crate::protocol::component::ComponentProtocol;
+use crate::protocol::dynamic::DynamicProtocol;
+use crate::protocol::message::MessageProtocol;
+use crate::protocol::registry::NetId;
+use crate::protocol::Protocol;
+
+/// Ordered description of the channels, messages and components of a [`Protocol`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolSchema {
+    /// Name, mode and direction of each channel
+    pub channels: Vec<String>,
+    /// Name and inner type of each message
+    pub messages: Vec<String>,
+    /// Name and inner type of each component
+    pub components: Vec<String>,
+    /// Type path of each message of the [`DynamicProtocol`]
+    pub dynamic_messages: Vec<String>,
+    /// Type path and direction of each component of the [`DynamicProtocol`]
+    pub dynamic_components: Vec<String>,
+}
+
+impl ProtocolSchema {
+    pub fn new<P: Protocol>(protocol: &P) -> Self {
+        let registry = protocol.channel_registry();
+        let channels = (0..registry.kind_map.next_net_id)
+            .filter_map(|net_id: NetId| {
+                let kind = registry.get_kind_from_net_id(net_id)?;
+                let settings = &registry.get_builder_from_kind(kind)?.settings;
+                Some(format!(
+                    "{} {} {:?}",
+                    registry.name(kind).unwrap_or_default(),
+                    mode_name(settings),
+                    settings.direction
+                ))
+            })
+            .collect();
+        Self {
+            channels,
+            messages: variant_entries(P::Message::variants()),
+            components: variant_entries(P::Components::variants()),
+            dynamic_messages: vec![],
+            dynamic_components: vec![],
+        }
+    }
+
+    /// Add the types registered in the [`DynamicProtocol`] to the schema
+    pub fn with_dynamic_protocol(mut self, dynamic_protocol: &DynamicProtocol) -> Self {
+        self.dynamic_messages = dynamic_protocol
+            .message_names()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        self.dynamic_components = dynamic_protocol
+            .component_names()
+            .into_iter()
+            .map(|(name, direction)| format!("{name} {direction:?}"))
+            .collect();
+        self
+    }
+
+    /// Hash of the schema. Two protocols with the same fingerprint are compatible.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = seahash::SeaHasher::new();
+        hasher.write(self.to_string().as_bytes());
+        hasher.finish()
+    }
+
+    /// Write the schema to a file, so that it can be diffed with the schema of another build
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+fn variant_entries(variants: Vec<(&'static str, &'static str)>) -> Vec<String> {
+    variants
+        .into_iter()
+        .map(|(name, type_name)| format!("{name}({type_name})"))
+        .collect()
+}
+
+/// Only the kind of channel is part of the schema: the reliability settings are local to each peer
+fn mode_name(settings: &ChannelSettings) -> &'static str {
+    match settings.mode {
+        ChannelMode::UnorderedUnreliableWithAcks => "UnorderedUnreliableWithAcks",
+        ChannelMode::UnorderedUnreliable => "UnorderedUnreliable",
+        ChannelMode::SequencedUnreliable => "SequencedUnreliable",
+        ChannelMode::UnorderedReliable(_) => "UnorderedReliable",
+        ChannelMode::SequencedReliable(_) => "SequencedReliable",
+        ChannelMode::OrderedReliable(_) => "OrderedReliable",
+        ChannelMode::KeyedOrderedReliable(_) => "KeyedOrderedReliable",
+        ChannelMode::TickBuffered => "TickBuffered",
+        ChannelMode::Streaming(_) => "Streaming",
+    }
+}
+
+impl Display for ProtocolSchema {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (section, entries) in [
+            ("channels", &self.channels),
+            ("messages", &self.messages),
+            ("components", &self.components),
+            ("dynamic messages", &self.dynamic_messages),
+            ("dynamic components", &self.dynamic_components),
+        ] {
+            writeln!(f, "[{section}]")?;
+            for (net_id, entry) in entries.iter().enumerate() {
+                writeln!(f, "{net_id} {entry}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::Protocol;
+    use crate::tests::protocol::*;
+
+    use super::*;
+
+    #[test]
+    fn test_schema() {
+        let schema = protocol().schema();
+        assert!(schema
+            .components
+            .iter()
+            .any(|c| c.starts_with("Component1(") && c.ends_with("::Component1)")));
+        assert!(schema
+            .messages
+            .iter()
+            .any(|m| m.starts_with("Message1(") && m.ends_with("::Message1)")));
+        let dump = schema.to_string();
+        assert!(dump.starts_with("[channels]\n0 "));
+        assert_eq!(schema.fingerprint(), protocol().schema().fingerprint());
+
+        // the order of the components is part of the schema
+        let mut other = schema.clone();
+        other.components.swap(0, 1);
+        assert_ne!(schema.fingerprint(), other.fingerprint());
+
+        // the inner types of the variants are part of the schema
+        assert!(MyMessageProtocol::variants()
+            .contains(&("Message1", std::any::type_name::<Message1>())));
+        let mut variants = MyMessageProtocol::variants();
+        variants[0].1 = std::any::type_name::<Message2>();
+        let mut other = schema.clone();
+        other.messages = variant_entries(variants);
+        assert_ne!(schema.fingerprint(), other.fingerprint());
+    }
+}
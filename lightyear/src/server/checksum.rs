@@ -0,0 +1,199 @@
+//! # Checksums
+//!
+//! For deterministic (or mostly-deterministic) games, it can be useful to detect when the state of a client
+//! diverged from the state of the server.
+//!
+//! The [`ChecksumPlugin`] makes the server periodically send to each client a checksum of the component `C`
+//! for every entity that is replicated to that client. The client compares the checksums with its own state for
+//! that tick (see [`client::checksum`](crate::client::checksum)) and emits a
+//! [`DesyncEvent::Entity`](crate::shared::checksum::DesyncEvent::Entity) if they differ.
+//!
+//! The plugin must be added on both the server and the client for each component that should be checked:
+//! ```rust,ignore
+//! server_app.add_plugins(server::ChecksumPlugin::<MyProtocol, Position>::default());
+//! client_app.add_plugins(client::ChecksumPlugin::<MyProtocol, Position>::default());
+//! ```
+use std::marker::PhantomData;
+
+use bevy::prelude::{
+    App, Entity, IntoSystemConfigs, IntoSystemSetConfigs, Plugin, PostUpdate, Query, Res, ResMut,
+    Resource, SystemSet,
+};
+use tracing::{error, trace};
+
+use crate::_reexport::{FromType, ServerMarker, WriteWordBuffer};
+use crate::channel::builder::DefaultUnorderedUnreliableChannel;
+use crate::client::components::SyncComponent;
+use crate::prelude::{ChannelKind, NetworkTarget};
+use crate::protocol::Protocol;
+use crate::serialize::writer::WriteBuffer;
+use crate::server::connection::ConnectionManager;
+use crate::server::message::ServerMessage;
+use crate::shared::checksum::{component_checksum, ChecksumMessage, MAX_CHECKSUMS_PER_MESSAGE};
+use crate::shared::replication::components::Replicate;
+use crate::shared::sets::InternalMainSet;
+use crate::shared::tick_manager::{Tick, TickManager};
+
+/// Configuration of the checksums sent by the server
+#[derive(Resource, Clone, Debug)]
+pub struct ChecksumConfig {
+    /// Number of ticks between two checksums
+    pub interval: u16,
+}
+
+impl Default for ChecksumConfig {
+    fn default() -> Self {
+        Self { interval: 32 }
+    }
+}
+
+/// Checksums computed for the current tick, before they get sent to each client
+#[derive(Resource)]
+struct PendingChecksums<P: Protocol> {
+    last_tick: Option<Tick>,
+    /// Whether we should send checksums during this frame
+    ready: bool,
+    checksums: Vec<(Entity, P::ComponentKinds, u64, NetworkTarget)>,
+}
+
+impl<P: Protocol> Default for PendingChecksums<P> {
+    fn default() -> Self {
+        Self {
+            last_tick: None,
+            ready: false,
+            checksums: Vec::new(),
+        }
+    }
+}
+
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+enum ChecksumSet {
+    Prepare,
+    Compute,
+    Send,
+}
+
+/// Plugin that periodically sends the checksums of the component `C` to the clients
+pub struct ChecksumPlugin<P: Protocol, C> {
+    _marker: PhantomData<(P, C)>,
+}
+
+impl<P: Protocol, C> Default for ChecksumPlugin<P, C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol, C: SyncComponent> Plugin for ChecksumPlugin<P, C>
+where
+    P::ComponentKinds: FromType<C>,
+{
+    fn build(&self, app: &mut App) {
+        // the systems shared by every component only need to be added once
+        if !app.world.contains_resource::<PendingChecksums<P>>() {
+            // RESOURCES
+            app.init_resource::<ChecksumConfig>();
+            app.init_resource::<PendingChecksums<P>>();
+            // SETS
+            app.configure_sets(
+                PostUpdate,
+                (
+                    ChecksumSet::Prepare,
+                    ChecksumSet::Compute,
+                    ChecksumSet::Send,
+                )
+                    .chain()
+                    .in_set(InternalMainSet::<ServerMarker>::Send)
+                    .before(InternalMainSet::<ServerMarker>::SendPackets),
+            );
+            // SYSTEMS
+            app.add_systems(
+                PostUpdate,
+                (
+                    prepare_checksums::<P>.in_set(ChecksumSet::Prepare),
+                    send_checksums::<P>.in_set(ChecksumSet::Send),
+                ),
+            );
+        }
+        app.add_systems(
+            PostUpdate,
+            compute_checksums::<P, C>.in_set(ChecksumSet::Compute),
+        );
+    }
+}
+
+/// Check if enough ticks have passed since the last checksums were sent
+fn prepare_checksums<P: Protocol>(
+    mut pending: ResMut<PendingChecksums<P>>,
+    config: Res<ChecksumConfig>,
+    tick_manager: Res<TickManager>,
+) {
+    let tick = tick_manager.tick();
+    pending.ready = pending
+        .last_tick
+        .map_or(true, |last_tick| tick - last_tick >= config.interval as i16);
+    if pending.ready {
+        pending.last_tick = Some(tick);
+    }
+}
+
+/// Compute the checksum of the component `C` for every replicated entity
+fn compute_checksums<P: Protocol, C: SyncComponent>(
+    mut pending: ResMut<PendingChecksums<P>>,
+    query: Query<(Entity, &C, &Replicate<P>)>,
+) where
+    P::ComponentKinds: FromType<C>,
+{
+    if !pending.ready {
+        return;
+    }
+    let kind = <P::ComponentKinds as FromType<C>>::from_type();
+    let mut writer = WriteWordBuffer::with_capacity(64);
+    for (entity, component, replicate) in query.iter() {
+        // the client doesn't receive updates for these components, so its state could differ legitimately
+        if replicate.is_disabled::<C>() || replicate.is_replicate_once::<C>() {
+            continue;
+        }
+        let checksum = component_checksum(&kind, component, &mut writer);
+        let target = replicate.target::<C>(replicate.replication_target.clone());
+        pending.checksums.push((entity, kind, checksum, target));
+    }
+}
+
+/// Send to each client the checksums of the components that are replicated to it.
+///
+/// The checksums are split into several messages of at most [`MAX_CHECKSUMS_PER_MESSAGE`] checksums,
+/// so that each message fits in a single packet and losing a packet only loses the checksums that it contains.
+fn send_checksums<P: Protocol>(
+    mut pending: ResMut<PendingChecksums<P>>,
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    tick_manager: Res<TickManager>,
+) {
+    if !pending.ready {
+        return;
+    }
+    let tick = tick_manager.tick();
+    let checksums = std::mem::take(&mut pending.checksums);
+    for (client_id, connection) in connection_manager.connections.iter_mut() {
+        let client_checksums: Vec<_> = checksums
+            .iter()
+            .filter(|(_, _, _, target)| target.should_send_to(client_id))
+            .map(|(entity, kind, checksum, _)| (*entity, *kind, *checksum))
+            .collect();
+        trace!(?client_id, ?tick, num_checksums = ?client_checksums.len(), "Sending checksums");
+        for chunk in client_checksums.chunks(MAX_CHECKSUMS_PER_MESSAGE) {
+            let message = ChecksumMessage {
+                tick,
+                checksums: chunk.to_vec(),
+            };
+            if let Err(e) = connection.message_manager.buffer_send(
+                ServerMessage::<P>::Checksum(message),
+                ChannelKind::of::<DefaultUnorderedUnreliableChannel>(),
+            ) {
+                error!("Error sending checksums: {:?}", e);
+            }
+        }
+    }
+}
@@ -1,12 +1,16 @@
 //! Defines server-specific configuration options
+use std::sync::Arc;
+
 use bevy::prelude::Resource;
 use governor::Quota;
 use nonzero_ext::nonzero;
 
 use crate::connection::netcode::Key;
-use crate::connection::server::NetConfig;
+use crate::connection::server::{ConnectionRequestHandler, NetConfig};
+use crate::packet::congestion::CongestionConfig;
+use crate::server::input::InputConfig;
 use crate::server::replication::ReplicationConfig;
-use crate::shared::config::SharedConfig;
+use crate::shared::config::{ResumptionConfig, SharedConfig};
 use crate::shared::ping::manager::PingConfig;
 
 #[derive(Clone, Debug)]
@@ -19,6 +23,9 @@ pub struct NetcodeConfig {
     pub client_timeout_secs: i32,
     pub protocol_id: u64,
     pub private_key: Option<Key>,
+    /// Optional hook to accept or reject incoming connection requests.
+    /// If `None`, every client with a valid connect token is accepted.
+    pub connection_request_handler: Option<Arc<dyn ConnectionRequestHandler>>,
 }
 
 impl Default for NetcodeConfig {
@@ -29,6 +36,7 @@ impl Default for NetcodeConfig {
             client_timeout_secs: 3,
             protocol_id: 0,
             private_key: None,
+            connection_request_handler: None,
         }
     }
 }
@@ -47,6 +55,14 @@ impl NetcodeConfig {
         self.client_timeout_secs = client_timeout_secs;
         self
     }
+
+    pub fn with_connection_request_handler(
+        mut self,
+        handler: Arc<dyn ConnectionRequestHandler>,
+    ) -> Self {
+        self.connection_request_handler = Some(handler);
+        self
+    }
 }
 
 /// Configuration related to sending packets
@@ -56,6 +72,9 @@ pub struct PacketConfig {
     pub per_client_send_bandwidth_cap: Quota,
     /// If false, there is no bandwidth cap and all messages are sent as soon as possible
     pub bandwidth_cap_enabled: bool,
+    /// Congestion control: estimate the available bandwidth from the measured packet loss and RTT,
+    /// and limit the amount of bytes sent accordingly
+    pub congestion: CongestionConfig,
 }
 
 impl Default for PacketConfig {
@@ -64,6 +83,7 @@ impl Default for PacketConfig {
             // 56 KB/s bandwidth cap
             per_client_send_bandwidth_cap: Quota::per_second(nonzero!(56000u32)),
             bandwidth_cap_enabled: false,
+            congestion: CongestionConfig::default(),
         }
     }
 }
@@ -84,6 +104,11 @@ impl PacketConfig {
         self.bandwidth_cap_enabled = true;
         self
     }
+
+    pub fn with_congestion_control(mut self, congestion: CongestionConfig) -> Self {
+        self.congestion = congestion;
+        self
+    }
 }
 
 /// Configuration for the server plugin
@@ -96,4 +121,6 @@ pub struct ServerConfig {
     pub packet: PacketConfig,
     pub ping: PingConfig,
     pub replication: ReplicationConfig,
+    pub resumption: ResumptionConfig,
+    pub input: InputConfig,
 }
@@ -3,19 +3,24 @@ use anyhow::{Context, Result};
 use bevy::ecs::component::Tick as BevyTick;
 use bevy::ecs::entity::{EntityHash, MapEntities};
 use bevy::prelude::{Entity, Resource, World};
-use bevy::utils::{HashMap, HashSet};
+use bevy::utils::{Duration, HashMap, HashSet};
 use hashbrown::hash_map::Entry;
 use serde::Serialize;
-use tracing::{debug, info, trace, trace_span, warn};
+use tracing::{debug, error, info, trace, trace_span, warn};
 
 use crate::_reexport::{
-    EntityUpdatesChannel, FromType, InputMessageKind, MessageProtocol, PingChannel,
-    ReplicationSend, ServerMarker, ShouldBeInterpolated,
+    BitSerializable, EntityUpdatesChannel, FromType, InputMessageKind, MessageProtocol,
+    PingChannel, ReplicationSend, ServerMarker, ShouldBeInterpolated,
+};
+use crate::channel::builder::{
+    AuthorityChannel, DefaultUnorderedUnreliableChannel, InputChannel, PlayersChannel,
 };
 use crate::channel::senders::ChannelSend;
-use crate::client::message::ClientMessage;
+use crate::client::message::{ClientMessage, HandshakeMessage};
 use crate::connection::id::ClientId;
-use crate::inputs::native::input_buffer::InputBuffer;
+use crate::connection::server::DeniedReason;
+use crate::inputs::native::input_buffer::{InputBuffer, InputMessage};
+use crate::packet::message::{MessageContainer, MessageId};
 use crate::packet::message_manager::MessageManager;
 use crate::packet::packet::Packet;
 use crate::packet::packet_manager::Payload;
@@ -23,25 +28,47 @@ use crate::prelude::{
     Channel, ChannelKind, Message, Mode, PreSpawnedPlayerObject, ShouldBePredicted,
 };
 use crate::protocol::channel::ChannelRegistry;
+use crate::protocol::dynamic::{DynamicProtocol, RawMessage};
+use crate::protocol::schema::ProtocolSchema;
 use crate::protocol::Protocol;
 use crate::serialize::reader::ReadBuffer;
+use crate::serialize::wordbuffer::reader::ReadWordBuffer;
 use crate::server::config::PacketConfig;
 use crate::server::events::ServerEvents;
+use crate::server::input::{ChecksumTracker, InputConfig, InputValidator, InputViolationEvent};
 use crate::server::message::ServerMessage;
+use crate::shared::config::{ResumptionConfig, ResumptionSecret};
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::ping::manager::{PingConfig, PingManager};
 use crate::shared::ping::message::SyncMessage;
+use crate::shared::replication::authority::{filter_unauthorized, Authority, AuthorityMessage};
 use crate::shared::replication::components::{NetworkTarget, Replicate, ReplicationGroupId};
 use crate::shared::replication::receive::ReplicationReceiver;
 use crate::shared::replication::send::ReplicationSender;
 use crate::shared::replication::ReplicationMessage;
 use crate::shared::replication::ReplicationMessageData;
+use crate::shared::rpc::{Request, RequestId, RequestMessage, ResponseMessage, RpcManager};
 use crate::shared::tick_manager::Tick;
 use crate::shared::tick_manager::TickManager;
 use crate::shared::time_manager::TimeManager;
 
 type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
 
+/// Maximum number of messages of a client that we buffer while waiting for its handshake
+const MAX_PENDING_MESSAGES: usize = 1024;
+
+/// Hook called for every message that a client asks the server to relay to other clients
+/// (via [`ClientConnectionManager::send_message_to_target`](crate::client::connection::ConnectionManager::send_message_to_target)).
+///
+/// The hook receives the id of the sender, the message and the requested [`NetworkTarget`]. It returns the [`NetworkTarget`]
+/// that the message should be relayed to, or `None` to drop the message.
+/// The server still receives the message as a `MessageEvent` even if it is not relayed.
+pub type RelayFilter<P> = Box<
+    dyn Fn(ClientId, &<P as Protocol>::Message, NetworkTarget) -> Option<NetworkTarget>
+        + Send
+        + Sync,
+>;
+
 #[derive(Resource)]
 pub struct ConnectionManager<P: Protocol> {
     pub(crate) connections: HashMap<ClientId, Connection<P>>,
@@ -57,8 +84,35 @@ pub struct ConnectionManager<P: Protocol> {
     // (we want to keep track of them because we need to replicate the entire world state to them)
     pub(crate) new_clients: Vec<ClientId>,
 
+    // clients that got disconnected but whose session can still be resumed,
+    // with the remaining duration of their grace period
+    pub(crate) suspended_clients: HashMap<ClientId, Duration>,
+    // suspended clients that reconnected, and whose handshake we are waiting for to know if they can
+    // resume their session
+    pub(crate) awaiting_handshake: HashSet<ClientId>,
+
+    relay_filter: Option<RelayFilter<P>>,
+
     packet_config: PacketConfig,
     ping_config: PingConfig,
+    pub(crate) resumption_config: ResumptionConfig,
+    pub(crate) input_config: InputConfig,
+    /// Checksums of the deterministic simulation reported by the clients
+    pub(crate) checksums: ChecksumTracker,
+    /// Native inputs that were rejected by the [`InputValidator`] since the last frame
+    pub(crate) input_violations: Vec<InputViolationEvent>,
+    /// Entities over which a client has authority
+    authority: EntityHashMap<Entity, Authority<P::ComponentKinds>>,
+    /// Requests that were still waiting for a response when their client disconnected.
+    /// The [`RpcPlugin`](crate::server::rpc::RpcPlugin) of each request type resolves them in the same frame;
+    /// the requests of types without an `RpcPlugin` are dropped at the end of the frame.
+    pub(crate) disconnected_requests: HashMap<ClientId, RpcManager>,
+    /// Schema of the protocol, without the types of the [`DynamicProtocol`]
+    protocol_schema: ProtocolSchema,
+    /// Fingerprint of the server's protocol, that the clients must present in their handshake
+    pub(crate) protocol_fingerprint: u64,
+    /// Messages and components registered on the [`App`](bevy::prelude::App)
+    dynamic_protocol: DynamicProtocol,
 }
 
 impl<P: Protocol> ConnectionManager<P> {
@@ -66,6 +120,9 @@ impl<P: Protocol> ConnectionManager<P> {
         channel_registry: ChannelRegistry,
         packet_config: PacketConfig,
         ping_config: PingConfig,
+        resumption_config: ResumptionConfig,
+        input_config: InputConfig,
+        protocol_schema: ProtocolSchema,
     ) -> Self {
         Self {
             connections: HashMap::default(),
@@ -73,11 +130,33 @@ impl<P: Protocol> ConnectionManager<P> {
             events: ServerEvents::new(),
             replicate_component_cache: EntityHashMap::default(),
             new_clients: vec![],
+            suspended_clients: HashMap::default(),
+            awaiting_handshake: HashSet::default(),
+            relay_filter: None,
             packet_config,
             ping_config,
+            resumption_config,
+            input_config,
+            checksums: ChecksumTracker::default(),
+            input_violations: vec![],
+            authority: EntityHashMap::default(),
+            disconnected_requests: HashMap::default(),
+            protocol_fingerprint: protocol_schema.fingerprint(),
+            protocol_schema,
+            dynamic_protocol: DynamicProtocol::default(),
         }
     }
 
+    /// Use the types registered in the [`DynamicProtocol`] (they are part of the protocol fingerprint)
+    pub(crate) fn set_dynamic_protocol(&mut self, dynamic_protocol: &DynamicProtocol) {
+        self.protocol_fingerprint = self
+            .protocol_schema
+            .clone()
+            .with_dynamic_protocol(dynamic_protocol)
+            .fingerprint();
+        self.dynamic_protocol = dynamic_protocol.clone();
+    }
+
     /// Find the list of clients that should receive the replication message
     pub(crate) fn apply_replication(
         &mut self,
@@ -136,8 +215,23 @@ impl<P: Protocol> ConnectionManager<P> {
         });
     }
 
+    /// Returns true if the client is disconnected but its session can still be resumed
+    pub fn is_suspended(&self, client_id: ClientId) -> bool {
+        self.suspended_clients.contains_key(&client_id)
+    }
+
     /// Add a new [`Connection`] to the list of connections with the given [`ClientId`]
+    ///
+    /// If the client had a suspended session, we wait for its handshake to know if it can resume the session.
     pub(crate) fn add(&mut self, client_id: ClientId) {
+        if self.suspended_clients.contains_key(&client_id) {
+            debug!(
+                "Client {} reconnected while its session was suspended, waiting for its handshake",
+                client_id
+            );
+            self.awaiting_handshake.insert(client_id);
+            return;
+        }
         if let Entry::Vacant(e) = self.connections.entry(client_id) {
             #[cfg(feature = "metrics")]
             metrics::gauge!("connected_clients").increment(1.0);
@@ -147,25 +241,144 @@ impl<P: Protocol> ConnectionManager<P> {
                 &self.channel_registry,
                 self.packet_config.clone(),
                 self.ping_config.clone(),
+                self.input_config.rebroadcast_inputs,
+                self.protocol_fingerprint,
             );
             self.events.push_connection(client_id);
             self.new_clients.push(client_id);
             e.insert(connection);
+            self.send_players();
         } else {
             info!("Client {} was already in the connections list", client_id);
         }
     }
 
+    /// Read the handshake of a suspended client that reconnected.
+    ///
+    /// The packets of the client are not given to its connection until we know which session they belong to:
+    /// they are not acked, so the client will send their reliable messages again.
+    /// Returns `None` if the packet does not contain the handshake, otherwise returns true if the client
+    /// presented the secret of its suspended session (with the same protocol as the server).
+    pub(crate) fn recv_handshake(&mut self, client_id: ClientId, packet: Packet) -> Option<bool> {
+        let net_id = *self
+            .channel_registry
+            .get_net_from_kind(&ChannelKind::of::<DefaultUnorderedUnreliableChannel>())?;
+        let handshake = packet
+            .data
+            .contents()
+            .remove(&net_id)?
+            .into_iter()
+            .find_map(|message| {
+                let MessageContainer::Single(data) = message else {
+                    return None;
+                };
+                let mut reader = ReadWordBuffer::start_read(data.bytes.as_ref());
+                match ClientMessage::<P>::decode(&mut reader) {
+                    Ok(ClientMessage::Handshake(handshake)) => Some(handshake),
+                    _ => None,
+                }
+            })?;
+        let connection = self.connections.get_mut(&client_id)?;
+        // a client with a different protocol starts a new session, which denies its handshake
+        let resumed = handshake.protocol_fingerprint == self.protocol_fingerprint
+            && handshake.resumption_secret == Some(connection.resumption_secret);
+        self.awaiting_handshake.remove(&client_id);
+        if resumed {
+            info!("Client {} resumed its session", client_id);
+            self.suspended_clients.remove(&client_id);
+            // issue a new secret for the next resumption
+            connection.resumption_secret = rand::random();
+            self.events.push_resumption(client_id);
+        } else {
+            warn!(
+                "Client {} reconnected without the secret of its suspended session, starting a new session",
+                client_id
+            );
+        }
+        Some(resumed)
+    }
+
     pub(crate) fn remove(&mut self, client_id: ClientId) {
         #[cfg(feature = "metrics")]
         metrics::gauge!("connected_clients").decrement(1.0);
 
         info!("Client {} disconnected", client_id);
         self.events.push_disconnection(client_id);
-        self.connections.remove(&client_id);
+        if let Some(connection) = self.connections.remove(&client_id) {
+            if connection.rpc.has_pending() {
+                self.disconnected_requests.insert(client_id, connection.rpc);
+            }
+        }
+        self.suspended_clients.remove(&client_id);
+        self.awaiting_handshake.remove(&client_id);
+        self.authority
+            .retain(|_, authority| authority.client_id != client_id);
+        self.send_players();
     }
 
-    /// Get the inputs for all clients for the given tick
+    /// Send to each client the list of the other connected clients, so that they know whose inputs they need.
+    ///
+    /// This is only needed if the server rebroadcasts the inputs.
+    fn send_players(&mut self) {
+        if !self.input_config.rebroadcast_inputs {
+            return;
+        }
+        let client_ids: Vec<ClientId> = self.connections.keys().copied().collect();
+        for (client_id, connection) in self.connections.iter_mut() {
+            let players = client_ids
+                .iter()
+                .filter(|id| *id != client_id)
+                .copied()
+                .collect();
+            if let Err(e) = connection.message_manager.buffer_send(
+                ServerMessage::<P>::Players(players),
+                ChannelKind::of::<PlayersChannel>(),
+            ) {
+                error!(?client_id, "could not send the list of players: {:?}", e);
+            }
+        }
+    }
+
+    /// Suspend the connection of a client that got disconnected, so that the session can be resumed
+    /// if the client reconnects before the end of the grace period.
+    ///
+    /// Returns false if session resumption is disabled, in which case the connection should be removed.
+    pub(crate) fn suspend(&mut self, client_id: ClientId) -> bool {
+        if !self.resumption_config.is_enabled() || !self.connections.contains_key(&client_id) {
+            return false;
+        }
+        info!("Client {} disconnected, suspending its session", client_id);
+        self.awaiting_handshake.remove(&client_id);
+        // the grace period is not reset if the client disconnects again before sending its handshake
+        if let Entry::Vacant(e) = self.suspended_clients.entry(client_id) {
+            e.insert(self.resumption_config.grace_period);
+            self.events.push_suspension(client_id);
+        }
+        true
+    }
+
+    /// Advance the grace period of the suspended sessions by `delta`, and return the clients whose
+    /// grace period expired.
+    pub(crate) fn expired_sessions(&mut self, delta: Duration) -> Vec<ClientId> {
+        let mut expired = vec![];
+        self.suspended_clients
+            .retain(|client_id, remaining| match remaining.checked_sub(delta) {
+                Some(r) if !r.is_zero() => {
+                    *remaining = r;
+                    true
+                }
+                _ => {
+                    expired.push(*client_id);
+                    false
+                }
+            });
+        expired
+    }
+
+    /// Pop the inputs of every client for the given tick.
+    ///
+    /// The inputs were already checked with the [`InputValidator`] when they were received (see [`Connection::accept_inputs`]):
+    /// if the input of a client is missing or was rejected, the last valid input of the client is used instead.
     pub(crate) fn pop_inputs(
         &mut self,
         tick: Tick,
@@ -202,6 +415,13 @@ impl<P: Protocol> ConnectionManager<P> {
             })
     }
 
+    /// Record that an input of the client was rejected by the [`InputValidator`].
+    ///
+    /// Returns the total number of violations of the client
+    pub(crate) fn record_input_violation(&mut self, client_id: ClientId) -> Result<u32> {
+        Ok(self.connection_mut(client_id)?.record_input_violation())
+    }
+
     pub(crate) fn buffer_message(
         &mut self,
         message: P::Message,
@@ -216,33 +436,285 @@ impl<P: Protocol> ConnectionManager<P> {
             // TODO: here we should avoid the clone, it's the same message.. just use Rc?
             //  need to update the ServerMessage enum to use Rc<P::Message>!
             //  or serialize first, so we can use Bytes? where would the buffer be?
-            .try_for_each(|(_, c)| c.buffer_message(message.clone(), channel))
+            .try_for_each(|(_, c)| c.buffer_message(message.clone(), channel).map(|_| ()))
+    }
+
+    /// Set the hook that decides if the messages sent by clients to other clients should be relayed
+    ///
+    /// ```rust,ignore
+    /// // only relay chat messages, and never send them back to the sender
+    /// connection_manager.set_relay_filter(|sender, message, target| match message {
+    ///     MyMessageProtocol::Chat(_) if target == NetworkTarget::All => {
+    ///         Some(NetworkTarget::AllExceptSingle(sender))
+    ///     }
+    ///     MyMessageProtocol::Chat(_) => Some(target),
+    ///     _ => None,
+    /// });
+    /// ```
+    pub fn set_relay_filter(
+        &mut self,
+        filter: impl Fn(ClientId, &P::Message, NetworkTarget) -> Option<NetworkTarget>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.relay_filter = Some(Box::new(filter));
     }
 
     /// Queues up a message to be sent to all clients matching the specific [`NetworkTarget`]
+    ///
+    /// Each client can assign a different [`MessageId`] to the message, so this returns the [`MessageId`]
+    /// of the message for each client (empty if the channel does not assign ids to its messages).
+    /// The delivery events of each client are emitted as for [`ConnectionManager::send_message`].
     pub fn send_message_to_target<C: Channel, M: Message>(
         &mut self,
         message: M,
         target: NetworkTarget,
-    ) -> Result<()>
+    ) -> Result<HashMap<ClientId, MessageId>>
     where
         M: Clone,
         P::Message: From<M>,
     {
-        self.buffer_message(message.into(), ChannelKind::of::<C>(), target)
+        let channel = ChannelKind::of::<C>();
+        let message: P::Message = message.into();
+        let mut message_ids = HashMap::default();
+        for (client_id, connection) in self
+            .connections
+            .iter_mut()
+            .filter(|(id, _)| target.should_send_to(id))
+        {
+            if let Some(message_id) = connection.send_message(message.clone(), channel)? {
+                message_ids.insert(*client_id, message_id);
+            }
+        }
+        Ok(message_ids)
     }
 
     /// Queues up a message to be sent to a client
+    ///
+    /// Returns the [`MessageId`] of the message, if the channel assigns ids to its messages.
+    /// If the channel watches acks (every [`ChannelMode`](crate::channel::builder::ChannelMode) except `UnorderedUnreliable`,
+    /// `SequencedUnreliable` and `TickBuffered`),
+    /// a [`MessageAckEvent`](crate::server::events::MessageAckEvent) or a [`MessageLostEvent`](crate::server::events::MessageLostEvent)
+    /// with the same id and client is emitted once we know if the message was delivered.
     pub fn send_message<C: Channel, M: Message>(
         &mut self,
         client_id: ClientId,
         message: M,
-    ) -> Result<()>
+    ) -> Result<Option<MessageId>>
     where
         M: Clone,
         P::Message: From<M>,
     {
-        self.send_message_to_target::<C, M>(message, NetworkTarget::Only(vec![client_id]))
+        self.connection_mut(client_id)?
+            .send_message(message.into(), ChannelKind::of::<C>())
+    }
+
+    /// Queues up a message that was registered in the [`DynamicProtocol`], to be sent to a client
+    pub fn send_dynamic_message<C: Channel, M: Message + Serialize>(
+        &mut self,
+        client_id: ClientId,
+        message: M,
+    ) -> Result<Option<MessageId>> {
+        let message = self.dynamic_protocol.raw_message(&message)?;
+        self.send_message::<C, RawMessage>(client_id, message)
+    }
+
+    /// Queues up a message that was registered in the [`DynamicProtocol`], to be sent to all clients
+    /// matching the specific [`NetworkTarget`]
+    pub fn send_dynamic_message_to_target<C: Channel, M: Message + Serialize>(
+        &mut self,
+        message: M,
+        target: NetworkTarget,
+    ) -> Result<HashMap<ClientId, MessageId>> {
+        let message = self.dynamic_protocol.raw_message(&message)?;
+        self.send_message_to_target::<C, RawMessage>(message, target)
+    }
+
+    /// Queues up a message to be sent to a client on the [`ChannelMode::KeyedOrderedReliable`](crate::channel::builder::ChannelMode::KeyedOrderedReliable)
+    /// channel `C`. The message is only ordered relative to the other messages sent with the same `key`.
+    ///
+    /// Note that the messages relayed from other clients are always sent with the key 0.
+    pub fn send_message_with_key<C: Channel, M: Message>(
+        &mut self,
+        client_id: ClientId,
+        message: M,
+        key: u32,
+    ) -> Result<Option<MessageId>>
+    where
+        P::Message: From<M>,
+    {
+        self.connection_mut(client_id)?.buffer_message_with_key(
+            message.into(),
+            ChannelKind::of::<C>(),
+            key,
+        )
+    }
+
+    /// Bandwidth (in bytes per second) of the link to the client, estimated by the congestion control.
+    ///
+    /// Returns None if congestion control is not enabled in the [`PacketConfig`](crate::server::config::PacketConfig)
+    pub fn estimated_bandwidth(&self, client_id: ClientId) -> Result<Option<u32>> {
+        Ok(self
+            .connection(client_id)?
+            .message_manager
+            .estimated_bandwidth())
+    }
+
+    /// Cancel a stream that is being sent to a client on the [`ChannelMode::Streaming`](crate::channel::builder::ChannelMode::Streaming)
+    /// channel `C`. The client discards the bytes it received and emits a [`StreamCancelledEvent`](crate::client::events::StreamCancelledEvent).
+    ///
+    /// `stream_id` is the id of the stream in the [`StreamProgressEvent`](crate::server::events::StreamProgressEvent)s.
+    /// Returns false if the stream was already fully received by the client.
+    pub fn cancel_stream<C: Channel>(
+        &mut self,
+        client_id: ClientId,
+        stream_id: MessageId,
+    ) -> Result<bool> {
+        self.connection_mut(client_id)?
+            .message_manager
+            .cancel_stream(ChannelKind::of::<C>(), stream_id)
+    }
+
+    /// Send a [`Request`] to a client on the channel `C`
+    ///
+    /// The response of the client is emitted as a [`ResponseEvent`](crate::server::events::ResponseEvent) with the
+    /// returned [`RequestId`]; the [`RpcPlugin`](crate::server::rpc::RpcPlugin) must be added for the request type `R`.
+    /// If the client disconnects before responding, the event contains [`RpcError::Disconnected`](crate::shared::rpc::RpcError::Disconnected).
+    pub fn send_request<C: Channel, R: Request>(
+        &mut self,
+        client_id: ClientId,
+        request: R,
+    ) -> Result<RequestId>
+    where
+        P::Message: From<RequestMessage<R>>,
+    {
+        let connection = self.connection_mut(client_id)?;
+        let request_id = connection.rpc.new_request::<R>();
+        let message = RequestMessage::new(request_id, request);
+        if let Err(e) = connection.send_message(message.into(), ChannelKind::of::<C>()) {
+            connection.rpc.cancel_request(request_id);
+            return Err(e);
+        }
+        Ok(request_id)
+    }
+
+    /// Respond to a [`Request`] received from a client, on the channel `C`
+    ///
+    /// `request_id` is the id of the [`RequestMessage`] that was received.
+    pub fn send_response<C: Channel, R: Request>(
+        &mut self,
+        client_id: ClientId,
+        request_id: RequestId,
+        response: R::Response,
+    ) -> Result<()>
+    where
+        P::Message: From<ResponseMessage<R::Response>>,
+    {
+        self.connection_mut(client_id)?
+            .send_message(
+                ResponseMessage::new(request_id, response).into(),
+                ChannelKind::of::<C>(),
+            )
+            .map(|_| ())
+    }
+
+    /// Grant the client `client_id` authority over the components `kinds` of the replicated `entity`.
+    ///
+    /// The server stops sending updates for these components to that client; instead the client replicates
+    /// its own values to the server, which forwards them to the other clients.
+    /// If another client had authority over the entity, its authority is revoked first.
+    ///
+    /// The client must have [`ReplicationConfig::enable_send`](crate::client::replication::ReplicationConfig) set.
+    pub fn grant_authority(
+        &mut self,
+        entity: Entity,
+        client_id: ClientId,
+        kinds: Vec<P::ComponentKinds>,
+    ) -> Result<()> {
+        if let Some(authority) = self.authority.get(&entity) {
+            if authority.client_id != client_id {
+                self.revoke_authority(entity)?;
+            }
+        }
+        let connection = self.connection_mut(client_id)?;
+        // the client will send the updates using the server entity, so that no mapping is needed on the client
+        let entity_map = &mut connection.replication_receiver.remote_entity_map;
+        if entity_map
+            .get_local(entity)
+            .map_or(false, |local_entity| *local_entity != entity)
+        {
+            return Err(anyhow::anyhow!(
+                "entity {entity:?} is already used by an entity replicated from client {client_id:?}"
+            ));
+        }
+        entity_map.insert(entity, entity);
+        debug!(?entity, ?client_id, ?kinds, "Granting authority");
+        connection.message_manager.buffer_send(
+            ServerMessage::<P>::Authority(AuthorityMessage::Grant {
+                entity,
+                kinds: kinds.clone(),
+            }),
+            ChannelKind::of::<AuthorityChannel>(),
+        )?;
+        self.authority
+            .insert(entity, Authority { client_id, kinds });
+        Ok(())
+    }
+
+    /// Revoke the authority that a client had over `entity`.
+    ///
+    /// The server becomes the sender of all the replicated components of the entity again, and
+    /// sends its latest state to the client.
+    pub fn revoke_authority(&mut self, entity: Entity) -> Result<()> {
+        let Some(authority) = self.authority.remove(&entity) else {
+            return Ok(());
+        };
+        let group_id = self
+            .replicate_component_cache
+            .get(&entity)
+            .map(|replicate| replicate.group_id(Some(entity)));
+        let Ok(connection) = self.connection_mut(authority.client_id) else {
+            // the client is not connected anymore
+            return Ok(());
+        };
+        debug!(?entity, client_id = ?authority.client_id, "Revoking authority");
+        connection
+            .replication_receiver
+            .remote_entity_map
+            .remove_by_remote(entity);
+        // the client did not receive any updates for these components while it had authority:
+        // send all the components of the group again
+        if let Some(channel) =
+            group_id.and_then(|id| connection.replication_sender.group_channels.get_mut(&id))
+        {
+            channel.collect_changes_since_this_tick = None;
+        }
+        connection.message_manager.buffer_send(
+            ServerMessage::<P>::Authority(AuthorityMessage::Revoke { entity }),
+            ChannelKind::of::<AuthorityChannel>(),
+        )?;
+        Ok(())
+    }
+
+    /// Returns the client that has authority over `entity`, and the kinds of the components concerned
+    pub fn authority(&self, entity: Entity) -> Option<&Authority<P::ComponentKinds>> {
+        self.authority.get(&entity)
+    }
+
+    /// Remove the client with authority over `kind` from the replication target
+    fn exclude_authority(
+        &self,
+        entity: Entity,
+        kind: &P::ComponentKinds,
+        mut target: NetworkTarget,
+    ) -> NetworkTarget {
+        if let Some(authority) = self.authority.get(&entity) {
+            if authority.kinds.contains(kind) {
+                target.exclude(vec![authority.client_id]);
+            }
+        }
+        target
     }
 
     /// Buffer all the replication messages to send.
@@ -272,13 +744,55 @@ impl<P: Protocol> ConnectionManager<P> {
             .for_each(|(client_id, connection)| {
                 let _span = trace_span!("receive", ?client_id).entered();
                 // receive events on the connection
-                let events = connection.receive(world, time_manager, tick_manager);
+                let events = connection.receive(
+                    world,
+                    time_manager,
+                    tick_manager,
+                    *client_id,
+                    &self.authority,
+                );
                 // move the events from the connection to the connection manager
                 self.events.push_events(*client_id, events);
 
                 // rebroadcast messages
-                messages_to_rebroadcast
-                    .extend(std::mem::take(&mut connection.messages_to_rebroadcast));
+                for (message, target, channel_kind) in
+                    std::mem::take(&mut connection.messages_to_rebroadcast)
+                {
+                    let target = match &self.relay_filter {
+                        Some(filter) => filter(*client_id, &message, target),
+                        None => Some(target),
+                    };
+                    match target {
+                        Some(target) => {
+                            messages_to_rebroadcast.push((message, target, channel_kind))
+                        }
+                        None => trace!(?client_id, "relay filter dropped message"),
+                    }
+                }
+                for (tick, checksum) in std::mem::take(&mut connection.received_checksums) {
+                    self.checksums.add(*client_id, tick, checksum);
+                }
+                // validate the inputs before they are used or forwarded to the other clients
+                let validator = world.get_resource::<InputValidator<P::Input>>();
+                for input_message in std::mem::take(&mut connection.received_inputs) {
+                    let Some(mut input_message) = connection.accept_inputs(
+                        *client_id,
+                        input_message,
+                        validator,
+                        &mut self.input_violations,
+                    ) else {
+                        continue;
+                    };
+                    if !connection.rebroadcast_inputs {
+                        continue;
+                    }
+                    input_message.client_id = Some(*client_id);
+                    messages_to_rebroadcast.push((
+                        input_message.into(),
+                        NetworkTarget::AllExcept(vec![*client_id]),
+                        ChannelKind::of::<InputChannel>(),
+                    ));
+                }
             });
         for (message, target, channel_kind) in messages_to_rebroadcast {
             self.buffer_message(message, channel_kind, target)?;
@@ -295,6 +809,8 @@ pub struct Connection<P: Protocol> {
     pub(crate) events: ConnectionEvents<P>,
 
     pub(crate) ping_manager: PingManager,
+    /// Interpolation delay most recently measured and reported by the client
+    interpolation_delay: Option<Duration>,
     /// Stores the inputs that we have received from the client.
     pub(crate) input_buffer: InputBuffer<P::Input>,
     /// Stores the last input we have received from the client.
@@ -304,6 +820,30 @@ pub struct Connection<P: Protocol> {
 
     // messages that we have received that need to be rebroadcasted to other clients
     pub(crate) messages_to_rebroadcast: Vec<(P::Message, NetworkTarget, ChannelKind)>,
+    /// If true, the native inputs received from the client are also forwarded to the other clients
+    rebroadcast_inputs: bool,
+    /// Native input messages that we have received, and that still need to be validated
+    received_inputs: Vec<InputMessage<P::Input>>,
+    /// Most recent tick for which we have accepted the native input of the client
+    last_input_tick: Option<Tick>,
+    // checksums of the deterministic simulation that the client reported
+    pub(crate) received_checksums: Vec<(Tick, u64)>,
+    /// Number of inputs of the client that were rejected by the [`InputValidator`]
+    input_violations: u32,
+    /// Requests sent to the client that are waiting for a response
+    pub(crate) rpc: RpcManager,
+    /// Secret that the client must present to resume this session after a disconnection
+    pub(crate) resumption_secret: ResumptionSecret,
+    /// Fingerprint of the server's protocol, that the client must present in its handshake
+    protocol_fingerprint: u64,
+    /// Reason sent to the client if its handshake was refused; the client is disconnected after the
+    /// next packets are sent
+    pub(crate) denied_reason: Option<DeniedReason>,
+    /// True once we received a handshake with the same protocol fingerprint as the server
+    handshake_verified: bool,
+    /// Messages and replication messages received before the handshake was verified.
+    /// They are only processed once we know that the client uses the same protocol.
+    pending_messages: Vec<(ChannelKind, Tick, ClientMessage<P>)>,
 }
 
 impl<P: Protocol> Connection<P> {
@@ -311,6 +851,8 @@ impl<P: Protocol> Connection<P> {
         channel_registry: &ChannelRegistry,
         packet_config: PacketConfig,
         ping_config: PingConfig,
+        rebroadcast_inputs: bool,
+        protocol_fingerprint: u64,
     ) -> Self {
         // create the message manager and the channels
         let mut message_manager = MessageManager::new(channel_registry, packet_config.into());
@@ -332,24 +874,106 @@ impl<P: Protocol> Connection<P> {
             replication_sender,
             replication_receiver,
             ping_manager: PingManager::new(ping_config),
+            interpolation_delay: None,
             input_buffer: InputBuffer::default(),
             last_input: None,
             events: ConnectionEvents::default(),
             messages_to_rebroadcast: vec![],
+            rebroadcast_inputs,
+            received_inputs: vec![],
+            last_input_tick: None,
+            received_checksums: vec![],
+            input_violations: 0,
+            rpc: RpcManager::default(),
+            resumption_secret: rand::random(),
+            protocol_fingerprint,
+            denied_reason: None,
+            handshake_verified: false,
+            pending_messages: vec![],
         }
     }
 
+    /// Interpolation delay of the client, as measured by its sync manager.
+    ///
+    /// Returns `None` until the client is synced and has reported it.
+    pub fn interpolation_delay(&self) -> Option<Duration> {
+        self.interpolation_delay
+    }
+
+    fn record_input_violation(&mut self) -> u32 {
+        self.input_violations += 1;
+        self.input_violations
+    }
+
+    /// Keep the inputs of the message for the ticks that we haven't received yet, and check them with the [`InputValidator`].
+    ///
+    /// The input of a tick is only accepted once, so the client cannot replace it later.
+    /// Rejected inputs are replaced with `None`: they are neither used by the server nor forwarded to the other clients.
+    ///
+    /// Returns the accepted inputs, or `None` if the message didn't contain any new tick.
+    pub(crate) fn accept_inputs(
+        &mut self,
+        client_id: ClientId,
+        message: InputMessage<P::Input>,
+        validator: Option<&InputValidator<P::Input>>,
+        violations: &mut Vec<InputViolationEvent>,
+    ) -> Option<InputMessage<P::Input>> {
+        let last_input_tick = self.last_input_tick;
+        if last_input_tick.map_or(false, |last_tick| message.end_tick <= last_tick) {
+            return None;
+        }
+        let inputs = message
+            .inputs()
+            .into_iter()
+            .filter(|(tick, _)| last_input_tick.map_or(true, |last_tick| *tick > last_tick))
+            .map(|(tick, input)| {
+                let mut input = input?;
+                match validator {
+                    Some(validator) if !validator.validate(client_id, tick, &mut input) => {
+                        debug!(?client_id, ?tick, "Rejected client input");
+                        violations.push(InputViolationEvent {
+                            client_id,
+                            tick,
+                            violations: self.record_input_violation(),
+                        });
+                        None
+                    }
+                    _ => Some(input),
+                }
+            })
+            .collect::<Vec<_>>();
+        self.last_input_tick = Some(message.end_tick);
+        let mut accepted = InputMessage::from_inputs(message.end_tick, inputs);
+        accepted.entities = message.entities;
+        self.input_buffer.update_from_message(accepted.clone());
+        Some(accepted)
+    }
+
     pub(crate) fn update(&mut self, time_manager: &TimeManager, tick_manager: &TickManager) {
         self.message_manager
             .update(time_manager, &self.ping_manager, tick_manager);
         self.ping_manager.update(time_manager);
+        self.rpc.update(time_manager.current_time());
+    }
+
+    /// Buffer a message sent by the user, and keep track of its delivery
+    pub(crate) fn send_message(
+        &mut self,
+        message: P::Message,
+        channel: ChannelKind,
+    ) -> Result<Option<MessageId>> {
+        let message_id = self.buffer_message(message, channel)?;
+        if let Some(message_id) = message_id {
+            self.message_manager.track_delivery(channel, message_id);
+        }
+        Ok(message_id)
     }
 
     pub(crate) fn buffer_message(
         &mut self,
         message: P::Message,
         channel: ChannelKind,
-    ) -> Result<()> {
+    ) -> Result<Option<MessageId>> {
         // TODO: i know channel names never change so i should be able to get them as static
         // TODO: just have a channel registry enum as well?
         let channel_name = self
@@ -360,8 +984,30 @@ impl<P: Protocol> Connection<P> {
             .to_string();
         let message = ServerMessage::<P>::Message(message);
         message.emit_send_logs(&channel_name);
-        self.message_manager.buffer_send(message, channel)?;
-        Ok(())
+        self.message_manager.buffer_send(message, channel)
+    }
+
+    pub(crate) fn buffer_message_with_key(
+        &mut self,
+        message: P::Message,
+        channel: ChannelKind,
+        key: u32,
+    ) -> Result<Option<MessageId>> {
+        let channel_name = self
+            .message_manager
+            .channel_registry
+            .name(&channel)
+            .unwrap_or("unknown")
+            .to_string();
+        let message = ServerMessage::<P>::Message(message);
+        message.emit_send_logs(&channel_name);
+        let message_id = self
+            .message_manager
+            .buffer_send_with_key(message, channel, key)?;
+        if let Some(message_id) = message_id {
+            self.message_manager.track_delivery(channel, message_id);
+        }
+        Ok(message_id)
     }
 
     pub(crate) fn buffer_replication_messages(
@@ -380,7 +1026,8 @@ impl<P: Protocol> Connection<P> {
                     .name(&channel)
                     .unwrap_or("unknown")
                     .to_string();
-                let message = ClientMessage::<P>::Replication(ReplicationMessage {
+                // the client decodes the message as a `ServerMessage`, whose encoding differs from `ClientMessage`
+                let message = ServerMessage::<P>::Replication(ReplicationMessage {
                     group_id,
                     data: message_data,
                 });
@@ -395,6 +1042,8 @@ impl<P: Protocol> Connection<P> {
                     self.replication_sender
                         .updates_message_id_to_group_id
                         .insert(message_id, (group_id, bevy_tick));
+                    self.replication_sender
+                        .track_delta_values(message_id, group_id);
                 }
                 Ok(())
             })
@@ -444,13 +1093,44 @@ impl<P: Protocol> Connection<P> {
         payloads
     }
 
+    /// Answer the handshake of the client.
+    ///
+    /// The client keeps sending its handshake until it receives the secret of the session, or until it
+    /// is denied because it was built with a different protocol.
+    fn recv_handshake(&mut self, client_id: ClientId, handshake: HandshakeMessage) {
+        let message = if handshake.protocol_fingerprint == self.protocol_fingerprint {
+            self.handshake_verified = true;
+            ServerMessage::<P>::Session(self.resumption_secret)
+        } else {
+            if self.denied_reason.is_none() {
+                error!(
+                    ?client_id,
+                    "client was built with a different protocol (fingerprint {:#x}, expected {:#x}), disconnecting it",
+                    handshake.protocol_fingerprint,
+                    self.protocol_fingerprint
+                );
+            }
+            self.denied_reason = Some(DeniedReason::ProtocolMismatch);
+            ServerMessage::<P>::Denied(DeniedReason::ProtocolMismatch)
+        };
+        if let Err(e) = self.message_manager.buffer_send(
+            message,
+            ChannelKind::of::<DefaultUnorderedUnreliableChannel>(),
+        ) {
+            error!("could not answer the handshake: {:?}", e);
+        }
+    }
+
     pub fn receive(
         &mut self,
         world: &mut World,
         time_manager: &TimeManager,
         tick_manager: &TickManager,
+        client_id: ClientId,
+        authority: &EntityHashMap<Entity, Authority<P::ComponentKinds>>,
     ) -> ConnectionEvents<P> {
         let _span = trace_span!("receive").entered();
+        let mut received = vec![];
         for (channel_kind, messages) in self.message_manager.read_messages::<ClientMessage<P>>() {
             let channel_name = self
                 .message_manager
@@ -463,45 +1143,16 @@ impl<P: Protocol> Connection<P> {
                 trace!(?channel_name, ?messages, "Received messages");
                 for (tick, message) in messages.into_iter() {
                     match message {
-                        ClientMessage::Message(mut message, target) => {
-                            trace!(
-                                "remote entity map: {:?}",
-                                self.replication_receiver.remote_entity_map
-                            );
-                            // map any entities inside the message
-                            message.map_entities(&mut self.replication_receiver.remote_entity_map);
-                            if target != NetworkTarget::None {
-                                self.messages_to_rebroadcast.push((
-                                    message.clone(),
-                                    target,
-                                    channel_kind,
-                                ));
-                            }
-                            // don't put InputMessage into events else the events won't be classified as empty
-                            match message.input_message_kind() {
-                                #[cfg(feature = "leafwing")]
-                                InputMessageKind::Leafwing => {
-                                    trace!("received input message, pushing it to events");
-                                    self.events.push_input_message(message);
-                                }
-                                InputMessageKind::Native => {
-                                    let input_message = message.try_into().unwrap();
-                                    debug!("Received input message: {:?}", input_message.end_tick);
-                                    self.input_buffer.update_from_message(input_message);
-                                }
-                                InputMessageKind::None => {
-                                    // buffer the message
-                                    self.events.push_message(channel_kind, message);
-                                }
-                            }
-                        }
-                        ClientMessage::Replication(replication) => {
-                            // buffer the replication message
-                            self.replication_receiver.recv_message(replication, tick);
+                        ClientMessage::Handshake(handshake) => {
+                            self.recv_handshake(client_id, handshake)
                         }
                         ClientMessage::Sync(ref sync) => {
                             match sync {
                                 SyncMessage::Ping(ping) => {
+                                    if let Some(delay_ms) = ping.interpolation_delay_ms {
+                                        self.interpolation_delay =
+                                            Some(Duration::from_millis(delay_ms as u64));
+                                    }
                                     // prepare a pong in response (but do not send yet, because we need
                                     // to set the correct send time)
                                     self.ping_manager.buffer_pending_pong(ping, time_manager);
@@ -513,10 +1164,74 @@ impl<P: Protocol> Connection<P> {
                                 }
                             }
                         }
+                        message => received.push((channel_kind, tick, message)),
                     }
                 }
             }
         }
+        // the messages of the client are only processed once we know that it uses the same protocol
+        if self.denied_reason.is_some() {
+            self.pending_messages.clear();
+        } else if !self.handshake_verified {
+            if self.pending_messages.len() + received.len() > MAX_PENDING_MESSAGES {
+                warn!(
+                    ?client_id,
+                    "dropping the messages received before the handshake, because there are too many"
+                );
+            } else {
+                self.pending_messages.extend(received);
+            }
+        } else {
+            for (channel_kind, tick, message) in std::mem::take(&mut self.pending_messages)
+                .into_iter()
+                .chain(received)
+            {
+                match message {
+                    ClientMessage::Message(mut message, target) => {
+                        trace!(
+                            "remote entity map: {:?}",
+                            self.replication_receiver.remote_entity_map
+                        );
+                        // map any entities inside the message
+                        message.map_entities(&mut self.replication_receiver.remote_entity_map);
+                        if target != NetworkTarget::None {
+                            self.messages_to_rebroadcast.push((
+                                message.clone(),
+                                target,
+                                channel_kind,
+                            ));
+                        }
+                        // don't put InputMessage into events else the events won't be classified as empty
+                        match message.input_message_kind() {
+                            #[cfg(feature = "leafwing")]
+                            InputMessageKind::Leafwing => {
+                                trace!("received input message, pushing it to events");
+                                self.events.push_input_message(message);
+                            }
+                            InputMessageKind::Native => {
+                                let input_message: InputMessage<P::Input> =
+                                    message.try_into().unwrap();
+                                debug!("Received input message: {:?}", input_message.end_tick);
+                                if let Some(checksum) = input_message.checksum {
+                                    self.received_checksums.push(checksum);
+                                }
+                                self.received_inputs.push(input_message);
+                            }
+                            InputMessageKind::None => {
+                                // buffer the message
+                                self.events.push_message(channel_kind, message);
+                            }
+                        }
+                    }
+                    ClientMessage::Replication(replication) => {
+                        // buffer the replication message
+                        self.replication_receiver.recv_message(replication, tick);
+                    }
+                    // handled as soon as they are received
+                    ClientMessage::Handshake(_) | ClientMessage::Sync(_) => {}
+                }
+            }
+        }
 
         // NOTE: we run this outside `messages.is_empty()` because we might have some messages from a future tick that we can now process
         // Check if we have any replication messages we can apply to the World (and emit events)
@@ -526,7 +1241,14 @@ impl<P: Protocol> Connection<P> {
             trace!(?group, ?replication_list, "read replication messages");
             replication_list
                 .into_iter()
-                .for_each(|(tick, replication)| {
+                .for_each(|(tick, mut replication)| {
+                    // the client can only replicate the components of server entities that it has authority over
+                    filter_unauthorized(
+                        &mut replication,
+                        client_id,
+                        authority,
+                        &self.replication_receiver.remote_entity_map,
+                    );
                     // TODO: we could include the server tick when this replication_message was sent.
                     self.replication_receiver.apply_world(
                         world,
@@ -538,6 +1260,15 @@ impl<P: Protocol> Connection<P> {
                 });
         }
 
+        // progress of the streams that are sent or received
+        for (channel_kind, update) in self.message_manager.take_stream_updates() {
+            self.events.push_stream_update(channel_kind, update);
+        }
+        // messages that we sent and that were acked or lost
+        for (channel_kind, delivery) in self.message_manager.take_delivery_updates() {
+            self.events.push_message_delivery(channel_kind, delivery);
+        }
+
         // TODO: do i really need this? I could just create events in this function directly?
         //  why do i need to make events a field of the connection?
         //  is it because of push_connection?
@@ -574,6 +1305,23 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         Ok(())
     }
 
+    fn update_send_interval(
+        &mut self,
+        replication_group_id: ReplicationGroupId,
+        client_id: ClientId,
+        send_interval: u16,
+    ) -> Result<()> {
+        debug!(
+            ?client_id,
+            ?replication_group_id,
+            "Set send interval to {:?}",
+            send_interval
+        );
+        let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
+        replication_sender.update_send_interval(replication_group_id, send_interval);
+        Ok(())
+    }
+
     fn new_connected_clients(&self) -> Vec<ClientId> {
         self.new_clients.clone()
     }
@@ -618,8 +1366,13 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
                     P::Components::from(ShouldBeInterpolated),
                 );
             }
-            // also set the priority for the group when we spawn it
+            // also set the priority and send interval for the group when we spawn it
             self.update_priority(group_id, client_id, replicate.replication_group.priority())?;
+            self.update_send_interval(
+                group_id,
+                client_id,
+                replicate.replication_group.send_interval(),
+            )?;
 
             Ok(())
         })
@@ -633,6 +1386,15 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         system_current_tick: BevyTick,
     ) -> Result<()> {
         let group_id = replicate.replication_group.group_id(Some(entity));
+        // the entity doesn't exist anymore, so no client can have authority over it
+        if let Some(authority) = self.authority.remove(&entity) {
+            if let Ok(connection) = self.connection_mut(authority.client_id) {
+                connection
+                    .replication_receiver
+                    .remote_entity_map
+                    .remove_by_remote(entity);
+            }
+        }
         self.apply_replication(target).try_for_each(|client_id| {
             // trace!(
             //     ?entity,
@@ -652,6 +1414,31 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         })
     }
 
+    fn prepare_entity_unreplicate(
+        &mut self,
+        entity: Entity,
+        replicate: &Replicate<P>,
+        target: NetworkTarget,
+        _: BevyTick,
+    ) -> Result<()> {
+        let group_id = replicate.replication_group.group_id(Some(entity));
+        // the entity is not replicated anymore, so no client can have authority over it
+        if let Some(authority) = self.authority.remove(&entity) {
+            if let Ok(connection) = self.connection_mut(authority.client_id) {
+                connection
+                    .replication_receiver
+                    .remote_entity_map
+                    .remove_by_remote(entity);
+            }
+        }
+        self.apply_replication(target).try_for_each(|client_id| {
+            self.connection_mut(client_id)?
+                .replication_sender
+                .prepare_entity_unreplicate(entity, group_id);
+            Ok(())
+        })
+    }
+
     // TODO: perf gain if we batch this? (send vec of components) (same for update/removes)
     fn prepare_component_insert(
         &mut self,
@@ -679,11 +1466,12 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         //     }));
 
         // same thing for PreSpawnedPlayerObject: that component should only be replicated to prediction_target
-        let mut actual_target = target;
+        let mut actual_target = self.exclude_authority(entity, &kind, target);
         if kind == <P::ComponentKinds as FromType<ShouldBePredicted>>::from_type()
             || kind == <P::ComponentKinds as FromType<PreSpawnedPlayerObject>>::from_type()
         {
-            actual_target = replicate.prediction_target.clone();
+            actual_target =
+                self.exclude_authority(entity, &kind, replicate.prediction_target.clone());
         }
 
         self.apply_replication(actual_target)
@@ -716,6 +1504,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
     ) -> Result<()> {
         let group_id = replicate.replication_group.group_id(Some(entity));
         debug!(?entity, ?component_kind, "Sending RemoveComponent");
+        let target = self.exclude_authority(entity, &component_kind, target);
         self.apply_replication(target).try_for_each(|client_id| {
             let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
             // TODO: I don't think it's actually correct to only correct the changes since that action.
@@ -754,6 +1543,7 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         );
 
         let group_id = replicate.group_id(Some(entity));
+        let target = self.exclude_authority(entity, &kind, target);
         self.apply_replication(target).try_for_each(|client_id| {
             // TODO: should we have additional state tracking so that we know we are in the process of sending this entity to clients?
             let replication_sender = &mut self.connection_mut(client_id)?.replication_sender;
@@ -791,6 +1581,26 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
         })
     }
 
+    fn has_pending_update(
+        &mut self,
+        group_id: ReplicationGroupId,
+        target: NetworkTarget,
+        component_change_tick: BevyTick,
+        system_current_tick: BevyTick,
+    ) -> bool {
+        self.apply_replication(target).any(|client_id| {
+            self.connections
+                .get(&client_id)
+                .map_or(false, |connection| {
+                    connection.replication_sender.is_update_pending(
+                        group_id,
+                        component_change_tick,
+                        system_current_tick,
+                    )
+                })
+        })
+    }
+
     /// Buffer the replication messages
     fn buffer_replication_messages(&mut self, tick: Tick, bevy_tick: BevyTick) -> Result<()> {
         self.buffer_replication_messages(tick, bevy_tick)
@@ -819,7 +1629,13 @@ impl<P: Protocol> ReplicationSend<P> for ConnectionManager<P> {
                         group_channel.last_action_tick = None;
                     }
                 }
+                if let Some(last_update_tick) = group_channel.last_update_tick {
+                    if tick - last_update_tick > (i16::MAX / 2) {
+                        group_channel.last_update_tick = None;
+                    }
+                }
             }
+            connection.replication_sender.cleanup_delta_values(tick);
             // if it's been enough time since we last had any update for the group, we update the latest_tick for the group
             for group_channel in connection.replication_receiver.group_channels.values_mut() {
                 debug!("Checking group channel: {:?}", group_channel);
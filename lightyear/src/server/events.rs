@@ -8,17 +8,21 @@ use crate::_reexport::{
     FromType, IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
     ServerMarker,
 };
+use crate::channel::stream::StreamUpdate;
 use crate::connection::id::ClientId;
 #[cfg(feature = "leafwing")]
 use crate::inputs::leafwing::{InputMessage, LeafwingUserAction};
+use crate::packet::delivery::MessageDelivery;
 use crate::packet::message::Message;
+use crate::protocol::channel::ChannelKind;
 use crate::protocol::Protocol;
 use crate::server::connection::ConnectionManager;
 use crate::server::networking::clear_events;
 #[cfg(feature = "leafwing")]
 use crate::shared::events::connection::IterInputMessageEvent;
 use crate::shared::events::connection::{
-    ConnectionEvents, IterEntityDespawnEvent, IterEntitySpawnEvent, IterMessageEvent,
+    ConnectionEvents, IterComponentKindEvent, IterEntityDespawnEvent, IterEntitySpawnEvent,
+    IterMessageDeliveryEvent, IterMessageEvent, IterStreamEvent,
 };
 use crate::shared::events::plugin::EventsPlugin;
 use crate::shared::sets::InternalMainSet;
@@ -43,6 +47,8 @@ impl<P: Protocol> Plugin for ServerEventsPlugin<P> {
         app
             // PLUGIN
             .add_plugins(EventsPlugin::<P, ClientId>::default())
+            .add_event::<SessionSuspendedEvent>()
+            .add_event::<SessionResumedEvent>()
             // SYSTEM_SET
             .add_systems(PostUpdate, clear_events::<P>);
     }
@@ -52,6 +58,8 @@ impl<P: Protocol> Plugin for ServerEventsPlugin<P> {
 pub struct ServerEvents<P: Protocol> {
     pub connections: Vec<ClientId>,
     pub disconnections: Vec<ClientId>,
+    pub suspensions: Vec<ClientId>,
+    pub resumptions: Vec<ClientId>,
     pub events: HashMap<ClientId, ConnectionEvents<P>>,
     pub empty: bool,
 }
@@ -61,6 +69,8 @@ impl<P: Protocol> ServerEvents<P> {
         Self {
             connections: Vec::new(),
             disconnections: Vec::new(),
+            suspensions: Vec::new(),
+            resumptions: Vec::new(),
             events: HashMap::default(),
             empty: true,
         }
@@ -70,6 +80,8 @@ impl<P: Protocol> ServerEvents<P> {
     pub(crate) fn clear(&mut self) {
         self.connections = Vec::new();
         self.disconnections = Vec::new();
+        self.suspensions = Vec::new();
+        self.resumptions = Vec::new();
         self.empty = true;
         self.events = HashMap::default();
     }
@@ -116,6 +128,14 @@ impl<P: Protocol> ServerEvents<P> {
         !self.disconnections.is_empty()
     }
 
+    pub fn iter_suspensions(&mut self) -> impl Iterator<Item = ClientId> + '_ {
+        std::mem::take(&mut self.suspensions).into_iter()
+    }
+
+    pub fn iter_resumptions(&mut self) -> impl Iterator<Item = ClientId> + '_ {
+        std::mem::take(&mut self.resumptions).into_iter()
+    }
+
     pub(crate) fn push_connection(&mut self, client_id: ClientId) {
         self.connections.push(client_id);
         // self.events.remove(&client_id);
@@ -128,6 +148,16 @@ impl<P: Protocol> ServerEvents<P> {
         self.empty = false;
     }
 
+    pub(crate) fn push_suspension(&mut self, client_id: ClientId) {
+        self.suspensions.push(client_id);
+        self.empty = false;
+    }
+
+    pub(crate) fn push_resumption(&mut self, client_id: ClientId) {
+        self.resumptions.push(client_id);
+        self.empty = false;
+    }
+
     pub(crate) fn push_events(&mut self, client_id: ClientId, events: ConnectionEvents<P>) {
         if !events.is_empty() {
             self.events.insert(client_id, events);
@@ -291,10 +321,119 @@ impl<P: Protocol> IterComponentInsertEvent<P, ClientId> for ServerEvents<P> {
     }
 }
 
+impl<P: Protocol> IterComponentKindEvent<P, ClientId> for ServerEvents<P> {
+    fn iter_component_kind_insert(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(move |(client_id, events)| {
+            let inserts = events
+                .iter_component_kind_insert(kind)
+                .map(|(entity, _)| entity);
+            let client_ids = std::iter::once(*client_id).cycle();
+            inserts.zip(client_ids)
+        }))
+    }
+
+    fn iter_component_kind_update(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(move |(client_id, events)| {
+            let updates = events
+                .iter_component_kind_update(kind)
+                .map(|(entity, _)| entity);
+            let client_ids = std::iter::once(*client_id).cycle();
+            updates.zip(client_ids)
+        }))
+    }
+
+    fn iter_component_kind_remove(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(move |(client_id, events)| {
+            let removes = events
+                .iter_component_kind_remove(kind)
+                .map(|(entity, _)| entity);
+            let client_ids = std::iter::once(*client_id).cycle();
+            removes.zip(client_ids)
+        }))
+    }
+}
+
+impl<P: Protocol> IterStreamEvent<ClientId> for ServerEvents<P> {
+    fn drain_stream_updates(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, StreamUpdate, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            events
+                .drain_stream_updates()
+                .map(|(channel, update, _)| (channel, update, *client_id))
+        }))
+    }
+
+    fn has_stream_updates(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_stream_updates())
+    }
+}
+
+impl<P: Protocol> IterMessageDeliveryEvent<ClientId> for ServerEvents<P> {
+    fn drain_message_deliveries(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, MessageDelivery, ClientId)> + '_> {
+        Box::new(self.events.iter_mut().flat_map(|(client_id, events)| {
+            events
+                .drain_message_deliveries()
+                .map(|(channel, delivery, _)| (channel, delivery, *client_id))
+        }))
+    }
+
+    fn has_message_deliveries(&self) -> bool {
+        self.events
+            .iter()
+            .any(|(_, connection_events)| connection_events.has_message_deliveries())
+    }
+}
+
 /// Bevy [`Event`] emitted on the server on the frame where a client is connected
 pub type ConnectEvent = crate::shared::events::components::ConnectEvent<ClientId>;
 /// Bevy [`Event`] emitted on the server on the frame where a client is disconnected
+///
+/// If session resumption is enabled (see [`ResumptionConfig`](crate::shared::config::ResumptionConfig)),
+/// this is only emitted when the session of the client ends, i.e. at the end of the grace period.
 pub type DisconnectEvent = crate::shared::events::components::DisconnectEvent<ClientId>;
+
+/// Bevy [`Event`] emitted on the server when a client lost its connection, but its session is kept
+/// until the end of the [`ResumptionConfig::grace_period`](crate::shared::config::ResumptionConfig::grace_period)
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionSuspendedEvent(ClientId);
+
+impl SessionSuspendedEvent {
+    pub fn new(client_id: ClientId) -> Self {
+        Self(client_id)
+    }
+
+    pub fn client_id(&self) -> ClientId {
+        self.0
+    }
+}
+
+/// Bevy [`Event`] emitted on the server when a client reconnected and resumed its suspended session
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionResumedEvent(ClientId);
+
+impl SessionResumedEvent {
+    pub fn new(client_id: ClientId) -> Self {
+        Self(client_id)
+    }
+
+    pub fn client_id(&self) -> ClientId {
+        self.0
+    }
+}
 /// Bevy [`Event`] emitted on the server on the frame where an input message from a client is received
 pub type InputEvent<I> = crate::shared::events::components::InputEvent<I, ClientId>;
 /// Bevy [`Event`] emitted on the server on the frame where a EntitySpawn replication message is received
@@ -317,6 +456,16 @@ pub(crate) type InputMessageEvent<A> =
     crate::shared::events::components::InputMessageEvent<A, ClientId>;
 /// Bevy [`Event`] emitted on the server on the frame where a (non-replication) message is received
 pub type MessageEvent<M> = crate::shared::events::components::MessageEvent<M, ClientId>;
+/// Bevy [`Event`] emitted on the server when a stream sent to or received from a client makes progress
+pub type StreamProgressEvent = crate::shared::events::components::StreamProgressEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a stream sent to or received from a client is cancelled
+pub type StreamCancelledEvent = crate::shared::events::components::StreamCancelledEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a message sent to a client is acked by the client
+pub type MessageAckEvent = crate::shared::events::components::MessageAckEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a message sent to a client is lost
+pub type MessageLostEvent = crate::shared::events::components::MessageLostEvent<ClientId>;
+/// Bevy [`Event`] emitted on the server when a client responds to a request, or when the request times out
+pub type ResponseEvent<M> = crate::shared::events::components::ResponseEvent<M, ClientId>;
 
 #[cfg(test)]
 mod tests {
@@ -1,13 +1,28 @@
 //! Handles client-generated inputs
+//!
+//! The server can validate the inputs of the clients before they reach the game logic, by inserting an
+//! [`InputValidator`] resource. Rejected inputs are reported with an [`InputViolationEvent`].
+//!
+//! In deterministic mode, the clients also report the checksum of their simulation along with their inputs.
+//! The server emits a [`DesyncEvent::Clients`] if two clients reported different checksums for the same tick.
 use bevy::prelude::{
-    App, EventReader, EventWriter, FixedPostUpdate, FixedPreUpdate, IntoSystemConfigs, Plugin, Res,
-    ResMut, SystemSet,
+    App, Event, EventReader, EventWriter, FixedPostUpdate, FixedPreUpdate, IntoSystemConfigs,
+    Plugin, PreUpdate, Res, ResMut, Resource, SystemSet,
 };
+use bevy::reflect::Reflect;
+use bevy::utils::HashMap;
 
-use crate::prelude::{TickManager, UserAction};
+use crate::_reexport::ServerMarker;
+use crate::connection::id::ClientId;
+use crate::prelude::{Tick, TickManager, UserAction};
 use crate::protocol::Protocol;
 use crate::server::connection::ConnectionManager;
 use crate::server::events::InputEvent;
+use crate::shared::checksum::DesyncEvent;
+use crate::shared::sets::InternalMainSet;
+
+/// Number of ticks for which the server keeps the checksums reported by the clients
+const CHECKSUM_HISTORY_TICKS: i16 = 256;
 
 // - ClientInputs:
 // - inputs will be sent via a special message
@@ -26,6 +41,107 @@ use crate::server::events::InputEvent;
 // - the input history is associated with a connection.
 // - in the server, we receive the inputs, open the packet, and update the entire ringbuffer of inputs?
 // - server is at tick 9. for example we didn't receive the input for tick 10,11; but we receive the packet for tick 12, which contains all the inputs for ticks 10,11,12.
+#[derive(Debug, Clone, Default, Reflect)]
+pub struct InputConfig {
+    /// If true, the server forwards the inputs received from each client to all the other clients.
+    ///
+    /// The clients can then use the inputs of the remote players to predict them more accurately:
+    /// - native inputs are emitted on the client as [`RemoteInputEvent`](crate::client::events::RemoteInputEvent)s,
+    ///   for each entity that the remote client declared with [`InputManager::add_controlled_entity`](crate::client::input::InputManager::add_controlled_entity)
+    ///   (or for the remote client itself if it didn't declare any entity)
+    /// - leafwing inputs are applied to the `ActionState` of the predicted entity of the remote player
+    pub rebroadcast_inputs: bool,
+}
+
+impl InputConfig {
+    pub fn with_rebroadcast_inputs(mut self, rebroadcast_inputs: bool) -> Self {
+        self.rebroadcast_inputs = rebroadcast_inputs;
+        self
+    }
+}
+
+/// Hook that lets the server inspect the inputs of each client before they are used by the game logic.
+///
+/// The inputs are checked as soon as they are received, so that rejected inputs are also not forwarded to
+/// the other clients (see [`InputConfig::rebroadcast_inputs`]). The input of each tick is only checked once.
+///
+/// The hook receives the id of the client, the tick of the input and a mutable reference to the input
+/// (so that the input can be clamped). It returns `false` if the input should be rejected:
+/// - native inputs are discarded: the [`InputEvent`] contains the last valid input of the client instead
+/// - for leafwing inputs, the validator is an `InputValidator<ActionDiff<A>>` and is called for every `ActionDiff`.
+///   Rejected diffs are not applied to the `ActionState`.
+///
+/// Every rejected input emits an [`InputViolationEvent`].
+///
+/// ```rust,ignore
+/// app.insert_resource(InputValidator::<MyInput>::new(|client_id, tick, input| {
+///     // clamp the speed requested by the client
+///     input.speed = input.speed.min(MAX_SPEED);
+///     // reject teleports
+///     !matches!(input.action, Action::Teleport)
+/// }));
+/// ```
+#[derive(Resource)]
+pub struct InputValidator<I> {
+    validate: Box<dyn Fn(ClientId, Tick, &mut I) -> bool + Send + Sync>,
+}
+
+impl<I> InputValidator<I> {
+    pub fn new(validate: impl Fn(ClientId, Tick, &mut I) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            validate: Box::new(validate),
+        }
+    }
+
+    /// Returns `false` if the input should be rejected
+    pub(crate) fn validate(&self, client_id: ClientId, tick: Tick, input: &mut I) -> bool {
+        (self.validate)(client_id, tick, input)
+    }
+}
+
+/// Bevy [`Event`] emitted on the server when an input of a client was rejected by the [`InputValidator`]
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct InputViolationEvent {
+    pub client_id: ClientId,
+    /// Tick of the rejected input
+    pub tick: Tick,
+    /// Total number of inputs of the client that were rejected since it connected.
+    /// This can be used to kick clients that keep sending invalid inputs
+    pub violations: u32,
+}
+
+/// Compares the checksums of the deterministic simulation reported by the clients
+#[derive(Debug, Default)]
+pub(crate) struct ChecksumTracker {
+    pub(crate) reported: HashMap<Tick, Vec<(ClientId, u64)>>,
+    /// The ticks where the clients reported different checksums, with the checksums of each client
+    desyncs: Vec<(Tick, Vec<(ClientId, u64)>)>,
+}
+
+impl ChecksumTracker {
+    pub(crate) fn add(&mut self, client_id: ClientId, tick: Tick, checksum: u64) {
+        let reports = self.reported.entry(tick).or_default();
+        // only report the first mismatch for each tick
+        let in_sync = reports
+            .iter()
+            .all(|(_, c)| reports.first().map_or(false, |(_, first)| c == first));
+        reports.push((client_id, checksum));
+        if in_sync && reports[0].1 != checksum {
+            self.desyncs.push((tick, reports.clone()));
+        }
+    }
+
+    /// Return the desyncs that were detected, and forget the checksums that are too old
+    fn drain_desyncs<P: Protocol>(&mut self, current_tick: Tick) -> Vec<DesyncEvent<P>> {
+        self.reported
+            .retain(|tick, _| current_tick - *tick < CHECKSUM_HISTORY_TICKS);
+        self.desyncs
+            .drain(..)
+            .map(|(tick, checksums)| DesyncEvent::Clients { tick, checksums })
+            .collect()
+    }
+}
+
 pub struct InputPlugin<P: Protocol> {
     _marker: std::marker::PhantomData<P>,
 }
@@ -50,6 +166,8 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
     fn build(&self, app: &mut App) {
         // EVENTS
         app.add_event::<InputEvent<P::Input>>();
+        app.add_event::<InputViolationEvent>();
+        app.add_event::<DesyncEvent<P>>();
         // SETS
         app.configure_sets(FixedPreUpdate, InputSystemSet::WriteInputEvents);
         app.configure_sets(FixedPostUpdate, InputSystemSet::ClearInputEvents);
@@ -63,6 +181,11 @@ impl<P: Protocol> Plugin for InputPlugin<P> {
             FixedPostUpdate,
             clear_input_events::<P::Input>.in_set(InputSystemSet::ClearInputEvents),
         );
+        app.add_systems(
+            PreUpdate,
+            (write_desync_events::<P>, write_input_violation_events::<P>)
+                .after(InternalMainSet::<ServerMarker>::Receive),
+        );
     }
 }
 
@@ -80,8 +203,173 @@ fn write_input_event<P: Protocol>(
     }
 }
 
+/// Emit an [`InputViolationEvent`] for each native input that was rejected when it was received
+fn write_input_violation_events<P: Protocol>(
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    mut violation_events: EventWriter<InputViolationEvent>,
+) {
+    violation_events.send_batch(std::mem::take(&mut connection_manager.input_violations));
+}
+
+/// Emit a [`DesyncEvent::Clients`] for each tick where the clients reported different checksums
+fn write_desync_events<P: Protocol>(
+    tick_manager: Res<TickManager>,
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    mut desync_events: EventWriter<DesyncEvent<P>>,
+) {
+    desync_events.send_batch(
+        connection_manager
+            .checksums
+            .drain_desyncs(tick_manager.tick()),
+    );
+}
+
 /// System that clears the input events.
 /// It is necessary because events are cleared every frame, but we want to clear every tick instead
 fn clear_input_events<I: UserAction>(mut input_events: EventReader<InputEvent<I>>) {
     input_events.clear();
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::prelude::Events;
+
+    use crate::inputs::native::input_buffer::InputMessage;
+    use crate::prelude::*;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    const CLIENT_ID: ClientId = ClientId::Netcode(111);
+
+    #[test]
+    fn test_checksum_tracker() {
+        let mut tracker = ChecksumTracker::default();
+        tracker.add(ClientId::Netcode(1), Tick(1), 10);
+        tracker.add(ClientId::Netcode(2), Tick(1), 10);
+        tracker.add(ClientId::Netcode(1), Tick(2), 10);
+        assert!(tracker.drain_desyncs::<MyProtocol>(Tick(2)).is_empty());
+
+        tracker.add(ClientId::Netcode(2), Tick(2), 20);
+        // only the first mismatch is reported
+        tracker.add(ClientId::Netcode(3), Tick(2), 30);
+        assert_eq!(
+            tracker.drain_desyncs::<MyProtocol>(Tick(2)),
+            vec![DesyncEvent::Clients {
+                tick: Tick(2),
+                checksums: vec![(ClientId::Netcode(1), 10), (ClientId::Netcode(2), 20)],
+            }]
+        );
+
+        // old checksums are forgotten
+        tracker.drain_desyncs::<MyProtocol>(Tick(2) + CHECKSUM_HISTORY_TICKS);
+        assert!(tracker.reported.is_empty());
+    }
+
+    #[test]
+    fn test_input_validation() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .server_app
+            .insert_resource(InputValidator::<MyInput>::new(|_, _, input| {
+                input.0 = input.0.min(5);
+                input.0 >= 0
+            }));
+
+        // the inputs are sent by the client, several times because of the input redundancy
+        let tick = stepper.client_tick();
+        let mut input_manager = stepper
+            .client_app
+            .world
+            .resource_mut::<client::InputManager<MyInput>>();
+        input_manager.add_input(MyInput(10), tick + 1);
+        input_manager.add_input(MyInput(-1), tick + 2);
+        input_manager.add_input(MyInput(-2), tick + 3);
+
+        let mut inputs = vec![];
+        let mut violations = vec![];
+        let mut input_reader = ManualEventReader::<InputEvent<MyInput>>::default();
+        let mut violation_reader = ManualEventReader::<InputViolationEvent>::default();
+        for _ in 0..20 {
+            stepper.frame_step();
+            let events = stepper
+                .server_app
+                .world
+                .resource::<Events<InputEvent<MyInput>>>();
+            inputs.extend(input_reader.read(events).map(|event| event.input().clone()));
+            let events = stepper
+                .server_app
+                .world
+                .resource::<Events<InputViolationEvent>>();
+            violations.extend(violation_reader.read(events).cloned());
+        }
+
+        // the input is clamped, and rejected inputs are replaced with the last valid input
+        assert!(inputs.ends_with(&[Some(MyInput(5)), Some(MyInput(5)), Some(MyInput(5))]));
+        assert_eq!(
+            violations,
+            vec![
+                InputViolationEvent {
+                    client_id: CLIENT_ID,
+                    tick: tick + 2,
+                    violations: 1,
+                },
+                InputViolationEvent {
+                    client_id: CLIENT_ID,
+                    tick: tick + 3,
+                    violations: 2,
+                },
+            ]
+        );
+    }
+
+    /// Rejected inputs, and inputs for ticks that were already received, are not forwarded to the other clients
+    #[test]
+    fn test_accept_inputs() {
+        let mut stepper = BevyStepper::default();
+        let validator = InputValidator::<MyInput>::new(|_, _, input| input.0 >= 0);
+        let mut violations = vec![];
+        let mut connection_manager = stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>();
+        let connection = connection_manager.connection_mut(CLIENT_ID).unwrap();
+
+        let message = InputMessage::from_inputs(
+            Tick(10),
+            [Some(MyInput(1)), Some(MyInput(-1)), Some(MyInput(2))],
+        );
+        let accepted = connection
+            .accept_inputs(CLIENT_ID, message, Some(&validator), &mut violations)
+            .unwrap();
+        assert_eq!(
+            accepted.inputs(),
+            vec![
+                (Tick(8), Some(MyInput(1))),
+                (Tick(9), None),
+                (Tick(10), Some(MyInput(2)))
+            ]
+        );
+        assert_eq!(violations.len(), 1);
+
+        // the client cannot replace the inputs that were already received
+        let message = InputMessage::from_inputs(
+            Tick(11),
+            [Some(MyInput(-1)), Some(MyInput(-1)), Some(MyInput(3))],
+        );
+        let accepted = connection
+            .accept_inputs(CLIENT_ID, message, Some(&validator), &mut violations)
+            .unwrap();
+        assert_eq!(accepted.inputs(), vec![(Tick(11), Some(MyInput(3)))]);
+        assert_eq!(violations.len(), 1);
+
+        // older messages are ignored
+        let message = InputMessage::from_inputs(Tick(10), [Some(MyInput(-1))]);
+        assert!(connection
+            .accept_inputs(CLIENT_ID, message, Some(&validator), &mut violations)
+            .is_none());
+        assert_eq!(violations.len(), 1);
+    }
+}
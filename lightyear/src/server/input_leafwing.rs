@@ -5,12 +5,13 @@ use bevy::prelude::*;
 use leafwing_input_manager::prelude::*;
 
 use crate::_reexport::ServerMarker;
+use crate::channel::builder::InputChannel;
 use crate::client::components::Confirmed;
 use crate::client::config::ClientConfig;
 use crate::client::prediction::Predicted;
 use crate::connection::client::NetClient;
 use crate::inputs::leafwing::input_buffer::{
-    ActionDiffBuffer, ActionDiffEvent, InputBuffer, InputTarget,
+    ActionDiff, ActionDiffBuffer, ActionDiffEvent, InputBuffer, InputTarget,
 };
 use crate::inputs::leafwing::{InputMessage, LeafwingUserAction};
 use crate::prelude::client::is_in_rollback;
@@ -19,8 +20,9 @@ use crate::protocol::Protocol;
 use crate::server::config::ServerConfig;
 use crate::server::connection::ConnectionManager;
 use crate::server::events::InputMessageEvent;
+use crate::server::input::{InputValidator, InputViolationEvent};
 use crate::shared::events::connection::IterInputMessageEvent;
-use crate::shared::replication::components::PrePredicted;
+use crate::shared::replication::components::{NetworkTarget, PrePredicted};
 use crate::shared::sets::InternalMainSet;
 
 pub struct LeafwingInputPlugin<P, A> {
@@ -62,11 +64,12 @@ pub enum InputSystemSet {
 
 impl<P: Protocol, A: LeafwingUserAction> Plugin for LeafwingInputPlugin<P, A>
 where
-    P::Message: TryInto<InputMessage<A>, Error = ()>,
+    P::Message: TryInto<InputMessage<A>, Error = ()> + From<InputMessage<A>>,
 {
     fn build(&self, app: &mut App) {
         // EVENTS
         app.add_event::<InputMessageEvent<A>>();
+        app.add_event::<InputViolationEvent>();
         // RESOURCES
         // app.init_resource::<GlobalActions<A>>();
         // TODO: (global action states) add a resource tracking the action-state of all clients
@@ -125,17 +128,70 @@ fn add_action_diff_buffer<A: LeafwingUserAction>(
 }
 
 /// Read the input messages from the server events to update the ActionDiffBuffers
+///
+/// The diffs are first checked with the [`InputValidator`] (if any); rejected diffs are dropped.
+///
+/// If [`InputConfig::rebroadcast_inputs`](crate::server::input::InputConfig) is enabled, the messages are also
+/// forwarded to the other clients so that they can predict the entities controlled by the client
 fn receive_input_message<P: Protocol, A: LeafwingUserAction>(
     // mut global: Option<ResMut<ActionDiffBuffer<A>>>,
+    config: Res<ServerConfig>,
+    validator: Option<Res<InputValidator<ActionDiff<A>>>>,
     mut connection_manager: ResMut<ConnectionManager<P>>,
+    mut violation_events: EventWriter<InputViolationEvent>,
     // TODO: currently we do not handle entities that are controlled by multiple clients
     mut query: Query<&mut ActionDiffBuffer<A>>,
 ) where
-    P::Message: TryInto<InputMessage<A>, Error = ()>,
+    P::Message: TryInto<InputMessage<A>, Error = ()> + From<InputMessage<A>>,
 {
+    let mut messages_to_rebroadcast = vec![];
+    let mut rejected_inputs = vec![];
     // let manager = &mut server.connection_manager;
     for (mut message, client_id) in connection_manager.events.into_iter_input_messages::<A>() {
         debug!(action = ?A::short_type_path(), ?message.end_tick, ?message.diffs, "received input message");
+        if let Some(validator) = validator.as_deref() {
+            for (target, diffs) in message.diffs.iter_mut() {
+                // the inputs are sent with redundancy: we only report the violations for the ticks that
+                // we haven't received yet, so that each violation is only reported once
+                let received_end_tick = match target {
+                    InputTarget::Entity(entity) | InputTarget::PrePredictedEntity(entity) => query
+                        .get(*entity)
+                        .ok()
+                        .and_then(|buffer| buffer.start_tick.map(|_| buffer.end_tick())),
+                    InputTarget::Global => None,
+                };
+                let start_tick = message.end_tick - (diffs.len() as u16).saturating_sub(1);
+                for (delta, diffs_for_tick) in diffs.iter_mut().enumerate() {
+                    let tick = start_tick + delta as i16;
+                    let num_diffs = diffs_for_tick.len();
+                    diffs_for_tick.retain_mut(|diff| validator.validate(client_id, tick, diff));
+                    if diffs_for_tick.len() < num_diffs
+                        && received_end_tick.map_or(true, |end_tick| tick > end_tick)
+                    {
+                        debug!(?client_id, ?tick, "Rejected client input");
+                        rejected_inputs.push((client_id, tick));
+                    }
+                }
+            }
+        }
+        if config.input.rebroadcast_inputs {
+            let mut rebroadcast = InputMessage::<A>::new(message.end_tick);
+            rebroadcast.diffs = message
+                .diffs
+                .iter()
+                .filter_map(|(target, diffs)| match target {
+                    // the entities have already been mapped to the server's entities
+                    InputTarget::Entity(entity) | InputTarget::PrePredictedEntity(entity) => {
+                        Some((InputTarget::Entity(*entity), diffs.clone()))
+                    }
+                    // global inputs are not attached to an entity, so other clients cannot use them
+                    InputTarget::Global => None,
+                })
+                .collect();
+            if !rebroadcast.is_empty() {
+                messages_to_rebroadcast.push((rebroadcast, client_id));
+            }
+        }
 
         for (target, diffs) in std::mem::take(&mut message.diffs) {
             match target {
@@ -161,6 +217,29 @@ fn receive_input_message<P: Protocol, A: LeafwingUserAction>(
             }
         }
     }
+    for (client_id, tick) in rejected_inputs {
+        match connection_manager.record_input_violation(client_id) {
+            Ok(violations) => {
+                violation_events.send(InputViolationEvent {
+                    client_id,
+                    tick,
+                    violations,
+                });
+            }
+            Err(err) => error!("Error while recording input violation: {:?}", err),
+        }
+    }
+    for (message, client_id) in messages_to_rebroadcast {
+        connection_manager
+            .send_message_to_target::<InputChannel, InputMessage<A>>(
+                message,
+                NetworkTarget::AllExcept(vec![client_id]),
+            )
+            .map(|_| ())
+            .unwrap_or_else(|err| {
+                error!("Error while rebroadcasting input message: {:?}", err);
+            });
+    }
 }
 
 /// Read the ActionDiff for the current tick from the buffer, and use them to update the ActionState
@@ -0,0 +1,349 @@
+//! # Lag compensation
+//!
+//! Clients see the other entities in the past: the entities they see are interpolated between
+//! server updates, which themselves took some time to arrive.
+//! For example, when a client fires a hitscan weapon, the server needs to check if the shot hit
+//! by looking at the positions of the entities *as the client saw them*.
+//!
+//! The [`LagCompensationPlugin`] records on the server the history of a component for the last
+//! [`LagCompensationConfig::history_ticks`] ticks. You can then:
+//! - estimate the tick that a client was seeing with [`LagCompensation::client_view_tick`]
+//! - read the historical value of a component with [`LagCompensationHistory::get`]
+//! - or temporarily rewind the whole world to that tick with [`rewind`]
+//!
+//! ```rust,ignore
+//! app.add_plugins(LagCompensationPlugin::<Position>::default());
+//!
+//! fn handle_shots(world: &mut World) {
+//!     // ...for each shot received from `client_id`
+//!     let tick = SystemState::<LagCompensation<MyProtocol>>::new(world)
+//!         .get(world)
+//!         .client_view_tick(client_id)
+//!         .unwrap();
+//!     let hit = rewind::<Position, _>(world, tick, |world| raycast(world, shot));
+//! }
+//! ```
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+
+use anyhow::Result;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::{
+    App, Commands, Component, DetectChanges, DetectChangesMut, Entity, FixedPostUpdate,
+    IntoSystemConfigs, Plugin, Query, Ref, Res, Resource, Without, World,
+};
+
+use crate::client::interpolation::plugin::InterpolationDelay;
+use crate::connection::id::ClientId;
+use crate::protocol::Protocol;
+use crate::server::config::ServerConfig;
+use crate::server::connection::ConnectionManager;
+use crate::shared::tick_manager::{Tick, TickManager};
+
+/// Configuration of the lag compensation
+#[derive(Resource, Clone)]
+pub struct LagCompensationConfig {
+    /// Number of ticks for which we keep the history of the components
+    pub history_ticks: u16,
+    /// The interpolation delay used for a client that hasn't reported its measured interpolation delay yet
+    /// (the clients report it once they are synced).
+    /// This should match the [`InterpolationConfig`](crate::client::interpolation::plugin::InterpolationConfig) of the clients
+    pub interpolation_delay: InterpolationDelay,
+}
+
+impl Default for LagCompensationConfig {
+    fn default() -> Self {
+        Self {
+            history_ticks: 64,
+            interpolation_delay: InterpolationDelay::default(),
+        }
+    }
+}
+
+/// History of the values of a component on the server, used for lag compensation
+///
+/// We only store the values for the ticks where the component changed.
+#[derive(Component, Debug)]
+pub struct LagCompensationHistory<C> {
+    buffer: VecDeque<(Tick, C)>,
+}
+
+impl<C> Default for LagCompensationHistory<C> {
+    fn default() -> Self {
+        Self {
+            buffer: VecDeque::new(),
+        }
+    }
+}
+
+impl<C> LagCompensationHistory<C> {
+    /// Get the value that the component had at the given tick.
+    ///
+    /// Returns `None` if the tick is older than the history that we kept.
+    pub fn get(&self, tick: Tick) -> Option<&C> {
+        self.buffer
+            .iter()
+            .rev()
+            .find(|(t, _)| *t - tick <= 0)
+            .map(|(_, value)| value)
+    }
+
+    fn add(&mut self, tick: Tick, value: C, history_ticks: u16) {
+        self.buffer.push_back((tick, value));
+        // only remove the oldest value if the next value is also old enough, so that we can still
+        // get the value for all the ticks in the history
+        while self
+            .buffer
+            .get(1)
+            .map_or(false, |(t, _)| tick - *t >= history_ticks as i16)
+        {
+            self.buffer.pop_front();
+        }
+    }
+}
+
+/// Plugin that records the history of the component `C` on the server, for lag compensation
+pub struct LagCompensationPlugin<C> {
+    _marker: PhantomData<C>,
+}
+
+impl<C> Default for LagCompensationPlugin<C> {
+    fn default() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<C: Component + Clone> Plugin for LagCompensationPlugin<C> {
+    fn build(&self, app: &mut App) {
+        // RESOURCES
+        app.init_resource::<LagCompensationConfig>();
+        // SYSTEMS
+        // record the values after the simulation ran for the tick
+        app.add_systems(
+            FixedPostUpdate,
+            (add_history::<C>, record_history::<C>).chain(),
+        );
+    }
+}
+
+/// Add a history to every entity that has the component `C`
+fn add_history<C: Component + Clone>(
+    mut commands: Commands,
+    query: Query<(Entity, &C), Without<LagCompensationHistory<C>>>,
+    tick_manager: Res<TickManager>,
+) {
+    for (entity, component) in query.iter() {
+        let mut history = LagCompensationHistory::<C>::default();
+        history
+            .buffer
+            .push_back((tick_manager.tick(), component.clone()));
+        commands.entity(entity).insert(history);
+    }
+}
+
+/// Store the value of the component for the current tick if it changed
+fn record_history<C: Component + Clone>(
+    mut query: Query<(Ref<C>, &mut LagCompensationHistory<C>)>,
+    tick_manager: Res<TickManager>,
+    config: Res<LagCompensationConfig>,
+) {
+    let tick = tick_manager.tick();
+    for (component, mut history) in query.iter_mut() {
+        if component.is_changed() && !component.is_added() {
+            history.add(tick, component.clone(), config.history_ticks);
+        }
+    }
+}
+
+/// Helpers to find which tick a client was seeing
+#[derive(SystemParam)]
+pub struct LagCompensation<'w, P: Protocol> {
+    connection_manager: Res<'w, ConnectionManager<P>>,
+    tick_manager: Res<'w, TickManager>,
+    server_config: Res<'w, ServerConfig>,
+    config: Res<'w, LagCompensationConfig>,
+}
+
+impl<'w, P: Protocol> LagCompensation<'w, P> {
+    /// Estimate the server tick of the world that the client was seeing when it sent a message that
+    /// the server is receiving now.
+    ///
+    /// The client's view of the world is late by:
+    /// - the round-trip time: the server state took half an RTT to reach the client, and the client's message
+    ///   took another half RTT to reach the server
+    /// - the interpolation delay of the client, as measured by the client's sync manager
+    pub fn client_view_tick(&self, client_id: ClientId) -> Result<Tick> {
+        let connection = self.connection_manager.connection(client_id)?;
+        let rtt = connection.ping_manager.rtt();
+        let interpolation_delay = connection.interpolation_delay().unwrap_or_else(|| {
+            self.config
+                .interpolation_delay
+                .to_duration(self.server_config.shared.server_send_interval)
+        });
+        let delay_ticks = ((rtt + interpolation_delay).as_secs_f64()
+            / self.tick_manager.config.tick_duration.as_secs_f64())
+        .round() as u16;
+        Ok(self.tick_manager.tick() - delay_ticks)
+    }
+}
+
+/// Temporarily set every entity that has a [`LagCompensationHistory<C>`] to the value that its component `C`
+/// had at `tick`, and run `f` on the rewound world.
+///
+/// The components are restored to their current value after `f` runs (so any modification of `C` done by `f`
+/// is discarded). Change detection is not triggered by the rewind.
+pub fn rewind<C: Component + Clone, R>(
+    world: &mut World,
+    tick: Tick,
+    f: impl FnOnce(&mut World) -> R,
+) -> R {
+    let mut query = world.query::<(Entity, &mut C, &LagCompensationHistory<C>)>();
+    let mut current_values = vec![];
+    for (entity, mut component, history) in query.iter_mut(world) {
+        if let Some(past_value) = history.get(tick) {
+            let current_value =
+                std::mem::replace(component.bypass_change_detection(), past_value.clone());
+            current_values.push((entity, current_value));
+        }
+    }
+    let result = f(world);
+    for (entity, value) in current_values {
+        if let Some(mut component) = world.get_mut::<C>(entity) {
+            *component.bypass_change_detection() = value;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+    use bevy::utils::Duration;
+
+    use crate::prelude::client::{InterpolationConfig, PredictionConfig, SyncConfig};
+    use crate::prelude::*;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut history = LagCompensationHistory::default();
+        history.add(Tick(1), 1.0, 3);
+        history.add(Tick(3), 3.0, 3);
+        assert_eq!(history.get(Tick(0)), None);
+        assert_eq!(history.get(Tick(2)), Some(&1.0));
+        assert_eq!(history.get(Tick(3)), Some(&3.0));
+        // the value for tick 1 is still needed to know the values for ticks 2 to 3
+        history.add(Tick(5), 5.0, 3);
+        assert_eq!(history.get(Tick(2)), Some(&1.0));
+        history.add(Tick(6), 6.0, 3);
+        assert_eq!(history.get(Tick(2)), None);
+        assert_eq!(history.get(Tick(4)), Some(&3.0));
+    }
+
+    #[test]
+    fn test_client_view_tick() {
+        let tick_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(tick_duration),
+            ..Default::default()
+        };
+        let link_conditioner = LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        };
+        // the client interpolates 100ms behind the server
+        let interpolation_config = InterpolationConfig::default().with_delay(
+            InterpolationDelay::default()
+                .with_min_delay(Duration::from_millis(100))
+                .with_send_interval_ratio(0.0),
+        );
+        let mut stepper = BevyStepper::new(
+            shared_config,
+            SyncConfig::default().speedup_factor(1.0),
+            PredictionConfig::default(),
+            interpolation_config,
+            link_conditioner,
+            tick_duration,
+        );
+        stepper
+            .server_app
+            .add_plugins(LagCompensationPlugin::<Component1>::default());
+        stepper.init();
+        for _ in 0..50 {
+            stepper.frame_step();
+        }
+
+        let client_id = ClientId::Netcode(111);
+        let connection = stepper
+            .server_app
+            .world
+            .resource::<ConnectionManager<MyProtocol>>()
+            .connection(client_id)
+            .unwrap();
+        let interpolation_delay = connection.interpolation_delay().unwrap();
+        let rtt = connection.ping_manager.rtt();
+        assert!(
+            interpolation_delay >= Duration::from_millis(90)
+                && interpolation_delay <= Duration::from_millis(110),
+            "{interpolation_delay:?}"
+        );
+
+        // the server uses the delay measured by the client, and not the configured one
+        let mut system_state =
+            SystemState::<LagCompensation<MyProtocol>>::new(&mut stepper.server_app.world);
+        let view_tick = system_state
+            .get(&stepper.server_app.world)
+            .client_view_tick(client_id)
+            .unwrap();
+        let expected_delay_ticks = ((rtt + interpolation_delay).as_secs_f64()
+            / tick_duration.as_secs_f64())
+        .round() as i16;
+        assert_eq!(stepper.server_tick() - view_tick, expected_delay_ticks);
+        assert!(expected_delay_ticks >= 9);
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .server_app
+            .add_plugins(LagCompensationPlugin::<Component1>::default());
+        stepper.init();
+
+        let entity = stepper.server_app.world.spawn(Component1(0.0)).id();
+        let mut values = vec![];
+        for i in 1..10 {
+            stepper.tick_step();
+            values.push((stepper.server_tick(), i as f32 - 1.0));
+            stepper
+                .server_app
+                .world
+                .get_mut::<Component1>(entity)
+                .unwrap()
+                .0 = i as f32;
+        }
+        let (tick, value) = values[3];
+        let history = stepper
+            .server_app
+            .world
+            .get::<LagCompensationHistory<Component1>>(entity)
+            .unwrap();
+        assert_eq!(history.get(tick), Some(&Component1(value)));
+
+        // rewind the world to a past tick
+        let rewound = rewind::<Component1, _>(&mut stepper.server_app.world, tick, |world| {
+            world.get::<Component1>(entity).unwrap().clone()
+        });
+        assert_eq!(rewound, Component1(value));
+        assert_eq!(
+            stepper.server_app.world.get::<Component1>(entity),
+            Some(&Component1(9.0))
+        );
+    }
+}
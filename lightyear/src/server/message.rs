@@ -5,8 +5,13 @@ use bitcode::__private::Fixed;
 use bitcode::{Decode, Encode};
 
 use crate::_reexport::{BitSerializable, MessageProtocol, ReadBuffer, WriteBuffer};
+use crate::connection::id::ClientId;
+use crate::connection::server::DeniedReason;
 use crate::prelude::Protocol;
+use crate::shared::checksum::ChecksumMessage;
+use crate::shared::config::ResumptionSecret;
 use crate::shared::ping::message::SyncMessage;
+use crate::shared::replication::authority::AuthorityMessage;
 use crate::shared::replication::{ReplicationMessage, ReplicationMessageData};
 
 #[derive(Encode, Decode, Clone, Debug)]
@@ -21,6 +26,27 @@ pub enum ServerMessage<P: Protocol> {
     // the sync messages can be added to packets that have other messages
     #[bitcode_hint(frequency = 1)]
     Sync(SyncMessage),
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Checksum(ChecksumMessage<P::ComponentKinds>),
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Authority(AuthorityMessage<P::ComponentKinds>),
+    /// Answer to the [`ClientMessage::Handshake`](crate::client::message::ClientMessage::Handshake),
+    /// with the secret that the client can use to resume the session
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Session(ResumptionSecret),
+    /// Answer to the [`ClientMessage::Handshake`](crate::client::message::ClientMessage::Handshake)
+    /// if the server refuses the client; the server disconnects the client right after sending it
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Denied(DeniedReason),
+    /// The other clients that are connected to the server.
+    /// This is only sent if the server rebroadcasts the inputs, so that the clients know whose inputs they need.
+    #[bitcode_hint(frequency = 1)]
+    #[bitcode(with_serde)]
+    Players(Vec<ClientId>),
 }
 
 impl<P: Protocol> BitSerializable for ServerMessage<P> {
@@ -64,6 +90,9 @@ impl<P: Protocol> ServerMessage<P> {
                                 #[cfg(metrics)]
                                 metrics::counter!("send_entity_despawn").increment(1);
                             }
+                            if actions.unreplicate {
+                                trace!("Send entity unreplicate");
+                            }
                             if !actions.insert.is_empty() {
                                 let components = actions
                                     .insert
@@ -134,6 +163,21 @@ impl<P: Protocol> ServerMessage<P> {
                     metrics::counter!("send_pong", "channel" => channel_name).increment(1);
                 }
             },
+            ServerMessage::Checksum(message) => {
+                trace!(channel = ?channel_name, tick = ?message.tick, "Sending checksums");
+            }
+            ServerMessage::Authority(message) => {
+                trace!(channel = ?channel_name, ?message, "Sending authority message");
+            }
+            ServerMessage::Session(_) => {
+                trace!(channel = ?channel_name, "Sending session secret");
+            }
+            ServerMessage::Denied(reason) => {
+                trace!(channel = ?channel_name, ?reason, "Sending denied reason");
+            }
+            ServerMessage::Players(players) => {
+                trace!(channel = ?channel_name, ?players, "Sending connected players");
+            }
         }
     }
 }
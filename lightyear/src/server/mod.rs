@@ -3,23 +3,30 @@
 //! # Server
 //! The server module contains all the code that is used to run the server.
 
+pub mod checksum;
+
 pub mod config;
 
 pub mod connection;
 
 pub mod events;
 
-mod input;
+pub mod input;
 
 pub mod plugin;
 
 pub mod room;
 
+pub mod spatial;
+
 #[cfg_attr(docsrs, doc(cfg(feature = "leafwing")))]
 #[cfg(feature = "leafwing")]
 pub mod input_leafwing;
+pub mod lag_compensation;
 pub(crate) mod message;
 pub(crate) mod prediction;
 
 mod networking;
+pub mod replay;
 pub mod replication;
+pub mod rpc;
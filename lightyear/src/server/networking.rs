@@ -4,15 +4,25 @@ use bevy::ecs::system::SystemChangeTick;
 use bevy::prelude::*;
 use tracing::{debug, error, trace, trace_span};
 
+use crate::_reexport::WriteBuffer;
 use crate::_reexport::{ComponentProtocol, ServerMarker};
+use crate::connection::netcode::MAX_PACKET_SIZE;
+use crate::connection::replay::ReplayDirection;
 use crate::connection::server::{NetConfig, NetServer, ServerConnection, ServerConnections};
 use crate::prelude::{TickManager, TimeManager};
+use crate::protocol::dynamic::{push_dynamic_events, DynamicProtocol};
 use crate::protocol::message::MessageProtocol;
 use crate::protocol::Protocol;
+use crate::serialize::wordbuffer::writer::WriteWordBuffer;
 use crate::server::connection::ConnectionManager;
-use crate::server::events::{ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent};
+use crate::server::events::{
+    ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, SessionResumedEvent,
+    SessionSuspendedEvent,
+};
+use crate::server::replay::ReplayRecorder;
 use crate::server::room::RoomManager;
 use crate::shared::events::connection::{IterEntityDespawnEvent, IterEntitySpawnEvent};
+use crate::shared::events::systems::{push_message_delivery_events, push_stream_events};
 use crate::shared::replication::ReplicationSend;
 use crate::shared::sets::InternalMainSet;
 use crate::shared::time_manager::is_server_ready_to_send;
@@ -61,7 +71,12 @@ impl<P: Protocol> Plugin for ServerNetworkingPlugin<P> {
             // SYSTEMS //
             .add_systems(
                 PreUpdate,
-                (receive::<P>.in_set(InternalMainSet::<ServerMarker>::Receive),),
+                (
+                    sync_dynamic_protocol::<P>
+                        .run_if(resource_exists_and_changed::<DynamicProtocol>)
+                        .before(InternalMainSet::<ServerMarker>::Receive),
+                    receive::<P>.in_set(InternalMainSet::<ServerMarker>::Receive),
+                ),
             )
             .add_systems(
                 PostUpdate,
@@ -70,6 +85,14 @@ impl<P: Protocol> Plugin for ServerNetworkingPlugin<P> {
     }
 }
 
+/// Update the [`ConnectionManager`] when types are registered in the [`DynamicProtocol`]
+fn sync_dynamic_protocol<P: Protocol>(
+    dynamic_protocol: Res<DynamicProtocol>,
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+) {
+    connection_manager.set_dynamic_protocol(&dynamic_protocol);
+}
+
 pub(crate) fn receive<P: Protocol>(world: &mut World) {
     trace!("Receive client packets");
     world.resource_scope(|world: &mut World, mut connection_manager: Mut<ConnectionManager<P>>| {
@@ -101,24 +124,63 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                 // handle disconnections
                                                 for client_id in netserver.new_disconnections().iter().copied() {
                                                     if netservers.client_server_map.remove(&client_id).is_some() {
-                                                        connection_manager.remove(client_id);
-                                                        room_manager.client_disconnect(client_id);
+                                                        // keep the state of the client if its session can be resumed
+                                                        let forced = netservers.forced_disconnections.remove(&client_id);
+                                                        if forced || !connection_manager.suspend(client_id) {
+                                                            connection_manager.remove(client_id);
+                                                            room_manager.client_disconnect(client_id);
+                                                        }
                                                     } else {
                                                         error!("Client disconnected but could not map client_id to the corresponding netserver");
                                                     }
                                                 };
                                             }
+                                            // end the sessions of the clients that did not reconnect in time
+                                            for client_id in connection_manager.expired_sessions(delta) {
+                                                // the client reconnected but did not send its handshake in time: start a new session
+                                                let reconnected = connection_manager.awaiting_handshake.contains(&client_id);
+                                                connection_manager.remove(client_id);
+                                                room_manager.client_disconnect(client_id);
+                                                if reconnected {
+                                                    connection_manager.add(client_id);
+                                                }
+                                            }
 
                                             // update connections
                                             connection_manager
                                                 .update(time_manager.as_ref(), tick_manager.as_ref());
 
                                             // RECV_PACKETS: buffer packets into message managers
+                                            let mut recorder = world.get_resource_mut::<ReplayRecorder>();
                                             for (server_idx, netserver) in netservers.servers.iter_mut().enumerate() {
                                                 while let Some((packet, client_id)) = netserver.recv() {
+                                                    if let Some(recorder) = recorder.as_mut() {
+                                                        // the netserver only gives us the decoded packet, re-encode it
+                                                        let mut writer = WriteWordBuffer::with_capacity(MAX_PACKET_SIZE);
+                                                        match packet.encode(&mut writer) {
+                                                            Ok(()) => recorder.record(
+                                                                ReplayDirection::ClientToServer,
+                                                                client_id,
+                                                                writer.finish_write(),
+                                                                time_manager.as_ref(),
+                                                                tick_manager.as_ref(),
+                                                            ),
+                                                            Err(e) => error!("could not encode packet for replay: {:?}", e),
+                                                        }
+                                                    }
                                                     // Note: the client_id might not be present in the connection_manager if we receive
                                                     // packets from a client
                                                     // TODO: use connection to apply on BOTH message manager and replication manager
+                                                    // the client reconnected while its session was suspended
+                                                    if connection_manager.awaiting_handshake.contains(&client_id) {
+                                                        if connection_manager.recv_handshake(client_id, packet) == Some(false) {
+                                                            // a client without the secret cannot take over the session: start a new one
+                                                            connection_manager.remove(client_id);
+                                                            room_manager.client_disconnect(client_id);
+                                                            connection_manager.add(client_id);
+                                                        }
+                                                        continue;
+                                                    }
                                                     if let Ok(connection) = connection_manager
                                                         .connection_mut(client_id) {
                                                         connection.recv_packet(packet, tick_manager.as_ref()).expect("could not receive packet");
@@ -172,6 +234,33 @@ pub(crate) fn receive<P: Protocol>(world: &mut World) {
                                                     }
                                                 }
 
+                                                if !connection_manager.events.suspensions.is_empty() {
+                                                    let mut suspended_event_writer =
+                                                        world.get_resource_mut::<Events<SessionSuspendedEvent>>().unwrap();
+                                                    for client_id in connection_manager.events.iter_suspensions() {
+                                                        debug!("Client session suspended event: {}", client_id);
+                                                        suspended_event_writer.send(SessionSuspendedEvent::new(client_id));
+                                                    }
+                                                }
+
+                                                if !connection_manager.events.resumptions.is_empty() {
+                                                    let mut resumed_event_writer =
+                                                        world.get_resource_mut::<Events<SessionResumedEvent>>().unwrap();
+                                                    for client_id in connection_manager.events.iter_resumptions() {
+                                                        debug!("Client session resumed event: {}", client_id);
+                                                        resumed_event_writer.send(SessionResumedEvent::new(client_id));
+                                                    }
+                                                }
+
+                                                // Messages and component events of the DynamicProtocol
+                                                push_dynamic_events(world, &mut connection_manager.events);
+
+                                                // Stream events
+                                                push_stream_events(world, &mut connection_manager.events);
+
+                                                // Delivery of the messages we sent
+                                                push_message_delivery_events(world, &mut connection_manager.events);
+
                                                 // Message Events
                                                 P::Message::push_message_events(world, &mut connection_manager.events);
 
@@ -211,6 +300,7 @@ pub(crate) fn send<P: Protocol>(
     mut connection_manager: ResMut<ConnectionManager<P>>,
     tick_manager: Res<TickManager>,
     time_manager: Res<TimeManager>,
+    mut recorder: Option<ResMut<ReplayRecorder>>,
 ) {
     trace!("Send packets to clients");
     // finalize any packets that are needed for replication
@@ -222,12 +312,19 @@ pub(crate) fn send<P: Protocol>(
 
     // SEND_PACKETS: send buffered packets to io
     let span = trace_span!("send_packets").entered();
+    let connection_manager = &mut *connection_manager;
     connection_manager
         .connections
         .iter_mut()
         .try_for_each(|(client_id, connection)| {
             let client_span =
                 trace_span!("send_packets_to_client", client_id = ?client_id).entered();
+            // the client is disconnected but its session can still be resumed: the packets are
+            // lost, but the reliable channels will resend their messages once the client reconnects
+            if connection_manager.suspended_clients.contains_key(client_id) {
+                connection.send_packets(&time_manager, &tick_manager)?;
+                return Ok(());
+            }
             let netserver_idx = *netservers
                 .client_server_map
                 .get(client_id)
@@ -237,6 +334,15 @@ pub(crate) fn send<P: Protocol>(
                 .get_mut(netserver_idx)
                 .context("could not find server with the provided netserver idx")?;
             for packet_byte in connection.send_packets(&time_manager, &tick_manager)? {
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.record(
+                        ReplayDirection::ServerToClient,
+                        *client_id,
+                        packet_byte.as_slice(),
+                        &time_manager,
+                        &tick_manager,
+                    );
+                }
                 netserver.send(packet_byte.as_slice(), *client_id)?;
             }
             Ok(())
@@ -245,9 +351,25 @@ pub(crate) fn send<P: Protocol>(
             error!("Error sending packets: {}", e);
         });
 
+    if let Some(recorder) = recorder.as_mut() {
+        recorder.flush();
+    }
+
+    // disconnect the clients whose handshake was refused, now that they received the reason
+    for (client_id, connection) in connection_manager.connections.iter() {
+        if connection.denied_reason.is_some() {
+            if let Err(e) = netservers.disconnect(*client_id) {
+                error!(?client_id, "could not disconnect denied client: {:?}", e);
+            }
+        }
+    }
+
     // clear the list of newly connected clients
     // (cannot just use the ConnectionEvent because it is cleared after each frame)
     connection_manager.new_clients.clear();
+    // the RpcPlugins resolved the requests of the clients that disconnected during this frame;
+    // the remaining requests have no RpcPlugin, so nobody would ever resolve them
+    connection_manager.disconnected_requests.clear();
 }
 
 /// Clear the received events
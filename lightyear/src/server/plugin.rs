@@ -49,6 +49,7 @@ impl<P: Protocol> ServerPlugin<P> {
 impl<P: Protocol> Plugin for ServerPlugin<P> {
     fn build(&self, app: &mut App) {
         let config = self.config.lock().unwrap().deref_mut().take().unwrap();
+        let protocol_schema = config.protocol.schema();
 
         app
             // RESOURCES //
@@ -57,6 +58,9 @@ impl<P: Protocol> Plugin for ServerPlugin<P> {
                 config.protocol.channel_registry().clone(),
                 config.server_config.packet,
                 config.server_config.ping,
+                config.server_config.resumption,
+                config.server_config.input,
+                protocol_schema,
             ))
             // PLUGINS
             .add_plugins(ServerEventsPlugin::<P>::default())
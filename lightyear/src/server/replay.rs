@@ -0,0 +1,193 @@
+//! Record all the packets exchanged by the server, so that they can be replayed later
+//!
+//! The recording can be fed back into a client with a [`ReplayPlayer`](crate::connection::replay::ReplayPlayer).
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use bevy::prelude::{App, Plugin, Resource};
+use tracing::error;
+
+use crate::connection::id::ClientId;
+use crate::connection::replay::{ReplayDirection, ReplayRecord};
+use crate::prelude::{TickManager, TimeManager};
+
+/// Resource that writes every packet sent or received by the server to a writer
+///
+/// The server networking systems record packets only if this resource is present.
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    writer: Box<dyn Write + Send + Sync>,
+}
+
+impl ReplayRecorder {
+    pub fn new(writer: impl Write + Send + Sync + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+        }
+    }
+
+    /// Create a recorder that writes to a new file at `path`
+    pub fn from_file(path: impl Into<PathBuf>) -> Result<Self> {
+        let file = File::create(path.into()).context("could not create replay file")?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+
+    /// Record a packet
+    pub(crate) fn record(
+        &mut self,
+        direction: ReplayDirection,
+        client_id: ClientId,
+        payload: &[u8],
+        time_manager: &TimeManager,
+        tick_manager: &TickManager,
+    ) {
+        let record = ReplayRecord {
+            direction,
+            tick: tick_manager.tick(),
+            time_ms: time_manager.current_time().millis(),
+            client_id,
+            payload: payload.to_vec(),
+        };
+        record
+            .write(&mut self.writer)
+            .unwrap_or_else(|e| error!("could not record packet: {:?}", e));
+    }
+
+    /// Flush the recorded packets to the underlying writer
+    pub(crate) fn flush(&mut self) {
+        self.writer
+            .flush()
+            .unwrap_or_else(|e| error!("could not flush replay recorder: {:?}", e));
+    }
+}
+
+/// Plugin that records all the packets sent or received by the server to a file.
+///
+/// Must be added after the [`ServerPlugin`](crate::server::plugin::ServerPlugin).
+pub struct ReplayRecorderPlugin {
+    path: PathBuf,
+}
+
+impl ReplayRecorderPlugin {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl Plugin for ReplayRecorderPlugin {
+    fn build(&self, app: &mut App) {
+        // the recording is optional: if the file cannot be created, the server runs without it
+        match ReplayRecorder::from_file(self.path.clone()) {
+            Ok(recorder) => {
+                app.insert_resource(recorder);
+            }
+            Err(e) => error!(
+                path = ?self.path,
+                "could not start the replay recorder, packets will not be recorded: {:?}", e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use bevy::utils::Duration;
+
+    use crate::client::networking::NetworkingState;
+    use crate::connection::client::NetClient;
+    use crate::connection::replay::ReplayPlayer;
+    use crate::prelude::client::*;
+    use crate::prelude::*;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let frame_duration = Duration::from_millis(10);
+        let tick_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(tick_duration),
+            ..Default::default()
+        };
+        let link_conditioner = LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        };
+        let mut stepper = BevyStepper::new(
+            shared_config,
+            SyncConfig::default(),
+            PredictionConfig::default(),
+            InterpolationConfig::default(),
+            link_conditioner,
+            frame_duration,
+        );
+        stepper.init();
+
+        let buffer = SharedBuffer::default();
+        stepper
+            .server_app
+            .world
+            .insert_resource(ReplayRecorder::new(buffer.clone()));
+        stepper
+            .server_app
+            .world
+            .spawn((Component1(1.0), Replicate::default()));
+        // step long enough for the client to send some pings
+        for _ in 0..20 {
+            stepper.frame_step();
+        }
+
+        let client_id = ClientId::Netcode(111);
+        let records = ReplayRecord::read_all(&mut buffer.0.lock().unwrap().as_slice()).unwrap();
+        let num_sent = records
+            .iter()
+            .filter(|r| r.direction == ReplayDirection::ServerToClient && r.client_id == client_id)
+            .count();
+        assert!(num_sent > 0);
+        assert!(records
+            .iter()
+            .any(|r| r.direction == ReplayDirection::ClientToServer && r.client_id == client_id));
+
+        // the player delivers all the packets that the server sent to the client
+        let mut player = ReplayPlayer::new(records, client_id);
+        player.connect().unwrap();
+        assert_eq!(player.state(), NetworkingState::Connected);
+        player.send(&[]).unwrap();
+        player.try_update(1.0).unwrap();
+        let mut num_received = 0;
+        while player.recv().is_some() {
+            num_received += 1;
+        }
+        assert_eq!(num_received, num_sent);
+        assert!(player.is_finished());
+    }
+
+    #[test]
+    fn test_recorder_plugin_invalid_path() {
+        let mut app = App::new();
+        app.add_plugins(ReplayRecorderPlugin::new(
+            "missing/directory/session.replay",
+        ));
+        assert!(app.world.get_resource::<ReplayRecorder>().is_none());
+    }
+}
@@ -2,8 +2,10 @@ use bevy::ecs::query::QueryFilter;
 use bevy::prelude::*;
 
 use crate::_reexport::ServerMarker;
+use crate::channel::builder::ChannelDirection;
 use crate::client::components::Confirmed;
 use crate::client::interpolation::Interpolated;
+use crate::client::prediction::prespawn::PreSpawnHashConfig;
 use crate::client::prediction::Predicted;
 use crate::connection::client::NetClient;
 use crate::prelude::client::ClientConnection;
@@ -60,6 +62,7 @@ impl<P: Protocol> Plugin for ServerReplicationPlugin<P> {
                 config.shared.tick.tick_duration,
                 config.replication.enable_send,
                 config.replication.enable_receive,
+                ChannelDirection::ServerToClient,
             ))
             // SYSTEM SETS
             .configure_sets(
@@ -81,7 +84,12 @@ impl<P: Protocol> Plugin for ServerReplicationPlugin<P> {
                 PostUpdate,
                 (compute_hash::<P>
                     .in_set(InternalReplicationSet::<ServerMarker>::SetPreSpawnedHash),),
-            );
+            )
+            // compute the hash at the end of the tick for the entities spawned during FixedUpdate,
+            // so that we use their exact spawn tick
+            .add_systems(FixedPostUpdate, compute_hash::<P>);
+        // RESOURCES
+        app.init_resource::<PreSpawnHashConfig<P>>();
 
         if app.world.resource::<ServerConfig>().shared.mode == Mode::HostServer {
             app.add_systems(
@@ -0,0 +1,181 @@
+/*! Plugin to handle the [`Request`]s sent to the clients, and the requests received from the clients
+
+See the [`rpc`](crate::shared::rpc) module for more information.
+*/
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::system::{BoxedSystem, SystemId};
+use bevy::prelude::{
+    App, EventReader, EventWriter, Events, IntoSystem, IntoSystemConfigs, Local, Plugin, PreUpdate,
+    Res, ResMut, Resource, World,
+};
+use bevy::utils::Duration;
+use tracing::error;
+
+use crate::connection::id::ClientId;
+use crate::prelude::{Channel, ChannelKind};
+use crate::protocol::Protocol;
+use crate::server::connection::ConnectionManager;
+use crate::server::events::{MessageEvent, ResponseEvent};
+use crate::shared::rpc::{Request, RequestMessage, ResponseMessage, RpcError, DEFAULT_RPC_TIMEOUT};
+use crate::shared::sets::{InternalMainSet, ServerMarker};
+
+/// Plugin that emits a [`ResponseEvent`] for each [`Request`] of type `R` sent to a client,
+/// when the client responds, when the request times out or when the client disconnects.
+///
+/// The requests of type `R` received from the clients can be handled either by reading the
+/// [`MessageEvent<RequestMessage<R>>`](MessageEvent) events and calling [`ConnectionManager::send_response`],
+/// or by registering a handler system with [`RpcPlugin::with_handler`].
+pub struct RpcPlugin<P: Protocol, R: Request> {
+    timeout: Duration,
+    handler: Mutex<Option<(ChannelKind, BoxedSystem<(ClientId, R), R::Response>)>>,
+    _marker: PhantomData<P>,
+}
+
+impl<P: Protocol, R: Request> Default for RpcPlugin<P, R> {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_RPC_TIMEOUT,
+            handler: Mutex::new(None),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol, R: Request> RpcPlugin<P, R> {
+    /// Delay after which a request that did not receive a response fails with [`RpcError::Timeout`]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Respond to the requests of type `R` received from the clients with the output of the `handler` system,
+    /// which receives the [`ClientId`] of the client that sent the request. The responses are sent on the channel `C`.
+    pub fn with_handler<C: Channel, M>(
+        self,
+        handler: impl IntoSystem<(ClientId, R), R::Response, M> + 'static,
+    ) -> Self {
+        *self.handler.lock().unwrap() = Some((
+            ChannelKind::of::<C>(),
+            Box::new(IntoSystem::into_system(handler)),
+        ));
+        self
+    }
+}
+
+#[derive(Resource)]
+struct RpcSettings<R> {
+    timeout: Duration,
+    _marker: PhantomData<R>,
+}
+
+#[derive(Resource)]
+struct RequestHandler<R: Request> {
+    channel: ChannelKind,
+    system: SystemId<(ClientId, R), R::Response>,
+}
+
+impl<P: Protocol, R: Request> Plugin for RpcPlugin<P, R>
+where
+    P::Message: From<RequestMessage<R>> + From<ResponseMessage<R::Response>>,
+{
+    fn build(&self, app: &mut App) {
+        app.add_event::<ResponseEvent<R::Response>>()
+            .insert_resource(RpcSettings::<R> {
+                timeout: self.timeout,
+                _marker: PhantomData,
+            })
+            .add_systems(
+                PreUpdate,
+                receive_responses::<P, R>.after(InternalMainSet::<ServerMarker>::Receive),
+            );
+        if let Some((channel, handler)) = self.handler.lock().unwrap().take() {
+            let system = app.world.register_boxed_system(handler);
+            app.insert_resource(RequestHandler::<R> { channel, system })
+                .add_systems(
+                    PreUpdate,
+                    handle_requests::<P, R>.after(InternalMainSet::<ServerMarker>::Receive),
+                );
+        }
+    }
+}
+
+/// Emit the responses received from the clients, and the requests that timed out or whose client disconnected
+fn receive_responses<P: Protocol, R: Request>(
+    settings: Res<RpcSettings<R>>,
+    mut connection_manager: ResMut<ConnectionManager<P>>,
+    mut messages: EventReader<MessageEvent<ResponseMessage<R::Response>>>,
+    mut events: EventWriter<ResponseEvent<R::Response>>,
+) {
+    for message in messages.read() {
+        let client_id = *message.context();
+        let message = message.message();
+        let Ok(connection) = connection_manager.connection_mut(client_id) else {
+            continue;
+        };
+        if connection.rpc.receive_response(message.id()) {
+            events.send(ResponseEvent::new(
+                message.id(),
+                Ok(message.response().clone()),
+                client_id,
+            ));
+        }
+    }
+    for (client_id, connection) in connection_manager.connections.iter_mut() {
+        for request_id in connection.rpc.take_expired::<R>(settings.timeout) {
+            events.send(ResponseEvent::new(
+                request_id,
+                Err(RpcError::Timeout),
+                *client_id,
+            ));
+        }
+    }
+    // the requests of the clients that disconnected are dropped at the end of the frame
+    for (client_id, rpc) in connection_manager.disconnected_requests.iter_mut() {
+        for request_id in rpc.take_pending::<R>() {
+            events.send(ResponseEvent::new(
+                request_id,
+                Err(RpcError::Disconnected),
+                *client_id,
+            ));
+        }
+    }
+}
+
+/// Run the handler system on the requests received from the clients, and send back the responses
+fn handle_requests<P: Protocol, R: Request>(
+    world: &mut World,
+    mut reader: Local<ManualEventReader<MessageEvent<RequestMessage<R>>>>,
+) where
+    P::Message: From<ResponseMessage<R::Response>>,
+{
+    let requests: Vec<(ClientId, RequestMessage<R>)> = reader
+        .read(world.resource::<Events<MessageEvent<RequestMessage<R>>>>())
+        .map(|event| (*event.context(), event.message().clone()))
+        .collect();
+    if requests.is_empty() {
+        return;
+    }
+    let handler = world.resource::<RequestHandler<R>>();
+    let (channel, system) = (handler.channel, handler.system);
+    for (client_id, request) in requests {
+        let response =
+            match world.run_system_with_input(system, (client_id, request.request().clone())) {
+                Ok(response) => response,
+                Err(e) => {
+                    error!("Error while running the request handler: {:?}", e);
+                    continue;
+                }
+            };
+        let message = ResponseMessage::new(request.id(), response);
+        if let Err(e) = world
+            .resource_mut::<ConnectionManager<P>>()
+            .connection_mut(client_id)
+            .and_then(|connection| connection.send_message(message.into(), channel))
+        {
+            error!("Error while sending the response to a request: {:?}", e);
+        }
+    }
+}
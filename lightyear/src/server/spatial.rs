@@ -0,0 +1,300 @@
+//! # Spatial interest management
+//!
+//! This module provides an alternative to [`Rooms`](crate::server::room::Room) to perform interest management:
+//! entities are replicated only to the clients that are close to them.
+//!
+//! Each client is represented by an entity with a [`SpatialObserver`] component (usually the entity controlled by the client).
+//! Entities that use [`ReplicationMode::Spatial`] are replicated to a client only if they are close enough to the client's
+//! observer entity. The positions are read from a component that implements [`SpatialPosition`].
+//!
+//! ```rust,ignore
+//! app.add_plugins(SpatialInterestPlugin::<MyProtocol, Transform>::new(SpatialInterestMode::Radius(100.0)));
+//!
+//! // the player entity determines what the client can see
+//! commands.spawn((Transform::default(), SpatialObserver(client_id)));
+//! // this entity is only replicated to clients within 100 units of it
+//! commands.spawn((Transform::default(), Replicate {
+//!     replication_mode: ReplicationMode::Spatial,
+//!     ..default()
+//! }));
+//! ```
+use bevy::app::App;
+use bevy::math::IVec3;
+use bevy::prelude::{
+    Component, IntoSystemConfigs, Plugin, PostUpdate, Query, Res, Resource, Transform, Vec3,
+};
+use bevy::utils::{HashMap, HashSet};
+
+use crate::connection::id::ClientId;
+use crate::protocol::Protocol;
+use crate::server::room::{ClientVisibility, RoomSystemSets};
+use crate::shared::replication::components::{Replicate, ReplicationMode};
+
+/// Component that provides the position used for spatial interest management
+pub trait SpatialPosition: Component {
+    fn spatial_position(&self) -> Vec3;
+}
+
+impl SpatialPosition for Transform {
+    fn spatial_position(&self) -> Vec3 {
+        self.translation
+    }
+}
+
+/// Marks the entity whose position determines which entities are visible to the client
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SpatialObserver(pub ClientId);
+
+/// How to decide if an entity is close enough to a client to be replicated
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub enum SpatialInterestMode {
+    /// The world is divided into cubic cells of size `cell_size`.
+    /// An entity is visible to a client if it is at most `view_distance` cells away from the client's cell
+    /// (along each axis).
+    Grid { cell_size: f32, view_distance: u32 },
+    /// An entity is visible to a client if it is at most at this distance from the client
+    Radius(f32),
+}
+
+impl SpatialInterestMode {
+    fn cell_size(&self) -> f32 {
+        match self {
+            SpatialInterestMode::Grid { cell_size, .. } => *cell_size,
+            SpatialInterestMode::Radius(radius) => *radius,
+        }
+    }
+
+    /// Number of neighbouring cells (along each axis) that can contain visible clients
+    fn cell_range(&self) -> i32 {
+        match self {
+            SpatialInterestMode::Grid { view_distance, .. } => *view_distance as i32,
+            SpatialInterestMode::Radius(_) => 1,
+        }
+    }
+
+    fn cell(&self, position: Vec3) -> IVec3 {
+        (position / self.cell_size()).floor().as_ivec3()
+    }
+
+    /// Returns true if an entity at `entity` is visible to an observer at `observer`
+    fn is_visible(&self, entity: Vec3, observer: Vec3) -> bool {
+        match self {
+            SpatialInterestMode::Grid { .. } => {
+                let distance = (self.cell(entity) - self.cell(observer)).abs();
+                distance.max_element() <= self.cell_range()
+            }
+            SpatialInterestMode::Radius(radius) => {
+                entity.distance_squared(observer) <= radius * radius
+            }
+        }
+    }
+}
+
+/// Plugin that updates the visibility of entities that use [`ReplicationMode::Spatial`]
+/// based on their distance to each client's [`SpatialObserver`].
+///
+/// `Pos` is the component used to read the positions of the entities and the observers.
+pub struct SpatialInterestPlugin<P: Protocol, Pos: SpatialPosition = Transform> {
+    mode: SpatialInterestMode,
+    _marker: std::marker::PhantomData<(P, Pos)>,
+}
+
+impl<P: Protocol, Pos: SpatialPosition> SpatialInterestPlugin<P, Pos> {
+    pub fn new(mode: SpatialInterestMode) -> Self {
+        Self {
+            mode,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<P: Protocol, Pos: SpatialPosition> Plugin for SpatialInterestPlugin<P, Pos> {
+    fn build(&self, app: &mut App) {
+        // RESOURCES
+        app.insert_resource(self.mode);
+        // SYSTEMS
+        app.add_systems(
+            PostUpdate,
+            update_spatial_replication_cache::<P, Pos>
+                .in_set(RoomSystemSets::UpdateReplicationCaches),
+        );
+    }
+}
+
+/// Update the replication-client-list of each entity that uses [`ReplicationMode::Spatial`]
+/// according to the current positions of the entity and of the observers
+fn update_spatial_replication_cache<P: Protocol, Pos: SpatialPosition>(
+    mode: Res<SpatialInterestMode>,
+    observers: Query<(&SpatialObserver, &Pos)>,
+    mut query: Query<(&mut Replicate<P>, &Pos)>,
+) {
+    // bucket the observers by cell, so that we only check the observers in the neighbouring cells
+    let mut grid: HashMap<IVec3, Vec<(ClientId, Vec3)>> = HashMap::default();
+    for (observer, pos) in observers.iter() {
+        let position = pos.spatial_position();
+        grid.entry(mode.cell(position))
+            .or_default()
+            .push((observer.0, position));
+    }
+    let range = mode.cell_range();
+
+    let mut visible = HashSet::default();
+    for (mut replicate, pos) in query.iter_mut() {
+        if replicate.replication_mode != ReplicationMode::Spatial {
+            continue;
+        }
+        let position = pos.spatial_position();
+        let cell = mode.cell(position);
+        visible.clear();
+        for x in -range..=range {
+            for y in -range..=range {
+                for z in -range..=range {
+                    let Some(observers) = grid.get(&(cell + IVec3::new(x, y, z))) else {
+                        continue;
+                    };
+                    visible.extend(
+                        observers
+                            .iter()
+                            .filter(|(_, observer)| mode.is_visible(position, *observer))
+                            .map(|(client_id, _)| *client_id),
+                    );
+                }
+            }
+        }
+
+        // avoid triggering change detection if the visibility did not change
+        let cache = &replicate.replication_clients_cache;
+        if cache.len() == visible.len() && visible.iter().all(|c| cache.contains_key(c)) {
+            continue;
+        }
+        let cache = &mut replicate.replication_clients_cache;
+        for (client_id, visibility) in cache.iter_mut() {
+            if !visible.contains(client_id) {
+                *visibility = ClientVisibility::Lost;
+            }
+        }
+        for client_id in visible.iter() {
+            cache
+                .entry(*client_id)
+                .and_modify(|vis| {
+                    if *vis == ClientVisibility::Lost {
+                        *vis = ClientVisibility::Maintained
+                    }
+                })
+                .or_insert(ClientVisibility::Gained);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Transform;
+
+    use crate::prelude::*;
+    use crate::shared::replication::components::ReplicationMode;
+    use crate::tests::protocol::Replicate;
+    use crate::tests::protocol::*;
+    use crate::tests::stepper::{BevyStepper, Step};
+
+    use super::*;
+
+    #[test]
+    fn test_is_visible() {
+        let radius = SpatialInterestMode::Radius(10.0);
+        assert!(radius.is_visible(Vec3::new(6.0, 8.0, 0.0), Vec3::ZERO));
+        assert!(!radius.is_visible(Vec3::new(6.0, 9.0, 0.0), Vec3::ZERO));
+
+        let grid = SpatialInterestMode::Grid {
+            cell_size: 10.0,
+            view_distance: 1,
+        };
+        assert!(grid.is_visible(Vec3::new(19.0, -5.0, 0.0), Vec3::new(1.0, 1.0, 0.0)));
+        assert!(!grid.is_visible(Vec3::new(20.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 0.0)));
+    }
+
+    #[test]
+    fn test_spatial_visibility() {
+        let mut stepper = BevyStepper::default();
+        stepper
+            .server_app
+            .add_plugins(SpatialInterestPlugin::<MyProtocol>::new(
+                SpatialInterestMode::Radius(10.0),
+            ));
+
+        let client_id = ClientId::Netcode(111);
+        let observer = stepper
+            .server_app
+            .world
+            .spawn((Transform::default(), SpatialObserver(client_id)))
+            .id();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Transform::from_xyz(5.0, 0.0, 0.0),
+                Replicate {
+                    replication_mode: ReplicationMode::Spatial,
+                    ..Default::default()
+                },
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // the entity is close to the client, so it is replicated
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        assert_eq!(
+            stepper
+                .server_app
+                .world
+                .get::<Replicate>(server_entity)
+                .unwrap()
+                .replication_clients_cache
+                .get(&client_id),
+            Some(&ClientVisibility::Maintained)
+        );
+
+        // the observer moves away: the entity gets despawned on the client
+        stepper
+            .server_app
+            .world
+            .get_mut::<Transform>(observer)
+            .unwrap()
+            .translation = Vec3::new(100.0, 0.0, 0.0);
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper.client_app.world.get_entity(client_entity).is_none());
+        assert!(stepper
+            .server_app
+            .world
+            .get::<Replicate>(server_entity)
+            .unwrap()
+            .replication_clients_cache
+            .is_empty());
+
+        // the entity moves close to the observer: it gets replicated again
+        stepper
+            .server_app
+            .world
+            .get_mut::<Transform>(server_entity)
+            .unwrap()
+            .translation = Vec3::new(95.0, 0.0, 0.0);
+        stepper.frame_step();
+        stepper.frame_step();
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .is_some());
+    }
+}
@@ -0,0 +1,70 @@
+//! Checksums of the component values, used to detect when the simulations of different peers diverge
+use std::hash::{Hash, Hasher};
+
+use bevy::prelude::{Entity, Event};
+use serde::{Deserialize, Serialize};
+
+use crate::_reexport::WriteWordBuffer;
+use crate::connection::id::ClientId;
+use crate::packet::message::Message;
+use crate::protocol::Protocol;
+use crate::serialize::writer::WriteBuffer;
+use crate::shared::tick_manager::Tick;
+
+/// Hash the serialized value of a component, so that the checksum is the same in every process
+/// (contrary to hashing the `TypeId` or using a randomly-seeded hasher)
+pub(crate) fn component_checksum<K: Hash, C: Message>(
+    kind: &K,
+    component: &C,
+    writer: &mut WriteWordBuffer,
+) -> u64 {
+    let mut hasher = seahash::SeaHasher::new();
+    kind.hash(&mut hasher);
+    writer.start_write();
+    if component.encode(writer).is_ok() {
+        writer.finish_write().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Bevy [`Event`] emitted when the simulations of different peers diverged
+#[derive(Event, Debug, Clone, PartialEq)]
+pub enum DesyncEvent<P: Protocol> {
+    /// Emitted on the server when clients running in deterministic mode reported different checksums
+    /// for the same tick
+    Clients {
+        tick: Tick,
+        /// The checksums that the clients reported for that tick
+        checksums: Vec<(ClientId, u64)>,
+    },
+    /// Emitted on the client when the state of an entity differs from the state of the server
+    Entity {
+        /// The server tick at which the states differ
+        tick: Tick,
+        /// The local entity whose state differs
+        entity: Entity,
+        /// The kinds of the components whose values differ
+        kinds: Vec<P::ComponentKinds>,
+    },
+}
+
+impl<P: Protocol> DesyncEvent<P> {
+    /// The tick at which the simulations diverged
+    pub fn tick(&self) -> Tick {
+        match self {
+            DesyncEvent::Clients { tick, .. } | DesyncEvent::Entity { tick, .. } => *tick,
+        }
+    }
+}
+
+/// Maximum number of checksums in a [`ChecksumMessage`], so that the message fits in a single packet
+/// (each checksum takes at most 8 bytes for the entity, a few bytes for the kind and 8 bytes for the hash)
+pub(crate) const MAX_CHECKSUMS_PER_MESSAGE: usize = 48;
+
+/// Checksums of the replicated components of the server at a given tick
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChecksumMessage<K> {
+    pub(crate) tick: Tick,
+    /// The checksum of each component, identified by the server entity and the component kind
+    pub(crate) checksums: Vec<(Entity, K, u64)>,
+}
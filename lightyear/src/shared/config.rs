@@ -32,6 +32,37 @@ pub enum Mode {
     ClientOnly,
 }
 
+/// Secret issued by the server to a client, that the client must present to resume its session
+pub(crate) type ResumptionSecret = [u8; 32];
+
+/// Configuration of the session resumption.
+///
+/// When a client gets disconnected (for example because of a transient network failure), the server keeps
+/// the client's connection state for the duration of the `grace_period`.
+/// If the client reconnects with the same [`ClientId`](crate::prelude::ClientId) before the end of the grace period, and
+/// presents the secret that the server issued to it during the previous session, the session is resumed:
+/// the client keeps its room memberships and its mapping of replicated entities, and only receives the updates it missed.
+/// A client that reconnects with the same `ClientId` but without the secret starts a new session instead.
+///
+/// The configuration should be the same on the client and the server.
+#[derive(Clone, Debug, Default, PartialEq, Reflect)]
+pub struct ResumptionConfig {
+    /// How long the server keeps the state of a disconnected client.
+    /// A duration of 0 disables session resumption.
+    pub grace_period: Duration,
+}
+
+impl ResumptionConfig {
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        !self.grace_period.is_zero()
+    }
+}
+
 impl SharedConfig {
     pub fn is_host_server_condition(config: Option<Res<ServerConfig>>) -> bool {
         config.map_or(false, |config| {
@@ -4,9 +4,12 @@ use std::marker::PhantomData;
 
 use bevy::prelude::{Component, Entity, Event};
 
+use crate::channel::stream::StreamDirection;
 #[cfg(feature = "leafwing")]
 use crate::inputs::leafwing::InputMessage;
-use crate::packet::message::Message;
+use crate::packet::message::{Message, MessageId};
+use crate::protocol::channel::ChannelKind;
+use crate::shared::rpc::{RequestId, RpcError};
 
 /// This event is emitted whenever a client connects to the server
 #[derive(Event)]
@@ -219,3 +222,211 @@ impl<C: Component, Ctx> ComponentRemoveEvent<C, Ctx> {
         &self.context
     }
 }
+
+/// Event emitted when a stream sent or received on a [`ChannelMode::Streaming`](crate::channel::builder::ChannelMode::Streaming)
+/// channel makes progress.
+///
+/// The sender emits an event with 0 bytes when the stream is buffered, then whenever chunks are acked.
+/// The receiver emits an event whenever new chunks are received. A stream emits at most one event per frame.
+#[derive(Event, Debug)]
+pub struct StreamProgressEvent<Ctx = ()> {
+    channel: ChannelKind,
+    stream_id: MessageId,
+    direction: StreamDirection,
+    bytes: u64,
+    total: u64,
+    context: Ctx,
+}
+
+impl<Ctx> StreamProgressEvent<Ctx> {
+    pub fn new(
+        channel: ChannelKind,
+        stream_id: MessageId,
+        direction: StreamDirection,
+        bytes: u64,
+        total: u64,
+        context: Ctx,
+    ) -> Self {
+        Self {
+            channel,
+            stream_id,
+            direction,
+            bytes,
+            total,
+            context,
+        }
+    }
+
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    /// Id of the stream, which is the [`MessageId`] of the payload on the sender
+    pub fn stream_id(&self) -> MessageId {
+        self.stream_id
+    }
+
+    pub fn direction(&self) -> StreamDirection {
+        self.direction
+    }
+
+    /// Number of bytes that were acked (when sending) or received (when receiving)
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Size of the payload in bytes
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.bytes == self.total
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// Event emitted when a stream is cancelled by the sender, or rejected by the receiver because
+/// it goes over one of the limits of the [`StreamSettings`](crate::channel::builder::StreamSettings)
+#[derive(Event, Debug)]
+pub struct StreamCancelledEvent<Ctx = ()> {
+    channel: ChannelKind,
+    stream_id: MessageId,
+    direction: StreamDirection,
+    context: Ctx,
+}
+
+impl<Ctx> StreamCancelledEvent<Ctx> {
+    pub fn new(
+        channel: ChannelKind,
+        stream_id: MessageId,
+        direction: StreamDirection,
+        context: Ctx,
+    ) -> Self {
+        Self {
+            channel,
+            stream_id,
+            direction,
+            context,
+        }
+    }
+
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    pub fn stream_id(&self) -> MessageId {
+        self.stream_id
+    }
+
+    pub fn direction(&self) -> StreamDirection {
+        self.direction
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// Event emitted when a message sent on a channel that watches acks
+/// (see [`ChannelMode::is_watching_acks`](crate::channel::builder::ChannelMode::is_watching_acks))
+/// was received by the remote
+#[derive(Event, Debug)]
+pub struct MessageAckEvent<Ctx = ()> {
+    channel: ChannelKind,
+    message_id: MessageId,
+    context: Ctx,
+}
+
+impl<Ctx> MessageAckEvent<Ctx> {
+    pub fn new(channel: ChannelKind, message_id: MessageId, context: Ctx) -> Self {
+        Self {
+            channel,
+            message_id,
+            context,
+        }
+    }
+
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    /// The [`MessageId`] returned when the message was sent
+    pub fn message_id(&self) -> MessageId {
+        self.message_id
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// Event emitted when a message sent on an unreliable channel that watches acks
+/// (for example [`ChannelMode::UnorderedUnreliableWithAcks`](crate::channel::builder::ChannelMode::UnorderedUnreliableWithAcks))
+/// was lost.
+///
+/// Messages sent on reliable channels are never lost: they are sent again until they are acked.
+#[derive(Event, Debug)]
+pub struct MessageLostEvent<Ctx = ()> {
+    channel: ChannelKind,
+    message_id: MessageId,
+    context: Ctx,
+}
+
+impl<Ctx> MessageLostEvent<Ctx> {
+    pub fn new(channel: ChannelKind, message_id: MessageId, context: Ctx) -> Self {
+        Self {
+            channel,
+            message_id,
+            context,
+        }
+    }
+
+    pub fn channel(&self) -> ChannelKind {
+        self.channel
+    }
+
+    /// The [`MessageId`] returned when the message was sent
+    pub fn message_id(&self) -> MessageId {
+        self.message_id
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
+
+/// Event emitted when the response to a [`Request`](crate::shared::rpc::Request) sent to the remote is received,
+/// or when no response was received before the timeout
+#[derive(Event, Debug)]
+pub struct ResponseEvent<M: Message, Ctx = ()> {
+    request_id: RequestId,
+    response: Result<M, RpcError>,
+    context: Ctx,
+}
+
+impl<M: Message, Ctx> ResponseEvent<M, Ctx> {
+    pub fn new(request_id: RequestId, response: Result<M, RpcError>, context: Ctx) -> Self {
+        Self {
+            request_id,
+            response,
+            context,
+        }
+    }
+
+    /// The [`RequestId`] returned when the request was sent
+    pub fn request_id(&self) -> RequestId {
+        self.request_id
+    }
+
+    pub fn response(&self) -> Result<&M, RpcError> {
+        self.response.as_ref().map_err(|e| *e)
+    }
+
+    pub fn context(&self) -> &Ctx {
+        &self.context
+    }
+}
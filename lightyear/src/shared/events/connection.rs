@@ -7,8 +7,10 @@ use bevy::utils::HashMap;
 use tracing::trace;
 
 use crate::_reexport::{FromType, MessageProtocol};
+use crate::channel::stream::StreamUpdate;
 #[cfg(feature = "leafwing")]
 use crate::inputs::leafwing::{InputMessage, LeafwingUserAction};
+use crate::packet::delivery::MessageDelivery;
 use crate::packet::message::Message;
 use crate::prelude::Tick;
 use crate::protocol::channel::ChannelKind;
@@ -41,6 +43,10 @@ pub struct ConnectionEvents<P: Protocol> {
     //  let's just start with the kind...
     //  also, normally the updates are sequenced
     pub component_updates: HashMap<P::ComponentKinds, Vec<Entity>>,
+    // streams
+    pub(crate) stream_updates: Vec<(ChannelKind, StreamUpdate)>,
+    // delivery of the messages we sent
+    pub(crate) message_deliveries: Vec<(ChannelKind, MessageDelivery)>,
     // // TODO: what happens if we receive on the same frame an Update for tick 4 and update for tick 10?
     // //  can we just discard the older one? what about for inserts/removes?
     // pub component_updates: EntityHashMap<Entity, HashMap<P::ComponentKinds, Tick>>,
@@ -71,6 +77,8 @@ impl<P: Protocol> ConnectionEvents<P> {
             component_inserts: Default::default(),
             component_removes: Default::default(),
             component_updates: Default::default(),
+            stream_updates: Vec::new(),
+            message_deliveries: Vec::new(),
             // components_with_updates: Default::default(),
             // bookkeeping
             empty: true,
@@ -86,6 +94,8 @@ impl<P: Protocol> ConnectionEvents<P> {
         self.component_inserts.clear();
         self.component_removes.clear();
         self.component_updates.clear();
+        self.stream_updates.clear();
+        self.message_deliveries.clear();
         self.empty = true;
     }
 
@@ -129,6 +139,22 @@ impl<P: Protocol> ConnectionEvents<P> {
         self.empty = false;
     }
 
+    pub(crate) fn push_stream_update(&mut self, channel_kind: ChannelKind, update: StreamUpdate) {
+        trace!(?channel_kind, ?update, "Stream update");
+        self.stream_updates.push((channel_kind, update));
+        self.empty = false;
+    }
+
+    pub(crate) fn push_message_delivery(
+        &mut self,
+        channel_kind: ChannelKind,
+        delivery: MessageDelivery,
+    ) {
+        trace!(?channel_kind, ?delivery, "Message delivery");
+        self.message_deliveries.push((channel_kind, delivery));
+        self.empty = false;
+    }
+
     pub(crate) fn push_spawn(&mut self, entity: Entity) {
         trace!(?entity, "Received entity spawn");
         #[cfg(feature = "metrics")]
@@ -293,6 +319,54 @@ impl<P: Protocol> IterMessageEvent<P> for ConnectionEvents<P> {
     }
 }
 
+pub(crate) trait IterStreamEvent<Ctx: EventContext = ()> {
+    fn drain_stream_updates(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, StreamUpdate, Ctx)> + '_>;
+    fn has_stream_updates(&self) -> bool;
+}
+
+impl<P: Protocol> IterStreamEvent for ConnectionEvents<P> {
+    fn drain_stream_updates(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, StreamUpdate, ())> + '_> {
+        let updates = std::mem::take(&mut self.stream_updates);
+        Box::new(
+            updates
+                .into_iter()
+                .map(|(channel_kind, update)| (channel_kind, update, ())),
+        )
+    }
+
+    fn has_stream_updates(&self) -> bool {
+        !self.stream_updates.is_empty()
+    }
+}
+
+pub(crate) trait IterMessageDeliveryEvent<Ctx: EventContext = ()> {
+    fn drain_message_deliveries(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, MessageDelivery, Ctx)> + '_>;
+    fn has_message_deliveries(&self) -> bool;
+}
+
+impl<P: Protocol> IterMessageDeliveryEvent for ConnectionEvents<P> {
+    fn drain_message_deliveries(
+        &mut self,
+    ) -> Box<dyn Iterator<Item = (ChannelKind, MessageDelivery, ())> + '_> {
+        let deliveries = std::mem::take(&mut self.message_deliveries);
+        Box::new(
+            deliveries
+                .into_iter()
+                .map(|(channel_kind, delivery)| (channel_kind, delivery, ())),
+        )
+    }
+
+    fn has_message_deliveries(&self) -> bool {
+        !self.message_deliveries.is_empty()
+    }
+}
+
 pub trait IterEntitySpawnEvent<Ctx: EventContext = ()> {
     fn into_iter_entity_spawn(&mut self) -> Box<dyn Iterator<Item = (Entity, Ctx)> + '_>;
     fn has_entity_spawn(&self) -> bool;
@@ -453,6 +527,53 @@ impl<P: Protocol> IterComponentInsertEvent<P> for ConnectionEvents<P> {
     }
 }
 
+/// Iterate through the component events of a given component kind.
+///
+/// This is used for the components of the [`DynamicProtocol`](crate::protocol::dynamic::DynamicProtocol),
+/// which don't have a type in the [`ComponentProtocol`](crate::protocol::component::ComponentProtocol)
+pub trait IterComponentKindEvent<P: Protocol, Ctx: EventContext = ()> {
+    fn iter_component_kind_insert(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, Ctx)> + '_>;
+
+    fn iter_component_kind_update(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, Ctx)> + '_>;
+
+    fn iter_component_kind_remove(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, Ctx)> + '_>;
+}
+
+impl<P: Protocol> IterComponentKindEvent<P> for ConnectionEvents<P> {
+    fn iter_component_kind_insert(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, ())> + '_> {
+        let entities = self.component_inserts.remove(&kind).unwrap_or_default();
+        Box::new(entities.into_iter().map(|entity| (entity, ())))
+    }
+
+    fn iter_component_kind_update(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, ())> + '_> {
+        let entities = self.component_updates.remove(&kind).unwrap_or_default();
+        Box::new(entities.into_iter().map(|entity| (entity, ())))
+    }
+
+    fn iter_component_kind_remove(
+        &mut self,
+        kind: P::ComponentKinds,
+    ) -> Box<dyn Iterator<Item = (Entity, ())> + '_> {
+        let entities = self.component_removes.remove(&kind).unwrap_or_default();
+        Box::new(entities.into_iter().map(|entity| (entity, ())))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::tests::protocol::*;
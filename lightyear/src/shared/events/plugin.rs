@@ -6,7 +6,8 @@ use bevy::prelude::Plugin;
 use crate::_reexport::{ComponentProtocol, EventContext, MessageProtocol};
 use crate::prelude::Protocol;
 use crate::shared::events::components::{
-    ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent,
+    ConnectEvent, DisconnectEvent, EntityDespawnEvent, EntitySpawnEvent, MessageAckEvent,
+    MessageLostEvent, StreamCancelledEvent, StreamProgressEvent,
 };
 
 pub struct EventsPlugin<P, Ctx> {
@@ -31,6 +32,10 @@ impl<P: Protocol, Ctx: EventContext> Plugin for EventsPlugin<P, Ctx> {
         app.add_event::<ConnectEvent<Ctx>>()
             .add_event::<DisconnectEvent<Ctx>>()
             .add_event::<EntitySpawnEvent<Ctx>>()
-            .add_event::<EntityDespawnEvent<Ctx>>();
+            .add_event::<EntityDespawnEvent<Ctx>>()
+            .add_event::<StreamProgressEvent<Ctx>>()
+            .add_event::<StreamCancelledEvent<Ctx>>()
+            .add_event::<MessageAckEvent<Ctx>>()
+            .add_event::<MessageLostEvent<Ctx>>();
     }
 }
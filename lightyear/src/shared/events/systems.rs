@@ -1,13 +1,17 @@
 use bevy::prelude::{Component, Events, World};
 
 use crate::_reexport::FromType;
+use crate::channel::stream::StreamUpdate;
+use crate::packet::delivery::MessageDelivery;
 use crate::packet::message::Message;
 use crate::protocol::{EventContext, Protocol};
 use crate::shared::events::components::{
-    ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, MessageEvent,
+    ComponentInsertEvent, ComponentRemoveEvent, ComponentUpdateEvent, MessageAckEvent,
+    MessageEvent, MessageLostEvent, StreamCancelledEvent, StreamProgressEvent,
 };
 use crate::shared::events::connection::{
-    IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent, IterMessageEvent,
+    IterComponentInsertEvent, IterComponentRemoveEvent, IterComponentUpdateEvent,
+    IterMessageDeliveryEvent, IterMessageEvent, IterStreamEvent,
 };
 
 // TODO: would it be easier to have this be a system?
@@ -102,3 +106,51 @@ pub fn push_component_update_events<
         }
     }
 }
+
+pub(crate) fn push_stream_events<E: IterStreamEvent<Ctx>, Ctx: EventContext>(
+    world: &mut World,
+    events: &mut E,
+) {
+    if events.has_stream_updates() {
+        for (channel, update, ctx) in events.drain_stream_updates() {
+            match update {
+                StreamUpdate::Progress {
+                    stream_id,
+                    direction,
+                    bytes,
+                    total,
+                } => {
+                    world.send_event(StreamProgressEvent::new(
+                        channel, stream_id, direction, bytes, total, ctx,
+                    ));
+                }
+                StreamUpdate::Cancelled {
+                    stream_id,
+                    direction,
+                } => {
+                    world.send_event(StreamCancelledEvent::new(
+                        channel, stream_id, direction, ctx,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn push_message_delivery_events<E: IterMessageDeliveryEvent<Ctx>, Ctx: EventContext>(
+    world: &mut World,
+    events: &mut E,
+) {
+    if events.has_message_deliveries() {
+        for (channel, delivery, ctx) in events.drain_message_deliveries() {
+            match delivery {
+                MessageDelivery::Acked(message_id) => {
+                    world.send_event(MessageAckEvent::new(channel, message_id, ctx));
+                }
+                MessageDelivery::Lost(message_id) => {
+                    world.send_event(MessageLostEvent::new(channel, message_id, ctx));
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,7 @@
 //! Shared code between the server and client.
 
+pub mod checksum;
+
 pub mod config;
 
 pub mod events;
@@ -12,6 +14,8 @@ pub mod plugin;
 
 pub mod replication;
 
+pub mod rpc;
+
 pub mod sets;
 
 pub mod tick_manager;
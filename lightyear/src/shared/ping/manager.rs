@@ -137,7 +137,10 @@ impl PingManager {
 
             let ping_id = self.ping_store.push_new(time_manager.current_time());
 
-            return Some(Ping { id: ping_id });
+            return Some(Ping {
+                id: ping_id,
+                interpolation_delay_ms: None,
+            });
         }
         None
     }
@@ -310,7 +313,10 @@ mod tests {
         // send pings
         assert_eq!(
             ping_manager.maybe_prepare_ping(&time_manager),
-            Some(Ping { id: PingId(0) })
+            Some(Ping {
+                id: PingId(0),
+                interpolation_delay_ms: None
+            })
         );
         let delta = Duration::from_millis(60);
         time_manager.update(delta);
@@ -322,7 +328,10 @@ mod tests {
         ping_manager.update(&time_manager);
         assert_eq!(
             ping_manager.maybe_prepare_ping(&time_manager),
-            Some(Ping { id: PingId(1) })
+            Some(Ping {
+                id: PingId(1),
+                interpolation_delay_ms: None
+            })
         );
 
         let delta = Duration::from_millis(100);
@@ -330,7 +339,10 @@ mod tests {
         ping_manager.update(&time_manager);
         assert_eq!(
             ping_manager.maybe_prepare_ping(&time_manager),
-            Some(Ping { id: PingId(2) })
+            Some(Ping {
+                id: PingId(2),
+                interpolation_delay_ms: None
+            })
         );
 
         // we sent all the pings we need
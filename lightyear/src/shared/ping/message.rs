@@ -9,6 +9,10 @@ use crate::shared::time_manager::WrappedTime;
 #[derive(Encode, Decode, Clone, Debug, PartialEq)]
 pub struct Ping {
     pub id: PingId,
+    /// Interpolation delay (in milliseconds) measured by the client: how far its interpolation timeline
+    /// is behind its estimate of the server time.
+    /// Only set by clients that are synced.
+    pub interpolation_delay_ms: Option<u32>,
 }
 
 /// Pong message sent in response to a ping
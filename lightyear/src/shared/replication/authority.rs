@@ -0,0 +1,189 @@
+//! Authority over replicated entities
+//!
+//! By default the server is the only peer that sends updates for the entities that it replicates.
+//! The server can grant a client authority over some of the components of an entity with
+//! [`ConnectionManager::grant_authority`](crate::server::connection::ConnectionManager::grant_authority):
+//! - the server stops sending updates for these components to that client
+//! - the client starts replicating its own values of these components to the server, which then replicates
+//!   them to the other clients
+//! - the server ignores any other replication action of that client on the entity: spawns, despawns, and the
+//!   components that it has no authority over
+//!
+//! The authority can be taken back with [`ConnectionManager::revoke_authority`](crate::server::connection::ConnectionManager::revoke_authority),
+//! in which case the server becomes the sender again and the client receives the latest server state.
+use std::fmt::Debug;
+use std::hash::Hash;
+
+use bevy::ecs::entity::EntityHashMap;
+use bevy::prelude::{Component, Entity, Reflect};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::connection::id::ClientId;
+use crate::shared::replication::entity_map::RemoteEntityMap;
+use crate::shared::replication::{EntityActions, ReplicationMessageData};
+
+/// Message sent by the server to notify a client that it gained or lost authority over an entity
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum AuthorityMessage<K> {
+    /// The client gets authority over the components `kinds` of the server `entity`
+    Grant { entity: Entity, kinds: Vec<K> },
+    /// The client loses authority over the server `entity`
+    Revoke { entity: Entity },
+}
+
+/// Authority granted by the server to a client over an entity
+#[derive(Clone, Debug, PartialEq)]
+pub struct Authority<K> {
+    /// The client that replicates the components to the server
+    pub client_id: ClientId,
+    /// The kinds of the components over which the client has authority
+    pub kinds: Vec<K>,
+}
+
+impl<K: PartialEq> Authority<K> {
+    /// Returns true if `client_id` has authority over the component `kind`
+    pub(crate) fn is_authority(&self, client_id: ClientId, kind: &K) -> bool {
+        self.client_id == client_id && self.kinds.contains(kind)
+    }
+}
+
+/// Remove from a replication message received from `client_id` everything that the client is not allowed to
+/// replicate for the server entities that it was granted authority over:
+/// - spawns, despawns and unreplicates, since the entity is still owned by the server
+/// - inserts, removals and updates of the components that the client has no authority over
+///
+/// The entities that the client spawned itself are not affected.
+pub(crate) fn filter_unauthorized<C, K>(
+    message: &mut ReplicationMessageData<C, K>,
+    client_id: ClientId,
+    authority: &EntityHashMap<Authority<K>>,
+    entity_map: &RemoteEntityMap,
+) where
+    K: Hash + Eq + Debug,
+    for<'a> &'a C: Into<K>,
+{
+    // the server entities are mapped to themselves for the clients that have authority over them
+    let get_authority = |remote_entity: &Entity| {
+        entity_map
+            .get_local(*remote_entity)
+            .and_then(|local_entity| authority.get(local_entity))
+    };
+    match message {
+        ReplicationMessageData::Actions(m) => {
+            for (entity, actions) in m.actions.iter_mut() {
+                let Some(authority) = get_authority(entity) else {
+                    continue;
+                };
+                if actions.spawn || actions.despawn || actions.unreplicate {
+                    warn!(
+                        ?client_id,
+                        ?entity,
+                        "Client tried to spawn or despawn an entity owned by the server"
+                    );
+                }
+                *actions = EntityActions {
+                    insert: retain_authorized(
+                        std::mem::take(&mut actions.insert),
+                        client_id,
+                        authority,
+                    ),
+                    remove: std::mem::take(&mut actions.remove)
+                        .into_iter()
+                        .filter(|kind| authority.is_authority(client_id, kind))
+                        .collect(),
+                    updates: retain_authorized(
+                        std::mem::take(&mut actions.updates),
+                        client_id,
+                        authority,
+                    ),
+                    ..Default::default()
+                };
+            }
+        }
+        ReplicationMessageData::Updates(m) => {
+            for (entity, components) in m.updates.iter_mut() {
+                if let Some(authority) = get_authority(entity) {
+                    *components =
+                        retain_authorized(std::mem::take(components), client_id, authority);
+                }
+            }
+            for (entity, deltas) in m.deltas.iter_mut() {
+                if let Some(authority) = get_authority(entity) {
+                    deltas.retain(|delta| authority.is_authority(client_id, &delta.kind));
+                }
+            }
+        }
+    }
+}
+
+/// Keep only the components that `client_id` has authority over
+fn retain_authorized<C, K>(
+    components: Vec<C>,
+    client_id: ClientId,
+    authority: &Authority<K>,
+) -> Vec<C>
+where
+    K: PartialEq + Debug,
+    for<'a> &'a C: Into<K>,
+{
+    components
+        .into_iter()
+        .filter(|component| {
+            let kind: K = component.into();
+            let authorized = authority.is_authority(client_id, &kind);
+            if !authorized {
+                debug!(?client_id, ?kind, "Ignoring component without authority");
+            }
+            authorized
+        })
+        .collect()
+}
+
+/// Marker component added on the client entity over which the client has authority.
+///
+/// If the entity is predicted, this is the `Predicted` entity, otherwise it is the `Confirmed` entity.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+pub struct HasAuthority;
+
+/// Tracks the entities over which the client has authority
+#[derive(Debug)]
+pub(crate) struct ClientAuthority<K> {
+    /// Map from the local entity with authority to the server entity and the kinds of the components
+    /// over which we have authority
+    pub(crate) entities: EntityHashMap<(Entity, Vec<K>)>,
+    /// Authority messages that cannot be applied yet, because the server entity has not been
+    /// replicated to the client yet
+    pub(crate) pending: Vec<AuthorityMessage<K>>,
+}
+
+impl<K> Default for ClientAuthority<K> {
+    fn default() -> Self {
+        Self {
+            entities: EntityHashMap::default(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<K: PartialEq> ClientAuthority<K> {
+    /// Returns the server entity and the kinds of the components over which we have authority
+    pub(crate) fn get(&self, local_entity: Entity) -> Option<&(Entity, Vec<K>)> {
+        self.entities.get(&local_entity)
+    }
+
+    /// Returns true if the client has authority over the component `kind` of the local entity
+    pub(crate) fn has_authority(&self, local_entity: Entity, kind: &K) -> bool {
+        self.entities
+            .get(&local_entity)
+            .map_or(false, |(_, kinds)| kinds.contains(kind))
+    }
+
+    /// Find the local entity with authority that corresponds to the server entity
+    pub(crate) fn local_entity(&self, server_entity: Entity) -> Option<Entity> {
+        self.entities
+            .iter()
+            .find(|(_, (e, _))| *e == server_entity)
+            .map(|(local, _)| *local)
+    }
+}
@@ -37,8 +37,9 @@ pub struct Replicate<P: Protocol> {
     #[doc(hidden)]
     pub replication_clients_cache: HashMap<ClientId, ClientVisibility>,
     pub replication_mode: ReplicationMode,
-    // TODO: currently, if the host removes Replicate, then the entity is not removed in the remote
-    //  it just keeps living but doesn't receive any updates. Should we make this configurable?
+    /// What happens to the remote entity when `Replicate` is removed from the entity, or when a client
+    /// loses visibility of the entity (in [`ReplicationMode::Room`] or [`ReplicationMode::Spatial`])
+    pub removal_policy: ReplicateRemovalPolicy,
     pub replication_group: ReplicationGroup,
     /// If true, recursively add `Replicate` and `ParentSync` components to all children to make sure they are replicated
     /// If false, you can still replicate hierarchies, but in a more fine-grained manner. You will have to add the `Replicate`
@@ -83,9 +84,12 @@ impl<P: Protocol> Replicate<P> {
     where
         P::ComponentKinds: FromType<C>,
     {
-        let kind = <P::ComponentKinds as FromType<C>>::from_type();
+        self.is_kind_disabled(&<P::ComponentKinds as FromType<C>>::from_type())
+    }
+
+    pub(crate) fn is_kind_disabled(&self, kind: &P::ComponentKinds) -> bool {
         self.per_component_metadata
-            .get(&kind)
+            .get(kind)
             .is_some_and(|metadata| metadata.disabled)
     }
 
@@ -95,21 +99,34 @@ impl<P: Protocol> Replicate<P> {
     where
         P::ComponentKinds: FromType<C>,
     {
-        let kind = <P::ComponentKinds as FromType<C>>::from_type();
+        self.is_kind_replicate_once(&<P::ComponentKinds as FromType<C>>::from_type())
+    }
+
+    pub(crate) fn is_kind_replicate_once(&self, kind: &P::ComponentKinds) -> bool {
         self.per_component_metadata
-            .get(&kind)
+            .get(kind)
             .is_some_and(|metadata| metadata.replicate_once)
     }
 
     /// Replication target for this specific component
     /// This will be the intersection of the provided `entity_target`, and the `target` of the component
     /// if it exists
-    pub fn target<C>(&self, mut entity_target: NetworkTarget) -> NetworkTarget
+    pub fn target<C>(&self, entity_target: NetworkTarget) -> NetworkTarget
     where
         P::ComponentKinds: FromType<C>,
     {
-        let kind = <P::ComponentKinds as FromType<C>>::from_type();
-        match self.per_component_metadata.get(&kind) {
+        self.kind_target(
+            &<P::ComponentKinds as FromType<C>>::from_type(),
+            entity_target,
+        )
+    }
+
+    pub(crate) fn kind_target(
+        &self,
+        kind: &P::ComponentKinds,
+        mut entity_target: NetworkTarget,
+    ) -> NetworkTarget {
+        match self.per_component_metadata.get(kind) {
             None => entity_target,
             Some(metadata) => {
                 entity_target.intersection(metadata.target.clone());
@@ -210,6 +227,9 @@ pub struct ReplicationGroup {
     /// the priority of the accumulation group
     /// (priority will get reset to this value every time a message gets sent successfully)
     base_priority: f32,
+    /// Minimum number of ticks between two updates for this group
+    /// (0 means that updates are sent every time the server sends replication messages)
+    send_interval: u16,
 }
 
 impl Default for ReplicationGroup {
@@ -217,6 +237,7 @@ impl Default for ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::FromEntity,
             base_priority: 1.0,
+            send_interval: 0,
         }
     }
 }
@@ -226,6 +247,7 @@ impl ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::FromEntity,
             base_priority: 1.0,
+            send_interval: 0,
         }
     }
 
@@ -233,6 +255,7 @@ impl ReplicationGroup {
         Self {
             id_builder: ReplicationGroupIdBuilder::Group(id),
             base_priority: 1.0,
+            send_interval: 0,
         }
     }
 
@@ -245,6 +268,11 @@ impl ReplicationGroup {
         }
     }
 
+    /// Returns true if the group id is derived from the entity, i.e. no group was chosen for the entity
+    pub(crate) fn is_from_entity(&self) -> bool {
+        matches!(self.id_builder, ReplicationGroupIdBuilder::FromEntity)
+    }
+
     pub(crate) fn priority(&self) -> f32 {
         self.base_priority
     }
@@ -254,6 +282,18 @@ impl ReplicationGroup {
         self
     }
 
+    pub(crate) fn send_interval(&self) -> u16 {
+        self.send_interval
+    }
+
+    /// Only send updates for this group every `send_interval` ticks.
+    ///
+    /// Entity actions (spawns, inserts, removals, despawns) are not affected and are always sent immediately.
+    pub fn set_send_interval(mut self, send_interval: u16) -> Self {
+        self.send_interval = send_interval;
+        self
+    }
+
     pub fn set_id(mut self, id: u64) -> Self {
         self.id_builder = ReplicationGroupIdBuilder::Group(id);
         self
@@ -263,10 +303,31 @@ impl ReplicationGroup {
 #[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Reflect)]
 pub struct ReplicationGroupId(pub u64);
 
+/// Behaviour of the remote entity when the local entity stops being replicated to that remote.
+///
+/// The entity stops being replicated when the `Replicate` component is removed, or when a client loses
+/// visibility of the entity because of rooms or spatial interest management.
+/// (the `remove_replicate` command stops replicating an entity without notifying the remote at all)
+#[derive(Clone, Copy, Default, Debug, PartialEq, Reflect)]
+pub enum ReplicateRemovalPolicy {
+    /// The remote entity is despawned
+    #[default]
+    Despawn,
+    /// The remote entity is kept as a regular local entity: it is no longer `Confirmed` and
+    /// won't be mapped to the local entity anymore
+    /// (its predicted or interpolated entities are despawned)
+    KeepLocal,
+    /// The remote entity is kept as it is: it stays `Confirmed`, but doesn't receive any updates anymore
+    Freeze,
+}
+
 #[derive(Clone, Copy, Default, Debug, PartialEq, Reflect)]
 pub enum ReplicationMode {
     /// We will replicate this entity only to clients that are in the same room as the entity
     Room,
+    /// We will replicate this entity only to clients that are close to the entity
+    /// (see [`SpatialInterestPlugin`](crate::server::spatial::SpatialInterestPlugin))
+    Spatial,
     /// We will replicate this entity to clients using only the [`NetworkTarget`], without caring about rooms
     #[default]
     NetworkTarget,
@@ -281,6 +342,7 @@ impl<P: Protocol> Default for Replicate<P> {
             interpolation_target: NetworkTarget::None,
             replication_clients_cache: HashMap::new(),
             replication_mode: ReplicationMode::default(),
+            removal_policy: ReplicateRemovalPolicy::default(),
             replication_group: Default::default(),
             replicate_hierarchy: true,
             per_component_metadata: HashMap::default(),
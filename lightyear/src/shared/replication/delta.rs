@@ -0,0 +1,82 @@
+//! Delta-compression of component updates
+//!
+//! Components that are marked with `#[protocol(delta)]` in the [`ComponentProtocol`](crate::protocol::component::ComponentProtocol)
+//! are not replicated in full every time they change. Instead, the sender keeps track (for each remote) of the last
+//! value of the component that the remote has acknowledged, and only sends the diff between that value and the
+//! new value. The receiver keeps a small history of the values it has received, so that it can re-apply the diff
+//! on top of the correct base value.
+//!
+//! The full value is still sent if the sender has no acked value to compute the diff against (for example
+//! when the component is first replicated), or if the update is sent in an entity-actions message.
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::shared::tick_manager::Tick;
+
+/// Trait for components that can be replicated by sending only the difference between two values
+///
+/// ```rust,ignore
+/// #[derive(Component, Serialize, Deserialize, Clone, PartialEq)]
+/// pub struct Inventory(pub Vec<u32>);
+///
+/// impl Diffable for Inventory {
+///     // the new length of the inventory, and the slots that changed
+///     type Delta = (usize, Vec<(usize, u32)>);
+///
+///     fn diff(&self, new: &Self) -> Self::Delta {
+///         let changed = new.0.iter().enumerate()
+///             .filter(|(i, item)| self.0.get(*i) != Some(item))
+///             .map(|(i, item)| (i, *item))
+///             .collect();
+///         (new.0.len(), changed)
+///     }
+///
+///     fn apply_diff(&mut self, delta: &Self::Delta) {
+///         self.0.resize(delta.0, 0);
+///         for (i, item) in delta.1.iter() {
+///             self.0[*i] = *item;
+///         }
+///     }
+/// }
+/// ```
+pub trait Diffable: Clone {
+    /// The type representing the difference between two values of the component
+    type Delta: Serialize + DeserializeOwned + Clone + Send + Sync + 'static;
+
+    /// Compute the diff needed to go from `self` to `new`
+    fn diff(&self, new: &Self) -> Self::Delta;
+
+    /// Apply a diff (computed with [`Diffable::diff`]) to `self`
+    fn apply_diff(&mut self, delta: &Self::Delta);
+}
+
+/// The diff of a component, computed against a value that the remote has previously received
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ComponentDelta<K> {
+    pub(crate) kind: K,
+    /// Remote tick of the updates message that contained the value this diff was computed against
+    pub(crate) base_tick: Tick,
+    /// The serialized [`Diffable::Delta`]
+    pub(crate) data: Vec<u8>,
+}
+
+#[doc(hidden)]
+/// Compute the serialized diff between two values of a component
+///
+/// (used by the `component_protocol` macro)
+pub fn diff_component<C: Diffable>(base: &C, new: &C) -> anyhow::Result<Vec<u8>> {
+    bitcode::serialize(&base.diff(new)).context("could not serialize component delta")
+}
+
+#[doc(hidden)]
+/// Apply a serialized diff to the base value of a component, and return the new value
+///
+/// (used by the `component_protocol` macro)
+pub fn apply_diff_component<C: Diffable>(base: &C, data: &[u8]) -> anyhow::Result<C> {
+    let delta: C::Delta =
+        bitcode::deserialize(data).context("could not deserialize component delta")?;
+    let mut new = base.clone();
+    new.apply_diff(&delta);
+    Ok(new)
+}
@@ -113,7 +113,7 @@ impl RemoteEntityMap {
         }
     }
 
-    pub(super) fn remove_by_remote(&mut self, remote_entity: Entity) -> Option<Entity> {
+    pub(crate) fn remove_by_remote(&mut self, remote_entity: Entity) -> Option<Entity> {
         let local_entity = self.remote_to_local.remove(&remote_entity);
         if let Some(local_entity) = local_entity {
             self.local_to_remote.remove(&local_entity);
@@ -147,12 +147,29 @@ impl EntityMapper for RemoteEntityMap {
     }
 }
 
+/// [`EntityMapper`] that doesn't map the entities, but records all the entities that are referenced by a component.
+///
+/// This is used to find out if a component references entities that have not been replicated yet.
+#[derive(Default, Debug)]
+pub(crate) struct EntityReferences(pub(crate) Vec<Entity>);
+
+impl EntityMapper for EntityReferences {
+    fn map_entity(&mut self, entity: Entity) -> Entity {
+        if entity != Entity::PLACEHOLDER {
+            self.0.push(entity);
+        }
+        entity
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use bevy::prelude::Entity;
     use bevy::utils::Duration;
 
     use crate::prelude::client::*;
     use crate::prelude::*;
+    use crate::shared::replication::receive::PENDING_REFERENCES_TIMEOUT;
     use crate::tests::protocol::*;
     use crate::tests::stepper::{BevyStepper, Step};
 
@@ -244,4 +261,192 @@ mod tests {
         );
         Ok(())
     }
+
+    fn setup() -> BevyStepper {
+        let frame_duration = Duration::from_millis(10);
+        let tick_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(tick_duration),
+            ..Default::default()
+        };
+        let link_conditioner = LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        };
+        let mut stepper = BevyStepper::new(
+            shared_config,
+            SyncConfig::default().speedup_factor(1.0),
+            PredictionConfig::default(),
+            InterpolationConfig::default(),
+            link_conditioner,
+            frame_duration,
+        );
+        stepper.init();
+        stepper
+    }
+
+    fn client_entity(stepper: &BevyStepper, server_entity: Entity) -> Option<Entity> {
+        stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .copied()
+    }
+
+    /// A component references an entity that is replicated later (in a different replication group):
+    /// the component is buffered on the client until the referenced entity is replicated
+    #[test]
+    fn test_buffer_unmapped_entity_references() {
+        let mut stepper = setup();
+
+        let server_target = stepper.server_app.world.spawn(Component1(0.0)).id();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component1(1.0),
+                Component4(server_target),
+                Replicate::default(),
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+
+        // the entity is replicated, but the component that references an unknown entity is buffered
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+        assert_eq!(
+            stepper.client_app.world.get::<Component1>(client_entity),
+            Some(&Component1(1.0))
+        );
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Component4>(client_entity)
+            .is_none());
+
+        // once the referenced entity is replicated, the buffered component is applied
+        stepper
+            .server_app
+            .world
+            .entity_mut(server_target)
+            .insert(Replicate::default());
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_target = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_target)
+            .unwrap();
+        assert_eq!(
+            stepper.client_app.world.get::<Component4>(client_entity),
+            Some(&Component4(client_target))
+        );
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .pending_references
+            .is_empty());
+    }
+
+    /// Entities that start being replicated in the same frame as a component that references them are put
+    /// in the same replication group, so that they are spawned in the same message on the remote
+    #[test]
+    fn test_group_referenced_entities() {
+        let mut stepper = setup();
+        let server_target = stepper
+            .server_app
+            .world
+            .spawn((Component1(0.0), Replicate::default()))
+            .id();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component4(server_target), Replicate::default()))
+            .id();
+        stepper.frame_step();
+
+        let group_id = |stepper: &BevyStepper, entity: Entity| {
+            stepper
+                .server_app
+                .world
+                .get::<Replicate>(entity)
+                .unwrap()
+                .group_id(Some(entity))
+        };
+        assert_eq!(
+            group_id(&stepper, server_target),
+            group_id(&stepper, server_entity)
+        );
+
+        stepper.frame_step();
+        let client_target = client_entity(&stepper, server_target).unwrap();
+        let client_entity = client_entity(&stepper, server_entity).unwrap();
+        assert_eq!(
+            stepper.client_app.world.get::<Component4>(client_entity),
+            Some(&Component4(client_target))
+        );
+    }
+
+    /// A component that references an entity that is never replicated is applied after a timeout,
+    /// instead of being buffered forever
+    #[test]
+    fn test_release_unmapped_entity_references() {
+        let mut stepper = setup();
+        let server_target = stepper.server_app.world.spawn(Component1(0.0)).id();
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((
+                Component1(0.0),
+                Component4(server_target),
+                Replicate::default(),
+            ))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = client_entity(&stepper, server_entity).unwrap();
+        assert!(stepper
+            .client_app
+            .world
+            .get::<Component4>(client_entity)
+            .is_none());
+
+        // keep sending updates so that the client applies replication messages
+        for _ in 0..PENDING_REFERENCES_TIMEOUT + 5 {
+            stepper
+                .server_app
+                .world
+                .get_mut::<Component1>(server_entity)
+                .unwrap()
+                .0 += 1.0;
+            stepper.frame_step();
+        }
+        assert_eq!(
+            stepper.client_app.world.get::<Component4>(client_entity),
+            Some(&Component4(server_target))
+        );
+        assert!(stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .pending_references
+            .is_empty());
+    }
 }
@@ -17,10 +17,13 @@ use crate::packet::message::MessageId;
 use crate::prelude::{NetworkTarget, Tick};
 use crate::protocol::{EventContext, Protocol};
 use crate::shared::replication::components::{Replicate, ReplicationGroupId};
+use crate::shared::replication::delta::ComponentDelta;
 
+pub mod authority;
 pub mod components;
 
-mod commands;
+pub(crate) mod commands;
+pub mod delta;
 pub mod entity_map;
 pub(crate) mod hierarchy;
 pub(crate) mod plugin;
@@ -51,6 +54,8 @@ pub mod systems;
 pub struct EntityActions<C, K: Hash + Eq> {
     pub(crate) spawn: bool,
     pub(crate) despawn: bool,
+    /// The entity is not replicated anymore, but the remote should keep it as a local entity
+    pub(crate) unreplicate: bool,
     // Cannot use HashSet because we would need ComponentProtocol to implement Hash + Eq
     pub(crate) insert: Vec<C>,
     pub(crate) remove: HashSet<K>,
@@ -63,6 +68,7 @@ impl<C, K: Hash + Eq> Default for EntityActions<C, K> {
         Self {
             spawn: false,
             despawn: false,
+            unreplicate: false,
             insert: Vec::new(),
             remove: HashSet::new(),
             updates: Vec::new(),
@@ -80,12 +86,15 @@ pub struct EntityActionMessage<C, K: Hash + Eq> {
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
-pub struct EntityUpdatesMessage<C> {
+pub struct EntityUpdatesMessage<C, K> {
     /// The last tick for which we sent an EntityActionsMessage for this group
     /// We set this to None after a certain amount of time without any new Actions, to signify on the receiver side
     /// that there is no ordering constraint with respect to Actions for this group (i.e. the Update can be applied immediately)
     last_action_tick: Option<Tick>,
     pub(crate) updates: Vec<(Entity, Vec<C>)>,
+    /// Updates for delta-compressed components, sent as a diff against a value that the remote already acked
+    /// (the receiver converts them back to full updates when the message is received)
+    pub(crate) deltas: Vec<(Entity, Vec<ComponentDelta<K>>)>,
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -93,7 +102,7 @@ pub enum ReplicationMessageData<C, K: Hash + Eq> {
     /// All the entity actions (Spawn/despawn/inserts/removals) for a given group
     Actions(EntityActionMessage<C, K>),
     /// All the entity updates for a given group
-    Updates(EntityUpdatesMessage<C>),
+    Updates(EntityUpdatesMessage<C, K>),
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
@@ -126,6 +135,18 @@ pub trait ReplicationSend<P: Protocol>: Resource {
         priority: f32,
     ) -> Result<()>;
 
+    /// Set the minimum number of ticks between two updates of a given replication group, for a given client
+    /// This lets you replicate less important entities (for example entities that are far away from the client)
+    /// less often.
+    ///
+    /// Entity actions are not affected and are always sent immediately.
+    fn update_send_interval(
+        &mut self,
+        replication_group_id: ReplicationGroupId,
+        client_id: ClientId,
+        send_interval: u16,
+    ) -> Result<()>;
+
     /// Return the list of clients that connected to the server since we last sent any replication messages
     /// (this is used to send the initial state of the world to new clients)
     fn new_connected_clients(&self) -> Vec<ClientId>;
@@ -146,6 +167,16 @@ pub trait ReplicationSend<P: Protocol>: Resource {
         system_current_tick: BevyTick,
     ) -> Result<()>;
 
+    /// Stop replicating the entity, but let the remote keep it as a local entity
+    /// (see [`ReplicateRemovalPolicy::KeepLocal`](crate::prelude::ReplicateRemovalPolicy::KeepLocal))
+    fn prepare_entity_unreplicate(
+        &mut self,
+        entity: Entity,
+        replicate: &Replicate<P>,
+        target: NetworkTarget,
+        system_current_tick: BevyTick,
+    ) -> Result<()>;
+
     fn prepare_component_insert(
         &mut self,
         entity: Entity,
@@ -178,6 +209,19 @@ pub trait ReplicationSend<P: Protocol>: Resource {
         system_current_tick: BevyTick,
     ) -> Result<()>;
 
+    /// Returns true if a component change made at `component_change_tick` on an entity of the group
+    /// would be sent by [`ReplicationSend::prepare_component_update`] to some of the clients in `target`,
+    /// i.e. if they did not ack an update of the group that is more recent than the change.
+    ///
+    /// This can be used to avoid serializing components that don't need to be sent.
+    fn has_pending_update(
+        &mut self,
+        group_id: ReplicationGroupId,
+        target: NetworkTarget,
+        component_change_tick: BevyTick,
+        system_current_tick: BevyTick,
+    ) -> bool;
+
     /// Any operation that needs to happen before we can send the replication messages
     /// (for example collecting the individual single component updates into a single message,
     ///
@@ -280,4 +324,83 @@ mod tests {
             .is_none());
         Ok(())
     }
+
+    // A delta-compressed component gets updated several times on the server,
+    // the client should always end up with the correct value.
+    #[test]
+    fn test_delta_compressed_component_update() -> anyhow::Result<()> {
+        let frame_duration = Duration::from_millis(10);
+        let tick_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(tick_duration),
+            ..Default::default()
+        };
+        let link_conditioner = LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        };
+        let sync_config = SyncConfig::default().speedup_factor(1.0);
+        let prediction_config = PredictionConfig::default().disable(false);
+        let interpolation_config = InterpolationConfig::default();
+        let mut stepper = BevyStepper::new(
+            shared_config,
+            sync_config,
+            prediction_config,
+            interpolation_config,
+            link_conditioner,
+            frame_duration,
+        );
+        stepper.init();
+
+        let server_entity = stepper
+            .server_app
+            .world
+            .spawn((Component5(vec![1, 2, 3]), Replicate::default()))
+            .id();
+        stepper.frame_step();
+        stepper.frame_step();
+        let client_entity = *stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .replication_receiver
+            .remote_entity_map
+            .get_local(server_entity)
+            .unwrap();
+
+        // the first updates are sent in full, the next ones are diffs against the acked values
+        for value in [vec![1, 2, 4], vec![5, 2], vec![5, 2, 6, 7]] {
+            stepper
+                .server_app
+                .world
+                .entity_mut(server_entity)
+                .get_mut::<Component5>()
+                .unwrap()
+                .0 = value.clone();
+            // step enough frames for the update to be acked by the client
+            for _ in 0..10 {
+                stepper.frame_step();
+            }
+            assert_eq!(
+                stepper
+                    .client_app
+                    .world
+                    .entity(client_entity)
+                    .get::<Component5>()
+                    .unwrap(),
+                &Component5(value)
+            );
+        }
+        // the server has a value acked by the client to compute diffs against
+        assert!(stepper
+            .server_app
+            .world
+            .resource::<crate::server::connection::ConnectionManager<MyProtocol>>()
+            .connection(ClientId::Netcode(111))?
+            .replication_sender
+            .delta_acked_values
+            .contains_key(&server_entity));
+        Ok(())
+    }
 }
@@ -3,6 +3,7 @@ use bevy::time::common_conditions::on_timer;
 use bevy::utils::Duration;
 
 use crate::_reexport::{ComponentProtocol, ReplicationSend, ShouldBeInterpolated};
+use crate::channel::builder::ChannelDirection;
 use crate::prelude::{
     NetworkTarget, PrePredicted, Protocol, RemoteEntityMap, ReplicationGroup, ReplicationMode,
     ShouldBePredicted,
@@ -22,15 +23,23 @@ pub(crate) struct ReplicationPlugin<P: Protocol, R: ReplicationSend<P>> {
     tick_duration: Duration,
     enable_send: bool,
     enable_receive: bool,
+    /// Direction in which this peer sends replication data
+    send_direction: ChannelDirection,
     _marker: std::marker::PhantomData<(P, R)>,
 }
 
 impl<P: Protocol, R: ReplicationSend<P>> ReplicationPlugin<P, R> {
-    pub(crate) fn new(tick_duration: Duration, enable_send: bool, enable_receive: bool) -> Self {
+    pub(crate) fn new(
+        tick_duration: Duration,
+        enable_send: bool,
+        enable_receive: bool,
+        send_direction: ChannelDirection,
+    ) -> Self {
         Self {
             tick_duration,
             enable_send,
             enable_receive,
+            send_direction,
             _marker: std::marker::PhantomData,
         }
     }
@@ -104,7 +113,7 @@ impl<P: Protocol, R: ReplicationSend<P>> Plugin for ReplicationPlugin<P, R> {
                 ),
             );
             // SYSTEMS
-            add_replication_send_systems::<P, R>(app);
+            add_replication_send_systems::<P, R>(app, self.send_direction);
             P::Components::add_per_component_replication_send_systems::<R>(app);
             app.add_systems(Last, cleanup::<P, R>.run_if(on_timer(clean_interval)));
             // PLUGINS
@@ -4,9 +4,9 @@ use std::iter::Extend;
 
 use anyhow::Context;
 use bevy::ecs::entity::{EntityHash, MapEntities};
-use bevy::prelude::{DespawnRecursiveExt, Entity, World};
+use bevy::prelude::{DespawnRecursiveExt, Entity, EntityWorldMut, World};
 use bevy::reflect::Reflect;
-use bevy::utils::HashSet;
+use bevy::utils::{HashMap, HashSet};
 use tracing::{debug, error, info, trace, trace_span, warn};
 
 use crate::packet::message::MessageId;
@@ -18,7 +18,7 @@ use crate::protocol::Protocol;
 use crate::shared::events::connection::ConnectionEvents;
 use crate::shared::replication::components::ReplicationGroupId;
 
-use super::entity_map::RemoteEntityMap;
+use super::entity_map::{EntityReferences, RemoteEntityMap};
 use super::{
     EntityActionMessage, EntityUpdatesMessage, ReplicationMessage, ReplicationMessageData,
 };
@@ -27,6 +27,13 @@ type EntityHashMap<K, V> = hashbrown::HashMap<K, V, EntityHash>;
 
 type EntityHashSet<K> = hashbrown::HashSet<K, EntityHash>;
 
+/// Number of ticks after which a component that references entities that were never replicated is applied anyway
+/// (the entities that could not be mapped are left as they are)
+pub(crate) const PENDING_REFERENCES_TIMEOUT: i16 = 128;
+/// Maximum number of entities that can have components waiting for the entities they reference to be replicated.
+/// Once this is reached, the components are applied without waiting
+pub(crate) const MAX_PENDING_REFERENCES: usize = 1024;
+
 pub(crate) struct ReplicationReceiver<P: Protocol> {
     /// Map between local and remote entities. (used mostly on client because it's when we receive entity updates)
     pub remote_entity_map: RemoteEntityMap,
@@ -34,6 +41,21 @@ pub(crate) struct ReplicationReceiver<P: Protocol> {
     /// Map from remote entity to the replication group-id
     pub remote_entity_to_group: EntityHashMap<Entity, ReplicationGroupId>,
 
+    /// History of the values received for each delta-compressed component of a remote entity (along with the
+    /// remote tick of the message that contained them), so that we can apply the diffs sent by the remote
+    pub delta_history:
+        EntityHashMap<Entity, HashMap<P::ComponentKinds, BTreeMap<Tick, P::Components>>>,
+
+    /// Components received for a local entity that reference remote entities that don't have a local
+    /// counterpart yet (for example because they are part of a different [`ReplicationGroup`](crate::prelude::ReplicationGroup)
+    /// whose spawn hasn't been received yet).
+    /// They are applied as soon as all the referenced entities have been replicated, or after
+    /// [`PENDING_REFERENCES_TIMEOUT`] ticks if the referenced entities are never replicated.
+    pub pending_references:
+        EntityHashMap<Entity, HashMap<P::ComponentKinds, PendingComponent<P::Components>>>,
+    /// Tick at which the oldest component in `pending_references` was buffered
+    oldest_pending_reference: Option<Tick>,
+
     // BOTH
     /// Buffer to so that we have an ordered receiver per group
     pub group_channels: EntityHashMap<ReplicationGroupId, GroupChannel<P>>,
@@ -45,6 +67,9 @@ impl<P: Protocol> ReplicationReceiver<P> {
             // RECEIVE
             remote_entity_map: RemoteEntityMap::default(),
             remote_entity_to_group: Default::default(),
+            delta_history: Default::default(),
+            pending_references: Default::default(),
+            oldest_pending_reference: None,
             // BOTH
             group_channels: Default::default(),
         }
@@ -53,10 +78,16 @@ impl<P: Protocol> ReplicationReceiver<P> {
     /// Recv a new replication message and buffer it
     pub(crate) fn recv_message(
         &mut self,
-        message: ReplicationMessage<P::Components, P::ComponentKinds>,
+        mut message: ReplicationMessage<P::Components, P::ComponentKinds>,
         remote_tick: Tick,
     ) {
         trace!(?message, ?remote_tick, "Received replication message");
+        // NOTE: we resolve the diffs as soon as we receive the message (and not when we apply it) because
+        //  the remote could compute its next diffs against a value that we received but never applied
+        //  (for example because a more recent update had already been applied)
+        if let ReplicationMessageData::Updates(ref mut m) = message.data {
+            self.resolve_deltas(m, remote_tick);
+        }
         let channel = self.group_channels.entry(message.group_id).or_default();
         match message.data {
             ReplicationMessageData::Actions(m) => {
@@ -87,8 +118,6 @@ impl<P: Protocol> ReplicationReceiver<P> {
                     return;
                 }
 
-                // TODO: include somewhere in the update message the m.last_ack_tick since when we compute changes?
-                //  (if we want to do diff compression?
                 // otherwise buffer the update
                 match m.last_action_tick {
                     None => {
@@ -142,6 +171,61 @@ impl<P: Protocol> ReplicationReceiver<P> {
             .collect()
     }
 
+    /// Convert the diffs contained in an updates message back into full component updates, using the values
+    /// that we previously received for these components.
+    ///
+    /// We also store the values of all the delta-compressed components in the message, since the remote
+    /// might compute future diffs against them.
+    fn resolve_deltas(
+        &mut self,
+        message: &mut EntityUpdatesMessage<P::Components, P::ComponentKinds>,
+        remote_tick: Tick,
+    ) {
+        for (entity, deltas) in std::mem::take(&mut message.deltas) {
+            let history = self.delta_history.entry(entity).or_default();
+            let mut resolved = Vec::new();
+            for delta in deltas {
+                let Some(kind_history) = history.get_mut(&delta.kind) else {
+                    error!(remote_entity = ?entity, kind = ?delta.kind, "Received a component diff but we have no value to apply it to");
+                    continue;
+                };
+                let Some(base) = kind_history.get(&delta.base_tick) else {
+                    error!(remote_entity = ?entity, kind = ?delta.kind, base_tick = ?delta.base_tick, "Received a component diff but we don't have its base value");
+                    continue;
+                };
+                match base.apply_diff(&delta.data) {
+                    Ok(component) => {
+                        // the remote only computes diffs against the most recent value it has acked,
+                        // so we can discard the values that are older than the base
+                        *kind_history = kind_history.split_off(&delta.base_tick);
+                        resolved.push(component);
+                    }
+                    Err(e) => {
+                        error!(remote_entity = ?entity, kind = ?delta.kind, "could not apply component diff: {:?}", e);
+                    }
+                }
+            }
+            if resolved.is_empty() {
+                continue;
+            }
+            match message.updates.iter_mut().find(|(e, _)| *e == entity) {
+                Some((_, components)) => components.extend(resolved),
+                None => message.updates.push((entity, resolved)),
+            }
+        }
+
+        for (entity, components) in message.updates.iter() {
+            for component in components.iter().filter(|c| c.is_delta_compressed()) {
+                self.delta_history
+                    .entry(*entity)
+                    .or_default()
+                    .entry(component.into())
+                    .or_default()
+                    .insert(remote_tick, component.clone());
+            }
+        }
+    }
+
     /// Gets the tick at which the provided confirmed entity currently is
     /// (i.e. the latest server tick at which we received an update for that entity)
     pub(crate) fn get_confirmed_tick(&self, confirmed_entity: Entity) -> Option<Tick> {
@@ -199,6 +283,7 @@ impl<P: Protocol> ReplicationReceiver<P> {
         events: &mut ConnectionEvents<P>,
     ) {
         let _span = trace_span!("Apply received replication message to world").entered();
+        let mut spawned = false;
         match replication {
             ReplicationMessageData::Actions(m) => {
                 debug!(?tick, ?m, "Received replication actions");
@@ -222,6 +307,7 @@ impl<P: Protocol> ReplicationReceiver<P> {
                         // TODO: optimization: spawn the bundle of insert components
                         let local_entity = world.spawn_empty();
                         self.remote_entity_map.insert(*entity, local_entity.id());
+                        spawned = true;
                         trace!("Updated remote entity map: {:?}", self.remote_entity_map);
 
                         debug!(remote_entity = ?entity, "Received entity spawn");
@@ -240,18 +326,41 @@ impl<P: Protocol> ReplicationReceiver<P> {
                             if let Some(group) = self.group_channels.get_mut(&group_id) {
                                 group.remote_entities.remove(&entity);
                             }
+                            self.pending_references.remove(&local_entity);
                             // TODO: we despawn all children as well right now, but that might not be what we want?
                             if let Some(entity_mut) = world.get_entity_mut(local_entity) {
                                 entity_mut.despawn_recursive();
                             }
                             events.push_despawn(local_entity);
                             self.remote_entity_to_group.remove(&entity);
+                            self.delta_history.remove(&entity);
                         } else {
                             error!("Received despawn for an entity that does not exist")
                         }
                         continue;
                     }
 
+                    // the remote stopped replicating the entity, but we keep it as a local entity
+                    if actions.unreplicate {
+                        debug!(remote_entity = ?entity, "Received entity unreplicate");
+                        if let Some(local_entity) = self.remote_entity_map.remove_by_remote(entity)
+                        {
+                            if let Some(group) = self.group_channels.get_mut(&group_id) {
+                                group.remote_entities.remove(&entity);
+                            }
+                            // removing Confirmed also despawns the predicted/interpolated entities
+                            if let Some(mut entity_mut) = world.get_entity_mut(local_entity) {
+                                entity_mut.remove::<Confirmed>();
+                            }
+                            self.pending_references.remove(&local_entity);
+                            self.remote_entity_to_group.remove(&entity);
+                            self.delta_history.remove(&entity);
+                        } else {
+                            error!("Received unreplicate for an entity that does not exist")
+                        }
+                        continue;
+                    }
+
                     // safety: we know by this point that the entity exists
                     let Ok(mut local_entity_mut) =
                         self.remote_entity_map.get_by_remote(world, entity)
@@ -267,16 +376,8 @@ impl<P: Protocol> ReplicationReceiver<P> {
                         .map(|c| c.into())
                         .collect::<HashSet<P::ComponentKinds>>();
                     debug!(remote_entity = ?entity, ?kinds, "Received InsertComponent");
-                    for mut component in actions.insert {
-                        // map any entities inside the component
-                        component.map_entities(&mut self.remote_entity_map);
-                        // TODO: figure out what to do with tick here
-                        events.push_insert_component(
-                            local_entity_mut.id(),
-                            (&component).into(),
-                            Tick(0),
-                        );
-                        component.insert(&mut local_entity_mut);
+                    for component in actions.insert {
+                        self.apply_component(&mut local_entity_mut, component, true, tick, events);
 
                         // TODO: special-case for pre-spawned entities: we receive them from a client, but then we
                         //  we should immediately take ownership of it, so we won't receive a despawn for it
@@ -291,6 +392,12 @@ impl<P: Protocol> ReplicationReceiver<P> {
                     // removals
                     trace!(remote_entity = ?entity, ?actions.remove, "Received RemoveComponent");
                     for kind in actions.remove {
+                        // a buffered component that was removed should not be applied anymore
+                        if let Some(pending) =
+                            self.pending_references.get_mut(&local_entity_mut.id())
+                        {
+                            pending.remove(&kind);
+                        }
                         events.push_remove_component(local_entity_mut.id(), kind, Tick(0));
                         kind.remove(&mut local_entity_mut);
                     }
@@ -304,15 +411,8 @@ impl<P: Protocol> ReplicationReceiver<P> {
                         .map(|c| c.into())
                         .collect::<Vec<P::ComponentKinds>>();
                     debug!(remote_entity = ?entity, ?kinds, "Received UpdateComponent");
-                    for mut component in actions.updates {
-                        // map any entities inside the component
-                        component.map_entities(&mut self.remote_entity_map);
-                        events.push_update_component(
-                            local_entity_mut.id(),
-                            (&component).into(),
-                            Tick(0),
-                        );
-                        component.update(&mut local_entity_mut);
+                    for component in actions.updates {
+                        self.apply_component(&mut local_entity_mut, component, false, tick, events);
                     }
                 }
             }
@@ -324,15 +424,8 @@ impl<P: Protocol> ReplicationReceiver<P> {
                     if let Ok(mut local_entity) =
                         self.remote_entity_map.get_by_remote(world, entity)
                     {
-                        for mut component in components {
-                            // map any entities inside the component
-                            component.map_entities(&mut self.remote_entity_map);
-                            events.push_update_component(
-                                local_entity.id(),
-                                (&component).into(),
-                                Tick(0),
-                            );
-                            component.update(&mut local_entity);
+                        for component in components {
+                            self.apply_component(&mut local_entity, component, false, tick, events);
                         }
                     } else {
                         // we can get a few buffered updates after the entity has been despawned
@@ -344,6 +437,16 @@ impl<P: Protocol> ReplicationReceiver<P> {
             }
         }
 
+        // the entities spawned by this message could be referenced by components that we buffered,
+        // and the components that waited for too long are applied anyway
+        if spawned
+            || self
+                .oldest_pending_reference
+                .map_or(false, |since| tick - since >= PENDING_REFERENCES_TIMEOUT)
+        {
+            self.apply_pending_references(world, tick, events);
+        }
+
         // update the Confirmed tick for all entities in the replication group
         // // TODO: maybe get the confirmed tick from the apply_world message directly?
         // let confirmed_tick = self.group_channels.get(&group_id).unwrap().latest_tick;
@@ -363,6 +466,149 @@ impl<P: Protocol> ReplicationReceiver<P> {
                 }
             });
     }
+
+    /// Map the entities referenced by the component and apply it to the local entity.
+    ///
+    /// If the component references remote entities that have not been replicated yet, it is buffered
+    /// until they are, so that we don't apply a component that contains an unmapped remote entity.
+    /// `tick` is the tick of the replication message that contained the component.
+    fn apply_component(
+        &mut self,
+        local_entity_mut: &mut EntityWorldMut,
+        component: P::Components,
+        insert: bool,
+        tick: Tick,
+        events: &mut ConnectionEvents<P>,
+    ) {
+        let local_entity = local_entity_mut.id();
+        let kind: P::ComponentKinds = (&component).into();
+        // a more recent value replaces the buffered one, but we still need to insert it if the
+        // buffered component was never inserted
+        let pending = self
+            .pending_references
+            .get_mut(&local_entity)
+            .and_then(|pending| pending.remove(&kind));
+        let insert = insert || pending.as_ref().map_or(false, |pending| pending.insert);
+        // the timeout starts when the first value was buffered, so that a component that keeps
+        // referencing an unknown entity is still applied eventually
+        let since = pending.map_or(tick, |pending| pending.since);
+        self.apply_or_buffer(local_entity_mut, component, insert, since, tick, events);
+    }
+
+    /// Apply the component, or buffer it if it references entities that have not been replicated yet
+    /// and it has been waiting for less than [`PENDING_REFERENCES_TIMEOUT`] ticks since `since`
+    fn apply_or_buffer(
+        &mut self,
+        local_entity_mut: &mut EntityWorldMut,
+        mut component: P::Components,
+        insert: bool,
+        since: Tick,
+        tick: Tick,
+        events: &mut ConnectionEvents<P>,
+    ) {
+        let local_entity = local_entity_mut.id();
+        let kind: P::ComponentKinds = (&component).into();
+        let mut references = EntityReferences::default();
+        component.map_entities(&mut references);
+        let unmapped = references
+            .0
+            .iter()
+            .any(|e| self.remote_entity_map.get_local(*e).is_none());
+        if unmapped && tick - since < PENDING_REFERENCES_TIMEOUT {
+            if self.pending_references.len() < MAX_PENDING_REFERENCES
+                || self.pending_references.contains_key(&local_entity)
+            {
+                debug!(
+                    ?local_entity,
+                    ?kind,
+                    "Component references entities that have not been replicated yet, buffering it"
+                );
+                self.pending_references
+                    .entry(local_entity)
+                    .or_default()
+                    .insert(
+                        kind,
+                        PendingComponent {
+                            component,
+                            insert,
+                            since,
+                        },
+                    );
+                self.oldest_pending_reference = Some(
+                    self.oldest_pending_reference
+                        .map_or(since, |oldest| oldest.min(since)),
+                );
+                return;
+            }
+            warn!(
+                ?local_entity,
+                ?kind,
+                "Too many components are waiting for entities to be replicated, applying the component without mapping its entities"
+            );
+        } else if unmapped {
+            warn!(
+                ?local_entity,
+                ?kind,
+                "The entities referenced by the component were not replicated in time, applying the component without mapping them"
+            );
+        }
+        if self
+            .pending_references
+            .get(&local_entity)
+            .map_or(false, |pending| pending.is_empty())
+        {
+            self.pending_references.remove(&local_entity);
+        }
+
+        // map any entities inside the component
+        component.map_entities(&mut self.remote_entity_map);
+        // TODO: figure out what to do with tick here
+        if insert {
+            events.push_insert_component(local_entity, kind, Tick(0));
+            component.insert(local_entity_mut);
+        } else {
+            events.push_update_component(local_entity, kind, Tick(0));
+            component.update(local_entity_mut);
+        }
+    }
+
+    /// Apply the buffered components whose referenced entities have been replicated since, and the
+    /// ones that have been waiting for more than [`PENDING_REFERENCES_TIMEOUT`] ticks
+    fn apply_pending_references(
+        &mut self,
+        world: &mut World,
+        tick: Tick,
+        events: &mut ConnectionEvents<P>,
+    ) {
+        self.oldest_pending_reference = None;
+        for (local_entity, components) in std::mem::take(&mut self.pending_references) {
+            // the entity could have been despawned in the meantime
+            let Some(mut local_entity_mut) = world.get_entity_mut(local_entity) else {
+                continue;
+            };
+            for (_, pending) in components {
+                // the components that still can't be mapped are buffered again
+                self.apply_or_buffer(
+                    &mut local_entity_mut,
+                    pending.component,
+                    pending.insert,
+                    pending.since,
+                    tick,
+                    events,
+                );
+            }
+        }
+    }
+}
+
+/// A component that is waiting for the entities that it references to be replicated
+#[derive(Debug)]
+pub struct PendingComponent<C> {
+    component: C,
+    /// True if the component should be inserted rather than updated
+    insert: bool,
+    /// Tick of the replication message that contained the first buffered value of the component
+    since: Tick,
 }
 
 /// Channel to keep track of receiving/sending replication messages for a given Group
@@ -380,10 +626,10 @@ pub struct GroupChannel<P: Protocol> {
     // the first tick is the last_action_tick (we can only apply the update if the last action tick has been reached)
     // the second tick is the update's server tick when it was sent
     pub buffered_updates_with_last_action_tick:
-        BTreeMap<Tick, BTreeMap<Tick, EntityUpdatesMessage<P::Components>>>,
+        BTreeMap<Tick, BTreeMap<Tick, EntityUpdatesMessage<P::Components, P::ComponentKinds>>>,
     // updates for which there is no condition on the last_action_tick: we can apply them immediately
     pub buffered_updates_without_last_action_tick:
-        BTreeMap<Tick, EntityUpdatesMessage<P::Components>>,
+        BTreeMap<Tick, EntityUpdatesMessage<P::Components, P::ComponentKinds>>,
     /// remote tick of the latest update/action that we applied to the local group
     pub latest_tick: Option<Tick>,
 }
@@ -434,7 +680,9 @@ impl<P: Protocol> GroupChannel<P> {
         Some(message)
     }
 
-    fn read_buffered_updates(&mut self) -> Vec<(Tick, EntityUpdatesMessage<P::Components>)> {
+    fn read_buffered_updates(
+        &mut self,
+    ) -> Vec<(Tick, EntityUpdatesMessage<P::Components, P::ComponentKinds>)> {
         // if we haven't applied any actions (latest_tick is None) we cannot apply any updates
         let Some(latest_tick) = self.latest_tick else {
             return vec![];
@@ -501,6 +749,7 @@ impl<P: Protocol> GroupChannel<P> {
 
 #[cfg(test)]
 mod tests {
+    use crate::shared::replication::delta::ComponentDelta;
     use crate::tests::protocol::*;
 
     use super::*;
@@ -563,6 +812,7 @@ mod tests {
                 data: ReplicationMessageData::Updates(EntityUpdatesMessage {
                     last_action_tick: Some(Tick(0)),
                     updates: Default::default(),
+                    deltas: Default::default(),
                 }),
             },
             Tick(1),
@@ -584,6 +834,7 @@ mod tests {
                 data: ReplicationMessageData::Updates(EntityUpdatesMessage {
                     last_action_tick: Some(Tick(2)),
                     updates: Default::default(),
+                    deltas: Default::default(),
                 }),
             },
             Tick(4),
@@ -635,4 +886,54 @@ mod tests {
         assert_eq!(replication_data.get(1).unwrap().0, Tick(3));
         assert_eq!(replication_data.get(2).unwrap().0, Tick(4));
     }
+
+    #[test]
+    fn test_recv_delta_compressed_updates() {
+        let mut manager = ReplicationReceiver::<MyProtocol>::new();
+        let group_id = ReplicationGroupId(0);
+        let entity = Entity::from_raw(0);
+        let base = MyComponentsProtocol::Component5(Component5(vec![1, 2, 3]));
+        let new = MyComponentsProtocol::Component5(Component5(vec![1, 4]));
+
+        // receive the full value
+        manager.recv_message(
+            ReplicationMessage {
+                group_id,
+                data: ReplicationMessageData::Updates(EntityUpdatesMessage {
+                    last_action_tick: None,
+                    updates: vec![(entity, vec![base.clone()])],
+                    deltas: vec![],
+                }),
+            },
+            Tick(1),
+        );
+
+        // receive a diff computed against the full value
+        manager.recv_message(
+            ReplicationMessage {
+                group_id,
+                data: ReplicationMessageData::Updates(EntityUpdatesMessage {
+                    last_action_tick: None,
+                    updates: vec![],
+                    deltas: vec![(
+                        entity,
+                        vec![ComponentDelta {
+                            kind: MyComponentsProtocolKind::Component5,
+                            base_tick: Tick(1),
+                            data: base.diff(&new).unwrap(),
+                        }],
+                    )],
+                }),
+            },
+            Tick(2),
+        );
+        let channel = manager.group_channels.get(&group_id).unwrap();
+        let updates = &channel.buffered_updates_without_last_action_tick[&Tick(2)];
+        assert_eq!(updates.updates, vec![(entity, vec![new.clone()])]);
+        assert!(updates.deltas.is_empty());
+        assert_eq!(
+            manager.delta_history[&entity][&MyComponentsProtocolKind::Component5][&Tick(2)],
+            new
+        );
+    }
 }
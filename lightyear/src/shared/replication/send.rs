@@ -18,6 +18,7 @@ use crate::protocol::component::ComponentProtocol;
 use crate::protocol::component::{ComponentBehaviour, ComponentKindBehaviour};
 use crate::protocol::Protocol;
 use crate::shared::replication::components::{Replicate, ReplicationGroupId};
+use crate::shared::replication::delta::ComponentDelta;
 
 use super::{EntityActionMessage, EntityActions, EntityUpdatesMessage, ReplicationMessageData};
 
@@ -53,6 +54,19 @@ pub(crate) struct ReplicationSender<P: Protocol> {
     /// Buffer to so that we have an ordered receiver per group
     pub group_channels: EntityHashMap<ReplicationGroupId, GroupChannel>,
 
+    // DELTA COMPRESSION
+    /// Most recent value of each delta-compressed component that was acked by the remote, along with the tick
+    /// of the updates message that contained it. We compute diffs against these values.
+    pub delta_acked_values:
+        EntityHashMap<Entity, HashMap<P::ComponentKinds, (Tick, P::Components)>>,
+    /// Values of the delta-compressed components that were included in an updates message.
+    /// When the message is acked, they become the new values that we compute diffs against.
+    pub delta_sent_values: HashMap<MessageId, (Tick, Vec<(Entity, P::Components)>)>,
+    /// Values of the delta-compressed components included in the updates messages that were just finalized,
+    /// waiting to be associated with the MessageId of their message
+    pub pending_delta_values:
+        EntityHashMap<ReplicationGroupId, (Tick, Vec<(Entity, P::Components)>)>,
+
     // PRIORITY
     /// Get notified whenever a message for a given ReplicationGroup was actually sent
     /// (sometimes they might not be sent because of bandwidth constraints
@@ -73,6 +87,10 @@ impl<P: Protocol> ReplicationSender<P> {
             pending_updates: EntityHashMap::default(),
             pending_unique_components: EntityHashMap::default(),
             group_channels: Default::default(),
+            // DELTA COMPRESSION
+            delta_acked_values: EntityHashMap::default(),
+            delta_sent_values: Default::default(),
+            pending_delta_values: EntityHashMap::default(),
             // PRIORITY
             message_send_receiver,
         }
@@ -132,7 +150,101 @@ impl<P: Protocol> ReplicationSender<P> {
             } else {
                 error!("Received an update message-id ack but we don't know the corresponding group id");
             }
+            // the delta-compressed values that were sent in this message can now be used as a base for diffs
+            if let Some((tick, values)) = self.delta_sent_values.remove(&message_id) {
+                for (entity, component) in values {
+                    let kind: P::ComponentKinds = (&component).into();
+                    let acked_values = self.delta_acked_values.entry(entity).or_default();
+                    // only keep the most recent acked value
+                    if acked_values
+                        .get(&kind)
+                        .map_or(true, |(acked_tick, _)| tick > *acked_tick)
+                    {
+                        trace!(
+                            ?entity,
+                            ?kind,
+                            ?tick,
+                            "Update acked value for delta-compression"
+                        );
+                        acked_values.insert(kind, (tick, component));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Associate the delta-compressed values that were included in the updates message for a given group
+    /// with the [`MessageId`] of that message, so that we can handle receiving an ACK for it later
+    pub(crate) fn track_delta_values(
+        &mut self,
+        message_id: MessageId,
+        group_id: ReplicationGroupId,
+    ) {
+        if let Some(values) = self.pending_delta_values.remove(&group_id) {
+            self.delta_sent_values.insert(message_id, values);
+        }
+    }
+
+    /// Replace the updates of delta-compressed components with a diff against the last value acked by the remote,
+    /// if there is one.
+    ///
+    /// Returns the diffs for each entity, and the full values of all delta-compressed components that are being sent
+    fn delta_compress(
+        delta_acked_values: &EntityHashMap<
+            Entity,
+            HashMap<P::ComponentKinds, (Tick, P::Components)>,
+        >,
+        updates: &mut EntityHashMap<Entity, Vec<P::Components>>,
+    ) -> (
+        Vec<(Entity, Vec<ComponentDelta<P::ComponentKinds>>)>,
+        Vec<(Entity, P::Components)>,
+    ) {
+        let mut deltas = Vec::new();
+        let mut sent_values = Vec::new();
+        for (entity, components) in updates.iter_mut() {
+            let mut entity_deltas = Vec::new();
+            components.retain(|component| {
+                if !component.is_delta_compressed() {
+                    return true;
+                }
+                sent_values.push((*entity, component.clone()));
+                let kind: P::ComponentKinds = component.into();
+                let Some((base_tick, base)) = delta_acked_values
+                    .get(entity)
+                    .and_then(|acked_values| acked_values.get(&kind))
+                else {
+                    // the remote hasn't acked any value yet, we need to send the full component
+                    return true;
+                };
+                match base.diff(component) {
+                    Ok(data) => {
+                        entity_deltas.push(ComponentDelta {
+                            kind,
+                            base_tick: *base_tick,
+                            data,
+                        });
+                        false
+                    }
+                    Err(e) => {
+                        error!(?entity, ?kind, "could not compute component diff: {:?}", e);
+                        true
+                    }
+                }
+            });
+            if !entity_deltas.is_empty() {
+                deltas.push((*entity, entity_deltas));
+            }
         }
+        // no need to send entities for which all the updates were delta-compressed
+        updates.retain(|_, components| !components.is_empty());
+        (deltas, sent_values)
+    }
+
+    /// Remove the delta-compression values that have been waiting for an ack for too long
+    /// (the message was probably lost)
+    pub(crate) fn cleanup_delta_values(&mut self, tick: Tick) {
+        self.delta_sent_values
+            .retain(|_, (sent_tick, _)| tick - *sent_tick <= (i16::MAX / 2));
     }
 }
 
@@ -142,6 +254,18 @@ impl<P: Protocol> ReplicationSender<P> {
 ///
 /// - all component inserts/removes/updates for an entity to be grouped together in a single message
 impl<P: Protocol> ReplicationSender<P> {
+    /// Update the send interval (in ticks) for the updates of a given group
+    pub(crate) fn update_send_interval(
+        &mut self,
+        group_id: ReplicationGroupId,
+        send_interval: u16,
+    ) {
+        self.group_channels
+            .entry(group_id)
+            .or_default()
+            .send_interval = send_interval;
+    }
+
     /// Update the base priority for a given group
     pub(crate) fn update_base_priority(&mut self, group_id: ReplicationGroupId, priority: f32) {
         let channel = self.group_channels.entry(group_id).or_default();
@@ -169,6 +293,7 @@ impl<P: Protocol> ReplicationSender<P> {
     }
 
     pub(crate) fn prepare_entity_despawn(&mut self, entity: Entity, group_id: ReplicationGroupId) {
+        self.delta_acked_values.remove(&entity);
         self.pending_actions
             .entry(group_id)
             .or_default()
@@ -177,6 +302,20 @@ impl<P: Protocol> ReplicationSender<P> {
             .despawn = true;
     }
 
+    pub(crate) fn prepare_entity_unreplicate(
+        &mut self,
+        entity: Entity,
+        group_id: ReplicationGroupId,
+    ) {
+        self.delta_acked_values.remove(&entity);
+        self.pending_actions
+            .entry(group_id)
+            .or_default()
+            .entry(entity)
+            .or_default()
+            .unreplicate = true;
+    }
+
     // we want to send all component inserts that happen together for the same entity in a single message
     // (because otherwise the inserts might be received at different packets/ticks by the remote, and
     // the remote might expect the components insert to be received at the same time)
@@ -241,6 +380,9 @@ impl<P: Protocol> ReplicationSender<P> {
             );
             return;
         }
+        if let Some(acked_values) = self.delta_acked_values.get_mut(&entity) {
+            acked_values.remove(&kind);
+        }
         self.pending_actions
             .entry(group_id)
             .or_default()
@@ -250,6 +392,22 @@ impl<P: Protocol> ReplicationSender<P> {
             .insert(kind);
     }
 
+    /// Returns true if a component change made at `component_change_tick` on an entity of the group
+    /// is more recent than the last update of the group that was acked, so it still has to be sent
+    pub(crate) fn is_update_pending(
+        &self,
+        group_id: ReplicationGroupId,
+        component_change_tick: BevyTick,
+        system_current_tick: BevyTick,
+    ) -> bool {
+        self.group_channels
+            .get(&group_id)
+            .and_then(|channel| channel.collect_changes_since_this_tick)
+            .map_or(true, |tick| {
+                component_change_tick.is_newer_than(tick, system_current_tick)
+            })
+    }
+
     pub(crate) fn prepare_entity_update(
         &mut self,
         entity: Entity,
@@ -299,12 +457,15 @@ impl<P: Protocol> ReplicationSender<P> {
         f32,
     )> {
         let mut messages = Vec::new();
+        self.pending_delta_values.clear();
 
         for (group_id, mut actions) in self.pending_actions.drain() {
             trace!(?group_id, "pending actions: {:?}", actions);
             // add any updates for that group
+            let channel = self.group_channels.entry(group_id).or_default();
             if let Some(updates) = self.pending_updates.remove(&group_id) {
                 trace!(?group_id, "found updates for group: {:?}", updates);
+                channel.last_update_tick = Some(tick);
                 for (entity, components) in updates {
                     actions
                         .entry(entity)
@@ -313,7 +474,6 @@ impl<P: Protocol> ReplicationSender<P> {
                         .extend(components.into_iter());
                 }
             }
-            let channel = self.group_channels.entry(group_id).or_default();
             let priority = channel
                 .accumulated_priority
                 .unwrap_or(channel.base_priority);
@@ -333,9 +493,28 @@ impl<P: Protocol> ReplicationSender<P> {
             debug!("final action messages to send: {:?}", messages);
         }
         // send the remaining updates
-        for (group_id, updates) in self.pending_updates.drain() {
+        for (group_id, mut updates) in self.pending_updates.drain() {
             trace!(?group_id, "pending updates: {:?}", updates);
             let channel = self.group_channels.entry(group_id).or_default();
+            // the updates that are not sent will be collected again at the next send, since we only
+            // stop collecting changes for a group once an update has been acked
+            if !channel.is_ready_to_send_updates(tick) {
+                trace!(
+                    ?group_id,
+                    "skipping updates because of the group's send interval"
+                );
+                continue;
+            }
+            channel.last_update_tick = Some(tick);
+            // NOTE: we only delta-compress updates that are sent in an updates message, because we track
+            //  acks only for those
+            let (deltas, sent_values) =
+                Self::delta_compress(&self.delta_acked_values, &mut updates);
+            if !sent_values.is_empty() {
+                self.pending_delta_values
+                    .insert(group_id, (tick, sent_values));
+            }
+            let channel = self.group_channels.entry(group_id).or_default();
             let priority = channel
                 .accumulated_priority
                 .unwrap_or(channel.base_priority);
@@ -347,6 +526,7 @@ impl<P: Protocol> ReplicationSender<P> {
                     last_action_tick: channel.last_action_tick,
                     // TODO: maybe we can just send the HashMap directly?
                     updates: Vec::from_iter(updates.into_iter()),
+                    deltas,
                 }),
                 priority,
             ));
@@ -366,7 +546,6 @@ impl<P: Protocol> ReplicationSender<P> {
 #[derive(Debug)]
 pub struct GroupChannel {
     pub actions_next_send_message_id: MessageId,
-    // TODO: maybe also keep track of which Tick this bevy-tick corresponds to? (will enable doing diff-compression)
     // bevy tick when we received an ack of an update for this group
     // at the start it's None, and we collect any changes
     pub collect_changes_since_this_tick: Option<BevyTick>,
@@ -378,6 +557,11 @@ pub struct GroupChannel {
     /// for this group because of the bandwidth cap, in which case it will be accumulated.
     pub accumulated_priority: Option<f32>,
     pub base_priority: f32,
+
+    /// Minimum number of ticks between two updates for this group
+    pub send_interval: u16,
+    /// Last tick for which we sent an update for this group
+    pub last_update_tick: Option<Tick>,
 }
 
 impl Default for GroupChannel {
@@ -388,6 +572,8 @@ impl Default for GroupChannel {
             accumulated_priority: None,
             collect_changes_since_this_tick: None,
             base_priority: 1.0,
+            send_interval: 0,
+            last_update_tick: None,
         }
     }
 }
@@ -405,6 +591,13 @@ impl GroupChannel {
         self.collect_changes_since_this_tick = Some(bevy_tick);
         // }
     }
+
+    /// Returns true if enough ticks have passed since the last update we sent for this group
+    pub(crate) fn is_ready_to_send_updates(&self, tick: Tick) -> bool {
+        self.last_update_tick.map_or(true, |last_update_tick| {
+            tick - last_update_tick >= self.send_interval.min(i16::MAX as u16) as i16
+        })
+    }
 }
 
 #[cfg(test)]
@@ -487,6 +680,7 @@ mod tests {
                     EntityActions {
                         spawn: true,
                         despawn: false,
+                        unreplicate: false,
                         insert: vec![MyComponentsProtocol::Component1(Component1(1.0))],
                         remove: HashSet::from_iter(vec![MyComponentsProtocolKind::Component2]),
                         updates: vec![MyComponentsProtocol::Component3(Component3(3.0))],
@@ -497,6 +691,7 @@ mod tests {
                     EntityActions {
                         spawn: false,
                         despawn: false,
+                        unreplicate: false,
                         insert: vec![],
                         remove: HashSet::default(),
                         updates: vec![MyComponentsProtocol::Component2(Component2(4.0))],
@@ -517,6 +712,7 @@ mod tests {
                         entity_3,
                         vec![MyComponentsProtocol::Component3(Component3(5.0))]
                     )],
+                    deltas: vec![],
                 }),
                 1.0
             )
@@ -538,4 +734,99 @@ mod tests {
             Some(Tick(2))
         );
     }
+
+    #[test]
+    fn test_delta_compression() {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut manager = ReplicationSender::<MyProtocol>::new(receiver.clone(), receiver);
+
+        let entity = Entity::from_raw(0);
+        let group = ReplicationGroupId(0);
+        manager.group_channels.insert(
+            group,
+            GroupChannel {
+                last_action_tick: Some(Tick(1)),
+                ..Default::default()
+            },
+        );
+
+        // no value has been acked yet: the full component is sent
+        manager.prepare_entity_update(
+            entity,
+            group,
+            MyComponentsProtocol::Component5(Component5(vec![1, 2, 3])),
+        );
+        let message = manager.finalize(Tick(2));
+        let ReplicationMessageData::Updates(ref updates) = message.first().unwrap().2 else {
+            panic!()
+        };
+        assert_eq!(
+            updates.updates,
+            vec![(
+                entity,
+                vec![MyComponentsProtocol::Component5(Component5(vec![1, 2, 3]))]
+            )]
+        );
+        assert!(updates.deltas.is_empty());
+
+        // the message gets acked
+        manager.track_delta_values(MessageId(0), group);
+        manager
+            .updates_message_id_to_group_id
+            .insert(MessageId(0), (group, BevyTick::new(0)));
+        sender.send(MessageId(0)).unwrap();
+        manager.recv_update_acks();
+
+        // we now only send the diff compared to the acked value
+        manager.prepare_entity_update(
+            entity,
+            group,
+            MyComponentsProtocol::Component5(Component5(vec![1, 4, 3])),
+        );
+        let message = manager.finalize(Tick(3));
+        let ReplicationMessageData::Updates(ref updates) = message.first().unwrap().2 else {
+            panic!()
+        };
+        assert!(updates.updates.is_empty());
+        let delta = &updates.deltas.first().unwrap().1[0];
+        assert_eq!(delta.kind, MyComponentsProtocolKind::Component5);
+        assert_eq!(delta.base_tick, Tick(2));
+        assert_eq!(
+            MyComponentsProtocol::Component5(Component5(vec![1, 2, 3]))
+                .apply_diff(&delta.data)
+                .unwrap(),
+            MyComponentsProtocol::Component5(Component5(vec![1, 4, 3]))
+        );
+    }
+
+    #[test]
+    fn test_send_interval() {
+        let (_, receiver) = crossbeam_channel::unbounded();
+        let mut manager = ReplicationSender::<MyProtocol>::new(receiver.clone(), receiver);
+
+        let entity = Entity::from_raw(0);
+        let group = ReplicationGroupId(0);
+        manager.group_channels.insert(
+            group,
+            GroupChannel {
+                last_action_tick: Some(Tick(1)),
+                ..Default::default()
+            },
+        );
+        manager.update_send_interval(group, 4);
+
+        let component = MyComponentsProtocol::Component1(Component1(1.0));
+        manager.prepare_entity_update(entity, group, component.clone());
+        assert_eq!(manager.finalize(Tick(2)).len(), 1);
+
+        // the updates are not sent until 4 ticks have passed
+        manager.prepare_entity_update(entity, group, component.clone());
+        assert!(manager.finalize(Tick(5)).is_empty());
+        manager.prepare_entity_update(entity, group, component.clone());
+        assert_eq!(manager.finalize(Tick(6)).len(), 1);
+
+        // actions are always sent
+        manager.prepare_entity_despawn(entity, group);
+        assert_eq!(manager.finalize(Tick(7)).len(), 1);
+    }
 }
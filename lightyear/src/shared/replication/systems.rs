@@ -2,37 +2,81 @@
 use std::any::TypeId;
 use std::ops::Deref;
 
-use bevy::ecs::entity::Entities;
+use bevy::ecs::component::{ComponentId, Tick as BevyTick};
+use bevy::ecs::entity::{Entities, MapEntities};
+use bevy::ecs::event::ManualEventReader;
+use bevy::ecs::removal_detection::RemovedComponentEntity;
 use bevy::ecs::system::SystemChangeTick;
 use bevy::prelude::{
-    Added, App, Commands, Component, DetectChanges, Entity, IntoSystemConfigs, PostUpdate,
-    PreUpdate, Query, Ref, RemovedComponents, Res, ResMut, With, Without,
+    Added, App, Changed, Commands, Component, DetectChanges, Entity, EntityRef, Has,
+    IntoSystemConfigs, Mut, ParamSet, PostUpdate, PreUpdate, Query, Ref, RemovedComponents, Res,
+    ResMut, With, World,
 };
+use bevy::utils::HashMap;
 use tracing::{debug, error, info, trace, warn};
 
-use crate::_reexport::FromType;
+use crate::_reexport::{ComponentKindBehaviour, FromType};
+use crate::channel::builder::ChannelDirection;
 use crate::prelude::{NetworkTarget, TickManager};
+use crate::protocol::dynamic::{DynamicProtocol, RawComponent};
 use crate::protocol::Protocol;
 use crate::server::replication::ServerReplicationSet;
 use crate::server::room::ClientVisibility;
-use crate::shared::replication::components::{DespawnTracker, Replicate, ReplicationMode};
+use crate::shared::replication::components::{
+    DespawnTracker, Replicate, ReplicateRemovalPolicy, ReplicationMode,
+};
+use crate::shared::replication::entity_map::EntityReferences;
 use crate::shared::replication::ReplicationSend;
 use crate::shared::sets::{InternalMainSet, InternalReplicationSet};
 
 // TODO: run these systems only if there is at least 1 remote connected!!! (so we don't burn CPU when there are no connections)
 
 /// For every entity that removes their Replicate component but are not despawned, remove the component
-/// from our replicate cache (so that the entity's despawns are no longer replicated)
+/// from our replicate cache (so that the entity's despawns are no longer replicated), and notify the remote
+/// according to the entity's [`ReplicateRemovalPolicy`]
 fn handle_replicate_remove<P: Protocol, R: ReplicationSend<P>>(
     mut sender: ResMut<R>,
     mut query: RemovedComponents<Replicate<P>>,
     entity_check: &Entities,
+    system_bevy_ticks: SystemChangeTick,
 ) {
     for entity in query.read() {
         if entity_check.contains(entity) {
             debug!("handling replicate component remove (delete from cache)");
-            sender.get_mut_replicate_component_cache().remove(&entity);
+            if let Some(replicate) = sender.get_mut_replicate_component_cache().remove(&entity) {
+                let target = replicate.replication_target.clone();
+                let _ = prepare_entity_removal(
+                    sender.as_mut(),
+                    entity,
+                    &replicate,
+                    target,
+                    system_bevy_ticks.this_run(),
+                )
+                .map_err(|e| {
+                    error!("error sending entity removal: {:?}", e);
+                });
+            }
+        }
+    }
+}
+
+/// Notify the remote that the entity is not replicated anymore to `target`, according to the entity's
+/// [`ReplicateRemovalPolicy`]
+fn prepare_entity_removal<P: Protocol, R: ReplicationSend<P>>(
+    sender: &mut R,
+    entity: Entity,
+    replicate: &Replicate<P>,
+    target: NetworkTarget,
+    system_current_tick: BevyTick,
+) -> anyhow::Result<()> {
+    match replicate.removal_policy {
+        ReplicateRemovalPolicy::Despawn => {
+            sender.prepare_entity_despawn(entity, replicate, target, system_current_tick)
+        }
+        ReplicateRemovalPolicy::KeepLocal => {
+            sender.prepare_entity_unreplicate(entity, replicate, target, system_current_tick)
         }
+        ReplicateRemovalPolicy::Freeze => Ok(()),
     }
 }
 
@@ -41,17 +85,29 @@ fn handle_replicate_remove<P: Protocol, R: ReplicationSend<P>>(
 /// This system adds DespawnTracker to each entity that was every replicated,
 /// so that we can track when they are despawned
 /// (we have a distinction between removing Replicate, which just stops replication; and despawning the entity)
+///
+/// It also keeps the [`ReplicateRemovalPolicy`] of the replicate cache up-to-date, so that we use the latest
+/// policy when `Replicate` gets removed. (the rest of the cache is only written when `Replicate` is added,
+/// because room-mode entities mutate `Replicate` every frame)
 fn add_despawn_tracker<P: Protocol, R: ReplicationSend<P>>(
     mut sender: ResMut<R>,
     mut commands: Commands,
-    query: Query<(Entity, &Replicate<P>), (Added<Replicate<P>>, Without<DespawnTracker>)>,
+    query: Query<(Entity, Ref<Replicate<P>>, Has<DespawnTracker>), Changed<Replicate<P>>>,
 ) {
-    for (entity, replicate) in query.iter() {
-        debug!("ADDING DESPAWN TRACKER");
-        commands.entity(entity).insert(DespawnTracker);
-        sender
-            .get_mut_replicate_component_cache()
-            .insert(entity, replicate.clone());
+    for (entity, replicate, has_despawn_tracker) in query.iter() {
+        if !has_despawn_tracker {
+            debug!("ADDING DESPAWN TRACKER");
+            commands.entity(entity).insert(DespawnTracker);
+        }
+        let cache = sender.get_mut_replicate_component_cache();
+        match cache.get_mut(&entity) {
+            Some(cached) if !replicate.is_added() => {
+                cached.removal_policy = replicate.removal_policy;
+            }
+            _ => {
+                cache.insert(entity, replicate.clone());
+            }
+        }
     }
 }
 
@@ -65,7 +121,10 @@ fn send_entity_despawn<P: Protocol, R: ReplicationSend<P>>(
 ) {
     // Despawn entities for clients that lost visibility
     query.iter().for_each(|(entity, replicate)| {
-        if matches!(replicate.replication_mode, ReplicationMode::Room) {
+        if matches!(
+            replicate.replication_mode,
+            ReplicationMode::Room | ReplicationMode::Spatial
+        ) {
             replicate
                 .replication_clients_cache
                 .iter()
@@ -73,18 +132,17 @@ fn send_entity_despawn<P: Protocol, R: ReplicationSend<P>>(
                     if replicate.replication_target.should_send_to(client_id)
                         && matches!(visibility, ClientVisibility::Lost)
                     {
-                        debug!("sending entity despawn for entity: {:?}", entity);
-                        // TODO: don't unwrap but handle errors
-                        let _ = sender
-                            .prepare_entity_despawn(
-                                entity,
-                                replicate,
-                                NetworkTarget::Only(vec![*client_id]),
-                                system_bevy_ticks.this_run(),
-                            )
-                            .map_err(|e| {
-                                error!("error sending entity despawn: {:?}", e);
-                            });
+                        debug!("sending entity removal for entity: {:?}", entity);
+                        let _ = prepare_entity_removal(
+                            sender.as_mut(),
+                            entity,
+                            replicate,
+                            NetworkTarget::Only(vec![*client_id]),
+                            system_bevy_ticks.this_run(),
+                        )
+                        .map_err(|e| {
+                            error!("error sending entity removal: {:?}", e);
+                        });
                     }
                 });
         }
@@ -124,7 +182,7 @@ fn send_entity_spawn<P: Protocol, R: ReplicationSend<P>>(
         match replicate.replication_mode {
             // for room mode, no need to handle newly-connected clients specially; they just need
             // to be added to the correct room
-            ReplicationMode::Room => {
+            ReplicationMode::Room | ReplicationMode::Spatial => {
                 replicate
                     .replication_clients_cache
                     .iter()
@@ -239,144 +297,238 @@ fn send_component_update<C: Component + Clone, P: Protocol, R: ReplicationSend<P
         if replicate.is_disabled::<C>() {
             return;
         }
-        match replicate.replication_mode {
-            ReplicationMode::Room => {
-                replicate
-                    .replication_clients_cache
-                    .iter()
-                    .for_each(|(client_id, visibility)| {
-                        if replicate.replication_target.should_send_to(client_id) {
-                            match visibility {
-                                // TODO: here we required the component to be clone because we send it to multiple clients.
-                                //  but maybe we can instead serialize it to Bytes early and then have the bytes be shared between clients?
-                                //  or just pass a reference?
-                                ClientVisibility::Gained => {
-                                    let target = replicate.target::<C>(NetworkTarget::Only(vec![*client_id]));
+        let component_is_changed = component.is_changed();
+        let component_is_added = component.is_added();
+        let component_last_changed = component.last_changed();
+        // TODO: here we required the component to be clone because we send it to multiple clients.
+        //  but maybe we can instead serialize it to Bytes early and then have the bytes be shared between clients?
+        //  or just pass a reference?
+        let component: P::Components = component.clone().into();
+        replicate_component_update(
+            sender.as_mut(),
+            entity,
+            kind,
+            &component,
+            component_is_added,
+            component_last_changed,
+            &replicate,
+            system_bevy_ticks.this_run(),
+        );
+    });
+}
+
+/// Send a ComponentInsert or a ComponentUpdate for a single component of a replicated entity,
+/// depending on the [`Replicate`] settings of the entity
+#[allow(clippy::too_many_arguments)]
+fn replicate_component_update<P: Protocol, R: ReplicationSend<P>>(
+    sender: &mut R,
+    entity: Entity,
+    kind: P::ComponentKinds,
+    component: &P::Components,
+    component_is_added: bool,
+    component_last_changed: BevyTick,
+    replicate: &Ref<Replicate<P>>,
+    this_run: BevyTick,
+) {
+    match replicate.replication_mode {
+        ReplicationMode::Room | ReplicationMode::Spatial => {
+            replicate
+                .replication_clients_cache
+                .iter()
+                .for_each(|(client_id, visibility)| {
+                    if replicate.replication_target.should_send_to(client_id) {
+                        match visibility {
+                            ClientVisibility::Gained => {
+                                let target = replicate
+                                    .kind_target(&kind, NetworkTarget::Only(vec![*client_id]));
+                                let _ = sender
+                                    .prepare_component_insert(
+                                        entity,
+                                        component.clone(),
+                                        replicate.as_ref(),
+                                        target,
+                                        this_run,
+                                    )
+                                    .map_err(|e| {
+                                        error!("error sending component insert: {:?}", e);
+                                    });
+                            }
+                            ClientVisibility::Lost => {}
+                            ClientVisibility::Maintained => {
+                                // send an component_insert for components that were newly added
+                                if component_is_added {
+                                    let target = replicate
+                                        .kind_target(&kind, NetworkTarget::Only(vec![*client_id]));
                                     let _ = sender
                                         .prepare_component_insert(
                                             entity,
-                                            component.clone().into(),
+                                            component.clone(),
                                             replicate.as_ref(),
                                             target,
-                                            system_bevy_ticks.this_run(),
+                                            this_run,
                                         )
                                         .map_err(|e| {
                                             error!("error sending component insert: {:?}", e);
                                         });
-                                }
-                                ClientVisibility::Lost => {}
-                                ClientVisibility::Maintained => {
-                                    // send an component_insert for components that were newly added
-                                    if component.is_added() {
-                                        let target = replicate.target::<C>(NetworkTarget::Only(vec![*client_id]));
-                                        let _ = sender
-                                            .prepare_component_insert(
-                                                entity,
-                                                component.clone().into(),
-                                                replicate.as_ref(),
-                                                target,
-                                                system_bevy_ticks.this_run(),
-                                            )
-                                            .map_err(|e| {
-                                                error!("error sending component insert: {:?}", e);
-                                            });
-                                        // only update components that were not newly added
-                                    } else {
-                                        // do not send updates for these components, only inserts/removes
-                                        if replicate.is_replicate_once::<C>() {
-                                            return;
-                                        }
-                                        let target = replicate.target::<C>(NetworkTarget::Only(vec![*client_id]));
-                                        let _ = sender
-                                            .prepare_component_update(
-                                                entity,
-                                                component.clone().into(),
-                                                replicate.as_ref(),
-                                                target,
-                                                component.last_changed(),
-                                                system_bevy_ticks.this_run(),
-                                            )
-                                            .map_err(|e| {
-                                                error!("error sending component update: {:?}", e);
-                                            });
+                                    // only update components that were not newly added
+                                } else {
+                                    // do not send updates for these components, only inserts/removes
+                                    if replicate.is_kind_replicate_once(&kind) {
+                                        return;
                                     }
+                                    let target = replicate
+                                        .kind_target(&kind, NetworkTarget::Only(vec![*client_id]));
+                                    let _ = sender
+                                        .prepare_component_update(
+                                            entity,
+                                            component.clone(),
+                                            replicate.as_ref(),
+                                            target,
+                                            component_last_changed,
+                                            this_run,
+                                        )
+                                        .map_err(|e| {
+                                            error!("error sending component update: {:?}", e);
+                                        });
                                 }
                             }
                         }
-                    })
-            }
-            ReplicationMode::NetworkTarget => {
-                let mut target = replicate.replication_target.clone();
+                    }
+                })
+        }
+        ReplicationMode::NetworkTarget => {
+            let mut target = replicate.replication_target.clone();
 
-                let new_connected_clients = sender.new_connected_clients().clone();
-                // replicate all components to newly connected clients
-                if !new_connected_clients.is_empty() {
-                    // replicate to the newly connected clients that match our target
-                    let mut new_connected_target = target.clone();
-                    new_connected_target
-                        .intersection(NetworkTarget::Only(new_connected_clients.clone()));
-                    let _ = sender
-                        .prepare_component_insert(
-                            entity,
-                            component.clone().into(),
-                            replicate.as_ref(),
-                            replicate.target::<C>(new_connected_target),
-                            system_bevy_ticks.this_run(),
-                        )
-                        .map_err(|e| {
-                            error!("error sending component insert: {:?}", e);
-                        });
-                    // don't re-send to newly connection client
-                    target.exclude(new_connected_clients.clone());
+            let new_connected_clients = sender.new_connected_clients().clone();
+            // replicate all components to newly connected clients
+            if !new_connected_clients.is_empty() {
+                // replicate to the newly connected clients that match our target
+                let mut new_connected_target = target.clone();
+                new_connected_target
+                    .intersection(NetworkTarget::Only(new_connected_clients.clone()));
+                let _ = sender
+                    .prepare_component_insert(
+                        entity,
+                        component.clone(),
+                        replicate.as_ref(),
+                        replicate.kind_target(&kind, new_connected_target),
+                        this_run,
+                    )
+                    .map_err(|e| {
+                        error!("error sending component insert: {:?}", e);
+                    });
+                // don't re-send to newly connection client
+                target.exclude(new_connected_clients.clone());
+            }
+            // send a component_insert for components that were newly added
+            // or if replicate was newly added.
+            // TODO: ideally what we should be checking is: is the component newly added
+            //  for the client we are sending to?
+            //  Otherwise another solution would be to also insert the component on ComponentUpdate if it's missing
+            //  Or should we just have ComponentInsert and ComponentUpdate be the same thing? Or we check
+            //  on the receiver's entity world mut to know if we emit a ComponentInsert or a ComponentUpdate?
+            if component_is_added || replicate.is_added() {
+                trace!("component is added");
+                let _ = sender
+                    .prepare_component_insert(
+                        entity,
+                        component.clone(),
+                        replicate.as_ref(),
+                        replicate.kind_target(&kind, target),
+                        this_run,
+                    )
+                    .map_err(|e| {
+                        error!("error sending component insert: {:?}", e);
+                    });
+            } else {
+                // do not send updates for these components, only inserts/removes
+                if replicate.is_kind_replicate_once(&kind) {
+                    trace!(
+                        ?entity,
+                        "not replicating updates for {:?} because it is marked as replicate_once",
+                        kind
+                    );
+                    return;
                 }
+                // otherwise send an update for all components that changed since the
+                // last update we have ack-ed
+                let _ = sender
+                    .prepare_component_update(
+                        entity,
+                        component.clone(),
+                        replicate.as_ref(),
+                        replicate.kind_target(&kind, target),
+                        component_last_changed,
+                        this_run,
+                    )
+                    .map_err(|e| {
+                        error!("error sending component update: {:?}", e);
+                    });
+            }
+        }
+    }
+}
 
-                // send a component_insert for components that were newly added
-                // or if replicate was newly added.
-                // TODO: ideally what we should be checking is: is the component newly added
-                //  for the client we are sending to?
-                //  Otherwise another solution would be to also insert the component on ComponentUpdate if it's missing
-                //  Or should we just have ComponentInsert and ComponentUpdate be the same thing? Or we check
-                //  on the receiver's entity world mut to know if we emit a ComponentInsert or a ComponentUpdate?
-                if component.is_added() || replicate.is_added() {
-                    trace!("component is added");
-                    let _ = sender
-                        .prepare_component_insert(
-                            entity,
-                            component.clone().into(),
-                            replicate.as_ref(),
-                            replicate.target::<C>(target),
-                            system_bevy_ticks.this_run(),
-                        )
-                        .map_err(|e| {
-                            error!("error sending component insert: {:?}", e);
-                        });
-                } else {
-                    // do not send updates for these components, only inserts/removes
-                    if replicate.is_replicate_once::<C>() {
-                        trace!(?entity,
-                            "not replicating updates for {:?} because it is marked as replicate_once",
-                            kind
-                        );
-                        return;
+/// Put the entities that start being replicated in the same [`ReplicationGroup`](crate::prelude::ReplicationGroup)
+/// as the entity whose component `C` references them, so that the remote receives their spawn in the same message
+/// as the component that references them.
+///
+/// Only the new entities that don't have an explicit group are moved, because the group of an entity cannot change
+/// once it has been spawned on the remote. The remote buffers the components that reference entities from other
+/// groups until these entities are replicated.
+fn group_entity_references<C: Component + Clone, P: Protocol>(
+    query: Query<(Entity, Ref<C>), With<Replicate<P>>>,
+    mut replicate_query: ParamSet<(Query<Entity, Added<Replicate<P>>>, Query<&mut Replicate<P>>)>,
+) where
+    P::Components: From<C>,
+{
+    let new_entities: Vec<Entity> = replicate_query.p0().iter().collect();
+    if new_entities.is_empty() {
+        return;
+    }
+    let mut replicate_query = replicate_query.p1();
+    for (entity, component) in query.iter() {
+        if !component.is_changed() && !new_entities.contains(&entity) {
+            continue;
+        }
+        let mut component: P::Components = component.clone().into();
+        let mut references = EntityReferences::default();
+        component.map_entities(&mut references);
+        let Ok(group_id) = replicate_query
+            .get(entity)
+            .map(|replicate| replicate.group_id(Some(entity)))
+        else {
+            continue;
+        };
+        for reference in references.0 {
+            let Ok(replicate) = replicate_query.get(reference) else {
+                warn!(
+                    ?entity,
+                    ?reference,
+                    kind = ?P::ComponentKinds::from(&component),
+                    "Replicated component references an entity that is not replicated"
+                );
+                continue;
+            };
+            let reference_group_id = replicate.group_id(Some(reference));
+            if reference_group_id == group_id
+                || !replicate.replication_group.is_from_entity()
+                || !new_entities.contains(&reference)
+            {
+                continue;
+            }
+            // also move the new entities that were already put in the group of the referenced entity
+            for new_entity in new_entities.iter() {
+                if let Ok(mut replicate) = replicate_query.get_mut(*new_entity) {
+                    if replicate.group_id(Some(*new_entity)) == reference_group_id {
+                        debug!(?entity, entity = ?new_entity, "Replicating referenced entity in the same group");
+                        replicate.replication_group =
+                            replicate.replication_group.set_id(group_id.0);
                     }
-                    // otherwise send an update for all components that changed since the
-                    // last update we have ack-ed
-                    let _ = sender
-                        .prepare_component_update(
-                            entity,
-                            component.clone().into(),
-                            replicate.as_ref(),
-                            replicate.target::<C>(target),
-                            component.last_changed(),
-                            system_bevy_ticks.this_run(),
-                        )
-                        .map_err(|e| {
-                            error!("error sending component update: {:?}", e);
-                        });
                 }
             }
         }
-    });
+    }
 }
 
 /// This system sends updates for all components that were removed
@@ -392,55 +544,198 @@ fn send_component_removed<C: Component + Clone, P: Protocol, R: ReplicationSend<
     let kind = <P::ComponentKinds as FromType<C>>::from_type();
     removed.read().for_each(|entity| {
         if let Ok(replicate) = query.get(entity) {
-            // do not replicate components that are disabled
-            if replicate.is_disabled::<C>() {
-                return;
-            }
-            match replicate.replication_mode {
-                ReplicationMode::Room => {
-                    replicate.replication_clients_cache.iter().for_each(
-                        |(client_id, visibility)| {
-                            if replicate.replication_target.should_send_to(client_id) {
-                                // TODO: maybe send no matter the vis?
-                                if matches!(visibility, ClientVisibility::Maintained) {
-                                    let _ = sender
-                                        .prepare_component_remove(
-                                            entity,
-                                            kind,
-                                            replicate,
-                                            replicate
-                                                .target::<C>(NetworkTarget::Only(vec![*client_id])),
-                                            system_bevy_ticks.this_run(),
-                                        )
-                                        .map_err(|e| {
-                                            error!("error sending component remove: {:?}", e);
-                                        });
-                                }
-                            }
-                        },
+            replicate_component_removal(
+                sender.as_mut(),
+                entity,
+                kind,
+                replicate,
+                system_bevy_ticks.this_run(),
+            );
+        }
+    })
+}
+
+/// Send a ComponentRemove for a single component of a replicated entity
+fn replicate_component_removal<P: Protocol, R: ReplicationSend<P>>(
+    sender: &mut R,
+    entity: Entity,
+    kind: P::ComponentKinds,
+    replicate: &Replicate<P>,
+    this_run: BevyTick,
+) {
+    // do not replicate components that are disabled
+    if replicate.is_kind_disabled(&kind) {
+        return;
+    }
+    match replicate.replication_mode {
+        ReplicationMode::Room | ReplicationMode::Spatial => replicate
+            .replication_clients_cache
+            .iter()
+            .for_each(|(client_id, visibility)| {
+                if replicate.replication_target.should_send_to(client_id) {
+                    // TODO: maybe send no matter the vis?
+                    if matches!(visibility, ClientVisibility::Maintained) {
+                        let _ = sender
+                            .prepare_component_remove(
+                                entity,
+                                kind,
+                                replicate,
+                                replicate.kind_target(&kind, NetworkTarget::Only(vec![*client_id])),
+                                this_run,
+                            )
+                            .map_err(|e| {
+                                error!("error sending component remove: {:?}", e);
+                            });
+                    }
+                }
+            }),
+        ReplicationMode::NetworkTarget => {
+            trace!("sending component remove!");
+            let _ = sender
+                .prepare_component_remove(
+                    entity,
+                    kind,
+                    replicate,
+                    replicate.kind_target(&kind, replicate.replication_target.clone()),
+                    this_run,
+                )
+                .map_err(|e| {
+                    error!("error sending component remove: {:?}", e);
+                });
+        }
+    }
+}
+
+/// This system sends inserts and updates for the components registered in the [`DynamicProtocol`]
+/// (the equivalent of `send_component_update` for components that are not part of the [`ComponentProtocol`](crate::protocol::component::ComponentProtocol))
+///
+/// Only the components that this peer is allowed to send (according to `direction`) are replicated.
+fn send_dynamic_component_update<P: Protocol, R: ReplicationSend<P>>(
+    world: &mut World,
+    direction: ChannelDirection,
+) {
+    let Some(components) = world
+        .get_resource::<DynamicProtocol>()
+        .map(|protocol| protocol.sent_components(direction))
+    else {
+        return;
+    };
+    if components.is_empty() {
+        return;
+    }
+    // in an exclusive system, the world's last change tick is the last time this system ran,
+    // so the change detection of the components behaves like in a regular system
+    let last_run = world.last_change_tick();
+    let this_run = world.read_change_tick();
+    let mut query = world.query::<(EntityRef, Ref<Replicate<P>>)>();
+    world.resource_scope(|world, mut sender: Mut<R>| {
+        let new_connected_clients = !sender.new_connected_clients().is_empty();
+        for (entity_ref, replicate) in query.iter(world) {
+            let entity = entity_ref.id();
+            // the component is sent as an insert to the clients that gained visibility or just connected
+            let send_insert = replicate.is_added()
+                || new_connected_clients
+                || replicate
+                    .replication_clients_cache
+                    .values()
+                    .any(|visibility| *visibility == ClientVisibility::Gained);
+            for (net_id, component_id, serialize) in components.iter() {
+                let kind = P::ComponentKinds::dynamic(*net_id);
+                if replicate.is_kind_disabled(&kind) {
+                    continue;
+                }
+                let Some(ticks) = entity_ref.get_change_ticks_by_id(*component_id) else {
+                    continue;
+                };
+                let is_added = ticks.is_added(last_run, this_run);
+                let last_changed = ticks.last_changed_tick();
+                // only serialize the components that `replicate_component_update` would send
+                if !send_insert
+                    && !is_added
+                    && !sender.has_pending_update(
+                        replicate.group_id(Some(entity)),
+                        update_target(&replicate),
+                        last_changed,
+                        this_run,
                     )
+                {
+                    continue;
                 }
-                ReplicationMode::NetworkTarget => {
-                    trace!("sending component remove!");
-                    let _ = sender
-                        .prepare_component_remove(
-                            entity,
-                            kind,
-                            replicate,
-                            replicate.target::<C>(replicate.replication_target.clone()),
-                            system_bevy_ticks.this_run(),
-                        )
-                        .map_err(|e| {
-                            error!("error sending component remove: {:?}", e);
-                        });
+                let Some(bytes) = serialize(&entity_ref) else {
+                    continue;
+                };
+                replicate_component_update(
+                    sender.as_mut(),
+                    entity,
+                    kind,
+                    &RawComponent::new(*net_id, bytes).into(),
+                    is_added,
+                    last_changed,
+                    &replicate,
+                    this_run,
+                );
+            }
+        }
+    });
+}
+
+/// Clients that can receive the component updates of an entity
+fn update_target<P: Protocol>(replicate: &Replicate<P>) -> NetworkTarget {
+    match replicate.replication_mode {
+        ReplicationMode::Room | ReplicationMode::Spatial => NetworkTarget::Only(
+            replicate
+                .replication_clients_cache
+                .keys()
+                .filter(|client_id| replicate.replication_target.should_send_to(client_id))
+                .copied()
+                .collect(),
+        ),
+        ReplicationMode::NetworkTarget => replicate.replication_target.clone(),
+    }
+}
+
+/// This system sends removals for the components registered in the [`DynamicProtocol`]
+fn send_dynamic_component_removed<P: Protocol, R: ReplicationSend<P>>(
+    world: &mut World,
+    readers: &mut HashMap<ComponentId, ManualEventReader<RemovedComponentEntity>>,
+    direction: ChannelDirection,
+) {
+    let Some(components) = world
+        .get_resource::<DynamicProtocol>()
+        .map(|protocol| protocol.sent_component_ids(direction))
+    else {
+        return;
+    };
+    let this_run = world.read_change_tick();
+    world.resource_scope(|world, mut sender: Mut<R>| {
+        for (net_id, component_id) in components {
+            let Some(events) = world.removed_components().get(component_id) else {
+                continue;
+            };
+            let kind = P::ComponentKinds::dynamic(net_id);
+            for entity in readers
+                .entry(component_id)
+                .or_default()
+                .read(events)
+                .cloned()
+                .map(Entity::from)
+            {
+                if let Some(replicate) = world.get::<Replicate<P>>(entity) {
+                    replicate_component_removal(sender.as_mut(), entity, kind, replicate, this_run);
                 }
             }
         }
-    })
+    });
 }
 
-// add replication systems that are shared between client and server
-pub fn add_replication_send_systems<P: Protocol, R: ReplicationSend<P>>(app: &mut App) {
+/// Add replication systems that are shared between client and server
+///
+/// `direction` is the direction in which this peer sends data; it is used to select the
+/// components of the [`DynamicProtocol`] that should be replicated.
+pub fn add_replication_send_systems<P: Protocol, R: ReplicationSend<P>>(
+    app: &mut App,
+    direction: ChannelDirection,
+) {
     // we need to add despawn trackers immediately for entities for which we add replicate
     app.add_systems(
         PreUpdate,
@@ -466,6 +761,18 @@ pub fn add_replication_send_systems<P: Protocol, R: ReplicationSend<P>>(app: &mu
                 .in_set(InternalReplicationSet::<R::SetMarker>::SendDespawnsAndRemovals),
         ),
     );
+    let mut removed_readers = HashMap::default();
+    app.add_systems(
+        PostUpdate,
+        (
+            (move |world: &mut World| {
+                send_dynamic_component_removed::<P, R>(world, &mut removed_readers, direction)
+            })
+            .in_set(InternalReplicationSet::<R::SetMarker>::SendDespawnsAndRemovals),
+            (move |world: &mut World| send_dynamic_component_update::<P, R>(world, direction))
+                .in_set(InternalReplicationSet::<R::SetMarker>::SendComponentUpdates),
+        ),
+    );
 }
 
 pub fn add_per_component_replication_send_systems<
@@ -490,6 +797,10 @@ pub fn add_per_component_replication_send_systems<
             //  and use up all the bandwidth
             send_component_update::<C, P, R>
                 .in_set(InternalReplicationSet::<R::SetMarker>::SendComponentUpdates),
+            // the groups must be decided before the entities are spawned on the remote
+            group_entity_references::<C, P>
+                .in_set(InternalMainSet::<R::SetMarker>::Send)
+                .before(InternalReplicationSet::<R::SetMarker>::All),
         ),
     );
 }
@@ -0,0 +1,246 @@
+/*! Request/response RPCs built on top of the messages of the protocol
+
+A type that implements [`Request`] can be sent with `send_request` on the `ConnectionManager`, which returns a [`RequestId`].
+The remote answers with `send_response`, and the response is routed back to the caller as a
+[`ResponseEvent`](crate::shared::events::components::ResponseEvent) with the same [`RequestId`].
+If no response is received before the timeout, the [`ResponseEvent`](crate::shared::events::components::ResponseEvent)
+contains a [`RpcError::Timeout`] instead (or a [`RpcError::Disconnected`] if the client that the server sent the request to
+disconnects before responding).
+
+Requests and responses are sent as regular messages, wrapped in a [`RequestMessage`] and a [`ResponseMessage`],
+so both wrappers must be added to the [`MessageProtocol`](crate::protocol::message::MessageProtocol):
+```rust,ignore
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BuyItem(pub u32);
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BuyResult(pub bool);
+
+impl Request for BuyItem {
+    type Response = BuyResult;
+}
+
+#[message_protocol(protocol = "MyProtocol")]
+pub enum Messages {
+    BuyItemRequest(RequestMessage<BuyItem>),
+    BuyItemResponse(ResponseMessage<BuyResult>),
+}
+```
+*/
+use std::collections::HashMap;
+
+use bevy::utils::Duration;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tracing::trace;
+
+use crate::packet::message::Message;
+use crate::protocol::message::MessageKind;
+use crate::shared::time_manager::WrappedTime;
+
+/// Default delay after which a request that did not receive a response fails with [`RpcError::Timeout`]
+pub(crate) const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A [`Message`] that expects a response of type [`Request::Response`] from the remote
+pub trait Request: Message + Serialize + DeserializeOwned {
+    type Response: Message + Serialize + DeserializeOwned;
+}
+
+/// Identifies a request, and the response to that request
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct RequestId(pub u32);
+
+/// Wrapper around a [`Request`] that is sent over the network
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RequestMessage<R> {
+    id: RequestId,
+    request: R,
+}
+
+impl<R> RequestMessage<R> {
+    pub(crate) fn new(id: RequestId, request: R) -> Self {
+        Self { id, request }
+    }
+
+    /// The id that must be used to respond to the request
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    pub fn request(&self) -> &R {
+        &self.request
+    }
+}
+
+/// Wrapper around the response to a [`Request`] that is sent over the network
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ResponseMessage<M> {
+    id: RequestId,
+    response: M,
+}
+
+impl<M> ResponseMessage<M> {
+    pub(crate) fn new(id: RequestId, response: M) -> Self {
+        Self { id, response }
+    }
+
+    /// The id of the request that this message responds to
+    pub fn id(&self) -> RequestId {
+        self.id
+    }
+
+    pub fn response(&self) -> &M {
+        &self.response
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcError {
+    #[error("no response was received before the timeout")]
+    Timeout,
+    #[error("the remote disconnected before responding")]
+    Disconnected,
+}
+
+struct PendingRequest {
+    kind: MessageKind,
+    sent_time: WrappedTime,
+}
+
+/// Keeps track of the requests sent to a remote that are waiting for a response
+#[derive(Default)]
+pub(crate) struct RpcManager {
+    next_request_id: RequestId,
+    pending: HashMap<RequestId, PendingRequest>,
+    current_time: WrappedTime,
+}
+
+impl RpcManager {
+    pub(crate) fn update(&mut self, current_time: WrappedTime) {
+        self.current_time = current_time;
+    }
+
+    /// Assign an id to a new request of type `R`, and wait for its response
+    pub(crate) fn new_request<R: Request>(&mut self) -> RequestId {
+        let id = self.next_request_id;
+        self.next_request_id.0 = self.next_request_id.0.wrapping_add(1);
+        self.pending.insert(
+            id,
+            PendingRequest {
+                kind: MessageKind::of::<R>(),
+                sent_time: self.current_time,
+            },
+        );
+        id
+    }
+
+    /// Stop waiting for the response to a request that could not be sent
+    pub(crate) fn cancel_request(&mut self, id: RequestId) {
+        self.pending.remove(&id);
+    }
+
+    /// Returns true if we were waiting for the response to the request.
+    ///
+    /// Responses to unknown requests (or to requests that already timed out) should be discarded.
+    pub(crate) fn receive_response(&mut self, id: RequestId) -> bool {
+        if self.pending.remove(&id).is_none() {
+            trace!(?id, "received a response to a request that is not pending");
+            return false;
+        }
+        true
+    }
+
+    /// Returns true if some requests are still waiting for a response
+    pub(crate) fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Stop waiting for the responses of all the requests of type `R`
+    pub(crate) fn take_pending<R: Request>(&mut self) -> Vec<RequestId> {
+        let kind = MessageKind::of::<R>();
+        let mut taken = vec![];
+        self.pending.retain(|id, request| {
+            if request.kind == kind {
+                taken.push(*id);
+                return false;
+            }
+            true
+        });
+        taken
+    }
+
+    /// Stop waiting for the responses of the requests of type `R` that were sent more than `timeout` ago
+    pub(crate) fn take_expired<R: Request>(&mut self, timeout: Duration) -> Vec<RequestId> {
+        let kind = MessageKind::of::<R>();
+        // a very large timeout (e.g. `Duration::MAX`) means that the requests never expire
+        let timeout = chrono::Duration::from_std(timeout).unwrap_or(chrono::Duration::max_value());
+        let mut expired = vec![];
+        self.pending.retain(|id, request| {
+            if request.kind == kind && self.current_time - request.sent_time > timeout {
+                expired.push(*id);
+                return false;
+            }
+            true
+        });
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Ping;
+
+    impl Request for Ping {
+        type Response = ();
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    struct Query;
+
+    impl Request for Query {
+        type Response = u32;
+    }
+
+    #[test]
+    fn test_rpc_manager() {
+        let mut manager = RpcManager::default();
+        let ping = manager.new_request::<Ping>();
+        let query = manager.new_request::<Query>();
+        assert_ne!(ping, query);
+
+        manager.update(WrappedTime::new(2_000));
+        // only the requests of the given type expire
+        assert!(manager
+            .take_expired::<Ping>(Duration::from_secs(5))
+            .is_empty());
+        assert_eq!(
+            manager.take_expired::<Ping>(Duration::from_secs(1)),
+            vec![ping]
+        );
+        assert!(!manager.receive_response(ping));
+
+        assert!(manager.receive_response(query));
+        assert!(!manager.receive_response(query));
+        assert!(manager
+            .take_expired::<Query>(Duration::from_secs(1))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_rpc_manager_no_timeout() {
+        let mut manager = RpcManager::default();
+        let ping = manager.new_request::<Ping>();
+        manager.new_request::<Query>();
+        manager.update(WrappedTime::new(u32::MAX));
+        assert!(manager.take_expired::<Ping>(Duration::MAX).is_empty());
+
+        // the pending requests can be taken by type
+        assert_eq!(manager.take_pending::<Ping>(), vec![ping]);
+        assert!(manager.has_pending());
+        manager.take_pending::<Query>();
+        assert!(!manager.has_pending());
+    }
+}
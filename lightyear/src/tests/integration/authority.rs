@@ -0,0 +1,246 @@
+//! Tests related to the server granting clients authority over entities
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::client::components::Confirmed;
+use crate::prelude::client::{
+    HasAuthority, InterpolationConfig, PredictionConfig, ReplicationConfig, SyncConfig,
+};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const CLIENT_ID: ClientId = ClientId::Netcode(111);
+
+fn setup() -> BevyStepper {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    let mut stepper = BevyStepper::new_with_replication(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        ReplicationConfig {
+            enable_send: true,
+            enable_receive: true,
+        },
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        },
+        Duration::from_millis(10),
+    );
+    stepper.init();
+    stepper
+}
+
+fn step(stepper: &mut BevyStepper, frames: usize) {
+    for _ in 0..frames {
+        stepper.frame_step();
+    }
+}
+
+fn confirmed_entity(stepper: &BevyStepper, server_entity: Entity) -> Entity {
+    *stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap()
+}
+
+fn grant(stepper: &mut BevyStepper, server_entity: Entity) {
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .grant_authority(
+            server_entity,
+            CLIENT_ID,
+            vec![MyComponentsProtocolKind::Component1],
+        )
+        .unwrap();
+}
+
+#[test]
+fn test_grant_and_revoke_authority() {
+    let mut stepper = setup();
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((Component1(0.0), Replicate::default()))
+        .id();
+    step(&mut stepper, 5);
+    let client_entity = confirmed_entity(&stepper, server_entity);
+
+    grant(&mut stepper, server_entity);
+    step(&mut stepper, 2);
+    assert!(stepper
+        .client_app
+        .world
+        .get::<HasAuthority>(client_entity)
+        .is_some());
+
+    // the client is now the sender of the component
+    stepper
+        .client_app
+        .world
+        .get_mut::<Component1>(client_entity)
+        .unwrap()
+        .0 = 5.0;
+    step(&mut stepper, 5);
+    assert_eq!(
+        stepper.server_app.world.get::<Component1>(server_entity),
+        Some(&Component1(5.0))
+    );
+
+    // the server doesn't send the component to the client that has authority
+    stepper
+        .server_app
+        .world
+        .get_mut::<Component1>(server_entity)
+        .unwrap()
+        .0 = 10.0;
+    step(&mut stepper, 5);
+    assert_eq!(
+        stepper.client_app.world.get::<Component1>(client_entity),
+        Some(&Component1(5.0))
+    );
+
+    // after the authority is revoked, the client receives the server state again
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .revoke_authority(server_entity)
+        .unwrap();
+    step(&mut stepper, 5);
+    assert!(stepper
+        .client_app
+        .world
+        .get::<HasAuthority>(client_entity)
+        .is_none());
+    assert_eq!(
+        stepper.client_app.world.get::<Component1>(client_entity),
+        Some(&Component1(10.0))
+    );
+    assert!(stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>()
+        .authority(server_entity)
+        .is_none());
+}
+
+#[test]
+fn test_authority_over_predicted_entity() {
+    let mut stepper = setup();
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((
+            Component1(0.0),
+            Component2(0.0),
+            Replicate {
+                prediction_target: NetworkTarget::All,
+                ..default()
+            },
+        ))
+        .id();
+    step(&mut stepper, 5);
+    let confirmed = confirmed_entity(&stepper, server_entity);
+    let predicted = stepper
+        .client_app
+        .world
+        .get::<Confirmed>(confirmed)
+        .unwrap()
+        .predicted
+        .unwrap();
+
+    grant(&mut stepper, server_entity);
+    step(&mut stepper, 2);
+    // the authority is given to the predicted entity
+    assert!(stepper
+        .client_app
+        .world
+        .get::<HasAuthority>(predicted)
+        .is_some());
+
+    stepper
+        .client_app
+        .world
+        .get_mut::<Component1>(predicted)
+        .unwrap()
+        .0 = 5.0;
+    // keep updating the confirmed entity so that the client checks for rollbacks
+    for _ in 0..5 {
+        stepper
+            .server_app
+            .world
+            .get_mut::<Component2>(server_entity)
+            .unwrap()
+            .0 += 1.0;
+        stepper.frame_step();
+    }
+    // the predicted component was not rolled back to the stale confirmed state
+    assert_eq!(
+        stepper.client_app.world.get::<Component1>(predicted),
+        Some(&Component1(5.0))
+    );
+    assert_eq!(
+        stepper.server_app.world.get::<Component1>(server_entity),
+        Some(&Component1(5.0))
+    );
+}
+
+#[test]
+fn test_ignore_components_without_authority() {
+    let mut stepper = setup();
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((Component1(0.0), Component2(0.0), Replicate::default()))
+        .id();
+    step(&mut stepper, 5);
+    let client_entity = confirmed_entity(&stepper, server_entity);
+    grant(&mut stepper, server_entity);
+    step(&mut stepper, 2);
+
+    // a misbehaving client claims authority over components that it was not granted
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .authority
+        .entities
+        .get_mut(&client_entity)
+        .unwrap()
+        .1
+        .extend([
+            MyComponentsProtocolKind::Component2,
+            MyComponentsProtocolKind::Component3,
+        ]);
+    let mut client_entity_mut = stepper.client_app.world.entity_mut(client_entity);
+    client_entity_mut.get_mut::<Component1>().unwrap().0 = 5.0;
+    client_entity_mut.get_mut::<Component2>().unwrap().0 = 5.0;
+    client_entity_mut.insert(Component3(5.0));
+    step(&mut stepper, 5);
+
+    // only the component that the client has authority over is applied on the server
+    let server_entity_ref = stepper.server_app.world.entity(server_entity);
+    assert_eq!(
+        server_entity_ref.get::<Component1>(),
+        Some(&Component1(5.0))
+    );
+    assert_eq!(
+        server_entity_ref.get::<Component2>(),
+        Some(&Component2(0.0))
+    );
+    assert!(server_entity_ref.get::<Component3>().is_none());
+}
@@ -0,0 +1,175 @@
+//! Tests related to the checksums sent by the server to detect desyncs
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::_reexport::FromType;
+use crate::prelude::client::{ChecksumPlugin, InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::server::ChecksumConfig;
+use crate::prelude::*;
+use crate::shared::checksum::MAX_CHECKSUMS_PER_MESSAGE;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+fn setup() -> BevyStepper {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        },
+        Duration::from_millis(10),
+    );
+    stepper
+        .server_app
+        .insert_resource(ChecksumConfig { interval: 1 })
+        .add_plugins((
+            server::ChecksumPlugin::<MyProtocol, Component1>::default(),
+            server::ChecksumPlugin::<MyProtocol, Component2>::default(),
+        ));
+    stepper.client_app.add_plugins((
+        ChecksumPlugin::<MyProtocol, Component1>::default(),
+        ChecksumPlugin::<MyProtocol, Component2>::default(),
+    ));
+    stepper.init();
+    stepper
+}
+
+fn read_desyncs(
+    stepper: &BevyStepper,
+    reader: &mut ManualEventReader<DesyncEvent<MyProtocol>>,
+) -> Vec<DesyncEvent<MyProtocol>> {
+    let events = stepper
+        .client_app
+        .world
+        .resource::<Events<DesyncEvent<MyProtocol>>>();
+    reader.read(events).cloned().collect()
+}
+
+/// Update `Component2` every frame so that the client receives a replication update for every tick
+fn step_with_update(stepper: &mut BevyStepper, server_entity: Entity) {
+    stepper
+        .server_app
+        .world
+        .get_mut::<Component2>(server_entity)
+        .unwrap()
+        .0 += 1.0;
+    stepper.frame_step();
+}
+
+#[test]
+fn test_desync_detection() {
+    let mut stepper = setup();
+    let mut reader = ManualEventReader::default();
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((Component1(0.0), Component2(0.0), Replicate::default()))
+        .id();
+    for _ in 0..10 {
+        step_with_update(&mut stepper, server_entity);
+    }
+    let client_entity = *stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+
+    // the client state matches the server state
+    assert!(read_desyncs(&stepper, &mut reader).is_empty());
+
+    // the client state diverges from the server state for Component1
+    stepper
+        .client_app
+        .world
+        .get_mut::<Component1>(client_entity)
+        .unwrap()
+        .0 = 1.0;
+    step_with_update(&mut stepper, server_entity);
+    step_with_update(&mut stepper, server_entity);
+    let desyncs = read_desyncs(&stepper, &mut reader);
+    assert!(!desyncs.is_empty());
+    for desync in desyncs {
+        let DesyncEvent::Entity { entity, kinds, .. } = desync else {
+            panic!("expected a desync of an entity");
+        };
+        assert_eq!(entity, client_entity);
+        assert_eq!(
+            kinds,
+            vec![<MyComponentsProtocolKind as FromType<Component1>>::from_type()]
+        );
+    }
+}
+
+#[test]
+fn test_checksums_split_into_several_messages() {
+    let mut stepper = setup();
+    let mut reader = ManualEventReader::default();
+    let server_entities: Vec<Entity> = (0..100)
+        .map(|i| {
+            stepper
+                .server_app
+                .world
+                .spawn((Component1(i as f32), Component2(0.0), Replicate::default()))
+                .id()
+        })
+        .collect();
+    let last_entity = *server_entities.last().unwrap();
+    for _ in 0..10 {
+        step_with_update(&mut stepper, last_entity);
+    }
+
+    // the 200 checksums of a tick are split into messages that fit in a packet
+    let messages = &stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .received_checksums;
+    assert!(!messages.is_empty());
+    assert!(messages
+        .iter()
+        .all(|message| message.checksums.len() <= MAX_CHECKSUMS_PER_MESSAGE));
+    let tick = messages[0].tick;
+    let num_checksums: usize = messages
+        .iter()
+        .filter(|message| message.tick == tick)
+        .map(|message| message.checksums.len())
+        .sum();
+    assert_eq!(num_checksums, 200);
+    assert!(read_desyncs(&stepper, &mut reader).is_empty());
+
+    // a desync is still detected for an entity whose checksum is not in the first message
+    let client_entity = *stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(last_entity)
+        .unwrap();
+    stepper
+        .client_app
+        .world
+        .get_mut::<Component1>(client_entity)
+        .unwrap()
+        .0 = -1.0;
+    step_with_update(&mut stepper, last_entity);
+    step_with_update(&mut stepper, last_entity);
+    let desyncs = read_desyncs(&stepper, &mut reader);
+    assert!(!desyncs.is_empty());
+    assert!(desyncs.iter().all(
+        |desync| matches!(desync, DesyncEvent::Entity { entity, .. } if *entity == client_entity)
+    ));
+}
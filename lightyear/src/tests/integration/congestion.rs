@@ -0,0 +1,79 @@
+//! Tests related to the congestion control
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const INITIAL_BANDWIDTH: u32 = 64_000;
+
+fn setup(incoming_loss: f32) -> BevyStepper {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss,
+        },
+        Duration::from_millis(10),
+    );
+    let packet_config = client::PacketConfig::default().with_congestion_control(CongestionConfig {
+        initial_bandwidth: INITIAL_BANDWIDTH,
+        ..CongestionConfig::default().enable()
+    });
+    stepper
+        .client_app
+        .world
+        .insert_resource(ClientConnectionManager::new(
+            protocol().channel_registry(),
+            packet_config,
+            SyncConfig::default().speedup_factor(1.0),
+            PingConfig::default(),
+            0,
+            protocol().schema(),
+        ));
+    stepper.init();
+    stepper
+}
+
+/// Try to send much more data than the estimated bandwidth, and return the estimated bandwidth
+fn flood(stepper: &mut BevyStepper) -> u32 {
+    for _ in 0..200 {
+        for _ in 0..20 {
+            stepper
+                .client_app
+                .world
+                .resource_mut::<ClientConnectionManager>()
+                .send_message::<Channel1, Message1>(Message1("a".repeat(500)))
+                .unwrap();
+        }
+        stepper.frame_step();
+    }
+    stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .estimated_bandwidth()
+        .unwrap()
+}
+
+#[test]
+fn test_congestion_control_increase() {
+    let mut stepper = setup(0.0);
+    assert!(flood(&mut stepper) > INITIAL_BANDWIDTH);
+}
+
+#[test]
+fn test_congestion_control_decrease() {
+    let mut stepper = setup(0.3);
+    assert!(flood(&mut stepper) < INITIAL_BANDWIDTH);
+}
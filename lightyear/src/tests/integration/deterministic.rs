@@ -0,0 +1,409 @@
+//! Tests related to the deterministic mode, where the clients only exchange inputs
+use std::net::SocketAddr;
+
+use bevy::ecs::event::ManualEventReader;
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::{Duration, Instant};
+
+use crate::channel::builder::InputChannel;
+use crate::client::input::InputManager;
+use crate::connection::netcode::generate_key;
+use crate::connection::server::{NetServer, ServerConnections};
+use crate::inputs::native::input_buffer::{InputData, InputMessage};
+use crate::prelude::client::{
+    Authentication, ClientConnection, Deterministic, DeterministicChecksums, InputSystemSet,
+    InterpolationConfig, NetClient, PredictionConfig, RemoteInputEvent, SyncConfig,
+};
+use crate::prelude::server::NetcodeConfig;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+use crate::transport::LOCAL_SOCKET;
+
+const CLIENT_ID: ClientId = ClientId::Netcode(111);
+const REMOTE_CLIENT_ID: ClientId = ClientId::Netcode(222);
+
+fn setup() -> BevyStepper {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default().with_deterministic(true),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        },
+        Duration::from_millis(10),
+    );
+    stepper.client_app.add_systems(
+        FixedPreUpdate,
+        buffer_input.in_set(InputSystemSet::BufferInputs),
+    );
+    stepper
+        .client_app
+        .add_systems(FixedUpdate, apply_remote_inputs);
+    stepper.init();
+    stepper
+}
+
+/// In deterministic mode, every player needs to send an input every tick
+fn buffer_input(mut input_manager: ResMut<InputManager<MyInput>>, tick_manager: Res<TickManager>) {
+    input_manager.add_input(MyInput(0), tick_manager.tick());
+}
+
+/// Deterministic simulation that uses the inputs of the remote players
+fn apply_remote_inputs(
+    mut query: Query<&mut Component1, With<Deterministic>>,
+    mut events: EventReader<RemoteInputEvent<MyInput>>,
+) {
+    for event in events.read() {
+        if let Some(input) = event.input() {
+            for mut component in query.iter_mut() {
+                component.0 += input.0 as f32;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_rollback_on_remote_input() {
+    let mut stepper = setup();
+    let entity = stepper
+        .client_app
+        .world
+        .spawn((Component1(0.0), Deterministic))
+        .id();
+    for _ in 0..5 {
+        stepper.frame_step();
+    }
+
+    // the inputs of the remote player arrive late: the client simulated these ticks without them
+    let start_tick = stepper.client_tick() - 3;
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_message::<InputChannel, _>(
+            CLIENT_ID,
+            InputMessage {
+                end_tick: start_tick + 1,
+                inputs: vec![InputData::Input(MyInput(1)), InputData::SameAsPrecedent],
+                entities: vec![],
+                client_id: Some(REMOTE_CLIENT_ID),
+                checksum: None,
+            },
+        )
+        .unwrap();
+    stepper.frame_step();
+    stepper.frame_step();
+
+    // the client rolled back to re-simulate the ticks with the remote inputs, and then predicted that
+    // the remote player kept the same input
+    let num_ticks = stepper.client_tick() - start_tick + 1;
+    assert_eq!(
+        stepper.client_app.world.get::<Component1>(entity),
+        Some(&Component1(num_ticks as f32))
+    );
+}
+
+/// Server that rebroadcasts the inputs of two clients running in deterministic mode
+struct DeterministicStepper {
+    server_app: App,
+    client_apps: Vec<App>,
+    current_time: Instant,
+}
+
+impl DeterministicStepper {
+    fn new() -> Self {
+        let tick_duration = Duration::from_millis(10);
+        let shared_config = SharedConfig {
+            tick: TickConfig::new(tick_duration),
+            ..Default::default()
+        };
+        let private_key = generate_key();
+        let now = Instant::now();
+        let mut server_channels = vec![];
+        let mut client_apps = vec![];
+        for client_id in [1, 2] {
+            let (from_server_send, from_server_recv) = crossbeam_channel::unbounded();
+            let (to_server_send, to_server_recv) = crossbeam_channel::unbounded();
+            let addr = SocketAddr::from(([127, 0, 0, 1], client_id as u16));
+            server_channels.push((addr, to_server_recv, from_server_send));
+
+            let mut client_app = App::new();
+            client_app.add_plugins(MinimalPlugins.build());
+            let config = client::ClientConfig {
+                shared: shared_config.clone(),
+                net: client::NetConfig::Netcode {
+                    auth: Authentication::Manual {
+                        // the packets received through a local channel come from this address
+                        server_addr: LOCAL_SOCKET,
+                        protocol_id: 0,
+                        private_key,
+                        client_id,
+                    },
+                    config: Default::default(),
+                    io: IoConfig::from_transport(TransportConfig::LocalChannel {
+                        send: to_server_send,
+                        recv: from_server_recv,
+                    }),
+                },
+                sync: SyncConfig::default().speedup_factor(1.0),
+                prediction: PredictionConfig::default().with_deterministic(true),
+                ..default()
+            };
+            client_app.add_plugins(client::ClientPlugin::new(client::PluginConfig::new(
+                config,
+                protocol(),
+            )));
+            client_app.add_systems(
+                FixedPreUpdate,
+                buffer_input_after_start.in_set(InputSystemSet::BufferInputs),
+            );
+            client_app.add_systems(FixedUpdate, apply_all_inputs);
+            client_app
+                .world
+                .resource_mut::<Time<Real>>()
+                .update_with_instant(now);
+            client_apps.push(client_app);
+        }
+
+        let mut server_app = App::new();
+        server_app.add_plugins(MinimalPlugins.build());
+        let config = server::ServerConfig {
+            shared: shared_config,
+            net: vec![server::NetConfig::Netcode {
+                config: NetcodeConfig::default()
+                    .with_protocol_id(0)
+                    .with_key(private_key),
+                io: IoConfig::from_transport(TransportConfig::Channels {
+                    channels: server_channels,
+                }),
+            }],
+            input: server::InputConfig::default().with_rebroadcast_inputs(true),
+            ..default()
+        };
+        server_app.add_plugins(server::ServerPlugin::new(server::PluginConfig::new(
+            config,
+            protocol(),
+        )));
+        server_app
+            .world
+            .resource_mut::<Time<Real>>()
+            .update_with_instant(now);
+
+        let mut stepper = Self {
+            server_app,
+            client_apps,
+            current_time: now,
+        };
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnections>()
+            .start()
+            .unwrap();
+        for client_app in stepper.client_apps.iter_mut() {
+            client_app
+                .world
+                .resource_mut::<ClientConnection>()
+                .connect()
+                .unwrap();
+        }
+        for _ in 0..100 {
+            if stepper.client_apps.iter().all(|client_app| {
+                client_app
+                    .world
+                    .resource::<ClientConnectionManager>()
+                    .is_synced()
+            }) {
+                break;
+            }
+            stepper.frame_step();
+        }
+
+        // the players start sending inputs at the same tick, and simulate the same entity
+        let start_tick = stepper
+            .client_apps
+            .iter()
+            .map(|client_app| client_app.world.resource::<TickManager>().tick().0)
+            .max()
+            .unwrap()
+            + 10;
+        for client_app in stepper.client_apps.iter_mut() {
+            client_app.insert_resource(StartTick(Tick(start_tick)));
+            client_app.world.spawn((Component1(0.0), Deterministic));
+        }
+        stepper
+    }
+
+    fn frame_step(&mut self) {
+        self.current_time += Duration::from_millis(10);
+        mock_instant::MockClock::advance(Duration::from_millis(10));
+        for client_app in self.client_apps.iter_mut() {
+            client_app.insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
+            client_app.update();
+        }
+        self.server_app
+            .insert_resource(TimeUpdateStrategy::ManualInstant(self.current_time));
+        self.server_app.update();
+    }
+
+    /// Latest tick for which the client reported a checksum to the server
+    fn last_reported_tick(&self, client_id: ClientId) -> Option<Tick> {
+        self.server_app
+            .world
+            .resource::<ServerConnectionManager>()
+            .checksums
+            .reported
+            .iter()
+            .filter(|(_, reports)| reports.iter().any(|(id, _)| *id == client_id))
+            .map(|(tick, _)| *tick)
+            .max()
+    }
+}
+
+/// Tick at which the players start sending inputs
+#[derive(Resource)]
+struct StartTick(Tick);
+
+fn buffer_input_after_start(
+    mut input_manager: ResMut<InputManager<MyInput>>,
+    tick_manager: Res<TickManager>,
+    start_tick: Option<Res<StartTick>>,
+) {
+    let tick = tick_manager.tick();
+    if start_tick.is_some_and(|start_tick| tick >= start_tick.0) {
+        input_manager.add_input(MyInput(1), tick);
+    }
+}
+
+/// Deterministic simulation that uses the inputs of every player
+fn apply_all_inputs(
+    mut query: Query<&mut Component1, With<Deterministic>>,
+    mut local_events: EventReader<client::InputEvent<MyInput>>,
+    mut remote_events: EventReader<RemoteInputEvent<MyInput>>,
+) {
+    let local = local_events
+        .read()
+        .filter_map(|event| event.input().clone());
+    let remote = remote_events
+        .read()
+        .filter_map(|event| event.input().clone());
+    let total: i16 = local.chain(remote).map(|input| input.0).sum();
+    for mut component in query.iter_mut() {
+        component.0 += total as f32;
+    }
+}
+
+/// The clients only report the checksums of the ticks for which they know the inputs of every player,
+/// so the server doesn't detect desyncs while the inputs of the other players are still in flight
+#[test]
+fn test_checksums_of_confirmed_ticks() {
+    let mut stepper = DeterministicStepper::new();
+    let mut reader = ManualEventReader::<DesyncEvent<MyProtocol>>::default();
+    for _ in 0..40 {
+        stepper.frame_step();
+        let events = stepper
+            .server_app
+            .world
+            .resource::<Events<DesyncEvent<MyProtocol>>>();
+        assert_eq!(reader.read(events).count(), 0);
+    }
+
+    // both clients reported checksums for the same ticks
+    let checksums = &stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>()
+        .checksums;
+    assert!(checksums
+        .reported
+        .values()
+        .any(|reports| reports.len() == 2));
+
+    // once the other player leaves, the client doesn't wait for its inputs anymore
+    stepper.client_apps[1]
+        .world
+        .resource_mut::<ClientConnection>()
+        .disconnect()
+        .unwrap();
+    let last_tick = stepper.last_reported_tick(ClientId::Netcode(2)).unwrap();
+    for _ in 0..20 {
+        stepper.frame_step();
+    }
+    assert!(stepper.last_reported_tick(ClientId::Netcode(1)).unwrap() > last_tick + 10);
+}
+
+#[test]
+fn test_desync_between_clients() {
+    let mut stepper = DeterministicStepper::new();
+    let start_tick = stepper.client_apps[1].world.resource::<StartTick>().0;
+    // the simulation of the second client diverges
+    stepper.client_apps[1].add_systems(
+        FixedUpdate,
+        move |mut query: Query<&mut Component1, With<Deterministic>>,
+              tick_manager: Res<TickManager>,
+              rollback: Res<client::Rollback>| {
+            let tick = rollback.get_rollback_tick().unwrap_or(tick_manager.tick());
+            if tick == start_tick + 5 {
+                for mut component in query.iter_mut() {
+                    component.0 += 100.0;
+                }
+            }
+        },
+    );
+
+    let mut reader = ManualEventReader::<DesyncEvent<MyProtocol>>::default();
+    let mut desyncs = vec![];
+    for _ in 0..40 {
+        stepper.frame_step();
+        let events = stepper
+            .server_app
+            .world
+            .resource::<Events<DesyncEvent<MyProtocol>>>();
+        desyncs.extend(reader.read(events).cloned());
+    }
+    assert!(!desyncs.is_empty());
+    assert!(desyncs[0].tick() >= start_tick + 5);
+    let DesyncEvent::Clients { checksums, .. } = &desyncs[0] else {
+        panic!("expected a desync between clients");
+    };
+    assert!(checksums
+        .iter()
+        .any(|(client_id, _)| *client_id == ClientId::Netcode(2)));
+}
+
+#[test]
+fn test_checksums() {
+    let mut stepper = DeterministicStepper::new();
+    for _ in 0..40 {
+        stepper.frame_step();
+    }
+
+    // the client reports the checksum of its simulation with its inputs
+    let tick = stepper.last_reported_tick(ClientId::Netcode(1)).unwrap();
+    let checksum = stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>()
+        .checksums
+        .reported[&tick]
+        .iter()
+        .find(|(client_id, _)| *client_id == ClientId::Netcode(1))
+        .map(|(_, checksum)| *checksum);
+    assert_eq!(
+        stepper.client_apps[0]
+            .world
+            .resource::<DeterministicChecksums>()
+            .get(tick),
+        checksum
+    );
+}
@@ -0,0 +1,264 @@
+//! Tests related to the messages and components registered in the `DynamicProtocol` instead of the protocol enums
+use bevy::prelude::*;
+use bevy::reflect::TypePath;
+use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::client::networking::NetworkingState;
+use crate::prelude::client::{
+    ComponentInsertEvent, DeniedReason, DisconnectEvent, InterpolationConfig, PredictionConfig,
+    ReplicationConfig, SyncConfig,
+};
+use crate::prelude::server::ServerConnections;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, TypePath)]
+struct Chat(String);
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, TypePath)]
+struct Health(u32);
+
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, TypePath)]
+struct Score(u32);
+
+fn step(stepper: &mut BevyStepper, frames: usize) {
+    for _ in 0..frames {
+        stepper.frame_step();
+    }
+}
+
+fn new_stepper() -> BevyStepper {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    BevyStepper::new_with_replication(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        ReplicationConfig {
+            enable_send: true,
+            enable_receive: true,
+        },
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        },
+        Duration::from_millis(10),
+    )
+}
+
+fn register(app: &mut App) {
+    app.add_message::<Chat>()
+        .register_component::<Health>(ChannelDirection::ServerToClient)
+        .register_component::<Score>(ChannelDirection::Bidirectional);
+}
+
+/// Register the same types as [`register`], in a different order
+fn register_reversed(app: &mut App) {
+    app.register_component::<Score>(ChannelDirection::Bidirectional)
+        .register_component::<Health>(ChannelDirection::ServerToClient)
+        .add_message::<Chat>();
+}
+
+fn setup() -> BevyStepper {
+    let mut stepper = new_stepper();
+    register(&mut stepper.server_app);
+    // the plugins of the client could register the types in a different order
+    register_reversed(&mut stepper.client_app);
+    stepper.init();
+    stepper
+}
+
+/// The NetIds and the fingerprint do not depend on the order in which the types are registered
+#[test]
+fn test_dynamic_protocol_registration_order() {
+    let mut app = App::new();
+    register(&mut app);
+    let mut reversed = App::new();
+    register_reversed(&mut reversed);
+
+    let protocol = app.world.resource::<DynamicProtocol>();
+    let reversed = reversed.world.resource::<DynamicProtocol>();
+    assert_eq!(
+        protocol.component_net_id::<Health>(),
+        reversed.component_net_id::<Health>()
+    );
+    assert_eq!(
+        protocol.component_net_id::<Score>(),
+        reversed.component_net_id::<Score>()
+    );
+    assert_eq!(protocol.component_names(), reversed.component_names());
+    assert_eq!(
+        MyProtocol::default()
+            .schema()
+            .with_dynamic_protocol(protocol)
+            .fingerprint(),
+        MyProtocol::default()
+            .schema()
+            .with_dynamic_protocol(reversed)
+            .fingerprint()
+    );
+}
+
+#[test]
+fn test_dynamic_message() {
+    let mut stepper = setup();
+
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .send_dynamic_message::<Channel1, Chat>(Chat("hello".to_string()))
+        .unwrap();
+    step(&mut stepper, 2);
+
+    let events: Vec<_> = stepper
+        .server_app
+        .world
+        .resource_mut::<Events<server::MessageEvent<Chat>>>()
+        .drain()
+        .map(|event| (event.message().clone(), *event.context()))
+        .collect();
+    assert_eq!(
+        events,
+        vec![(Chat("hello".to_string()), ClientId::Netcode(111))]
+    );
+}
+
+#[test]
+fn test_dynamic_component() {
+    let mut stepper = setup();
+
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((Component1(0.0), Health(10), Replicate::default()))
+        .id();
+    step(&mut stepper, 2);
+    let client_entity = *stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+    assert_eq!(
+        stepper.client_app.world.get::<Health>(client_entity),
+        Some(&Health(10))
+    );
+    assert_eq!(
+        stepper
+            .client_app
+            .world
+            .resource::<Events<ComponentInsertEvent<Health>>>()
+            .len(),
+        1
+    );
+
+    // update
+    stepper
+        .server_app
+        .world
+        .entity_mut(server_entity)
+        .insert((Health(5), Score(1)));
+    step(&mut stepper, 2);
+    assert_eq!(
+        stepper.client_app.world.get::<Health>(client_entity),
+        Some(&Health(5))
+    );
+    assert_eq!(
+        stepper.client_app.world.get::<Score>(client_entity),
+        Some(&Score(1))
+    );
+
+    // remove
+    stepper
+        .server_app
+        .world
+        .entity_mut(server_entity)
+        .remove::<Health>();
+    step(&mut stepper, 2);
+    assert!(stepper
+        .client_app
+        .world
+        .get::<Health>(client_entity)
+        .is_none());
+    assert_eq!(
+        stepper.client_app.world.get::<Score>(client_entity),
+        Some(&Score(1))
+    );
+}
+
+#[test]
+fn test_dynamic_component_direction() {
+    let mut stepper = setup();
+
+    // the client is allowed to replicate Score, but not Health
+    stepper.client_app.world.spawn((
+        Component1(0.0),
+        Health(10),
+        Score(3),
+        Replicate {
+            replication_target: NetworkTarget::All,
+            ..default()
+        },
+    ));
+    step(&mut stepper, 4);
+    let mut query = stepper
+        .server_app
+        .world
+        .query_filtered::<(&Score, Has<Health>), With<Component1>>();
+    assert_eq!(
+        query.iter(&stepper.server_app.world).collect::<Vec<_>>(),
+        vec![(&Score(3), false)]
+    );
+}
+
+#[test]
+fn test_dynamic_protocol_mismatch() {
+    let mut stepper = new_stepper();
+    register(&mut stepper.server_app);
+    // the client does not register all the components of the server
+    stepper
+        .client_app
+        .add_message::<Chat>()
+        .register_component::<Health>(ChannelDirection::ServerToClient);
+    // connect through the networking state machine, so that the denial is surfaced as a DisconnectEvent
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnections>()
+        .start()
+        .unwrap();
+    stepper
+        .client_app
+        .world
+        .resource_mut::<NextState<NetworkingState>>()
+        .set(NetworkingState::Connecting);
+
+    let mut reasons = vec![];
+    for _ in 0..50 {
+        stepper.frame_step();
+        let events = stepper
+            .client_app
+            .world
+            .resource::<Events<DisconnectEvent>>();
+        reasons.extend(events.get_reader().read(events).map(|event| event.reason()));
+    }
+    assert_eq!(reasons.last(), Some(&Some(DeniedReason::ProtocolMismatch)));
+    assert_eq!(
+        stepper
+            .client_app
+            .world
+            .resource::<State<NetworkingState>>()
+            .get(),
+        &NetworkingState::Disconnected
+    );
+}
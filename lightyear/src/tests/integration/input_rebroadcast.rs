@@ -0,0 +1,198 @@
+//! Tests related to the inputs of remote clients that are rebroadcast by the server
+use bevy::prelude::*;
+
+use crate::channel::builder::InputChannel;
+use crate::client::components::Confirmed;
+use crate::inputs::native::input_buffer::{InputData, InputMessage};
+use crate::prelude::client::{InputManager, RemoteInputEvent, RemoteInputTarget};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const CLIENT_ID: ClientId = ClientId::Netcode(111);
+const REMOTE_CLIENT_ID: ClientId = ClientId::Netcode(222);
+const REMOTE_TARGET: RemoteInputTarget = RemoteInputTarget::Client(REMOTE_CLIENT_ID);
+
+/// Simulate the server rebroadcasting the inputs of another client, that control the given server entities
+fn send_remote_inputs(
+    stepper: &mut BevyStepper,
+    end_tick: Tick,
+    inputs: Vec<InputData<MyInput>>,
+    entities: Vec<Entity>,
+) {
+    let message = InputMessage {
+        end_tick,
+        inputs,
+        entities,
+        client_id: Some(REMOTE_CLIENT_ID),
+        checksum: None,
+    };
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_message::<InputChannel, _>(CLIENT_ID, message)
+        .unwrap();
+}
+
+fn remote_input(stepper: &BevyStepper, target: RemoteInputTarget, tick: Tick) -> Option<MyInput> {
+    stepper
+        .client_app
+        .world
+        .resource::<InputManager<MyInput>>()
+        .get_remote_input(target, tick)
+}
+
+/// Spawn a predicted entity on the server and return the server entity and the client predicted entity
+fn spawn_predicted(stepper: &mut BevyStepper) -> (Entity, Entity) {
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((
+            Component1(0.0),
+            Replicate {
+                prediction_target: NetworkTarget::All,
+                ..default()
+            },
+        ))
+        .id();
+    stepper.frame_step();
+    stepper.frame_step();
+    let confirmed = *stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+    let predicted = stepper
+        .client_app
+        .world
+        .get::<Confirmed>(confirmed)
+        .unwrap()
+        .predicted
+        .unwrap();
+    (server_entity, predicted)
+}
+
+#[test]
+fn test_receive_remote_inputs() {
+    let mut stepper = BevyStepper::default();
+    let tick = stepper.client_tick() + 4;
+
+    send_remote_inputs(
+        &mut stepper,
+        tick,
+        vec![
+            InputData::Input(MyInput(1)),
+            InputData::SameAsPrecedent,
+            InputData::Input(MyInput(2)),
+        ],
+        vec![],
+    );
+    stepper.frame_step();
+    stepper.frame_step();
+
+    assert_eq!(
+        remote_input(&stepper, REMOTE_TARGET, tick - 2),
+        Some(MyInput(1))
+    );
+    assert_eq!(
+        remote_input(&stepper, REMOTE_TARGET, tick - 1),
+        Some(MyInput(1))
+    );
+    assert_eq!(
+        remote_input(&stepper, REMOTE_TARGET, tick),
+        Some(MyInput(2))
+    );
+    // we haven't received the inputs for future ticks yet, so we re-use the latest input
+    assert_eq!(
+        remote_input(&stepper, REMOTE_TARGET, tick + 3),
+        Some(MyInput(2))
+    );
+    assert_eq!(
+        remote_input(
+            &stepper,
+            RemoteInputTarget::Client(ClientId::Netcode(333)),
+            tick
+        ),
+        None
+    );
+
+    // the remote inputs are emitted every tick
+    let events = stepper
+        .client_app
+        .world
+        .resource::<Events<RemoteInputEvent<MyInput>>>();
+    assert!(events
+        .get_reader()
+        .read(events)
+        .any(|event| event.context() == &REMOTE_TARGET && event.input().is_some()));
+}
+
+/// The remote client controls several entities: the inputs are stored on the predicted copy of each entity
+#[test]
+fn test_receive_remote_inputs_per_entity() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity_1, predicted_1) = spawn_predicted(&mut stepper);
+    let (server_entity_2, predicted_2) = spawn_predicted(&mut stepper);
+    let tick = stepper.client_tick() + 2;
+
+    send_remote_inputs(
+        &mut stepper,
+        tick,
+        vec![InputData::Input(MyInput(1))],
+        vec![server_entity_1, server_entity_2],
+    );
+    stepper.frame_step();
+    stepper.frame_step();
+
+    for predicted in [predicted_1, predicted_2] {
+        assert_eq!(
+            remote_input(&stepper, RemoteInputTarget::Entity(predicted), tick),
+            Some(MyInput(1))
+        );
+    }
+    // the inputs are not stored for the client itself
+    assert_eq!(remote_input(&stepper, REMOTE_TARGET, tick), None);
+
+    let events = stepper
+        .client_app
+        .world
+        .resource::<Events<RemoteInputEvent<MyInput>>>();
+    let targets: Vec<_> = events
+        .get_reader()
+        .read(events)
+        .map(|event| *event.context())
+        .collect();
+    assert!(targets.contains(&RemoteInputTarget::Entity(predicted_1)));
+    assert!(targets.contains(&RemoteInputTarget::Entity(predicted_2)));
+}
+
+#[test]
+fn test_remote_inputs_expire() {
+    let mut stepper = BevyStepper::default();
+    let tick = stepper.client_tick() + 2;
+
+    send_remote_inputs(
+        &mut stepper,
+        tick,
+        vec![InputData::Input(MyInput(1))],
+        vec![],
+    );
+    stepper.frame_step();
+    stepper.frame_step();
+    assert_eq!(
+        remote_input(&stepper, REMOTE_TARGET, tick),
+        Some(MyInput(1))
+    );
+
+    // the remote client stops sending inputs: once its inputs are older than the interpolation tick,
+    // we stop predicting it
+    for _ in 0..50 {
+        stepper.frame_step();
+    }
+    assert!(stepper.interpolation_tick() > tick);
+    assert_eq!(remote_input(&stepper, REMOTE_TARGET, tick), None);
+}
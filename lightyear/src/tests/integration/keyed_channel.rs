@@ -0,0 +1,89 @@
+//! Tests related to the [`ChannelMode::KeyedOrderedReliable`] channels
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+#[test]
+fn test_keyed_ordered_messages() {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.2,
+        },
+        Duration::from_millis(10),
+    );
+    stepper.init();
+
+    let client_id = ClientId::Netcode(111);
+    for i in 0..20 {
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .send_message_with_key::<KeyedChannel, Message1>(
+                client_id,
+                Message1(format!("a{i}")),
+                1,
+            )
+            .unwrap();
+        stepper
+            .server_app
+            .world
+            .resource_mut::<ServerConnectionManager>()
+            .send_message_with_key::<KeyedChannel, Message1>(
+                client_id,
+                Message1(format!("b{i}")),
+                2,
+            )
+            .unwrap();
+    }
+    // sending on a channel that is not keyed is an error
+    assert!(stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_message_with_key::<Channel1, Message1>(client_id, Message1("c".to_string()), 1)
+        .is_err());
+
+    let mut received = vec![];
+    for _ in 0..200 {
+        stepper.frame_step();
+        received.extend(
+            stepper
+                .client_app
+                .world
+                .resource_mut::<Events<client::MessageEvent<Message1>>>()
+                .drain()
+                .map(|event| event.message().0.clone()),
+        );
+        if received.len() == 40 {
+            break;
+        }
+    }
+    // every message is received, and the messages of each key are in order
+    for key in ["a", "b"] {
+        let messages: Vec<_> = received
+            .iter()
+            .filter(|message| message.starts_with(key))
+            .cloned()
+            .collect();
+        assert_eq!(
+            messages,
+            (0..20).map(|i| format!("{key}{i}")).collect::<Vec<_>>()
+        );
+    }
+}
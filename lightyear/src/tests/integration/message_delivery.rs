@@ -0,0 +1,159 @@
+//! Tests related to the [`MessageAckEvent`](crate::client::events::MessageAckEvent) and
+//! [`MessageLostEvent`](crate::client::events::MessageLostEvent) emitted for the messages sent by the user
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+fn setup(incoming_loss: f32) -> BevyStepper {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss,
+        },
+        Duration::from_millis(10),
+    );
+    stepper.init();
+    stepper
+}
+
+/// Drain the delivery events of the client, and return the ids of the acked and lost messages
+fn client_deliveries(stepper: &mut BevyStepper) -> (Vec<MessageId>, Vec<MessageId>) {
+    let acked = stepper
+        .client_app
+        .world
+        .resource_mut::<Events<client::MessageAckEvent>>()
+        .drain()
+        .map(|event| {
+            assert_eq!(event.channel(), ChannelKind::of::<Channel2>());
+            event.message_id()
+        })
+        .collect();
+    let lost = stepper
+        .client_app
+        .world
+        .resource_mut::<Events<client::MessageLostEvent>>()
+        .drain()
+        .map(|event| {
+            assert_eq!(event.channel(), ChannelKind::of::<Channel2>());
+            event.message_id()
+        })
+        .collect();
+    (acked, lost)
+}
+
+#[test]
+fn test_message_acked() {
+    let mut stepper = setup(0.0);
+    let client_id = ClientId::Netcode(111);
+
+    let client_message_id = stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .send_message::<Channel2, Message1>(Message1("a".to_string()))
+        .unwrap()
+        .unwrap();
+    let server_message_id = stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_message::<Channel2, Message1>(client_id, Message1("b".to_string()))
+        .unwrap()
+        .unwrap();
+    // when sending to multiple clients, we get the id of the message for each client
+    let target_message_ids = stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_message_to_target::<Channel2, Message1>(Message1("d".to_string()), NetworkTarget::All)
+        .unwrap();
+    assert_eq!(target_message_ids.len(), 1);
+    let target_message_id = target_message_ids[&client_id];
+    // channels that don't watch acks do not assign ids to the messages
+    assert!(stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .send_message::<Channel1, Message1>(Message1("c".to_string()))
+        .unwrap()
+        .is_none());
+
+    let mut client_acked = vec![];
+    let mut server_acked = vec![];
+    for _ in 0..10 {
+        stepper.frame_step();
+        let (acked, lost) = client_deliveries(&mut stepper);
+        assert!(lost.is_empty());
+        client_acked.extend(acked);
+        server_acked.extend(
+            stepper
+                .server_app
+                .world
+                .resource_mut::<Events<server::MessageAckEvent>>()
+                .drain()
+                .map(|event| (*event.context(), event.message_id())),
+        );
+    }
+    assert_eq!(client_acked, vec![client_message_id]);
+    server_acked.sort_by_key(|(_, message_id)| *message_id);
+    assert_eq!(
+        server_acked,
+        vec![
+            (client_id, server_message_id),
+            (client_id, target_message_id)
+        ]
+    );
+    assert!(stepper
+        .server_app
+        .world
+        .resource::<Events<server::MessageLostEvent>>()
+        .is_empty());
+}
+
+#[test]
+fn test_message_lost() {
+    let mut stepper = setup(0.2);
+    let mut sent = HashSet::new();
+    // every message is either acked or lost, exactly once
+    let mut acked = HashSet::new();
+    let mut lost = HashSet::new();
+    for i in 0..650 {
+        if i < 50 {
+            let message_id = stepper
+                .client_app
+                .world
+                .resource_mut::<ClientConnectionManager>()
+                .send_message::<Channel2, Message1>(Message1("a".to_string()))
+                .unwrap()
+                .unwrap();
+            sent.insert(message_id);
+        }
+        stepper.frame_step();
+        let (new_acked, new_lost) = client_deliveries(&mut stepper);
+        for message_id in new_acked {
+            assert!(acked.insert(message_id));
+        }
+        for message_id in new_lost {
+            assert!(lost.insert(message_id));
+        }
+    }
+    assert!(!lost.is_empty());
+    assert!(acked.is_disjoint(&lost));
+    assert_eq!(acked.union(&lost).copied().collect::<HashSet<_>>(), sent);
+}
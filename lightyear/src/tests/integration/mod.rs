@@ -1,2 +1,17 @@
+mod authority;
+mod checksum;
+mod congestion;
+mod deterministic;
+mod dynamic_protocol;
+mod input_rebroadcast;
+mod keyed_channel;
+mod message_delivery;
 mod multi_transport;
+mod protocol_mismatch;
+mod relay;
+mod replay;
+mod replicate_removal;
+mod rpc;
+mod session_resumption;
+mod streaming;
 mod tick_wrapping;
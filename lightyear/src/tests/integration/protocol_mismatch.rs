@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+
+use crate::client::networking::NetworkingState;
+use crate::connection::client::{ClientConnection, NetClient};
+use crate::prelude::client::{DeniedReason, DisconnectEvent};
+use crate::prelude::server;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+/// Reconnect the client to a server that was built with a different protocol
+fn reconnect_with_mismatched_protocol(stepper: &mut BevyStepper) {
+    // pretend that the server was built with a different protocol
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .protocol_fingerprint ^= 1;
+
+    // reconnect the client, so that it sends a new handshake
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnection>()
+        .disconnect()
+        .unwrap();
+    stepper.frame_step();
+    stepper.frame_step();
+    stepper
+        .client_app
+        .world
+        .resource_mut::<NextState<NetworkingState>>()
+        .set(NetworkingState::Connecting);
+    stepper.frame_step();
+}
+
+/// A client built with a different protocol than the server is disconnected with a clear reason
+#[test]
+fn test_protocol_mismatch() {
+    let mut stepper = BevyStepper::default();
+    reconnect_with_mismatched_protocol(&mut stepper);
+
+    let mut reasons = vec![];
+    for _ in 0..50 {
+        stepper.frame_step();
+        let events = stepper
+            .client_app
+            .world
+            .resource::<Events<DisconnectEvent>>();
+        reasons.extend(events.get_reader().read(events).map(|event| event.reason()));
+    }
+    assert_eq!(reasons.last(), Some(&Some(DeniedReason::ProtocolMismatch)));
+    assert_eq!(
+        stepper
+            .client_app
+            .world
+            .resource::<State<NetworkingState>>()
+            .get(),
+        &NetworkingState::Disconnected
+    );
+    // the server disconnected the client
+    assert!(stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>()
+        .connections
+        .is_empty());
+}
+
+/// The messages that a client with a different protocol sends along with its handshake are never processed
+#[test]
+fn test_protocol_mismatch_drops_messages() {
+    let mut stepper = BevyStepper::default();
+    reconnect_with_mismatched_protocol(&mut stepper);
+
+    let mut received = 0;
+    for _ in 0..50 {
+        // the message is sent in the same packets as the handshake
+        let _ = stepper
+            .client_app
+            .world
+            .resource_mut::<ClientConnectionManager>()
+            .send_message::<Channel1, Message1>(Message1("hello".to_string()));
+        stepper.frame_step();
+        let events = stepper
+            .server_app
+            .world
+            .resource::<Events<server::MessageEvent<Message1>>>();
+        received += events.get_reader().read(events).count();
+    }
+    assert_eq!(received, 0);
+    assert!(stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>()
+        .connections
+        .is_empty());
+}
@@ -0,0 +1,80 @@
+use bevy::prelude::*;
+
+use crate::prelude::client;
+use crate::prelude::server;
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+fn send_relayed_message(stepper: &mut BevyStepper, message: &str) {
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .send_message_to_target::<Channel1, Message1>(
+            Message1(message.to_string()),
+            NetworkTarget::All,
+        )
+        .unwrap();
+}
+
+fn client_received(stepper: &BevyStepper) -> Vec<String> {
+    let events = stepper
+        .client_app
+        .world
+        .resource::<Events<client::MessageEvent<Message1>>>();
+    events
+        .get_reader()
+        .read(events)
+        .map(|event| event.message().0.clone())
+        .collect()
+}
+
+fn server_received(stepper: &BevyStepper) -> Vec<String> {
+    let events = stepper
+        .server_app
+        .world
+        .resource::<Events<server::MessageEvent<Message1>>>();
+    events
+        .get_reader()
+        .read(events)
+        .map(|event| event.message().0.clone())
+        .collect()
+}
+
+#[test]
+fn test_relay_message() {
+    let mut stepper = BevyStepper::default();
+
+    send_relayed_message(&mut stepper, "hello");
+    stepper.frame_step();
+    // the server receives the message and relays it in the same frame
+    assert_eq!(server_received(&stepper), vec!["hello".to_string()]);
+    stepper.frame_step();
+    assert_eq!(client_received(&stepper), vec!["hello".to_string()]);
+}
+
+#[test]
+fn test_relay_filter() {
+    let mut stepper = BevyStepper::default();
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .set_relay_filter(|_, message, target| match message {
+            MyMessageProtocol::Message1(m) if m.0 == "blocked" => None,
+            _ => Some(target),
+        });
+
+    send_relayed_message(&mut stepper, "blocked");
+    stepper.frame_step();
+    stepper.frame_step();
+    // the server still receives the message, but doesn't relay it
+    assert_eq!(server_received(&stepper), vec!["blocked".to_string()]);
+    assert!(client_received(&stepper).is_empty());
+
+    send_relayed_message(&mut stepper, "allowed");
+    stepper.frame_step();
+    stepper.frame_step();
+    assert_eq!(client_received(&stepper), vec!["allowed".to_string()]);
+}
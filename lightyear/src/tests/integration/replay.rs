@@ -0,0 +1,126 @@
+//! Record a session on the server with the [`ReplayRecorderPlugin`](crate::server::replay::ReplayRecorderPlugin),
+//! then rebuild it offline on a client that uses [`NetConfig::Replay`](crate::prelude::client::NetConfig::Replay)
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy::utils::{Duration, Instant};
+
+use crate::connection::client::{ClientConnection, NetClient};
+use crate::prelude::client::{ClientConfig, InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::*;
+use crate::server::replay::ReplayRecorderPlugin;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const CLIENT_ID: ClientId = ClientId::Netcode(111);
+
+fn shared_config() -> SharedConfig {
+    SharedConfig {
+        tick: TickConfig::new(Duration::from_millis(10)),
+        ..Default::default()
+    }
+}
+
+fn client_value(world: &mut World) -> Option<f32> {
+    world.query::<&Component1>().iter(world).next().map(|c| c.0)
+}
+
+/// Run a session between a server and a client, and record it to `path`
+fn record_session(path: PathBuf) {
+    let mut stepper = BevyStepper::new(
+        shared_config(),
+        SyncConfig::default(),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        },
+        Duration::from_millis(10),
+    );
+    stepper
+        .server_app
+        .add_plugins(ReplayRecorderPlugin::new(path));
+    stepper.init();
+
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((Component1(1.0), Replicate::default()))
+        .id();
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(client_value(&mut stepper.client_app.world), Some(1.0));
+    stepper
+        .server_app
+        .world
+        .entity_mut(server_entity)
+        .insert(Component1(2.0));
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    assert_eq!(client_value(&mut stepper.client_app.world), Some(2.0));
+}
+
+/// Client app that replays the recording at `path`
+fn replay_client(path: PathBuf) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins.build());
+    let config = ClientConfig {
+        shared: shared_config(),
+        net: client::NetConfig::Replay {
+            path,
+            client_id: CLIENT_ID,
+        },
+        ..default()
+    };
+    app.add_plugins(client::ClientPlugin::new(client::PluginConfig::new(
+        config,
+        protocol(),
+    )));
+    app
+}
+
+#[test]
+fn test_record_and_replay_session() {
+    let path = std::env::temp_dir().join(format!("lightyear_replay_{}.bin", std::process::id()));
+    record_session(path.clone());
+
+    let mut app = replay_client(path.clone());
+    let mut now = Instant::now();
+    app.world
+        .resource_mut::<Time<Real>>()
+        .update_with_instant(now);
+    app.world
+        .resource_mut::<ClientConnection>()
+        .connect()
+        .unwrap();
+    // the client rebuilds the recorded session without any server
+    let mut values = vec![];
+    for _ in 0..200 {
+        now += Duration::from_millis(10);
+        app.insert_resource(TimeUpdateStrategy::ManualInstant(now));
+        mock_instant::MockClock::advance(Duration::from_millis(10));
+        app.update();
+        values.extend(client_value(&mut app.world));
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(values.first(), Some(&1.0));
+    assert_eq!(values.last(), Some(&2.0));
+}
+
+#[test]
+fn test_replay_missing_file() {
+    let mut app = replay_client(PathBuf::from("missing/directory/session.replay"));
+    // the error is returned when connecting instead of panicking
+    assert!(app
+        .world
+        .resource_mut::<ClientConnection>()
+        .connect()
+        .is_err());
+    app.update();
+}
@@ -0,0 +1,204 @@
+//! Tests related to the behaviour of the remote entity when `Replicate` is removed from an entity
+use bevy::prelude::*;
+
+use crate::client::components::Confirmed;
+use crate::prelude::server::{RoomId, RoomManager};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const CLIENT_ID: ClientId = ClientId::Netcode(111);
+
+fn step(stepper: &mut BevyStepper, frames: usize) {
+    for _ in 0..frames {
+        stepper.frame_step();
+    }
+}
+
+/// Spawn a replicated entity on the server and return the server entity and the corresponding client entity
+fn spawn(stepper: &mut BevyStepper, replicate: Replicate) -> (Entity, Entity) {
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((Component1(0.0), replicate))
+        .id();
+    step(stepper, 2);
+    let client_entity = *stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+    (server_entity, client_entity)
+}
+
+fn interpolated_entity(stepper: &BevyStepper, client_entity: Entity) -> Entity {
+    stepper
+        .client_app
+        .world
+        .get::<Confirmed>(client_entity)
+        .unwrap()
+        .interpolated
+        .unwrap()
+}
+
+/// Remove `Replicate` from the server entity, and update the server component
+fn remove_replicate(stepper: &mut BevyStepper, server_entity: Entity) {
+    stepper
+        .server_app
+        .world
+        .entity_mut(server_entity)
+        .remove::<Replicate>()
+        .insert(Component1(1.0));
+    step(stepper, 2);
+}
+
+#[test]
+fn test_removal_policy_despawn() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity, client_entity) = spawn(&mut stepper, Replicate::default());
+
+    remove_replicate(&mut stepper, server_entity);
+    assert!(stepper.client_app.world.get_entity(client_entity).is_none());
+    // the server entity is not despawned
+    assert!(stepper.server_app.world.get_entity(server_entity).is_some());
+}
+
+#[test]
+fn test_removal_policy_keep_local() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity, client_entity) = spawn(
+        &mut stepper,
+        Replicate {
+            removal_policy: ReplicateRemovalPolicy::KeepLocal,
+            interpolation_target: NetworkTarget::All,
+            ..default()
+        },
+    );
+
+    let interpolated = interpolated_entity(&stepper, client_entity);
+    remove_replicate(&mut stepper, server_entity);
+    // the entity is now a regular client entity
+    assert!(stepper.client_app.world.get_entity(interpolated).is_none());
+    assert_eq!(
+        stepper.client_app.world.get::<Component1>(client_entity),
+        Some(&Component1(0.0))
+    );
+    assert!(stepper
+        .client_app
+        .world
+        .get::<Confirmed>(client_entity)
+        .is_none());
+    assert!(stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .is_none());
+
+    // despawning the server entity doesn't affect the client entity anymore
+    stepper.server_app.world.despawn(server_entity);
+    step(&mut stepper, 2);
+    assert!(stepper.client_app.world.get_entity(client_entity).is_some());
+}
+
+#[test]
+fn test_removal_policy_freeze() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity, client_entity) = spawn(
+        &mut stepper,
+        Replicate {
+            removal_policy: ReplicateRemovalPolicy::Freeze,
+            interpolation_target: NetworkTarget::All,
+            ..default()
+        },
+    );
+
+    let interpolated = interpolated_entity(&stepper, client_entity);
+    remove_replicate(&mut stepper, server_entity);
+    assert!(stepper.client_app.world.get_entity(interpolated).is_some());
+    // the entity is still confirmed, but doesn't receive updates anymore
+    assert_eq!(
+        stepper.client_app.world.get::<Component1>(client_entity),
+        Some(&Component1(0.0))
+    );
+    assert!(stepper
+        .client_app
+        .world
+        .get::<Confirmed>(client_entity)
+        .is_some());
+}
+
+#[test]
+fn test_removal_policy_changed_after_spawn() {
+    let mut stepper = BevyStepper::default();
+    let (server_entity, client_entity) = spawn(&mut stepper, Replicate::default());
+
+    // the latest policy is used when Replicate is removed
+    stepper
+        .server_app
+        .world
+        .get_mut::<Replicate>(server_entity)
+        .unwrap()
+        .removal_policy = ReplicateRemovalPolicy::Freeze;
+    step(&mut stepper, 1);
+    remove_replicate(&mut stepper, server_entity);
+    // the client entity is not despawned
+    assert!(stepper.client_app.world.get_entity(client_entity).is_some());
+}
+
+#[test]
+fn test_removal_policy_room_visibility() {
+    let mut stepper = BevyStepper::default();
+    let room_id = RoomId(0);
+    stepper
+        .server_app
+        .world
+        .resource_mut::<RoomManager>()
+        .add_client(CLIENT_ID, room_id);
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((
+            Component1(0.0),
+            Replicate {
+                replication_mode: ReplicationMode::Room,
+                removal_policy: ReplicateRemovalPolicy::KeepLocal,
+                interpolation_target: NetworkTarget::All,
+                ..default()
+            },
+        ))
+        .id();
+    stepper
+        .server_app
+        .world
+        .resource_mut::<RoomManager>()
+        .add_entity(server_entity, room_id);
+    step(&mut stepper, 2);
+    let client_entity = *stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .unwrap();
+
+    // the client loses visibility of the entity: the entity is kept as a local entity
+    stepper
+        .server_app
+        .world
+        .resource_mut::<RoomManager>()
+        .remove_entity(server_entity, room_id);
+    step(&mut stepper, 2);
+    assert!(stepper.client_app.world.get_entity(client_entity).is_some());
+    assert!(stepper
+        .client_app
+        .world
+        .get::<Confirmed>(client_entity)
+        .is_none());
+}
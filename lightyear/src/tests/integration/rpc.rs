@@ -0,0 +1,227 @@
+//! Tests related to the request/response RPCs
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::connection::client::{ClientConnection, NetClient};
+use crate::prelude::client::{InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const CLIENT_ID: ClientId = ClientId::Netcode(111);
+
+fn stepper() -> BevyStepper {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    BevyStepper::new(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss: 0.0,
+        },
+        Duration::from_millis(10),
+    )
+}
+
+fn respond(In((client_id, request)): In<(ClientId, Message2)>) -> Message1 {
+    assert_eq!(client_id, CLIENT_ID);
+    Message1(request.0.to_string())
+}
+
+#[test]
+fn test_request_handled_by_system() {
+    let mut stepper = stepper();
+    stepper.server_app.add_plugins(
+        server::RpcPlugin::<MyProtocol, Message2>::default().with_handler::<Channel2, _>(respond),
+    );
+    stepper
+        .client_app
+        .add_plugins(client::RpcPlugin::<MyProtocol, Message2>::default());
+    stepper.init();
+
+    let first = stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .send_request::<Channel2, Message2>(Message2(1))
+        .unwrap();
+    let second = stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .send_request::<Channel2, Message2>(Message2(2))
+        .unwrap();
+    assert_ne!(first, second);
+
+    let mut responses = vec![];
+    let mut num_requests = 0;
+    for _ in 0..10 {
+        stepper.frame_step();
+        // the requests handled by the plugin can still be read by the user
+        num_requests += stepper
+            .server_app
+            .world
+            .resource_mut::<Events<server::MessageEvent<RequestMessage<Message2>>>>()
+            .drain()
+            .count();
+        responses.extend(
+            stepper
+                .client_app
+                .world
+                .resource_mut::<Events<client::ResponseEvent<Message1>>>()
+                .drain()
+                .map(|event| (event.request_id(), event.response().cloned())),
+        );
+    }
+    responses.sort_by_key(|(request_id, _)| request_id.0);
+    assert_eq!(
+        responses,
+        vec![
+            (first, Ok(Message1("1".to_string()))),
+            (second, Ok(Message1("2".to_string())))
+        ]
+    );
+    assert_eq!(num_requests, 2);
+}
+
+#[test]
+fn test_request_handled_with_events_and_timeout() {
+    let mut stepper = stepper();
+    stepper.server_app.add_plugins(
+        server::RpcPlugin::<MyProtocol, Message2>::default()
+            .with_timeout(Duration::from_millis(200)),
+    );
+    stepper.init();
+
+    let answered = stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_request::<Channel2, Message2>(CLIENT_ID, Message2(1))
+        .unwrap();
+    let unanswered = stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_request::<Channel2, Message2>(CLIENT_ID, Message2(2))
+        .unwrap();
+
+    let mut responses = vec![];
+    for _ in 0..50 {
+        stepper.frame_step();
+        // the client only answers the first request
+        let requests: Vec<RequestMessage<Message2>> = stepper
+            .client_app
+            .world
+            .resource_mut::<Events<client::MessageEvent<RequestMessage<Message2>>>>()
+            .drain()
+            .map(|event| event.message().clone())
+            .collect();
+        for request in requests {
+            if request.id() == answered {
+                stepper
+                    .client_app
+                    .world
+                    .resource_mut::<ClientConnectionManager>()
+                    .send_response::<Channel2, Message2>(
+                        request.id(),
+                        Message1(request.request().0.to_string()),
+                    )
+                    .unwrap();
+            }
+        }
+        responses.extend(
+            stepper
+                .server_app
+                .world
+                .resource_mut::<Events<server::ResponseEvent<Message1>>>()
+                .drain()
+                .map(|event| {
+                    assert_eq!(*event.context(), CLIENT_ID);
+                    (event.request_id(), event.response().cloned())
+                }),
+        );
+    }
+    assert_eq!(
+        responses,
+        vec![
+            (answered, Ok(Message1("1".to_string()))),
+            (unanswered, Err(RpcError::Timeout))
+        ]
+    );
+}
+
+#[test]
+fn test_request_resolved_on_disconnect() {
+    let mut stepper = stepper();
+    // the request never times out
+    stepper.server_app.add_plugins(
+        server::RpcPlugin::<MyProtocol, Message2>::default().with_timeout(Duration::MAX),
+    );
+    stepper.init();
+
+    let request_id = stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_request::<Channel2, Message2>(CLIENT_ID, Message2(1))
+        .unwrap();
+    stepper.frame_step();
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnection>()
+        .disconnect()
+        .unwrap();
+
+    let mut responses = vec![];
+    for _ in 0..10 {
+        stepper.frame_step();
+        responses.extend(
+            stepper
+                .server_app
+                .world
+                .resource_mut::<Events<server::ResponseEvent<Message1>>>()
+                .drain()
+                .map(|event| (event.request_id(), event.response().cloned())),
+        );
+    }
+    assert_eq!(responses, vec![(request_id, Err(RpcError::Disconnected))]);
+}
+
+/// The requests of a type without an `RpcPlugin` are not kept after their client disconnects
+#[test]
+fn test_request_without_plugin_dropped_on_disconnect() {
+    let mut stepper = stepper();
+    stepper.init();
+
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .send_request::<Channel2, Message2>(CLIENT_ID, Message2(1))
+        .unwrap();
+    stepper.frame_step();
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnection>()
+        .disconnect()
+        .unwrap();
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+    let connection_manager = stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>();
+    assert!(connection_manager.connections.is_empty());
+    assert!(connection_manager.disconnected_requests.is_empty());
+}
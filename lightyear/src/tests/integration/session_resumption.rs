@@ -0,0 +1,275 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::client::networking::{NetworkingState, ResumableSession};
+use crate::connection::client::{ClientConnection, NetClient};
+use crate::prelude::client::ClientConfig;
+use crate::prelude::server::{
+    ConnectEvent, DisconnectEvent, RoomId, RoomManager, SessionResumedEvent, SessionSuspendedEvent,
+};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+const CLIENT_ID: ClientId = ClientId::Netcode(111);
+
+/// Number of connection events of each kind received by the server
+#[derive(Resource, Default, Debug, PartialEq)]
+struct SessionEvents {
+    connected: usize,
+    disconnected: usize,
+    suspended: usize,
+    resumed: usize,
+}
+
+fn count_session_events(
+    mut counts: ResMut<SessionEvents>,
+    mut connect: EventReader<ConnectEvent>,
+    mut disconnect: EventReader<DisconnectEvent>,
+    mut suspended: EventReader<SessionSuspendedEvent>,
+    mut resumed: EventReader<SessionResumedEvent>,
+) {
+    counts.connected += connect.read().count();
+    counts.disconnected += disconnect.read().count();
+    counts.suspended += suspended.read().count();
+    counts.resumed += resumed.read().count();
+}
+
+fn setup(grace_period: Duration) -> (BevyStepper, Entity) {
+    let mut stepper = BevyStepper::default();
+    let resumption = ResumptionConfig::default().with_grace_period(grace_period);
+    stepper
+        .server_app
+        .world
+        .resource_mut::<ServerConnectionManager>()
+        .resumption_config = resumption.clone();
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConfig>()
+        .resumption = resumption;
+
+    let server_entity = stepper
+        .server_app
+        .world
+        .spawn((
+            Component1(1.0),
+            Replicate {
+                replication_mode: ReplicationMode::Room,
+                ..default()
+            },
+        ))
+        .id();
+    let mut room_manager = stepper.server_app.world.resource_mut::<RoomManager>();
+    room_manager.room_mut(RoomId(0)).add_client(CLIENT_ID);
+    room_manager.room_mut(RoomId(0)).add_entity(server_entity);
+    stepper.frame_step();
+    stepper.frame_step();
+    stepper
+        .server_app
+        .add_systems(PostUpdate, count_session_events)
+        .init_resource::<SessionEvents>();
+    (stepper, server_entity)
+}
+
+fn reconnect(stepper: &mut BevyStepper) {
+    stepper
+        .client_app
+        .world
+        .resource_mut::<NextState<NetworkingState>>()
+        .set(NetworkingState::Connecting);
+    for _ in 0..50 {
+        stepper.frame_step();
+    }
+}
+
+fn client_entity(stepper: &BevyStepper, server_entity: Entity) -> Option<Entity> {
+    stepper
+        .client_app
+        .world
+        .resource::<ClientConnectionManager>()
+        .replication_receiver
+        .remote_entity_map
+        .get_local(server_entity)
+        .copied()
+}
+
+/// Simulate a network failure: the client loses its connection without closing the session
+fn interrupt_connection(stepper: &mut BevyStepper) {
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnection>()
+        .disconnect()
+        .unwrap();
+    stepper.frame_step();
+    stepper.frame_step();
+    assert_eq!(
+        stepper
+            .client_app
+            .world
+            .resource::<State<NetworkingState>>()
+            .get(),
+        &NetworkingState::Disconnected
+    );
+}
+
+#[test]
+fn test_resume_session() {
+    let (mut stepper, server_entity) = setup(Duration::from_secs(1));
+    let client_entity = client_entity(&stepper, server_entity).unwrap();
+
+    interrupt_connection(&mut stepper);
+    assert!(stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>()
+        .is_suspended(CLIENT_ID));
+
+    // the world keeps changing while the client is disconnected
+    stepper
+        .server_app
+        .world
+        .get_mut::<Component1>(server_entity)
+        .unwrap()
+        .0 = 2.0;
+    let new_server_entity = stepper
+        .server_app
+        .world
+        .spawn((
+            Component2(1.0),
+            Replicate {
+                replication_mode: ReplicationMode::Room,
+                ..default()
+            },
+        ))
+        .id();
+    stepper
+        .server_app
+        .world
+        .resource_mut::<RoomManager>()
+        .room_mut(RoomId(0))
+        .add_entity(new_server_entity);
+    for _ in 0..10 {
+        stepper.frame_step();
+    }
+
+    // reconnect before the end of the grace period
+    reconnect(&mut stepper);
+    assert!(!stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>()
+        .is_suspended(CLIENT_ID));
+    assert!(stepper
+        .server_app
+        .world
+        .resource::<RoomManager>()
+        .has_client_id(CLIENT_ID, RoomId(0)));
+
+    // the client kept its entity mapping and received the changes it missed
+    assert_eq!(
+        self::client_entity(&stepper, server_entity),
+        Some(client_entity)
+    );
+    assert_eq!(
+        stepper.client_app.world.get::<Component1>(client_entity),
+        Some(&Component1(2.0))
+    );
+    let new_client_entity = self::client_entity(&stepper, new_server_entity).unwrap();
+    assert_eq!(
+        stepper
+            .client_app
+            .world
+            .get::<Component2>(new_client_entity),
+        Some(&Component2(1.0))
+    );
+
+    // the server was notified that the session was suspended and resumed, but it never ended
+    assert_eq!(
+        stepper.server_app.world.resource::<SessionEvents>(),
+        &SessionEvents {
+            suspended: 1,
+            resumed: 1,
+            ..default()
+        }
+    );
+}
+
+/// A client that reconnects with the same `ClientId` but without the secret of the session cannot take it over
+#[test]
+fn test_resume_session_without_secret() {
+    let (mut stepper, server_entity) = setup(Duration::from_secs(1));
+    let client_entity = client_entity(&stepper, server_entity).unwrap();
+    interrupt_connection(&mut stepper);
+
+    // another client connects with the same ClientId: it does not have the state nor the secret of the session
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ResumableSession>()
+        .closed = true;
+    reconnect(&mut stepper);
+    assert!(
+        stepper
+            .client_app
+            .world
+            .resource::<ClientConnectionManager>()
+            .handshake_done
+    );
+
+    // the previous session ended, and a new session started
+    assert_eq!(
+        stepper.server_app.world.resource::<SessionEvents>(),
+        &SessionEvents {
+            connected: 1,
+            disconnected: 1,
+            suspended: 1,
+            resumed: 0,
+        }
+    );
+    let connection_manager = stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>();
+    assert!(!connection_manager.is_suspended(CLIENT_ID));
+    assert!(connection_manager.connection(CLIENT_ID).is_ok());
+    assert!(!stepper
+        .server_app
+        .world
+        .resource::<RoomManager>()
+        .has_client_id(CLIENT_ID, RoomId(0)));
+    // the new client did not inherit the entities of the previous session
+    assert_ne!(
+        self::client_entity(&stepper, server_entity),
+        Some(client_entity)
+    );
+}
+
+#[test]
+fn test_session_expires() {
+    let (mut stepper, _) = setup(Duration::from_millis(100));
+
+    interrupt_connection(&mut stepper);
+    assert!(stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>()
+        .is_suspended(CLIENT_ID));
+
+    // the client does not reconnect before the end of the grace period
+    for _ in 0..20 {
+        stepper.frame_step();
+    }
+    let connection_manager = stepper
+        .server_app
+        .world
+        .resource::<ServerConnectionManager>();
+    assert!(!connection_manager.is_suspended(CLIENT_ID));
+    assert!(connection_manager.connection(CLIENT_ID).is_err());
+    assert!(!stepper
+        .server_app
+        .world
+        .resource::<RoomManager>()
+        .has_client_id(CLIENT_ID, RoomId(0)));
+}
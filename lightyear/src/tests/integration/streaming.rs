@@ -0,0 +1,191 @@
+//! Tests related to the [`ChannelMode::Streaming`] channels
+use bevy::prelude::*;
+use bevy::utils::Duration;
+
+use crate::prelude::client::{InterpolationConfig, PredictionConfig, SyncConfig};
+use crate::prelude::*;
+use crate::tests::protocol::*;
+use crate::tests::stepper::{BevyStepper, Step};
+
+/// Much larger than what can be sent as a fragmented message (255 fragments)
+const PAYLOAD_SIZE: usize = 400_000;
+
+fn setup(incoming_loss: f32) -> BevyStepper {
+    let tick_duration = Duration::from_millis(10);
+    let shared_config = SharedConfig {
+        tick: TickConfig::new(tick_duration),
+        ..Default::default()
+    };
+    let mut stepper = BevyStepper::new(
+        shared_config,
+        SyncConfig::default().speedup_factor(1.0),
+        PredictionConfig::default(),
+        InterpolationConfig::default(),
+        LinkConditionerConfig {
+            incoming_latency: Duration::from_millis(0),
+            incoming_jitter: Duration::from_millis(0),
+            incoming_loss,
+        },
+        Duration::from_millis(10),
+    );
+    stepper.init();
+    stepper
+}
+
+fn payload() -> String {
+    (0..PAYLOAD_SIZE)
+        .map(|i| char::from(b'a' + (i % 26) as u8))
+        .collect()
+}
+
+fn send_payload(stepper: &mut BevyStepper) {
+    stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .send_message::<StreamChannel, Message1>(Message1(payload()))
+        .unwrap();
+}
+
+fn server_received(stepper: &mut BevyStepper) -> Vec<Message1> {
+    stepper
+        .server_app
+        .world
+        .resource_mut::<Events<server::MessageEvent<Message1>>>()
+        .drain()
+        .map(|event| event.message().clone())
+        .collect()
+}
+
+/// Step until the server receives the payload, and return the progress events emitted by each peer
+fn stream_until_received(
+    stepper: &mut BevyStepper,
+) -> (
+    Vec<client::StreamProgressEvent>,
+    Vec<server::StreamProgressEvent>,
+) {
+    let mut client_progress = vec![];
+    let mut server_progress = vec![];
+    for _ in 0..500 {
+        stepper.frame_step();
+        client_progress.extend(
+            stepper
+                .client_app
+                .world
+                .resource_mut::<Events<client::StreamProgressEvent>>()
+                .drain(),
+        );
+        server_progress.extend(
+            stepper
+                .server_app
+                .world
+                .resource_mut::<Events<server::StreamProgressEvent>>()
+                .drain(),
+        );
+        let received = server_received(stepper);
+        if !received.is_empty() {
+            assert_eq!(received, vec![Message1(payload())]);
+            // wait for the last acks to reach the client
+            for _ in 0..100 {
+                if client_progress.last().unwrap().is_complete() {
+                    break;
+                }
+                stepper.frame_step();
+                client_progress.extend(
+                    stepper
+                        .client_app
+                        .world
+                        .resource_mut::<Events<client::StreamProgressEvent>>()
+                        .drain(),
+                );
+            }
+            return (client_progress, server_progress);
+        }
+    }
+    panic!("the payload was never received");
+}
+
+#[test]
+fn test_stream_large_payload() {
+    let mut stepper = setup(0.0);
+    send_payload(&mut stepper);
+    let (client_progress, server_progress) = stream_until_received(&mut stepper);
+
+    // the progress is reported incrementally on both peers
+    assert!(server_progress.len() > 1);
+    assert!(server_progress
+        .windows(2)
+        .all(|w| w[0].bytes() < w[1].bytes()));
+    let last = server_progress.last().unwrap();
+    assert!(last.is_complete());
+    assert_eq!(last.direction(), StreamDirection::Receive);
+    assert_eq!(*last.context(), ClientId::Netcode(111));
+    assert_eq!(last.channel(), ChannelKind::of::<StreamChannel>());
+
+    let first = client_progress.first().unwrap();
+    assert_eq!(first.bytes(), 0);
+    assert_eq!(first.direction(), StreamDirection::Send);
+    assert!(client_progress.last().unwrap().is_complete());
+    assert_eq!(first.stream_id(), last.stream_id());
+}
+
+#[test]
+fn test_stream_with_packet_loss() {
+    let mut stepper = setup(0.2);
+    send_payload(&mut stepper);
+    let (_, server_progress) = stream_until_received(&mut stepper);
+    // the lost chunks are sent again without restarting the transfer
+    assert!(server_progress
+        .windows(2)
+        .all(|w| w[0].bytes() < w[1].bytes()));
+    assert!(server_progress.last().unwrap().is_complete());
+}
+
+#[test]
+fn test_stream_cancel() {
+    let mut stepper = setup(0.0);
+    send_payload(&mut stepper);
+    stepper.frame_step();
+    stepper.frame_step();
+    let stream_id = stepper
+        .client_app
+        .world
+        .resource_mut::<Events<client::StreamProgressEvent>>()
+        .drain()
+        .next()
+        .unwrap()
+        .stream_id();
+    assert!(stepper
+        .client_app
+        .world
+        .resource_mut::<ClientConnectionManager>()
+        .cancel_stream::<StreamChannel>(stream_id)
+        .unwrap());
+    let mut client_cancelled = vec![];
+    let mut server_cancelled = vec![];
+    for _ in 0..10 {
+        stepper.frame_step();
+        client_cancelled.extend(
+            stepper
+                .client_app
+                .world
+                .resource_mut::<Events<client::StreamCancelledEvent>>()
+                .drain()
+                .map(|event| (event.stream_id(), event.direction())),
+        );
+        server_cancelled.extend(
+            stepper
+                .server_app
+                .world
+                .resource_mut::<Events<server::StreamCancelledEvent>>()
+                .drain()
+                .map(|event| (event.stream_id(), event.direction())),
+        );
+        assert!(server_received(&mut stepper).is_empty());
+    }
+    assert_eq!(client_cancelled, vec![(stream_id, StreamDirection::Send)]);
+    assert_eq!(
+        server_cancelled,
+        vec![(stream_id, StreamDirection::Receive)]
+    );
+}
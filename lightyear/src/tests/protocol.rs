@@ -16,10 +16,17 @@ pub struct Message1(pub String);
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Reflect)]
 pub struct Message2(pub u32);
 
+/// Request that is answered with the string representation of the number
+impl Request for Message2 {
+    type Response = Message1;
+}
+
 #[message_protocol_internal(protocol = "MyProtocol")]
 pub enum MyMessageProtocol {
     Message1(Message1),
     Message2(Message2),
+    Request2(RequestMessage<Message2>),
+    Response1(ResponseMessage<Message1>),
 }
 
 // Components
@@ -48,6 +55,32 @@ impl MapEntities for Component4 {
     }
 }
 
+#[derive(Component, Serialize, Deserialize, Clone, Debug, PartialEq, Reflect)]
+pub struct Component5(pub Vec<u32>);
+
+impl Diffable for Component5 {
+    // new length of the vec, and the values that changed
+    type Delta = (usize, Vec<(usize, u32)>);
+
+    fn diff(&self, new: &Self) -> Self::Delta {
+        let changed = new
+            .0
+            .iter()
+            .enumerate()
+            .filter(|(i, v)| self.0.get(*i) != Some(*v))
+            .map(|(i, v)| (i, *v))
+            .collect();
+        (new.0.len(), changed)
+    }
+
+    fn apply_diff(&mut self, delta: &Self::Delta) {
+        self.0.resize(delta.0, 0);
+        for (i, v) in delta.1.iter() {
+            self.0[*i] = *v;
+        }
+    }
+}
+
 #[component_protocol_internal(protocol = "MyProtocol")]
 pub enum MyComponentsProtocol {
     #[protocol(sync(mode = "full"))]
@@ -58,6 +91,8 @@ pub enum MyComponentsProtocol {
     Component3(Component3),
     #[protocol(sync(mode = "simple"), map_entities)]
     Component4(Component4),
+    #[protocol(delta)]
+    Component5(Component5),
     Resource1(ReplicateResource<Resource1>),
 }
 
@@ -115,6 +150,12 @@ pub struct Channel1;
 #[derive(ChannelInternal, Reflect)]
 pub struct Channel2;
 
+#[derive(ChannelInternal, Reflect)]
+pub struct StreamChannel;
+
+#[derive(ChannelInternal, Reflect)]
+pub struct KeyedChannel;
+
 pub fn protocol() -> MyProtocol {
     let mut p = MyProtocol::default();
     p.add_channel::<Channel1>(ChannelSettings {
@@ -125,5 +166,16 @@ pub fn protocol() -> MyProtocol {
         mode: ChannelMode::UnorderedUnreliableWithAcks,
         ..default()
     });
+    p.add_channel::<StreamChannel>(ChannelSettings {
+        mode: ChannelMode::Streaming(StreamSettings {
+            max_chunks_in_flight: 32,
+            ..default()
+        }),
+        ..default()
+    });
+    p.add_channel::<KeyedChannel>(ChannelSettings {
+        mode: ChannelMode::KeyedOrderedReliable(ReliableSettings::default()),
+        ..default()
+    });
     p
 }
@@ -13,7 +13,8 @@ use tracing_subscriber::fmt::format::FmtSpan;
 use crate::connection::netcode::generate_key;
 use crate::connection::server::{NetServer, ServerConnection, ServerConnections};
 use crate::prelude::client::{
-    Authentication, ClientConfig, InputConfig, InterpolationConfig, PredictionConfig, SyncConfig,
+    Authentication, ClientConfig, InputConfig, InterpolationConfig, PredictionConfig,
+    ReplicationConfig, SyncConfig,
 };
 use crate::prelude::server::{NetcodeConfig, ServerConfig};
 use crate::prelude::*;
@@ -75,6 +76,27 @@ impl BevyStepper {
         interpolation_config: InterpolationConfig,
         conditioner: LinkConditionerConfig,
         frame_duration: Duration,
+    ) -> Self {
+        Self::new_with_replication(
+            shared_config,
+            sync_config,
+            prediction_config,
+            interpolation_config,
+            ReplicationConfig::default(),
+            conditioner,
+            frame_duration,
+        )
+    }
+
+    /// Same as [`BevyStepper::new`], but with a custom replication config for the client
+    pub fn new_with_replication(
+        shared_config: SharedConfig,
+        sync_config: SyncConfig,
+        prediction_config: PredictionConfig,
+        interpolation_config: InterpolationConfig,
+        replication_config: ReplicationConfig,
+        conditioner: LinkConditionerConfig,
+        frame_duration: Duration,
     ) -> Self {
         // tracing_subscriber::FmtSubscriber::builder()
         //     // .with_span_events(FmtSpan::ENTER)
@@ -140,6 +162,7 @@ impl BevyStepper {
             sync: sync_config,
             prediction: prediction_config,
             interpolation: interpolation_config,
+            replication: replication_config,
             ..default()
         };
         let plugin_config = client::PluginConfig::new(config, protocol());
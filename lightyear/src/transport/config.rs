@@ -10,6 +10,8 @@ use {
     wtransport::tls::Certificate,
 };
 
+#[cfg(feature = "zstd")]
+use crate::connection::netcode::CompressionConfig;
 use crate::prelude::Io;
 use crate::transport::channels::Channels;
 use crate::transport::dummy::DummyIo;
@@ -137,6 +139,12 @@ pub struct IoConfig {
     #[reflect(ignore)]
     pub transport: TransportConfig,
     pub conditioner: Option<LinkConditionerConfig>,
+    /// Compress the payload of the packets sent through this io. The remote must use the same compression settings.
+    ///
+    /// The compression is applied by the netcode connection on the payload before it is encrypted
+    /// (encrypted data does not compress), so it has no effect with the other connection types.
+    #[cfg(feature = "zstd")]
+    pub compression: Option<CompressionConfig>,
 }
 
 impl Default for IoConfig {
@@ -145,6 +153,8 @@ impl Default for IoConfig {
         Self {
             transport: TransportConfig::UdpSocket(SocketAddr::new(IpAddr::from([127, 0, 0, 1]), 0)),
             conditioner: None,
+            #[cfg(feature = "zstd")]
+            compression: None,
         }
     }
 
@@ -154,6 +164,8 @@ impl Default for IoConfig {
         Self {
             transport: TransportConfig::LocalChannel { recv, send },
             conditioner: None,
+            #[cfg(feature = "zstd")]
+            compression: None,
         }
     }
 }
@@ -163,6 +175,8 @@ impl IoConfig {
         Self {
             transport,
             conditioner: None,
+            #[cfg(feature = "zstd")]
+            compression: None,
         }
     }
     pub fn with_conditioner(mut self, conditioner_config: LinkConditionerConfig) -> Self {
@@ -170,6 +184,12 @@ impl IoConfig {
         self
     }
 
+    #[cfg(feature = "zstd")]
+    pub fn with_compression(mut self, compression_config: CompressionConfig) -> Self {
+        self.compression = Some(compression_config);
+        self
+    }
+
     pub fn connect(self) -> Result<Io> {
         let transport = self.transport.build().connect()?;
         let local_addr = transport.local_addr();
@@ -39,7 +39,7 @@ impl Default for Io {
     }
 }
 
-// TODO: add stats/compression to middleware
+// TODO: add stats to middleware
 #[derive(Default, Debug)]
 pub struct IoStats {
     pub bytes_sent: usize,
@@ -78,7 +78,7 @@ impl Debug for Io {
 
 impl PacketReceiver for Io {
     fn recv(&mut self) -> Result<Option<(&mut [u8], SocketAddr)>> {
-        // todo: compression + bandwidth monitoring
+        // todo: bandwidth monitoring
         self.receiver.as_mut().recv().map(|x| {
             if let Some((ref buffer, _)) = x {
                 #[cfg(feature = "metrics")]
@@ -96,7 +96,7 @@ impl PacketReceiver for Io {
 
 impl PacketSender for Io {
     fn send(&mut self, payload: &[u8], address: &SocketAddr) -> Result<()> {
-        // todo: compression + bandwidth monitoring
+        // todo: bandwidth monitoring
         #[cfg(feature = "metrics")]
         {
             metrics::counter!("transport.packets_sent").increment(1);
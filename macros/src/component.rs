@@ -7,8 +7,8 @@ use quote::{format_ident, quote};
 use std::ops::Deref;
 use syn::punctuated::Punctuated;
 use syn::{
-    parse_macro_input, parse_quote, Field, Fields, GenericParam, Generics, ItemEnum, MetaList,
-    PathArguments, Token, Type, TypeParam,
+    parse_macro_input, parse_quote, Field, Fields, GenericParam, Generics, ItemEnum, LitStr,
+    MetaList, PathArguments, Token, Type, TypeParam,
 };
 
 // TODO: use FromDeriveInput ?
@@ -31,6 +31,8 @@ struct AttrField {
     sync: Option<SyncField>,
     #[darling(default)]
     map_entities: MapField,
+    /// the component is replicated by sending only the diff with the last acked value
+    delta: Flag,
 }
 
 #[derive(Debug, FromMeta, PartialEq, Eq)]
@@ -124,7 +126,12 @@ pub fn component_protocol_impl(
 
     // Helper Properties
     let fields = get_fields(&input);
-    let input_without_attributes = strip_attributes(&input, ATTRIBUTES);
+    let mut input_without_attributes = strip_attributes(&input, ATTRIBUTES);
+    // the components that are registered dynamically (in the `DynamicProtocol`) are replicated
+    // through this variant. It is not part of `fields` because it is not a regular component
+    input_without_attributes.variants.push(parse_quote! {
+        DynamicComponent(RawComponent)
+    });
     let attr_fields: Vec<AttrField> = fields
         .iter()
         .map(|field| FromField::from_field(field).unwrap())
@@ -156,6 +163,8 @@ pub fn component_protocol_impl(
     let insert_method = insert_method(&input, &fields);
     let update_method = update_method(&input, &fields);
     let type_ids_method = type_ids_method(&fields, &enum_kind_name);
+    let variants_method = variants_method(&fields);
+    let delta_methods = delta_methods(&attr_fields, &shared_crate_name);
 
     // EnumKind methods
     let enum_kind = get_enum_kind(&input, &enum_kind_name);
@@ -187,8 +196,10 @@ pub fn component_protocol_impl(
                 type Protocol = #protocol;
 
                 #type_ids_method
+                #variants_method
                 #insert_method
                 #update_method
+                #delta_methods
                 #add_resource_send_method
                 #add_resource_receive_method
                 #add_systems_method
@@ -465,7 +476,8 @@ fn get_enum_kind(input: &ItemEnum, enum_kind_name: &Ident) -> TokenStream {
     let variants = input.variants.iter().map(|v| v.ident.clone());
     quote! {
         pub enum #enum_kind_name {
-            #(#variants),*
+            #(#variants,)*
+            DynamicComponent(u16),
         }
     }
 }
@@ -497,6 +509,7 @@ fn from_method(input: &ItemEnum, enum_kind_name: &Ident, fields: &Vec<Field>) ->
             fn from(value: &'a #enum_name) -> Self {
                 match value {
                     #body
+                    &#enum_name::DynamicComponent(ref raw) => #enum_kind_name::DynamicComponent(raw.net_id()),
                 }
             }
         }
@@ -516,15 +529,22 @@ fn remove_method(input: &ItemEnum, fields: &[Field], enum_kind_name: &Ident) ->
     for (component_type, component_kind_name) in component_types.zip(component_kind_names) {
         field_body = quote! {
             #field_body
-            #enum_kind_name::#component_kind_name => entity.remove::<#component_type>(),
+            #enum_kind_name::#component_kind_name => {
+                entity.remove::<#component_type>();
+            }
         };
     }
     quote! {
         fn remove(self, entity: &mut EntityWorldMut) {
             match self {
                 #field_body
+                #enum_kind_name::DynamicComponent(net_id) => RawComponent::remove(net_id, entity),
             };
         }
+
+        fn dynamic(net_id: u16) -> Self {
+            #enum_kind_name::DynamicComponent(net_id)
+        }
     }
 }
 
@@ -606,6 +626,7 @@ fn map_entities_method(
             fn map_entities<M: EntityMapper>(&mut self, entity_mapper: &mut M) {
                 match self {
                     #map_entities_body
+                    Self::DynamicComponent(_) => {},
                 }
             }
         }
@@ -650,6 +671,7 @@ fn insert_method(input: &ItemEnum, fields: &Vec<Field>) -> TokenStream {
         fn insert(self, entity: &mut EntityWorldMut) {
             match self {
                 #body
+                Self::DynamicComponent(raw) => raw.insert(entity),
             }
         }
     }
@@ -707,6 +729,7 @@ fn update_method(input: &ItemEnum, fields: &Vec<Field>) -> TokenStream {
         fn update(self, entity: &mut EntityWorldMut) {
             match self {
                 #body
+                Self::DynamicComponent(raw) => raw.update(entity),
             }
         }
     }
@@ -730,3 +753,68 @@ fn type_ids_method(fields: &Vec<Field>, enum_kind_name: &Ident) -> TokenStream {
         }
     }
 }
+
+fn variants_method(fields: &[Field]) -> TokenStream {
+    let mut body = quote! {};
+    for field in fields.iter() {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let name = LitStr::new(&ident.to_string(), Span::call_site());
+        body = quote! {
+            #body
+            (#name, std::any::type_name::<#ty>()),
+        };
+    }
+    quote! {
+        fn variants() -> Vec<(&'static str, &'static str)> {
+            vec![#body]
+        }
+    }
+}
+
+fn delta_methods(fields: &[AttrField], shared_crate_name: &TokenStream) -> TokenStream {
+    let mut is_delta_body = quote! {};
+    let mut diff_body = quote! {};
+    let mut apply_diff_body = quote! {};
+    for field in fields.iter().filter(|f| f.delta.is_present()) {
+        let ident = &field.ident;
+        is_delta_body = quote! {
+            #is_delta_body
+            Self::#ident(_) => true,
+        };
+        diff_body = quote! {
+            #diff_body
+            (Self::#ident(base), Self::#ident(new)) => diff_component(base, new),
+        };
+        apply_diff_body = quote! {
+            #apply_diff_body
+            Self::#ident(base) => apply_diff_component(base, delta).map(Self::#ident),
+        };
+    }
+    quote! {
+        fn is_delta_compressed(&self) -> bool {
+            match self {
+                #is_delta_body
+                _ => false,
+            }
+        }
+
+        fn diff(&self, new: &Self) -> #shared_crate_name::_reexport::anyhow::Result<Vec<u8>> {
+            match (self, new) {
+                #diff_body
+                _ => Err(#shared_crate_name::_reexport::anyhow::anyhow!(
+                    "cannot compute a delta between {:?} and {:?}", self, new
+                )),
+            }
+        }
+
+        fn apply_diff(&self, delta: &[u8]) -> #shared_crate_name::_reexport::anyhow::Result<Self> {
+            match self {
+                #apply_diff_body
+                _ => Err(#shared_crate_name::_reexport::anyhow::anyhow!(
+                    "component {:?} is not delta-compressed", self
+                )),
+            }
+        }
+    }
+}
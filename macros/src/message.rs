@@ -67,6 +67,10 @@ pub fn message_protocol_impl(
     input.variants.push(parse_quote! {
         InputMessage(#shared_crate_name::inputs::native::InputMessage<<#protocol as Protocol>::Input>)
     });
+    // messages registered dynamically (in the `DynamicProtocol`) are sent through this variant
+    input.variants.push(parse_quote! {
+        DynamicMessage(#shared_crate_name::protocol::dynamic::RawMessage)
+    });
 
     #[cfg(feature = "leafwing")]
     for i in 1..3 {
@@ -101,6 +105,7 @@ pub fn message_protocol_impl(
     let add_events_method = add_events_method(&fields);
     let push_message_events_method = push_message_events_method(&fields, protocol);
     let name_method = name_method(&input, &fields);
+    let variants_method = variants_method(&fields);
     let map_entities_impl = map_entities_impl(&input, &fields);
     let encode_method = encode_method();
     let decode_method = decode_method();
@@ -124,6 +129,7 @@ pub fn message_protocol_impl(
                 type Protocol = #protocol;
 
                 #name_method
+                #variants_method
                 #message_kind_method
                 #input_message_kind_method
                 #add_events_method
@@ -310,6 +316,24 @@ fn name_method(input: &ItemEnum, fields: &Vec<AttrField>) -> TokenStream {
     }
 }
 
+fn variants_method(fields: &[AttrField]) -> TokenStream {
+    let mut body = quote! {};
+    for field in fields.iter() {
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let name = LitStr::new(&ident.to_string(), Span::call_site());
+        body = quote! {
+            #body
+            (#name, std::any::type_name::<#ty>()),
+        };
+    }
+    quote! {
+        fn variants() -> Vec<(&'static str, &'static str)> {
+            vec![#body]
+        }
+    }
+}
+
 fn map_entities_impl(input: &ItemEnum, fields: &Vec<AttrField>) -> TokenStream {
     let enum_name = &input.ident;
     let mut map_entities_body = quote! {};